@@ -0,0 +1,439 @@
+//! Channel membership, history, and the chat command grammar.
+//!
+//! Pure state: no I/O. `main.rs` feeds it requests and ships the resulting lines.
+//!
+//! `party` and `guild` are scoped: the broker tells us which party and guild each session is in
+//! (from the shard, which owns both), and only members of the same one hear each other. The same
+//! scope carries the session's ignore list, so nobody hears a player they ignore on any channel.
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use mudproto::color;
+use mudproto::session::SessionId;
+use serde::{Deserialize, Serialize};
+
+pub const CHANNELS: [&str; 5] = ["ooc", "newbie", "trade", "party", "guild"];
+/// Joined automatically the first time a name is seen.
+const DEFAULT_CHANNELS: [&str; 2] = ["ooc", "newbie"];
+const HISTORY_SHOWN: usize = 20;
+const MAX_MSG_CHARS: usize = 400;
+
+fn is_scoped(ch: &str) -> bool {
+    matches!(ch, "party" | "guild")
+}
+
+fn channel(s: &str) -> Option<&'static str> {
+    let s = s.trim().to_ascii_lowercase();
+    CHANNELS.into_iter().find(|c| *c == s)
+}
+
+fn channel_color(ch: &str) -> &'static str {
+    match ch {
+        "ooc" => "{c}",
+        "newbie" => "{g}",
+        "trade" => "{y}",
+        "party" => "{G}",
+        _ => "{B}",
+    }
+}
+
+/// Per-player channel settings, persisted by lowercase name.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Prefs {
+    #[serde(default)]
+    pub joined: BTreeSet<String>,
+    #[serde(default)]
+    pub muted: BTreeSet<String>,
+}
+
+impl Prefs {
+    fn hears(&self, ch: &str) -> bool {
+        self.joined.contains(ch) && !self.muted.contains(ch)
+    }
+}
+
+/// The party and guild a session is in, and who it ignores, as last reported by the broker.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Scope {
+    pub party: Option<String>,
+    pub guild: Option<String>,
+    /// Lowercase names.
+    pub ignores: BTreeSet<String>,
+}
+
+fn ignoring(scopes: &HashMap<SessionId, Scope>, session: SessionId, speaker_lc: &str) -> bool {
+    scopes
+        .get(&session)
+        .is_some_and(|s| s.ignores.contains(speaker_lc))
+}
+
+/// History/delivery room for `ch` as heard by `session`; `None` if it isn't in a party/guild.
+fn room(scopes: &HashMap<SessionId, Scope>, session: SessionId, ch: &str) -> Option<String> {
+    if !is_scoped(ch) {
+        return Some(ch.to_string());
+    }
+    let scope = scopes.get(&session)?;
+    let id = if ch == "party" {
+        scope.party.as_ref()
+    } else {
+        scope.guild.as_ref()
+    };
+    id.map(|id| format!("{ch}:{id}"))
+}
+
+#[derive(Debug)]
+struct Member {
+    name: String,
+    conn: u64,
+}
+
+/// Text for one session on broker connection `conn`; `err` maps to `EVT_ERR`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Out {
+    pub conn: u64,
+    pub session: SessionId,
+    pub err: bool,
+    pub text: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Chat {
+    members: HashMap<SessionId, Member>,
+    /// Kept apart from `members`: a scope can arrive before the session joins.
+    scopes: HashMap<SessionId, Scope>,
+    prefs: HashMap<String, Prefs>,
+    /// Per room: (speaker lowercase, line).
+    history: HashMap<String, VecDeque<(String, String)>>,
+    history_max: usize,
+    dirty: bool,
+}
+
+pub fn usage_text() -> String {
+    "chat:\r\n \
+- chat list\r\n \
+- chat join <channel>   (party and guild reach your current party or guild)\r\n \
+- chat leave <channel>\r\n \
+- chat mute|unmute <channel>\r\n \
+- chat history <channel>\r\n \
+- chat <channel> <message>   (or: ooc|newbie|trade|guild <message>)\r\n\
+channels: ooc, newbie, trade, party, guild\r\n"
+        .to_string()
+}
+
+fn split_word(s: &str) -> (&str, &str) {
+    match s.trim().split_once(char::is_whitespace) {
+        Some((a, b)) => (a, b.trim()),
+        None => (s.trim(), ""),
+    }
+}
+
+impl Chat {
+    pub fn new(prefs: HashMap<String, Prefs>, history_max: usize) -> Self {
+        Self {
+            prefs,
+            history_max: history_max.max(1),
+            ..Default::default()
+        }
+    }
+
+    pub fn join(&mut self, conn: u64, session: SessionId, name: &str) {
+        let name = name.trim().to_string();
+        let key = name.to_ascii_lowercase();
+        if let std::collections::hash_map::Entry::Vacant(e) = self.prefs.entry(key) {
+            e.insert(Prefs {
+                joined: DEFAULT_CHANNELS.iter().map(|s| s.to_string()).collect(),
+                ..Default::default()
+            });
+            self.dirty = true;
+        }
+        self.members.insert(session, Member { name, conn });
+    }
+
+    pub fn leave(&mut self, session: SessionId) {
+        self.members.remove(&session);
+        self.scopes.remove(&session);
+    }
+
+    /// A broker connection went away; its sessions go with it.
+    pub fn drop_conn(&mut self, conn: u64) {
+        self.members.retain(|_, m| m.conn != conn);
+        let members = &self.members;
+        self.scopes.retain(|sid, _| members.contains_key(sid));
+    }
+
+    pub fn set_scope(&mut self, session: SessionId, scope: Scope) {
+        self.scopes.insert(session, scope);
+    }
+
+    /// Settings need writing again (the last write failed).
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Settings, if they changed since the last call.
+    pub fn take_dirty(&mut self) -> Option<&HashMap<String, Prefs>> {
+        if !std::mem::take(&mut self.dirty) {
+            return None;
+        }
+        Some(&self.prefs)
+    }
+
+    pub fn say(&mut self, session: SessionId, line: &str) -> Vec<Out> {
+        // The broker always joins first; anything else is stale.
+        let Some(m) = self.members.get(&session) else {
+            return Vec::new();
+        };
+        let conn = m.conn;
+        let name = m.name.clone();
+        let reply = |text: String| {
+            vec![Out {
+                conn,
+                session,
+                err: false,
+                text,
+            }]
+        };
+        let err = |text: &str| {
+            vec![Out {
+                conn,
+                session,
+                err: true,
+                text: format!("chat: {text}\r\n"),
+            }]
+        };
+
+        let (cmd, rest) = split_word(line);
+        let cmd = cmd.to_ascii_lowercase();
+        let prefs = self.prefs.entry(name.to_ascii_lowercase()).or_default();
+
+        match cmd.as_str() {
+            "" | "help" => reply(usage_text()),
+            "list" => {
+                let mut s = String::from("chat channels:\r\n");
+                for ch in CHANNELS {
+                    let mut state = if prefs.joined.contains(ch) {
+                        "joined".to_string()
+                    } else {
+                        "-".to_string()
+                    };
+                    if prefs.muted.contains(ch) {
+                        state.push_str(" (muted)");
+                    }
+                    if prefs.joined.contains(ch) && room(&self.scopes, session, ch).is_none() {
+                        state.push_str(&format!(" (not in a {ch})"));
+                    }
+                    s.push_str(&format!(" - {ch}: {state}\r\n"));
+                }
+                reply(s)
+            }
+            "join" => {
+                let Some(ch) = channel(rest) else {
+                    return err("unknown channel");
+                };
+                prefs.joined.insert(ch.to_string());
+                prefs.muted.remove(ch);
+                self.dirty = true;
+                reply(format!("chat: joined {ch}\r\n"))
+            }
+            "leave" => {
+                let Some(ch) = channel(rest) else {
+                    return err("unknown channel");
+                };
+                prefs.joined.remove(ch);
+                prefs.muted.remove(ch);
+                self.dirty = true;
+                reply(format!("chat: left {ch}\r\n"))
+            }
+            "mute" | "unmute" => {
+                let Some(ch) = channel(rest) else {
+                    return err("unknown channel");
+                };
+                if cmd == "mute" {
+                    prefs.muted.insert(ch.to_string());
+                } else {
+                    prefs.muted.remove(ch);
+                }
+                self.dirty = true;
+                reply(format!("chat: {cmd}d {ch}\r\n"))
+            }
+            "history" => {
+                let Some(ch) = channel(rest) else {
+                    return err("unknown channel");
+                };
+                if !prefs.joined.contains(ch) {
+                    return err(&format!("you're not on {ch}"));
+                }
+                let Some(room) = room(&self.scopes, session, ch) else {
+                    return err(&format!("you're not in a {ch}"));
+                };
+                let lines = self
+                    .history
+                    .get(&room)
+                    .into_iter()
+                    .flatten()
+                    .filter(|(speaker, _)| !ignoring(&self.scopes, session, speaker))
+                    .map(|(_, l)| l.as_str())
+                    .collect::<Vec<_>>();
+                if lines.is_empty() {
+                    return reply(format!("chat: no {ch} history\r\n"));
+                }
+                let mut s = format!("{ch} history:\r\n");
+                for l in &lines[lines.len().saturating_sub(HISTORY_SHOWN)..] {
+                    s.push_str(l);
+                }
+                reply(s)
+            }
+            other => match channel(other) {
+                Some(ch) => self.speak(session, &name, ch, rest),
+                None => err("unknown channel or command (try: chat help)"),
+            },
+        }
+    }
+
+    fn speak(&mut self, session: SessionId, name: &str, ch: &str, text: &str) -> Vec<Out> {
+        let conn = self.members.get(&session).map_or(0, |m| m.conn);
+        let err = |text: String| {
+            vec![Out {
+                conn,
+                session,
+                err: true,
+                text: format!("chat: {text}\r\n"),
+            }]
+        };
+        let Some(prefs) = self.prefs.get(&name.to_ascii_lowercase()) else {
+            return Vec::new();
+        };
+        if !prefs.joined.contains(ch) {
+            return err(format!("you're not on {ch} (chat join {ch})"));
+        }
+        let Some(room) = room(&self.scopes, session, ch) else {
+            return err(format!("you're not in a {ch}"));
+        };
+        if text.is_empty() {
+            return err(format!("{ch} what?"));
+        }
+
+        let text = text.chars().take(MAX_MSG_CHARS).collect::<String>();
+        let line = format!(
+            "{}[{ch}]{{x}} {name}: {}\r\n",
+            channel_color(ch),
+            color::escape(&text)
+        );
+
+        let speaker = name.to_ascii_lowercase();
+        let h = self.history.entry(room.clone()).or_default();
+        h.push_back((speaker.clone(), line.clone()));
+        while h.len() > self.history_max {
+            h.pop_front();
+        }
+
+        let mut out = Vec::new();
+        for (sid, m) in &self.members {
+            // Speakers always see their own line, even on a muted channel.
+            let hears = *sid == session
+                || (self
+                    .prefs
+                    .get(&m.name.to_ascii_lowercase())
+                    .is_some_and(|p| p.hears(ch))
+                    && self::room(&self.scopes, *sid, ch).as_deref() == Some(room.as_str())
+                    && !ignoring(&self.scopes, *sid, &speaker));
+            if hears {
+                out.push(Out {
+                    conn: m.conn,
+                    session: *sid,
+                    err: false,
+                    text: line.clone(),
+                });
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heard(out: &[Out]) -> Vec<u128> {
+        let mut v = out.iter().map(|o| o.session.0).collect::<Vec<_>>();
+        v.sort();
+        v
+    }
+
+    fn scope(party: Option<&str>, guild: Option<&str>) -> Scope {
+        Scope {
+            party: party.map(str::to_string),
+            guild: guild.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn channels_mute_scopes_and_history() {
+        let mut c = Chat::new(HashMap::new(), 10);
+        c.join(1, SessionId(1), "Alice");
+        c.join(2, SessionId(2), "Bob");
+        c.join(2, SessionId(3), "Cat");
+
+        assert_eq!(heard(&c.say(SessionId(1), "ooc hi")), vec![1, 2, 3]);
+        c.say(SessionId(2), "mute ooc");
+        assert_eq!(heard(&c.say(SessionId(1), "ooc again")), vec![1, 3]);
+
+        // Trade isn't a default channel.
+        assert!(c.say(SessionId(1), "trade wts sword")[0].err);
+
+        for sid in 1..=3 {
+            c.say(SessionId(sid), "join party");
+            c.say(SessionId(sid), "join guild");
+        }
+        // Not in a party yet; picking a key by hand is gone.
+        assert!(c.say(SessionId(1), "party go")[0].err);
+        assert!(c.say(SessionId(3), "join party red")[0].err);
+
+        c.set_scope(SessionId(1), scope(Some("s/1"), Some("s/9")));
+        c.set_scope(SessionId(2), scope(Some("s/1"), None));
+        c.set_scope(SessionId(3), scope(Some("s/2"), Some("s/9")));
+        assert_eq!(heard(&c.say(SessionId(1), "party go")), vec![1, 2]);
+        assert_eq!(heard(&c.say(SessionId(1), "guild hi")), vec![1, 3]);
+        assert!(c.say(SessionId(2), "guild hi")[0].err);
+
+        // Leaving the party (scope update) stops delivery.
+        c.set_scope(SessionId(2), scope(None, None));
+        assert_eq!(heard(&c.say(SessionId(1), "party again")), vec![1]);
+
+        let h = c.say(SessionId(3), "history ooc");
+        assert!(h[0].text.contains("Alice: again"));
+        let h = c.say(SessionId(3), "history party");
+        assert!(h[0].text.contains("no party history"));
+        assert!(c.take_dirty().is_some());
+        assert!(c.take_dirty().is_none());
+    }
+
+    #[test]
+    fn ignored_speakers_are_not_heard() {
+        let mut c = Chat::new(HashMap::new(), 10);
+        c.join(1, SessionId(1), "Alice");
+        c.join(1, SessionId(2), "Mallory");
+        c.join(2, SessionId(3), "Bob");
+        c.say(SessionId(2), "ooc before");
+
+        let mut alice = scope(Some("s/1"), None);
+        alice.ignores.insert("mallory".to_string());
+        c.set_scope(SessionId(1), alice);
+        c.set_scope(SessionId(2), scope(Some("s/1"), None));
+        c.say(SessionId(1), "join party");
+        c.say(SessionId(2), "join party");
+
+        assert_eq!(heard(&c.say(SessionId(2), "ooc hi")), vec![2, 3]);
+        assert_eq!(heard(&c.say(SessionId(2), "party hi")), vec![2]);
+        assert_eq!(heard(&c.say(SessionId(1), "ooc hey")), vec![1, 2, 3]);
+
+        // History holds back their older lines too.
+        let h = c.say(SessionId(1), "history ooc");
+        assert!(h[0].text.contains("Alice: hey") && !h[0].text.contains("Mallory"));
+        let h = c.say(SessionId(3), "history ooc");
+        assert!(h[0].text.contains("Mallory: before"));
+        let h = c.say(SessionId(1), "history party");
+        assert!(h[0].text.contains("no party history"));
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use bytes::Bytes;
+use mudproto::chat::{ChatReq, EVT_ERR, EVT_LINE};
+use mudproto::session::SessionId;
+use slopio::frame::{FrameReader, FrameWriter};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Mutex, mpsc};
+use tracing::{Level, info, warn};
+
+mod channels;
+
+/// How often changed channel settings are written out.
+const PREFS_FLUSH: Duration = Duration::from_secs(2);
+
+fn usage_and_exit() -> ! {
+    eprintln!(
+        "chatd
+
+USAGE:
+  chatd [--bind HOST:PORT]
+
+ENV:
+  CHATD_BIND        default 127.0.0.1:5100
+  CHATD_PREFS_PATH  default var/chatd_prefs.json (per-player channel settings)
+  CHATD_HISTORY     default 100 (lines kept per channel)
+"
+    );
+    std::process::exit(2);
+}
+
+#[derive(Clone, Debug)]
+struct Config {
+    bind: SocketAddr,
+    prefs_path: PathBuf,
+    history: usize,
+}
+
+fn parse_args() -> Config {
+    let mut bind: SocketAddr = std::env::var("CHATD_BIND")
+        .unwrap_or_else(|_| "127.0.0.1:5100".to_string())
+        .parse()
+        .unwrap_or_else(|_| usage_and_exit());
+    let prefs_path: PathBuf = std::env::var("CHATD_PREFS_PATH")
+        .unwrap_or_else(|_| "var/chatd_prefs.json".to_string())
+        .into();
+    let history: usize = std::env::var("CHATD_HISTORY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(100);
+
+    let mut it = std::env::args().skip(1);
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--bind" => {
+                let v = it.next().unwrap_or_else(|| usage_and_exit());
+                bind = v.parse().unwrap_or_else(|_| usage_and_exit());
+            }
+            "-h" | "--help" => usage_and_exit(),
+            _ => usage_and_exit(),
+        }
+    }
+
+    Config {
+        bind,
+        prefs_path,
+        history,
+    }
+}
+
+struct Hub {
+    chat: channels::Chat,
+    conns: HashMap<u64, mpsc::Sender<Bytes>>,
+    next_conn: u64,
+}
+
+impl Hub {
+    /// Queue outputs to their broker connections (drops if a connection is backed up).
+    fn dispatch(&self, out: Vec<channels::Out>) {
+        for o in out {
+            let Some(tx) = self.conns.get(&o.conn) else {
+                continue;
+            };
+            let t = if o.err { EVT_ERR } else { EVT_LINE };
+            let mut b = Vec::with_capacity(1 + SessionId::LEN + o.text.len());
+            b.push(t);
+            b.extend_from_slice(&o.session.to_be_bytes());
+            b.extend_from_slice(o.text.as_bytes());
+            let _ = tx.try_send(Bytes::from(b));
+        }
+    }
+}
+
+fn load_prefs(path: &Path) -> HashMap<String, channels::Prefs> {
+    let Ok(s) = std::fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+    match serde_json::from_str(&s) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!(path = %path.display(), err = %e, "ignoring unreadable chat prefs");
+            HashMap::new()
+        }
+    }
+}
+
+/// Write changed settings every `PREFS_FLUSH`, off the hub lock, so a burst of `chat join`s costs
+/// one write rather than one per request.
+async fn prefs_writer(hub: Arc<Mutex<Hub>>, path: PathBuf) {
+    let mut tick = tokio::time::interval(PREFS_FLUSH);
+    loop {
+        tick.tick().await;
+        let Some(prefs) = hub.lock().await.chat.take_dirty().cloned() else {
+            continue;
+        };
+        let p = path.clone();
+        let res = tokio::task::spawn_blocking(move || save_prefs(&p, &prefs))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|r| r);
+        if let Err(e) = res {
+            warn!(err = %e, "failed to persist chat prefs");
+            hub.lock().await.chat.mark_dirty();
+        }
+    }
+}
+
+fn save_prefs(path: &Path, prefs: &HashMap<String, channels::Prefs>) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("create prefs dir {}", parent.display()))?;
+    }
+    let sorted = prefs.iter().collect::<std::collections::BTreeMap<_, _>>();
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string_pretty(&sorted)?)
+        .with_context(|| format!("write prefs tmp {}", tmp.display()))?;
+    std::fs::rename(&tmp, path)
+        .with_context(|| format!("rename prefs tmp -> {}", path.display()))?;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "info,chatd=info".into()),
+        )
+        .with_target(false)
+        .with_max_level(Level::INFO)
+        .init();
+
+    let cfg = parse_args();
+    let listener = TcpListener::bind(cfg.bind).await?;
+    info!(bind = %cfg.bind, prefs_path = %cfg.prefs_path.display(), "chatd listening");
+
+    let hub = Arc::new(Mutex::new(Hub {
+        chat: channels::Chat::new(load_prefs(&cfg.prefs_path), cfg.history),
+        conns: HashMap::new(),
+        next_conn: 1,
+    }));
+    tokio::spawn(prefs_writer(hub.clone(), cfg.prefs_path.clone()));
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        info!(peer = %peer, "broker connected");
+        let hub = hub.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_broker(stream, hub).await {
+                warn!(peer = %peer, err = %e, "broker connection ended with error");
+            }
+        });
+    }
+}
+
+async fn handle_broker(stream: TcpStream, hub: Arc<Mutex<Hub>>) -> anyhow::Result<()> {
+    let (rd, wr) = stream.into_split();
+    let mut fr = FrameReader::new(rd);
+    let mut fw = FrameWriter::new(wr);
+
+    let (tx, mut rx) = mpsc::channel::<Bytes>(4096);
+    let conn = {
+        let mut h = hub.lock().await;
+        let id = h.next_conn;
+        h.next_conn += 1;
+        h.conns.insert(id, tx);
+        id
+    };
+    let writer = tokio::spawn(async move {
+        while let Some(b) = rx.recv().await {
+            if fw.write_frame(&b).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let res = read_loop(&mut fr, &hub, conn).await;
+
+    {
+        let mut h = hub.lock().await;
+        h.conns.remove(&conn);
+        h.chat.drop_conn(conn);
+    }
+    writer.abort();
+    res
+}
+
+async fn read_loop(
+    fr: &mut FrameReader<tokio::net::tcp::OwnedReadHalf>,
+    hub: &Arc<Mutex<Hub>>,
+    conn: u64,
+) -> anyhow::Result<()> {
+    while let Some(frame) = fr.read_frame().await? {
+        let req = match mudproto::chat::parse_req(frame) {
+            Ok(r) => r,
+            Err(e) => {
+                warn!(err = %e, "bad chat request");
+                continue;
+            }
+        };
+        let mut h = hub.lock().await;
+        match req {
+            ChatReq::Join { session, name } => {
+                let name = String::from_utf8_lossy(&name);
+                if !name.trim().is_empty() {
+                    h.chat.join(conn, session, &name);
+                }
+            }
+            ChatReq::Leave { session } => h.chat.leave(session),
+            ChatReq::Say { session, msg } => {
+                let out = h.chat.say(session, &String::from_utf8_lossy(&msg));
+                h.dispatch(out);
+            }
+            ChatReq::Scope {
+                session,
+                party,
+                guild,
+                ignores,
+            } => {
+                let id = |b: Option<Bytes>| b.map(|b| String::from_utf8_lossy(&b).into_owned());
+                h.chat.set_scope(
+                    session,
+                    channels::Scope {
+                        party: id(party),
+                        guild: id(guild),
+                        ignores: ignores
+                            .iter()
+                            .map(|n| String::from_utf8_lossy(n).to_ascii_lowercase())
+                            .collect(),
+                    },
+                );
+            }
+        }
+    }
+    Ok(())
+}
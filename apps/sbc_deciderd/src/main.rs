@@ -1,7 +1,10 @@
+mod policy;
+
 use std::path::PathBuf;
 
 use anyhow::Context;
-use sbc_core::{AdminReq, AdminResp, ExemptPrefixes, IpPrefix};
+use policy::{Decider, Policy, Rule};
+use sbc_core::{AdminReq, AdminResp, ExemptPrefixes};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::UnixStream;
 use tracing::{Level, info, warn};
@@ -16,12 +19,25 @@ USAGE:
 ENV:
   SBC_DECIDER_ENABLED             default 0
   SBC_DECIDER_POLL_S              default 5
-  SBC_DECIDER_THRESHOLD_BYTES_IN  default 0 (disabled)
-  SBC_DECIDER_TTL_S               default 3600
+  SBC_DECIDER_RULES_PATH          default empty; JSON policy (see below)
+  SBC_DECIDER_SHADOW              default 0; when 1, every rule only logs would-be bans
+  SBC_DECIDER_RECONCILE_S         default 300 (refresh active bans from raft)
+  SBC_DECIDER_THRESHOLD_BYTES_IN  default 0 (disabled); without a rules file, one src bytes_in rule
+  SBC_DECIDER_WINDOW              default 60s (that rule's sbc_metricsd /top window)
+  SBC_DECIDER_TTL_S               default 3600 (that rule's first-offence TTL)
   SBC_METRICS_URL                 default http://127.0.0.1:9912
   SBC_ADMIN_SOCK                  default /run/slopmud/sbc-admin.sock
   SBC_DECIDER_CREATED_BY          default sbc-deciderd
   SBC_EXEMPT_PREFIXES_PATH        default empty
+
+RULES:
+  {{\"rules\": [{{\"name\": \"src-flood\", \"metric\": \"bytes_in\", \"group\": \"src\",
+              \"window\": \"60s\", \"threshold\": 50000000, \"ttl_s\": 3600, \"shadow\": false}}],
+   \"escalation\": {{\"factor\": 4, \"max_ttl_s\": 604800, \"memory_s\": 604800}}}}
+  metric: bytes_in | bytes_out | conns | login_failures | accounts_created | reports
+  group: src | v4_18 | v6_48
+  e.g. credential stuffing: login_failures by src over 10m; account farms: accounts_created by
+  v4_18 / v6_48 over 1h
 "
     );
     std::process::exit(2);
@@ -31,15 +47,15 @@ ENV:
 struct Config {
     enabled: bool,
     poll_s: u64,
-    threshold_bytes_in: u64,
-    ttl_s: u64,
+    reconcile_s: u64,
+    policy: Policy,
     metrics_url: String,
     admin_sock: PathBuf,
     created_by: String,
     exempt: ExemptPrefixes,
 }
 
-fn parse_args() -> Config {
+fn parse_args() -> anyhow::Result<Config> {
     let enabled = std::env::var("SBC_DECIDER_ENABLED")
         .ok()
         .is_some_and(|v| v == "1");
@@ -51,10 +67,42 @@ fn parse_args() -> Config {
         .ok()
         .and_then(|s| s.parse().ok())
         .unwrap_or(0);
+    let window = std::env::var("SBC_DECIDER_WINDOW").unwrap_or_else(|_| "60s".to_string());
     let ttl_s = std::env::var("SBC_DECIDER_TTL_S")
         .ok()
         .and_then(|s| s.parse().ok())
         .unwrap_or(3600);
+    let reconcile_s = std::env::var("SBC_DECIDER_RECONCILE_S")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(300);
+    let shadow = std::env::var("SBC_DECIDER_SHADOW")
+        .ok()
+        .is_some_and(|v| v == "1");
+    let mut policy = match std::env::var("SBC_DECIDER_RULES_PATH").ok() {
+        Some(p) if !p.trim().is_empty() => Policy::load(std::path::Path::new(&p))?,
+        _ => Policy {
+            rules: if threshold_bytes_in == 0 {
+                Vec::new()
+            } else {
+                vec![Rule {
+                    name: "src_bytes_in".to_string(),
+                    metric: "bytes_in".to_string(),
+                    group: "src".to_string(),
+                    window,
+                    threshold: threshold_bytes_in,
+                    ttl_s,
+                    shadow: false,
+                }]
+            },
+            escalation: Default::default(),
+        },
+    };
+    if shadow {
+        for r in &mut policy.rules {
+            r.shadow = true;
+        }
+    }
     let metrics_url =
         std::env::var("SBC_METRICS_URL").unwrap_or_else(|_| "http://127.0.0.1:9912".to_string());
     let admin_sock: PathBuf = std::env::var("SBC_ADMIN_SOCK")
@@ -81,24 +129,20 @@ fn parse_args() -> Config {
         }
     }
 
-    Config {
+    Ok(Config {
         enabled,
         poll_s,
-        threshold_bytes_in,
-        ttl_s,
+        reconcile_s,
+        policy,
         metrics_url,
         admin_sock,
         created_by,
         exempt,
-    }
+    })
 }
 
 #[derive(Debug, serde::Deserialize)]
 struct TopResp {
-    #[allow(dead_code)]
-    metric: String,
-    #[allow(dead_code)]
-    group: String,
     top: Vec<TopItem>,
 }
 
@@ -132,89 +176,125 @@ async fn main() -> anyhow::Result<()> {
         .with_max_level(Level::INFO)
         .init();
 
-    let cfg = parse_args();
+    let cfg = parse_args()?;
     info!(
         enabled = cfg.enabled,
         poll_s = cfg.poll_s,
-        threshold_bytes_in = cfg.threshold_bytes_in,
-        ttl_s = cfg.ttl_s,
+        rules = cfg.policy.rules.len(),
         metrics_url = %cfg.metrics_url,
         admin_sock = %cfg.admin_sock.display(),
         "sbc_deciderd starting"
     );
+    for r in &cfg.policy.rules {
+        info!(
+            rule = %r.name,
+            metric = %r.metric,
+            group = %r.group,
+            window = %r.window,
+            threshold = r.threshold,
+            ttl_s = r.ttl_s,
+            shadow = r.shadow,
+            "rule loaded"
+        );
+    }
 
-    if !cfg.enabled || cfg.threshold_bytes_in == 0 {
-        info!("decider disabled (set SBC_DECIDER_ENABLED=1 and threshold envs)");
+    if !cfg.enabled || cfg.policy.rules.is_empty() {
+        info!("decider disabled (set SBC_DECIDER_ENABLED=1 and a rules file or threshold envs)");
     }
 
     let http = reqwest::Client::new();
-    let mut banned = std::collections::HashSet::<String>::new();
+    let mut decider = Decider::new(cfg.policy.clone(), cfg.exempt.clone());
+    // Reconcile before the first decision so a restart doesn't re-ban what is already banned.
+    let mut last_reconcile: Option<u64> = None;
 
     loop {
         tokio::time::sleep(std::time::Duration::from_secs(cfg.poll_s.max(1))).await;
-        if !cfg.enabled || cfg.threshold_bytes_in == 0 {
-            continue;
-        }
-
-        let url = format!(
-            "{}/top?metric=bytes_in&group=src&n=20",
-            cfg.metrics_url.trim_end_matches('/')
-        );
-        let resp = match http.get(&url).send().await {
-            Ok(r) => r,
-            Err(e) => {
-                warn!(err=%e, "metrics fetch failed");
-                continue;
-            }
-        };
-        if !resp.status().is_success() {
-            warn!(status=%resp.status(), "metrics fetch non-200");
+        if !cfg.enabled || cfg.policy.rules.is_empty() {
             continue;
         }
-        let top: TopResp = match resp.json().await {
-            Ok(v) => v,
-            Err(e) => {
-                warn!(err=%e, "metrics json parse failed");
-                continue;
-            }
-        };
 
-        for item in top.top {
-            if item.value < cfg.threshold_bytes_in {
-                continue;
+        let now = now_unix();
+        if last_reconcile.is_none_or(|t| now.saturating_sub(t) >= cfg.reconcile_s) {
+            match send_admin_req(&cfg.admin_sock, &AdminReq::GetState).await {
+                Ok(AdminResp::OkState { bans, .. }) => {
+                    decider.reconcile(&bans, &cfg.created_by, now);
+                    info!(bans = bans.len(), "reconciled active bans");
+                    last_reconcile = Some(now);
+                }
+                Ok(other) => warn!(resp=?other, "unexpected get_state response"),
+                Err(e) => warn!(err=%e, "get_state failed"),
             }
-            if banned.contains(&item.key) {
+            if last_reconcile.is_none() {
                 continue;
             }
+        }
 
-            // Ban exact IP.
-            let ip: std::net::IpAddr = match item.key.parse() {
-                Ok(v) => v,
-                Err(_) => continue,
+        for (idx, rule) in cfg.policy.rules.iter().enumerate() {
+            let url = format!(
+                "{}/top?metric={}&group={}&n=50&window={}",
+                cfg.metrics_url.trim_end_matches('/'),
+                rule.metric,
+                rule.group,
+                rule.window
+            );
+            let resp = match http.get(&url).send().await {
+                Ok(r) => r,
+                Err(e) => {
+                    warn!(err=%e, rule=%rule.name, "metrics fetch failed");
+                    continue;
+                }
             };
-            let plen = if ip.is_ipv4() { 32 } else { 128 };
-            let pfx = IpPrefix::new(ip, plen).context("build prefix")?;
-            if cfg.exempt.contains_prefix(&pfx) {
+            if !resp.status().is_success() {
+                warn!(status=%resp.status(), rule=%rule.name, "metrics fetch non-200");
                 continue;
             }
-            let req = AdminReq::UpsertBan {
-                key: pfx.to_cidr_string(),
-                ttl_s: cfg.ttl_s,
-                created_by: cfg.created_by.clone(),
-                reason: format!("auto bytes_in {}", item.value),
-            };
-            match send_admin_req(&cfg.admin_sock, &req).await {
-                Ok(AdminResp::OkBan { .. } | AdminResp::Ok { .. }) => {
-                    banned.insert(item.key);
+            let top: TopResp = match resp.json().await {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!(err=%e, rule=%rule.name, "metrics json parse failed");
+                    continue;
                 }
-                Ok(AdminResp::Err { message }) => {
-                    warn!(message=%message, "ban rejected");
+            };
+            let readings = top
+                .top
+                .into_iter()
+                .map(|i| (i.key, i.value))
+                .collect::<Vec<_>>();
+
+            for d in decider.evaluate(idx, &readings, now) {
+                let key = d.key.to_cidr_string();
+                if d.shadow {
+                    info!(rule=%d.rule, key=%key, ttl_s=d.ttl_s, strike=d.strike, reason=%d.reason, "shadow ban");
+                    decider.record(&d, now);
+                    continue;
                 }
-                Err(e) => {
-                    warn!(err=%e, "ban proposal failed");
+                let req = AdminReq::UpsertBan {
+                    key: key.clone(),
+                    ttl_s: d.ttl_s,
+                    created_by: cfg.created_by.clone(),
+                    reason: d.reason.clone(),
+                };
+                match send_admin_req(&cfg.admin_sock, &req).await {
+                    Ok(AdminResp::OkBan { .. } | AdminResp::Ok { .. }) => {
+                        info!(rule=%d.rule, key=%key, ttl_s=d.ttl_s, strike=d.strike, "ban proposed");
+                        decider.record(&d, now);
+                    }
+                    Ok(AdminResp::Err { message }) => {
+                        warn!(message=%message, key=%key, "ban rejected");
+                    }
+                    Err(e) => {
+                        warn!(err=%e, key=%key, "ban proposal failed");
+                    }
+                    _ => {}
                 }
-                _ => {}
             }
         }
     }
 }
+
+fn now_unix() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
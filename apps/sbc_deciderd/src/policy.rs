@@ -0,0 +1,387 @@
+//! Ban policy: which metric readings turn into bans, and for how long.
+//!
+//! Pure bookkeeping; the caller fetches metrics, proposes bans and passes the clock in.
+
+use std::collections::HashMap;
+
+use sbc_core::{BanEntry, ExemptPrefixes, IpPrefix};
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default = "default_metric")]
+    pub metric: String, // bytes_in | bytes_out | conns | login_failures | accounts_created | reports
+    #[serde(default = "default_group")]
+    pub group: String, // src | v4_18 | v6_48
+    #[serde(default = "default_window")]
+    pub window: String, // sbc_metricsd window, e.g. 60s
+    pub threshold: u64,
+    pub ttl_s: u64, // first offence; 0 = never expires
+    /// Log would-be bans instead of proposing them.
+    #[serde(default)]
+    pub shadow: bool,
+}
+
+fn default_metric() -> String {
+    "bytes_in".to_string()
+}
+
+fn default_group() -> String {
+    "src".to_string()
+}
+
+fn default_window() -> String {
+    "60s".to_string()
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct Escalation {
+    /// Each repeat ban multiplies the previous TTL by this.
+    pub factor: u64,
+    pub max_ttl_s: u64,
+    /// Offences older than this are forgotten.
+    pub memory_s: u64,
+}
+
+impl Default for Escalation {
+    fn default() -> Self {
+        Self {
+            factor: 4,
+            max_ttl_s: 7 * 86400,
+            memory_s: 7 * 86400,
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct Policy {
+    pub rules: Vec<Rule>,
+    #[serde(default)]
+    pub escalation: Escalation,
+}
+
+impl Policy {
+    pub fn load(path: &std::path::Path) -> anyhow::Result<Self> {
+        let s = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read rules {:?}: {e}", path))?;
+        let p: Policy =
+            serde_json::from_str(&s).map_err(|e| anyhow::anyhow!("bad rules {:?}: {e}", path))?;
+        p.validate()?;
+        Ok(p)
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for r in &self.rules {
+            if !matches!(
+                r.metric.as_str(),
+                "bytes_in"
+                    | "bytes_out"
+                    | "conns"
+                    | "login_failures"
+                    | "accounts_created"
+                    | "reports"
+            ) {
+                anyhow::bail!("rule {}: bad metric {:?}", r.name, r.metric);
+            }
+            if !matches!(r.group.as_str(), "src" | "v4_18" | "v6_48") {
+                anyhow::bail!("rule {}: bad group {:?}", r.name, r.group);
+            }
+            if r.threshold == 0 {
+                anyhow::bail!("rule {}: threshold must be > 0", r.name);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Decision {
+    pub rule: String,
+    pub key: IpPrefix,
+    pub ttl_s: u64,
+    pub strike: u32,
+    pub shadow: bool,
+    pub reason: String,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Offender {
+    strikes: u32,
+    last_unix: u64,
+}
+
+#[derive(Debug)]
+pub struct Decider {
+    pub policy: Policy,
+    exempt: ExemptPrefixes,
+    /// Bans currently in force (ours or anyone's): cidr -> (prefix, expires_at_unix; 0 = never).
+    active: HashMap<String, (IpPrefix, u64)>,
+    /// Would-be bans in shadow mode, so each is logged once per TTL rather than every poll.
+    shadowed: HashMap<String, u64>,
+    offenders: HashMap<String, Offender>,
+}
+
+fn live(expires_at_unix: u64, now: u64) -> bool {
+    expires_at_unix == 0 || expires_at_unix > now
+}
+
+fn ban_prefix(group: &str, key: &str) -> Option<IpPrefix> {
+    match group {
+        "src" => {
+            let ip: std::net::IpAddr = key.parse().ok()?;
+            let plen = if ip.is_ipv4() { 32 } else { 128 };
+            IpPrefix::new(ip, plen).ok()
+        }
+        _ => IpPrefix::parse_cidr(key).ok(),
+    }
+}
+
+impl Decider {
+    pub fn new(policy: Policy, exempt: ExemptPrefixes) -> Self {
+        Self {
+            policy,
+            exempt,
+            active: HashMap::new(),
+            shadowed: HashMap::new(),
+            offenders: HashMap::new(),
+        }
+    }
+
+    /// Replace the view of active bans with the cluster's. Our own live bans count as one
+    /// strike each, so a restart doesn't reset escalation for offenders still serving a ban.
+    pub fn reconcile(&mut self, bans: &[BanEntry], created_by: &str, now: u64) {
+        self.active.clear();
+        for b in bans.iter().filter(|b| live(b.expires_at_unix, now)) {
+            let cidr = b.key.to_cidr_string();
+            self.active
+                .insert(cidr.clone(), (b.key.clone(), b.expires_at_unix));
+            if b.created_by == created_by {
+                let o = self.offenders.entry(cidr).or_insert(Offender {
+                    strikes: 0,
+                    last_unix: b.created_at_unix,
+                });
+                o.strikes = o.strikes.max(1);
+                o.last_unix = o.last_unix.max(b.created_at_unix);
+            }
+        }
+    }
+
+    fn covered(&self, pfx: &IpPrefix, now: u64) -> bool {
+        self.active
+            .values()
+            .any(|(p, exp)| live(*exp, now) && p.contains_prefix(pfx))
+    }
+
+    fn exempted(&self, pfx: &IpPrefix) -> bool {
+        // Also refuse block bans that would swallow an exempt range.
+        self.exempt.overlaps(pfx)
+    }
+
+    fn ttl_for(&self, base: u64, strike: u32) -> u64 {
+        if base == 0 {
+            return 0;
+        }
+        let esc = &self.policy.escalation;
+        let mut ttl = base;
+        for _ in 1..strike {
+            ttl = ttl.saturating_mul(esc.factor.max(1));
+        }
+        if esc.max_ttl_s > 0 {
+            ttl = ttl.min(esc.max_ttl_s.max(base));
+        }
+        ttl
+    }
+
+    /// Decide on one rule's readings (`(key, value)` from `/top`).
+    pub fn evaluate(
+        &mut self,
+        rule_idx: usize,
+        readings: &[(String, u64)],
+        now: u64,
+    ) -> Vec<Decision> {
+        let Some(rule) = self.policy.rules.get(rule_idx).cloned() else {
+            return Vec::new();
+        };
+        self.shadowed.retain(|_, until| live(*until, now));
+        let memory_s = self.policy.escalation.memory_s;
+        self.offenders
+            .retain(|_, o| o.last_unix.saturating_add(memory_s) > now);
+
+        let mut out = Vec::new();
+        for (key, value) in readings {
+            if *value < rule.threshold {
+                continue;
+            }
+            let Some(pfx) = ban_prefix(&rule.group, key) else {
+                continue;
+            };
+            let cidr = pfx.to_cidr_string();
+            if self.exempted(&pfx) || self.covered(&pfx, now) {
+                continue;
+            }
+            let shadow = rule.shadow;
+            if shadow && self.shadowed.contains_key(&cidr) {
+                continue;
+            }
+            let strike = self.offenders.get(&cidr).map_or(0, |o| o.strikes) + 1;
+            let ttl_s = self.ttl_for(rule.ttl_s, strike);
+            out.push(Decision {
+                rule: rule.name.clone(),
+                key: pfx,
+                ttl_s,
+                strike,
+                shadow,
+                reason: format!(
+                    "auto rule={} {} {} over {} (strike {strike})",
+                    rule.name, rule.metric, value, rule.window
+                ),
+            });
+        }
+        out
+    }
+
+    /// Record a decision that was acted on (proposed, or logged in shadow mode).
+    pub fn record(&mut self, d: &Decision, now: u64) {
+        let cidr = d.key.to_cidr_string();
+        let until = if d.ttl_s == 0 {
+            0
+        } else {
+            now.saturating_add(d.ttl_s)
+        };
+        if d.shadow {
+            self.shadowed.insert(cidr, until);
+            return;
+        }
+        self.active.insert(cidr.clone(), (d.key.clone(), until));
+        let o = self.offenders.entry(cidr).or_insert(Offender {
+            strikes: 0,
+            last_unix: now,
+        });
+        o.strikes = d.strike;
+        o.last_unix = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(shadow: bool) -> Policy {
+        Policy {
+            rules: vec![
+                Rule {
+                    name: "src".to_string(),
+                    metric: default_metric(),
+                    group: "src".to_string(),
+                    window: default_window(),
+                    threshold: 1000,
+                    ttl_s: 60,
+                    shadow,
+                },
+                Rule {
+                    name: "block".to_string(),
+                    metric: default_metric(),
+                    group: "v4_18".to_string(),
+                    window: "5m".to_string(),
+                    threshold: 5000,
+                    ttl_s: 600,
+                    shadow,
+                },
+            ],
+            escalation: Escalation {
+                factor: 4,
+                max_ttl_s: 1000,
+                memory_s: 86400,
+            },
+        }
+    }
+
+    fn reading(k: &str, v: u64) -> (String, u64) {
+        (k.to_string(), v)
+    }
+
+    #[test]
+    fn repeat_offenders_get_longer_bans() {
+        let mut d = Decider::new(policy(false), ExemptPrefixes::empty());
+        let mut now = 1_000;
+        let mut ttls = Vec::new();
+        for _ in 0..4 {
+            let ds = d.evaluate(
+                0,
+                &[reading("198.51.100.7", 5000), reading("198.51.100.8", 10)],
+                now,
+            );
+            assert_eq!(ds.len(), 1);
+            assert_eq!(ds[0].key.to_cidr_string(), "198.51.100.7/32");
+            d.record(&ds[0], now);
+            // Still banned: nothing new until it expires.
+            assert!(
+                d.evaluate(0, &[reading("198.51.100.7", 5000)], now + 1)
+                    .is_empty()
+            );
+            ttls.push(ds[0].ttl_s);
+            now += ds[0].ttl_s;
+        }
+        assert_eq!(ttls, vec![60, 240, 960, 1000]);
+    }
+
+    #[test]
+    fn respects_exemptions_and_existing_bans() {
+        let exempt = ExemptPrefixes::new(vec![IpPrefix::parse_cidr("192.0.2.0/24").unwrap()]);
+        let mut d = Decider::new(policy(false), exempt);
+        let prior = IpPrefix::parse_cidr("203.0.113.5/32").unwrap();
+        d.reconcile(
+            &[BanEntry {
+                ban_id: "x".to_string(),
+                key: prior,
+                created_at_unix: 900,
+                created_by: "sbc-deciderd".to_string(),
+                reason: String::new(),
+                expires_at_unix: 2_000,
+            }],
+            "sbc-deciderd",
+            1_000,
+        );
+        let ds = d.evaluate(
+            0,
+            &[
+                reading("192.0.2.9", 9999),
+                reading("203.0.113.5", 9999),
+                reading("198.51.100.1", 9999),
+            ],
+            1_000,
+        );
+        assert_eq!(ds.len(), 1);
+        assert_eq!(ds[0].key.to_cidr_string(), "198.51.100.1/32");
+
+        // A /18 that swallows the exempt /24 is refused.
+        assert!(
+            d.evaluate(1, &[reading("192.0.0.0/18", 9999)], 1_000)
+                .is_empty()
+        );
+        assert_eq!(
+            d.evaluate(1, &[reading("203.0.64.0/18", 9999)], 1_000)
+                .len(),
+            1
+        );
+
+        // Once the reconciled ban lapses, it still counts as a first strike.
+        let ds = d.evaluate(0, &[reading("203.0.113.5", 9999)], 2_000);
+        assert_eq!((ds[0].strike, ds[0].ttl_s), (2, 240));
+    }
+
+    #[test]
+    fn shadow_mode_logs_once_and_never_escalates() {
+        let mut d = Decider::new(policy(true), ExemptPrefixes::empty());
+        let ds = d.evaluate(0, &[reading("198.51.100.7", 5000)], 1_000);
+        assert!(ds[0].shadow);
+        d.record(&ds[0], 1_000);
+        assert!(
+            d.evaluate(0, &[reading("198.51.100.7", 5000)], 1_030)
+                .is_empty()
+        );
+        let ds = d.evaluate(0, &[reading("198.51.100.7", 5000)], 1_060);
+        assert_eq!((ds[0].strike, ds[0].ttl_s), (1, 60));
+    }
+}
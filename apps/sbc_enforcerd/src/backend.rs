@@ -0,0 +1,383 @@
+//! Enforcement backends: where applied bans actually take effect.
+//!
+//! The enforcer decides *which* bans should be applied (DNS gate, expiry, exempt prefixes);
+//! a backend only turns that decision into kernel state.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use sbc_core::{BanEntry, IpFamily, IpPrefix, aggregate};
+use tracing::info;
+
+pub trait Backend: Send + std::fmt::Debug {
+    fn name(&self) -> &'static str;
+
+    /// Install the backend and make `bans` the complete applied set, replacing whatever was
+    /// applied before. Also used to resync after a snapshot.
+    fn attach(&mut self, bans: &[BanEntry]) -> anyhow::Result<()>;
+
+    /// Remove everything the backend installed. Must be safe to call when not attached.
+    fn detach(&mut self) -> anyhow::Result<()>;
+
+    fn upsert(&mut self, entry: &BanEntry) -> anyhow::Result<()>;
+
+    fn delete(&mut self, ban_id: &str) -> anyhow::Result<()>;
+
+    /// The ruleset as the backend would install it, for the status endpoint.
+    fn ruleset(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Tracks nothing in the kernel; the enforcer's own applied-ban bookkeeping is the only effect.
+#[derive(Debug, Default)]
+pub struct NoopBackend;
+
+impl Backend for NoopBackend {
+    fn name(&self) -> &'static str {
+        "noop"
+    }
+
+    fn attach(&mut self, _bans: &[BanEntry]) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn detach(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn upsert(&mut self, _entry: &BanEntry) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn delete(&mut self, _ban_id: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+const DRY_RUN_KEEP: usize = 64;
+
+/// nftables backend: an `inet` table with IPv4/IPv6 interval sets of banned and exempt
+/// prefixes, dropped at prerouting before conntrack.
+///
+/// Interval sets reject overlapping elements, so the kernel sets hold the aggregate of the
+/// applied prefixes (the "cover": nested prefixes dropped, adjacent ones merged). Each change is
+/// applied as a diff against the current cover in a single nft transaction.
+#[derive(Debug)]
+pub struct NftBackend {
+    table: String,
+    nft_bin: String,
+    dry_run: bool,
+    exempt: Vec<IpPrefix>,
+    applied: HashMap<String, IpPrefix>, // ban_id -> prefix
+    cover: Vec<IpPrefix>,               // as loaded in the kernel sets, address order
+    attached: bool,
+    /// Scripts that would have been run, newest last (dry-run only).
+    pub rendered: VecDeque<String>,
+}
+
+fn family_set(kind: &str, family: IpFamily) -> String {
+    match family {
+        IpFamily::V4 => format!("{kind}_v4"),
+        IpFamily::V6 => format!("{kind}_v6"),
+    }
+}
+
+fn element_lines(verb: &str, table: &str, prefixes: &[&IpPrefix]) -> String {
+    let mut out = String::new();
+    for family in [IpFamily::V4, IpFamily::V6] {
+        let elems = prefixes
+            .iter()
+            .filter(|p| p.family() == family)
+            .map(|p| p.to_cidr_string())
+            .collect::<Vec<_>>();
+        if !elems.is_empty() {
+            out.push_str(&format!(
+                "{verb} element inet {table} {} {{ {} }}\n",
+                family_set("ban", family),
+                elems.join(", ")
+            ));
+        }
+    }
+    out
+}
+
+impl NftBackend {
+    pub fn new(table: &str, nft_bin: &str, dry_run: bool, exempt: &[IpPrefix]) -> Self {
+        Self {
+            table: table.to_string(),
+            nft_bin: nft_bin.to_string(),
+            dry_run,
+            exempt: exempt.to_vec(),
+            applied: HashMap::new(),
+            cover: Vec::new(),
+            attached: false,
+            rendered: VecDeque::new(),
+        }
+    }
+
+    fn render_table(&self, cover: &[IpPrefix]) -> String {
+        let exempt = aggregate(&self.exempt);
+        let mut out = format!("table inet {} {{\n", self.table);
+        for (kind, elems) in [("exempt", &exempt[..]), ("ban", cover)] {
+            for family in [IpFamily::V4, IpFamily::V6] {
+                let addr_type = match family {
+                    IpFamily::V4 => "ipv4_addr",
+                    IpFamily::V6 => "ipv6_addr",
+                };
+                out.push_str(&format!(
+                    "  set {} {{\n    type {addr_type}\n    flags interval\n",
+                    family_set(kind, family)
+                ));
+                let elems = elems
+                    .iter()
+                    .filter(|p| p.family() == family)
+                    .map(|p| p.to_cidr_string())
+                    .collect::<Vec<_>>();
+                if !elems.is_empty() {
+                    out.push_str(&format!("    elements = {{ {} }}\n", elems.join(", ")));
+                }
+                out.push_str("  }\n");
+            }
+        }
+        out.push_str(
+            "  chain prerouting {\n    type filter hook prerouting priority -300; policy accept;\n",
+        );
+        out.push_str("    ip saddr @exempt_v4 accept\n    ip6 saddr @exempt_v6 accept\n");
+        out.push_str("    ip saddr @ban_v4 counter drop\n    ip6 saddr @ban_v6 counter drop\n");
+        out.push_str("  }\n}\n");
+        out
+    }
+
+    /// `add` then `delete` makes the delete succeed whether or not the table exists.
+    fn reset_prelude(&self) -> String {
+        format!(
+            "add table inet {t}\ndelete table inet {t}\n",
+            t = self.table
+        )
+    }
+
+    fn run(&mut self, script: String) -> anyhow::Result<()> {
+        if self.dry_run {
+            info!(table=%self.table, script=%script, "nft dry run");
+            if self.rendered.len() >= DRY_RUN_KEEP {
+                self.rendered.pop_front();
+            }
+            self.rendered.push_back(script);
+            return Ok(());
+        }
+        let mut child = Command::new(&self.nft_bin)
+            .args(["-f", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("spawn {}: {e}", self.nft_bin))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(script.as_bytes())?;
+        }
+        let out = child.wait_with_output()?;
+        if !out.status.success() {
+            anyhow::bail!(
+                "nft failed ({}): {}",
+                out.status,
+                String::from_utf8_lossy(&out.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+
+    /// Make `applied` the applied set, touching only the cover elements that change.
+    fn sync(&mut self, applied: HashMap<String, IpPrefix>) -> anyhow::Result<()> {
+        let cover = aggregate(applied.values());
+        let old = self.cover.iter().collect::<HashSet<_>>();
+        let new = cover.iter().collect::<HashSet<_>>();
+        let removed = self
+            .cover
+            .iter()
+            .filter(|p| !new.contains(p))
+            .collect::<Vec<_>>();
+        let added = cover
+            .iter()
+            .filter(|p| !old.contains(p))
+            .collect::<Vec<_>>();
+        if !removed.is_empty() || !added.is_empty() {
+            // Deletes first: the kernel rejects an add that overlaps an element still present.
+            let script = format!(
+                "{}{}",
+                element_lines("delete", &self.table, &removed),
+                element_lines("add", &self.table, &added)
+            );
+            self.run(script)?;
+        }
+        self.applied = applied;
+        self.cover = cover;
+        Ok(())
+    }
+}
+
+impl Backend for NftBackend {
+    fn name(&self) -> &'static str {
+        "nftables"
+    }
+
+    fn attach(&mut self, bans: &[BanEntry]) -> anyhow::Result<()> {
+        let applied = bans
+            .iter()
+            .map(|b| (b.ban_id.clone(), b.key.clone()))
+            .collect::<HashMap<_, _>>();
+        let cover = aggregate(applied.values());
+        // One transaction: the old table is replaced atomically, never absent.
+        let script = format!("{}{}", self.reset_prelude(), self.render_table(&cover));
+        self.run(script)?;
+        self.applied = applied;
+        self.cover = cover;
+        self.attached = true;
+        Ok(())
+    }
+
+    fn detach(&mut self) -> anyhow::Result<()> {
+        let script = self.reset_prelude();
+        self.run(script)?;
+        self.applied.clear();
+        self.cover.clear();
+        self.attached = false;
+        Ok(())
+    }
+
+    fn upsert(&mut self, entry: &BanEntry) -> anyhow::Result<()> {
+        if !self.attached {
+            anyhow::bail!("nftables backend not attached");
+        }
+        if self.applied.get(&entry.ban_id) == Some(&entry.key) {
+            return Ok(());
+        }
+        let mut applied = self.applied.clone();
+        applied.insert(entry.ban_id.clone(), entry.key.clone());
+        self.sync(applied)
+    }
+
+    fn delete(&mut self, ban_id: &str) -> anyhow::Result<()> {
+        if !self.attached {
+            anyhow::bail!("nftables backend not attached");
+        }
+        if !self.applied.contains_key(ban_id) {
+            return Ok(());
+        }
+        let mut applied = self.applied.clone();
+        applied.remove(ban_id);
+        self.sync(applied)
+    }
+
+    fn ruleset(&self) -> Option<String> {
+        Some(self.render_table(&self.cover))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ban(id: &str, cidr: &str) -> BanEntry {
+        BanEntry {
+            ban_id: id.to_string(),
+            key: IpPrefix::parse_cidr(cidr).unwrap(),
+            created_at_unix: 0,
+            created_by: "test".to_string(),
+            reason: String::new(),
+            expires_at_unix: 0,
+        }
+    }
+
+    fn backend() -> NftBackend {
+        let exempt = [IpPrefix::parse_cidr("192.0.2.0/24").unwrap()];
+        NftBackend::new("sbc", "nft", true, &exempt)
+    }
+
+    fn last(b: &NftBackend) -> &str {
+        b.rendered.back().unwrap()
+    }
+
+    #[test]
+    fn attach_renders_the_whole_table() {
+        let mut b = backend();
+        b.attach(&[
+            ban("a", "198.51.100.0/24"),
+            ban("b", "198.51.100.128/25"),
+            ban("c", "2001:db8::/32"),
+        ])
+        .unwrap();
+        let script = last(&b);
+        assert!(script.starts_with("add table inet sbc\ndelete table inet sbc\ntable inet sbc {"));
+        assert!(script.contains("elements = { 198.51.100.0/24 }"));
+        assert!(script.contains("elements = { 2001:db8::/32 }"));
+        assert!(script.contains("elements = { 192.0.2.0/24 }"));
+        assert!(script.contains("ip saddr @exempt_v4 accept"));
+        assert!(!script.contains("198.51.100.128/25"));
+    }
+
+    #[test]
+    fn incremental_updates_keep_the_set_aggregated() {
+        let mut b = backend();
+        b.attach(&[]).unwrap();
+
+        b.upsert(&ban("small", "203.0.113.8/29")).unwrap();
+        assert_eq!(last(&b), "add element inet sbc ban_v4 { 203.0.113.8/29 }\n");
+
+        b.upsert(&ban("big", "203.0.113.0/24")).unwrap();
+        assert_eq!(
+            last(&b),
+            "delete element inet sbc ban_v4 { 203.0.113.8/29 }\n\
+             add element inet sbc ban_v4 { 203.0.113.0/24 }\n"
+        );
+
+        // Nested inside an applied prefix: tracked, nothing to run.
+        let n = b.rendered.len();
+        b.upsert(&ban("inner", "203.0.113.64/26")).unwrap();
+        assert_eq!(b.rendered.len(), n);
+
+        b.delete("big").unwrap();
+        assert_eq!(
+            last(&b),
+            "delete element inet sbc ban_v4 { 203.0.113.0/24 }\n\
+             add element inet sbc ban_v4 { 203.0.113.8/29, 203.0.113.64/26 }\n"
+        );
+
+        b.delete("inner").unwrap();
+        assert_eq!(
+            last(&b),
+            "delete element inet sbc ban_v4 { 203.0.113.64/26 }\n"
+        );
+        assert!(
+            b.ruleset()
+                .unwrap()
+                .contains("elements = { 203.0.113.8/29 }")
+        );
+
+        // Adjacent /24s load as one /23, and split again when one goes.
+        b.upsert(&ban("left", "198.51.100.0/24")).unwrap();
+        b.upsert(&ban("right", "198.51.101.0/24")).unwrap();
+        assert_eq!(
+            last(&b),
+            "delete element inet sbc ban_v4 { 198.51.100.0/24 }\n\
+             add element inet sbc ban_v4 { 198.51.100.0/23 }\n"
+        );
+        b.delete("left").unwrap();
+        assert_eq!(
+            last(&b),
+            "delete element inet sbc ban_v4 { 198.51.100.0/23 }\n\
+             add element inet sbc ban_v4 { 198.51.101.0/24 }\n"
+        );
+    }
+
+    #[test]
+    fn refuses_updates_while_detached() {
+        let mut b = backend();
+        assert!(b.upsert(&ban("a", "198.51.100.0/24")).is_err());
+        b.attach(&[ban("a", "198.51.100.0/24")]).unwrap();
+        b.detach().unwrap();
+        assert_eq!(last(&b), "add table inet sbc\ndelete table inet sbc\n");
+        assert!(b.delete("a").is_err());
+    }
+}
@@ -1,3 +1,5 @@
+mod backend;
+
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::path::PathBuf;
@@ -5,6 +7,7 @@ use std::sync::Arc;
 
 use anyhow::Context;
 use axum::{Json, Router, routing::get};
+use backend::{Backend, NftBackend, NoopBackend};
 use sbc_core::{
     AdminReq, AdminResp, BanApplyResult, BanEntry, EnforcementStatus, Event, EventEnvelope,
     EventsReq, ExemptPrefixes, SubscribeMode,
@@ -20,18 +23,23 @@ fn usage_and_exit() -> ! {
 
 USAGE:
   sbc_enforcerd [--status-http HOST:PORT]
-  sbc_enforcerd --detach-xdp --iface IFACE
+  sbc_enforcerd --detach        remove installed kernel state and exit (alias: --detach-xdp)
 
 ENV:
   SBC_NODE_ID                  default $HOSTNAME or unknown
   SBC_ADMIN_SOCK               default /run/slopmud/sbc-admin.sock
   SBC_EVENTS_SOCK              default /run/slopmud/sbc-events.sock
   SBC_STATUS_HTTP              default 127.0.0.1:9911
+  SBC_STATUS_HEARTBEAT_S       default 30; status is re-reported at least this often so sbc_raftd can spot stale nodes
   SBC_ENABLE_DNS_NAME          default empty (enforcement disabled)
   SBC_ENABLE_DNS_IP            default empty (presence check); when set, enforcement enabled only if DNS resolves to this IP
   SBC_ENABLE_DNS_INTERVAL_S    default 60
   SBC_APPLY_SNAPSHOT           default 0 (tail-only; non-persistent)
   SBC_EXEMPT_PREFIXES_PATH     default empty (no exemptions)
+  SBC_BACKEND                  noop | nftables (default noop)
+  SBC_NFT_TABLE                default sbc (inet family)
+  SBC_NFT_BIN                  default nft
+  SBC_NFT_DRY_RUN              default 0; when 1, nft scripts are logged and served at /ruleset instead of run
 "
     );
     std::process::exit(2);
@@ -43,17 +51,22 @@ struct Config {
     admin_sock: PathBuf,
     events_sock: PathBuf,
     status_http: SocketAddr,
+    status_heartbeat_s: u64,
     enable_dns_name: String,
     enable_dns_ip: Option<std::net::IpAddr>,
     enable_dns_interval_s: u64,
     apply_snapshot: bool,
     exempt_prefixes_path: Option<PathBuf>,
+    backend: String,
+    nft_table: String,
+    nft_bin: String,
+    nft_dry_run: bool,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum Mode {
     Run,
-    DetachXdp,
+    Detach,
 }
 
 fn parse_args() -> anyhow::Result<(Config, Mode)> {
@@ -71,6 +84,10 @@ fn parse_args() -> anyhow::Result<(Config, Mode)> {
         .unwrap_or_else(|_| "127.0.0.1:9911".to_string())
         .parse()
         .map_err(|_| anyhow::anyhow!("bad SBC_STATUS_HTTP"))?;
+    let status_heartbeat_s: u64 = std::env::var("SBC_STATUS_HEARTBEAT_S")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(30);
 
     let enable_dns_name = std::env::var("SBC_ENABLE_DNS_NAME").unwrap_or_default();
     let enable_dns_ip: Option<std::net::IpAddr> = match std::env::var("SBC_ENABLE_DNS_IP").ok() {
@@ -91,6 +108,15 @@ fn parse_args() -> anyhow::Result<(Config, Mode)> {
     let exempt_prefixes_path = std::env::var("SBC_EXEMPT_PREFIXES_PATH")
         .ok()
         .map(Into::into);
+    let backend = std::env::var("SBC_BACKEND").unwrap_or_else(|_| "noop".to_string());
+    if backend != "noop" && backend != "nftables" {
+        anyhow::bail!("bad SBC_BACKEND (want noop or nftables)");
+    }
+    let nft_table = std::env::var("SBC_NFT_TABLE").unwrap_or_else(|_| "sbc".to_string());
+    let nft_bin = std::env::var("SBC_NFT_BIN").unwrap_or_else(|_| "nft".to_string());
+    let nft_dry_run = std::env::var("SBC_NFT_DRY_RUN")
+        .ok()
+        .is_some_and(|v| v == "1");
 
     let mut mode = Mode::Run;
 
@@ -101,7 +127,7 @@ fn parse_args() -> anyhow::Result<(Config, Mode)> {
                 let v = it.next().unwrap_or_else(|| usage_and_exit());
                 status_http = v.parse().unwrap_or_else(|_| usage_and_exit());
             }
-            "--detach-xdp" => mode = Mode::DetachXdp,
+            "--detach" | "--detach-xdp" => mode = Mode::Detach,
             "--iface" => {
                 let _iface = it.next().unwrap_or_else(|| usage_and_exit());
                 // currently unused; kept for forward compatibility
@@ -117,17 +143,22 @@ fn parse_args() -> anyhow::Result<(Config, Mode)> {
             admin_sock,
             events_sock,
             status_http,
+            status_heartbeat_s,
             enable_dns_name,
             enable_dns_ip,
             enable_dns_interval_s,
             apply_snapshot,
             exempt_prefixes_path,
+            backend,
+            nft_table,
+            nft_bin,
+            nft_dry_run,
         },
         mode,
     ))
 }
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 struct SharedState {
     node_id: String,
     dns_name: String,
@@ -142,8 +173,9 @@ struct SharedState {
     enforcement_mode: String,   // enforcing | fail_open
     enforcement_reason: String, // dns_enabled | dns_disabled_or_error | startup
 
-    backend: String,
+    backend: Box<dyn Backend>,
     backend_attached: bool,
+    backend_last_error: Option<String>,
 
     events_connected: bool,
     events_last_index: u64,
@@ -152,7 +184,7 @@ struct SharedState {
     // Desired bans from raft (regardless of enforcement mode).
     desired_bans: HashMap<String, BanEntry>,
 
-    // No-op backend tracks "applied" bans while enforcement is enabled.
+    // Bans the backend has accepted while attached.
     applied_bans: HashMap<String, BanEntry>,
 
     exempt: ExemptPrefixes,
@@ -188,6 +220,7 @@ struct EnforcementView {
     reason: String,
     backend: String,
     backend_attached: bool,
+    backend_last_error: Option<String>,
 }
 
 #[derive(Clone, Debug, serde::Serialize)]
@@ -217,6 +250,23 @@ fn now_unix() -> u64 {
         .as_secs()
 }
 
+fn apply_report(
+    node_id: &str,
+    ban_id: &str,
+    op: &str,
+    result: &str,
+    error: Option<String>,
+) -> BanApplyResult {
+    BanApplyResult {
+        node_id: node_id.to_string(),
+        ban_id: ban_id.to_string(),
+        op: op.to_string(),
+        result: result.to_string(),
+        error,
+        reported_at_unix: now_unix(),
+    }
+}
+
 async fn dns_check_value(
     name: &str,
     expect: Option<std::net::IpAddr>,
@@ -266,8 +316,14 @@ async fn send_admin_req(sock: &PathBuf, req: &AdminReq) -> anyhow::Result<AdminR
 }
 
 async fn report_status_task(cfg: Config, st: Arc<Mutex<SharedState>>, mut rx: mpsc::Receiver<()>) {
+    let heartbeat = std::time::Duration::from_secs(cfg.status_heartbeat_s.max(1));
     let mut last_sent: Option<(bool, Option<String>, bool, String, String, String)> = None;
-    while rx.recv().await.is_some() {
+    let mut last_sent_at = std::time::Instant::now();
+    loop {
+        // Changes report right away; an unchanged status still goes out every heartbeat.
+        if let Ok(None) = tokio::time::timeout(heartbeat, rx.recv()).await {
+            return;
+        }
         let snapshot = {
             let s = st.lock().await;
             (
@@ -276,11 +332,12 @@ async fn report_status_task(cfg: Config, st: Arc<Mutex<SharedState>>, mut rx: mp
                 s.backend_attached,
                 s.enforcement_mode.clone(),
                 s.dns_name.clone(),
-                s.backend.clone(),
+                s.backend.name().to_string(),
             )
         };
 
-        if last_sent.as_ref().is_some_and(|p| p == &snapshot) {
+        if last_sent.as_ref().is_some_and(|p| p == &snapshot) && last_sent_at.elapsed() < heartbeat
+        {
             continue;
         }
 
@@ -291,10 +348,13 @@ async fn report_status_task(cfg: Config, st: Arc<Mutex<SharedState>>, mut rx: mp
                 dns_name: s.dns_name.clone(),
                 dns_enabled: s.dns_enabled,
                 dns_last_error: s.dns_last_error.clone(),
-                backend: s.backend.clone(),
+                backend: s.backend.name().to_string(),
                 backend_attached: s.backend_attached,
                 enforcement_mode: s.enforcement_mode.clone(),
                 reported_at_unix: now_unix(),
+                // Not part of the change check: our own report bumps the index.
+                events_last_index: s.events_last_index,
+                reported_at_index: 0,
             }
         };
 
@@ -309,6 +369,7 @@ async fn report_status_task(cfg: Config, st: Arc<Mutex<SharedState>>, mut rx: mp
         }
 
         last_sent = Some(snapshot);
+        last_sent_at = std::time::Instant::now();
     }
 }
 
@@ -352,46 +413,68 @@ async fn dns_task(cfg: Config, st: Arc<Mutex<SharedState>>, status_tx: mpsc::Sen
                 changed = true;
             }
 
-            // No-op backend: treat "attached" as the same as "enforcing".
             let want_attached = s.enforcement_mode == "enforcing";
             if s.backend_attached != want_attached {
-                s.backend_attached = want_attached;
-                changed = true;
-
-                // Detach: clear all applied state.
-                if !want_attached {
-                    s.applied_bans.clear();
-                } else {
+                let now = now_unix();
+                if want_attached {
                     // Attach: (re)apply the current desired ban set.
-                    let now = now_unix();
-                    let desired = s.desired_bans.values().cloned().collect::<Vec<_>>();
-                    let mut new_applied = HashMap::new();
-                    for b in desired {
+                    let mut eligible = Vec::new();
+                    for b in s.desired_bans.values() {
                         if b.expires_at_unix != 0 && b.expires_at_unix <= now {
                             continue;
                         }
                         if s.exempt.contains_prefix(&b.key) {
-                            sync_reports.push(BanApplyResult {
-                                node_id: cfg.node_id.clone(),
-                                ban_id: b.ban_id.clone(),
-                                op: "sync".to_string(),
-                                result: "skipped".to_string(),
-                                error: Some("exempt_prefix".to_string()),
-                                reported_at_unix: now,
-                            });
+                            sync_reports.push(apply_report(
+                                &cfg.node_id,
+                                &b.ban_id,
+                                "sync",
+                                "skipped",
+                                Some("exempt_prefix".to_string()),
+                            ));
                             continue;
                         }
-                        new_applied.insert(b.ban_id.clone(), b.clone());
-                        sync_reports.push(BanApplyResult {
-                            node_id: cfg.node_id.clone(),
-                            ban_id: b.ban_id.clone(),
-                            op: "sync".to_string(),
-                            result: "ok".to_string(),
-                            error: None,
-                            reported_at_unix: now,
-                        });
+                        eligible.push(b.clone());
+                    }
+                    match s.backend.attach(&eligible) {
+                        Ok(()) => {
+                            for b in &eligible {
+                                sync_reports.push(apply_report(
+                                    &cfg.node_id,
+                                    &b.ban_id,
+                                    "sync",
+                                    "ok",
+                                    None,
+                                ));
+                            }
+                            s.applied_bans = eligible
+                                .into_iter()
+                                .map(|b| (b.ban_id.clone(), b))
+                                .collect();
+                            s.backend_attached = true;
+                            s.backend_last_error = None;
+                            changed = true;
+                        }
+                        Err(e) => {
+                            // Retried on the next tick; skip the per-ban reports until then.
+                            warn!(err=%e, "backend attach failed");
+                            sync_reports.clear();
+                            s.backend_last_error = Some(e.to_string());
+                        }
+                    }
+                } else {
+                    // Detach: clear all applied state.
+                    match s.backend.detach() {
+                        Ok(()) => {
+                            s.applied_bans.clear();
+                            s.backend_attached = false;
+                            s.backend_last_error = None;
+                            changed = true;
+                        }
+                        Err(e) => {
+                            warn!(err=%e, "backend detach failed");
+                            s.backend_last_error = Some(e.to_string());
+                        }
                     }
-                    s.applied_bans = new_applied;
                 }
             }
         }
@@ -421,11 +504,13 @@ async fn events_task(cfg: Config, st: Arc<Mutex<SharedState>>, status_tx: mpsc::
     };
 
     loop {
-        {
+        // After the first connection, pick up right after the last event we saw.
+        let from_index = {
             let mut s = st.lock().await;
             s.events_connected = false;
             s.events_last_error = None;
-        }
+            (s.events_last_index > 0).then(|| s.events_last_index + 1)
+        };
 
         let mut stream = match UnixStream::connect(&cfg.events_sock).await {
             Ok(s) => s,
@@ -439,7 +524,7 @@ async fn events_task(cfg: Config, st: Arc<Mutex<SharedState>>, status_tx: mpsc::
             }
         };
 
-        let sub = EventsReq::Subscribe { mode };
+        let sub = EventsReq::Subscribe { mode, from_index };
         if let Err(e) = stream
             .write_all(serde_json::to_string(&sub).unwrap().as_bytes())
             .await
@@ -492,7 +577,7 @@ async fn events_task(cfg: Config, st: Arc<Mutex<SharedState>>, status_tx: mpsc::
             {
                 let mut s = st.lock().await;
                 s.events_last_index = s.events_last_index.max(env.index);
-                let enforcing = s.enforcement_mode == "enforcing";
+                let enforcing = s.enforcement_mode == "enforcing" && s.backend_attached;
 
                 match env.event {
                     Event::Snapshot { bans, .. } => {
@@ -506,75 +591,94 @@ async fn events_task(cfg: Config, st: Arc<Mutex<SharedState>>, status_tx: mpsc::
                         }
 
                         if enforcing {
-                            let desired = s.desired_bans.values().cloned().collect::<Vec<_>>();
-                            let mut new_applied = HashMap::new();
-                            for b in desired {
-                                if s.exempt.contains_prefix(&b.key) {
-                                    continue;
+                            let eligible = s
+                                .desired_bans
+                                .values()
+                                .filter(|b| !s.exempt.contains_prefix(&b.key))
+                                .cloned()
+                                .collect::<Vec<_>>();
+                            match s.backend.attach(&eligible) {
+                                Ok(()) => {
+                                    s.applied_bans = eligible
+                                        .into_iter()
+                                        .map(|b| (b.ban_id.clone(), b))
+                                        .collect();
+                                    s.backend_last_error = None;
+                                }
+                                Err(e) => {
+                                    warn!(err=%e, "backend resync from snapshot failed");
+                                    s.backend_last_error = Some(e.to_string());
                                 }
-                                new_applied.insert(b.ban_id.clone(), b);
                             }
-                            s.applied_bans = new_applied;
                         }
                     }
                     Event::BanUpserted { entry } => {
-                        let op = "upsert".to_string();
+                        let op = "upsert";
                         s.desired_bans.insert(entry.ban_id.clone(), entry.clone());
-                        if enforcing {
-                            if s.exempt.contains_prefix(&entry.key) {
-                                report = Some(BanApplyResult {
-                                    node_id: cfg.node_id.clone(),
-                                    ban_id: entry.ban_id.clone(),
-                                    op,
-                                    result: "skipped".to_string(),
-                                    error: Some("exempt_prefix".to_string()),
-                                    reported_at_unix: now_unix(),
-                                });
-                            } else {
-                                s.applied_bans.insert(entry.ban_id.clone(), entry.clone());
-                                report = Some(BanApplyResult {
-                                    node_id: cfg.node_id.clone(),
-                                    ban_id: entry.ban_id.clone(),
-                                    op,
-                                    result: "ok".to_string(),
-                                    error: None,
-                                    reported_at_unix: now_unix(),
-                                });
-                            }
-                        } else {
-                            report = Some(BanApplyResult {
-                                node_id: cfg.node_id.clone(),
-                                ban_id: entry.ban_id.clone(),
+                        report = Some(if !enforcing {
+                            apply_report(
+                                &cfg.node_id,
+                                &entry.ban_id,
                                 op,
-                                result: "skipped".to_string(),
-                                error: Some("enforcement_disabled".to_string()),
-                                reported_at_unix: now_unix(),
-                            });
-                        }
+                                "skipped",
+                                Some("enforcement_disabled".to_string()),
+                            )
+                        } else if s.exempt.contains_prefix(&entry.key) {
+                            apply_report(
+                                &cfg.node_id,
+                                &entry.ban_id,
+                                op,
+                                "skipped",
+                                Some("exempt_prefix".to_string()),
+                            )
+                        } else {
+                            match s.backend.upsert(&entry) {
+                                Ok(()) => {
+                                    s.applied_bans.insert(entry.ban_id.clone(), entry.clone());
+                                    apply_report(&cfg.node_id, &entry.ban_id, op, "ok", None)
+                                }
+                                Err(e) => {
+                                    warn!(err=%e, ban_id=%entry.ban_id, "backend upsert failed");
+                                    apply_report(
+                                        &cfg.node_id,
+                                        &entry.ban_id,
+                                        op,
+                                        "err",
+                                        Some(e.to_string()),
+                                    )
+                                }
+                            }
+                        });
                     }
                     Event::BanDeleted { ban_id } => {
-                        let op = "delete".to_string();
+                        let op = "delete";
                         s.desired_bans.remove(&ban_id);
-                        if enforcing {
-                            s.applied_bans.remove(&ban_id);
-                            report = Some(BanApplyResult {
-                                node_id: cfg.node_id.clone(),
-                                ban_id: ban_id.clone(),
+                        report = Some(if !enforcing {
+                            apply_report(
+                                &cfg.node_id,
+                                &ban_id,
                                 op,
-                                result: "ok".to_string(),
-                                error: None,
-                                reported_at_unix: now_unix(),
-                            });
+                                "skipped",
+                                Some("enforcement_disabled".to_string()),
+                            )
                         } else {
-                            report = Some(BanApplyResult {
-                                node_id: cfg.node_id.clone(),
-                                ban_id: ban_id.clone(),
-                                op,
-                                result: "skipped".to_string(),
-                                error: Some("enforcement_disabled".to_string()),
-                                reported_at_unix: now_unix(),
-                            });
-                        }
+                            match s.backend.delete(&ban_id) {
+                                Ok(()) => {
+                                    s.applied_bans.remove(&ban_id);
+                                    apply_report(&cfg.node_id, &ban_id, op, "ok", None)
+                                }
+                                Err(e) => {
+                                    warn!(err=%e, ban_id=%ban_id, "backend delete failed");
+                                    apply_report(
+                                        &cfg.node_id,
+                                        &ban_id,
+                                        op,
+                                        "err",
+                                        Some(e.to_string()),
+                                    )
+                                }
+                            }
+                        });
                     }
                     Event::LegalHoldUpserted { .. } | Event::LegalHoldDeleted { .. } => {}
                     Event::EnforcementStatus { .. } | Event::BanApplyResult { .. } => {}
@@ -611,8 +715,9 @@ async fn status_server(cfg: Config, st: Arc<Mutex<SharedState>>) -> anyhow::Resu
             enforcement: EnforcementView {
                 mode: s.enforcement_mode.clone(),
                 reason: s.enforcement_reason.clone(),
-                backend: s.backend.clone(),
+                backend: s.backend.name().to_string(),
                 backend_attached: s.backend_attached,
+                backend_last_error: s.backend_last_error.clone(),
             },
             events: EventsView {
                 connected: s.events_connected,
@@ -623,7 +728,7 @@ async fn status_server(cfg: Config, st: Arc<Mutex<SharedState>>) -> anyhow::Resu
             exempt_prefixes: ExemptView {
                 loaded: s.exempt_loaded,
                 path: s.exempt_path.clone(),
-                count: s.exempt.prefixes.len(),
+                count: s.exempt.prefixes().len(),
                 last_error: s.exempt_last_error.clone(),
             },
             applied_bans: AppliedBansView {
@@ -635,6 +740,14 @@ async fn status_server(cfg: Config, st: Arc<Mutex<SharedState>>) -> anyhow::Resu
     let app = Router::new()
         .route("/healthz", get(|| async { "ok\n" }))
         .route("/status", get(handler))
+        .route(
+            "/ruleset",
+            get(
+                |st: axum::extract::State<Arc<Mutex<SharedState>>>| async move {
+                    st.lock().await.backend.ruleset().unwrap_or_default()
+                },
+            ),
+        )
         .with_state(st);
 
     info!(bind=%cfg.status_http, "status http listening");
@@ -657,11 +770,6 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     let (cfg, mode) = parse_args()?;
-    if mode == Mode::DetachXdp {
-        // Placeholder for future XDP backend. Must be safe to call from systemd ExecStopPost.
-        info!("detach-xdp requested (noop backend)");
-        return Ok(());
-    }
 
     let (exempt, exempt_loaded, exempt_path, exempt_err) = match cfg.exempt_prefixes_path.as_ref() {
         Some(p) => match ExemptPrefixes::load(p) {
@@ -676,6 +784,29 @@ async fn main() -> anyhow::Result<()> {
         None => (ExemptPrefixes::empty(), false, None, None),
     };
 
+    let mut backend: Box<dyn Backend> = match cfg.backend.as_str() {
+        "nftables" => Box::new(NftBackend::new(
+            &cfg.nft_table,
+            &cfg.nft_bin,
+            cfg.nft_dry_run,
+            exempt.prefixes(),
+        )),
+        _ => Box::new(NoopBackend),
+    };
+
+    if mode == Mode::Detach {
+        // Must be safe to call from systemd ExecStopPost, attached or not.
+        backend.detach()?;
+        info!(backend = backend.name(), "detached");
+        return Ok(());
+    }
+    // Start fail-open: clear anything a previous run left installed. The DNS poller attaches
+    // again once enforcement is enabled.
+    let backend_last_error = backend.detach().err().map(|e| {
+        warn!(err=%e, "startup detach failed");
+        e.to_string()
+    });
+
     let st = Arc::new(Mutex::new(SharedState {
         node_id: cfg.node_id.clone(),
         dns_name: cfg.enable_dns_name.clone(),
@@ -690,8 +821,9 @@ async fn main() -> anyhow::Result<()> {
         enforcement_mode: "fail_open".to_string(),
         enforcement_reason: "startup".to_string(),
 
-        backend: "noop".to_string(),
+        backend,
         backend_attached: false,
+        backend_last_error,
 
         events_connected: false,
         events_last_index: 0,
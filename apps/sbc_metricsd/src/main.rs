@@ -1,4 +1,7 @@
+mod window;
+
 use std::collections::HashMap;
+use std::fmt::Write as _;
 use std::net::SocketAddr;
 use std::sync::Arc;
 
@@ -7,6 +10,7 @@ use axum::{Json, Router, extract::Query, routing::get};
 use sbc_core::IpPrefix;
 use tokio::sync::Mutex;
 use tracing::{Level, info, warn};
+use window::{Counters, Metric, Windowed};
 
 fn usage_and_exit() -> ! {
     eprintln!(
@@ -16,8 +20,15 @@ USAGE:
   sbc_metricsd [--statsd-bind HOST:PORT] [--http-bind HOST:PORT]
 
 ENV:
-  SBC_STATSD_BIND  default 0.0.0.0:8125
-  SBC_METRICS_HTTP default 127.0.0.1:9912
+  SBC_STATSD_BIND          default 0.0.0.0:8125
+  SBC_METRICS_HTTP         default 127.0.0.1:9912
+  SBC_METRICS_BUCKET_S     default 10 (bucket granularity)
+  SBC_METRICS_HORIZON_S    default 3600 (longest queryable window)
+  SBC_METRICS_MAX_KEYS     default 100000 (per group; least recently updated keys evicted)
+
+HTTP:
+  GET /top?metric=bytes_in|bytes_out|conns|login_failures|accounts_created|reports&group=src|v4_18|v6_48&n=20&window=60s
+  GET /metrics   Prometheus text format
 "
     );
     std::process::exit(2);
@@ -27,6 +38,9 @@ ENV:
 struct Config {
     statsd_bind: SocketAddr,
     http_bind: SocketAddr,
+    bucket_s: u64,
+    horizon_s: u64,
+    max_keys: usize,
 }
 
 fn parse_args() -> Config {
@@ -38,6 +52,18 @@ fn parse_args() -> Config {
         .unwrap_or_else(|_| "127.0.0.1:9912".to_string())
         .parse()
         .unwrap_or_else(|_| usage_and_exit());
+    let bucket_s: u64 = std::env::var("SBC_METRICS_BUCKET_S")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(10);
+    let horizon_s: u64 = std::env::var("SBC_METRICS_HORIZON_S")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(3600);
+    let max_keys: usize = std::env::var("SBC_METRICS_MAX_KEYS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(100_000);
 
     let mut it = std::env::args().skip(1);
     while let Some(arg) = it.next() {
@@ -58,21 +84,60 @@ fn parse_args() -> Config {
     Config {
         statsd_bind,
         http_bind,
+        bucket_s,
+        horizon_s,
+        max_keys,
     }
 }
 
-#[derive(Clone, Default, Debug)]
-struct Counters {
-    bytes_in: u64,
-    bytes_out: u64,
-    conns: u64,
+#[derive(Debug)]
+struct Agg {
+    by_src: Windowed,
+    by_v4_18: Windowed,
+    by_v6_48: Windowed,
+    // Everything, as a single key; backs the global rates on /metrics.
+    all: Windowed,
+    lifetime: Counters,
+    lines_ok: u64,
+    lines_ignored: u64,
 }
 
-#[derive(Clone, Default, Debug)]
-struct Agg {
-    by_src: HashMap<String, Counters>,
-    by_v4_18: HashMap<String, Counters>,
-    by_v6_48: HashMap<String, Counters>,
+impl Agg {
+    fn new(cfg: &Config) -> Self {
+        let w = || Windowed::new(cfg.bucket_s, cfg.horizon_s, cfg.max_keys);
+        Self {
+            by_src: w(),
+            by_v4_18: w(),
+            by_v6_48: w(),
+            all: Windowed::new(cfg.bucket_s, cfg.horizon_s, 1),
+            lifetime: Counters::default(),
+            lines_ok: 0,
+            lines_ignored: 0,
+        }
+    }
+
+    fn group(&self, group: &str) -> Option<&Windowed> {
+        match group {
+            "src" => Some(&self.by_src),
+            "v4_18" => Some(&self.by_v4_18),
+            "v6_48" => Some(&self.by_v6_48),
+            _ => None,
+        }
+    }
+
+    fn expire(&mut self, now_s: u64) {
+        self.by_src.expire(now_s);
+        self.by_v4_18.expire(now_s);
+        self.by_v6_48.expire(now_s);
+        self.all.expire(now_s);
+    }
+}
+
+fn now_unix() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
 }
 
 fn parse_statsd_line(line: &str) -> Option<(String, i64, String, HashMap<String, String>)> {
@@ -132,118 +197,192 @@ async fn statsd_task(cfg: Config, agg: Arc<Mutex<Agg>>) -> anyhow::Result<()> {
         let s = String::from_utf8_lossy(&buf[..n]);
         for raw_line in s.lines() {
             let Some((name, val, typ, tags)) = parse_statsd_line(raw_line) else {
+                if !raw_line.trim().is_empty() {
+                    agg.lock().await.lines_ignored += 1;
+                }
                 continue;
             };
-            if typ != "c" {
-                continue;
-            }
-            let Some(src) = tags.get("src").cloned() else {
+            // `sbc.<metric>`, e.g. sbc.bytes_in or sbc.login_failures.
+            let c = name
+                .strip_prefix("sbc.")
+                .and_then(Metric::parse)
+                .map(|m| Counters::of(m, val.max(0) as u64))
+                .unwrap_or_default();
+            let mut a = agg.lock().await;
+            let Some(src) = tags
+                .get("src")
+                .filter(|_| typ == "c" && c != Counters::default())
+            else {
+                a.lines_ignored += 1;
                 continue;
             };
+            a.lines_ok += 1;
 
-            let mut a = agg.lock().await;
-            let c = a.by_src.entry(src.clone()).or_default();
-            match name.as_str() {
-                "sbc.bytes_in" => c.bytes_in = c.bytes_in.saturating_add(val.max(0) as u64),
-                "sbc.bytes_out" => c.bytes_out = c.bytes_out.saturating_add(val.max(0) as u64),
-                "sbc.conns" => c.conns = c.conns.saturating_add(val.max(0) as u64),
-                _ => continue,
-            }
+            let now = now_unix();
+            a.lifetime.add(&c);
+            a.all.add("all", now, &c);
+            a.by_src.add(src, now, &c);
 
             // Also aggregate into v4/v6 blocks.
             if let Ok(ip) = src.parse::<std::net::IpAddr>() {
                 match ip {
-                    std::net::IpAddr::V4(v4) => {
-                        let k = v4_18_key(v4);
-                        let c = a.by_v4_18.entry(k).or_default();
-                        match name.as_str() {
-                            "sbc.bytes_in" => {
-                                c.bytes_in = c.bytes_in.saturating_add(val.max(0) as u64)
-                            }
-                            "sbc.bytes_out" => {
-                                c.bytes_out = c.bytes_out.saturating_add(val.max(0) as u64)
-                            }
-                            "sbc.conns" => c.conns = c.conns.saturating_add(val.max(0) as u64),
-                            _ => {}
-                        }
-                    }
-                    std::net::IpAddr::V6(v6) => {
-                        let k = v6_48_key(v6);
-                        let c = a.by_v6_48.entry(k).or_default();
-                        match name.as_str() {
-                            "sbc.bytes_in" => {
-                                c.bytes_in = c.bytes_in.saturating_add(val.max(0) as u64)
-                            }
-                            "sbc.bytes_out" => {
-                                c.bytes_out = c.bytes_out.saturating_add(val.max(0) as u64)
-                            }
-                            "sbc.conns" => c.conns = c.conns.saturating_add(val.max(0) as u64),
-                            _ => {}
-                        }
-                    }
+                    std::net::IpAddr::V4(v4) => a.by_v4_18.add(&v4_18_key(v4), now, &c),
+                    std::net::IpAddr::V6(v6) => a.by_v6_48.add(&v6_48_key(v6), now, &c),
                 }
             }
         }
     }
 }
 
+async fn expire_task(agg: Arc<Mutex<Agg>>, bucket_s: u64) {
+    let mut tick = tokio::time::interval(std::time::Duration::from_secs(bucket_s.max(1)));
+    loop {
+        tick.tick().await;
+        agg.lock().await.expire(now_unix());
+    }
+}
+
 #[derive(serde::Deserialize)]
 struct TopQuery {
-    metric: Option<String>, // bytes_in | bytes_out | conns
+    metric: Option<String>, // bytes_in | bytes_out | conns | login_failures | accounts_created | reports
     group: Option<String>,  // src | v4_18 | v6_48
     n: Option<usize>,
+    window: Option<String>, // 60s | 5m | 1h | seconds; default 60s
 }
 
 #[derive(serde::Serialize)]
 struct TopItem {
     key: String,
     value: u64,
+    rate_per_s: f64,
 }
 
 #[derive(serde::Serialize)]
 struct TopResp {
     metric: String,
     group: String,
+    window_s: u64,
     top: Vec<TopItem>,
 }
 
 async fn top_handler(
     Query(q): Query<TopQuery>,
     axum::extract::State(agg): axum::extract::State<Arc<Mutex<Agg>>>,
-) -> Json<TopResp> {
-    let metric = q.metric.unwrap_or_else(|| "bytes_in".to_string());
+) -> Result<Json<TopResp>, (axum::http::StatusCode, String)> {
+    let bad = |m: &str| (axum::http::StatusCode::BAD_REQUEST, format!("{m}\n"));
+    let metric = Metric::parse(q.metric.as_deref().unwrap_or("bytes_in"))
+        .ok_or_else(|| bad("bad metric"))?;
     let group = q.group.unwrap_or_else(|| "src".to_string());
     let n = q.n.unwrap_or(20).clamp(1, 200);
-
-    let a = agg.lock().await;
-    let map: &HashMap<String, Counters> = match group.as_str() {
-        "src" => &a.by_src,
-        "v4_18" => &a.by_v4_18,
-        "v6_48" => &a.by_v6_48,
-        _ => &a.by_src,
+    let window_s = match q.window.as_deref() {
+        Some(w) => window::parse_window(w).ok_or_else(|| bad("bad window"))?,
+        None => 60,
     };
 
-    let mut v = map
-        .iter()
-        .map(|(k, c)| {
-            let val = match metric.as_str() {
-                "bytes_out" => c.bytes_out,
-                "conns" => c.conns,
-                _ => c.bytes_in,
-            };
-            TopItem {
-                key: k.clone(),
-                value: val,
-            }
+    let a = agg.lock().await;
+    let w = a.group(&group).ok_or_else(|| bad("bad group"))?;
+    let window_s = w.effective_window_s(window_s);
+    let top = w
+        .top(metric, now_unix(), window_s, n)
+        .into_iter()
+        .map(|(key, value)| TopItem {
+            key,
+            value,
+            rate_per_s: value as f64 / window_s as f64,
         })
-        .collect::<Vec<_>>();
-    v.sort_by_key(|x| std::cmp::Reverse(x.value));
-    v.truncate(n);
-    Json(TopResp {
-        metric,
+        .collect();
+    Ok(Json(TopResp {
+        metric: metric.name().to_string(),
         group,
-        top: v,
-    })
+        window_s,
+        top,
+    }))
+}
+
+fn render_prometheus(a: &Agg, now_s: u64) -> String {
+    let mut out = String::new();
+    for m in Metric::ALL {
+        let name = m.name();
+        let _ = writeln!(
+            out,
+            "# HELP sbc_{name}_total Lifetime {name} reported over statsd."
+        );
+        let _ = writeln!(out, "# TYPE sbc_{name}_total counter");
+        let _ = writeln!(out, "sbc_{name}_total {}", a.lifetime.get(m));
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP sbc_rate_per_second Average rate over a trailing window."
+    );
+    let _ = writeln!(out, "# TYPE sbc_rate_per_second gauge");
+    let mut windows = vec![10, 60, 300, a.all.horizon_s()];
+    windows.retain(|w| *w <= a.all.horizon_s());
+    windows.dedup();
+    for w in windows {
+        let w = a.all.effective_window_s(w);
+        let sum = a.all.sum("all", now_s, w);
+        for m in Metric::ALL {
+            let _ = writeln!(
+                out,
+                "sbc_rate_per_second{{metric=\"{}\",window=\"{w}s\"}} {}",
+                m.name(),
+                sum.get(m) as f64 / w as f64
+            );
+        }
+    }
+
+    let groups = [
+        ("src", &a.by_src),
+        ("v4_18", &a.by_v4_18),
+        ("v6_48", &a.by_v6_48),
+    ];
+    let _ = writeln!(out, "# HELP sbc_metricsd_keys Keys currently tracked.");
+    let _ = writeln!(out, "# TYPE sbc_metricsd_keys gauge");
+    for (g, w) in groups {
+        let _ = writeln!(out, "sbc_metricsd_keys{{group=\"{g}\"}} {}", w.len());
+    }
+    let _ = writeln!(
+        out,
+        "# HELP sbc_metricsd_evictions_total Keys evicted to stay under the key cap."
+    );
+    let _ = writeln!(out, "# TYPE sbc_metricsd_evictions_total counter");
+    for (g, w) in groups {
+        let _ = writeln!(
+            out,
+            "sbc_metricsd_evictions_total{{group=\"{g}\"}} {}",
+            w.evicted
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP sbc_metricsd_statsd_lines_total StatsD lines received."
+    );
+    let _ = writeln!(out, "# TYPE sbc_metricsd_statsd_lines_total counter");
+    let _ = writeln!(
+        out,
+        "sbc_metricsd_statsd_lines_total{{result=\"ok\"}} {}",
+        a.lines_ok
+    );
+    let _ = writeln!(
+        out,
+        "sbc_metricsd_statsd_lines_total{{result=\"ignored\"}} {}",
+        a.lines_ignored
+    );
+    out
+}
+
+async fn metrics_handler(
+    axum::extract::State(agg): axum::extract::State<Arc<Mutex<Agg>>>,
+) -> impl axum::response::IntoResponse {
+    let body = render_prometheus(&*agg.lock().await, now_unix());
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        body,
+    )
 }
 
 #[tokio::main]
@@ -256,7 +395,7 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     let cfg = parse_args();
-    let agg = Arc::new(Mutex::new(Agg::default()));
+    let agg = Arc::new(Mutex::new(Agg::new(&cfg)));
 
     // StatsD UDP listener in background.
     {
@@ -269,9 +408,12 @@ async fn main() -> anyhow::Result<()> {
         });
     }
 
+    tokio::spawn(expire_task(agg.clone(), cfg.bucket_s));
+
     let app = Router::new()
         .route("/healthz", get(|| async { "ok\n" }))
         .route("/top", get(top_handler))
+        .route("/metrics", get(metrics_handler))
         .with_state(agg);
 
     info!(bind=%cfg.http_bind, "metrics http listening");
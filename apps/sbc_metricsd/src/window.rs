@@ -0,0 +1,313 @@
+//! Per-key counters in fixed-width time buckets over a bounded horizon.
+//!
+//! Buckets are stored sparsely (only buckets that saw traffic), so a quiet key costs a few
+//! entries rather than the whole horizon. Keys are capped; past the cap the least recently
+//! updated key is evicted.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct Counters {
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub conns: u64,
+    pub login_failures: u64,
+    pub accounts_created: u64,
+    pub reports: u64,
+}
+
+impl Counters {
+    pub fn add(&mut self, o: &Counters) {
+        self.bytes_in = self.bytes_in.saturating_add(o.bytes_in);
+        self.bytes_out = self.bytes_out.saturating_add(o.bytes_out);
+        self.conns = self.conns.saturating_add(o.conns);
+        self.login_failures = self.login_failures.saturating_add(o.login_failures);
+        self.accounts_created = self.accounts_created.saturating_add(o.accounts_created);
+        self.reports = self.reports.saturating_add(o.reports);
+    }
+
+    /// A single reading of `metric`.
+    pub fn of(metric: Metric, value: u64) -> Self {
+        let mut c = Self::default();
+        match metric {
+            Metric::BytesIn => c.bytes_in = value,
+            Metric::BytesOut => c.bytes_out = value,
+            Metric::Conns => c.conns = value,
+            Metric::LoginFailures => c.login_failures = value,
+            Metric::AccountsCreated => c.accounts_created = value,
+            Metric::Reports => c.reports = value,
+        }
+        c
+    }
+
+    pub fn get(&self, metric: Metric) -> u64 {
+        match metric {
+            Metric::BytesIn => self.bytes_in,
+            Metric::BytesOut => self.bytes_out,
+            Metric::Conns => self.conns,
+            Metric::LoginFailures => self.login_failures,
+            Metric::AccountsCreated => self.accounts_created,
+            Metric::Reports => self.reports,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    BytesIn,
+    BytesOut,
+    Conns,
+    LoginFailures,
+    AccountsCreated,
+    Reports,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 6] = [
+        Metric::BytesIn,
+        Metric::BytesOut,
+        Metric::Conns,
+        Metric::LoginFailures,
+        Metric::AccountsCreated,
+        Metric::Reports,
+    ];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "bytes_in" => Some(Self::BytesIn),
+            "bytes_out" => Some(Self::BytesOut),
+            "conns" => Some(Self::Conns),
+            "login_failures" => Some(Self::LoginFailures),
+            "accounts_created" => Some(Self::AccountsCreated),
+            "reports" => Some(Self::Reports),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::BytesIn => "bytes_in",
+            Self::BytesOut => "bytes_out",
+            Self::Conns => "conns",
+            Self::LoginFailures => "login_failures",
+            Self::AccountsCreated => "accounts_created",
+            Self::Reports => "reports",
+        }
+    }
+}
+
+/// Parse a window like `60s`, `5m`, `1h` or bare seconds.
+pub fn parse_window(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (num, mul) = match s.char_indices().last()? {
+        (i, 's') => (&s[..i], 1),
+        (i, 'm') => (&s[..i], 60),
+        (i, 'h') => (&s[..i], 3600),
+        _ => (s, 1),
+    };
+    let v: u64 = num.parse().ok()?;
+    (v > 0).then(|| v.saturating_mul(mul))
+}
+
+#[derive(Debug)]
+struct Series {
+    buckets: VecDeque<(u64, Counters)>, // (bucket number, counts), oldest first
+    lru_seq: u64,
+}
+
+impl Series {
+    fn add(&mut self, bucket: u64, c: &Counters) {
+        match self.buckets.back_mut() {
+            // Arrival time never goes backwards by more than a bucket in practice; fold any
+            // straggler into the newest bucket rather than reordering.
+            Some((b, cur)) if *b >= bucket => cur.add(c),
+            _ => self.buckets.push_back((bucket, *c)),
+        }
+    }
+
+    fn prune(&mut self, oldest: u64) {
+        while self.buckets.front().is_some_and(|(b, _)| *b < oldest) {
+            self.buckets.pop_front();
+        }
+    }
+
+    fn sum_since(&self, oldest: u64) -> Counters {
+        let mut out = Counters::default();
+        for (_, c) in self.buckets.iter().rev().take_while(|(b, _)| *b >= oldest) {
+            out.add(c);
+        }
+        out
+    }
+}
+
+#[derive(Debug)]
+pub struct Windowed {
+    bucket_s: u64,
+    horizon_buckets: u64,
+    max_keys: usize,
+    series: HashMap<String, Series>,
+    lru: BTreeMap<u64, String>, // lru_seq -> key, coldest first
+    next_seq: u64,
+    pub evicted: u64,
+}
+
+impl Windowed {
+    pub fn new(bucket_s: u64, horizon_s: u64, max_keys: usize) -> Self {
+        let bucket_s = bucket_s.max(1);
+        Self {
+            bucket_s,
+            horizon_buckets: horizon_s.div_ceil(bucket_s).max(1),
+            max_keys: max_keys.max(1),
+            series: HashMap::new(),
+            lru: BTreeMap::new(),
+            next_seq: 0,
+            evicted: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.series.len()
+    }
+
+    pub fn horizon_s(&self) -> u64 {
+        self.horizon_buckets * self.bucket_s
+    }
+
+    /// The window actually covered for a requested `window_s`: whole buckets, within the horizon.
+    pub fn effective_window_s(&self, window_s: u64) -> u64 {
+        self.window_buckets(window_s) * self.bucket_s
+    }
+
+    fn window_buckets(&self, window_s: u64) -> u64 {
+        window_s
+            .div_ceil(self.bucket_s)
+            .clamp(1, self.horizon_buckets)
+    }
+
+    fn oldest_bucket(&self, now_s: u64, window_s: u64) -> u64 {
+        (now_s / self.bucket_s + 1).saturating_sub(self.window_buckets(window_s))
+    }
+
+    pub fn add(&mut self, key: &str, now_s: u64, c: &Counters) {
+        let bucket = now_s / self.bucket_s;
+        let oldest = self.oldest_bucket(now_s, self.horizon_s());
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        if let Some(s) = self.series.get_mut(key) {
+            self.lru.remove(&s.lru_seq);
+            s.lru_seq = seq;
+            s.add(bucket, c);
+            s.prune(oldest);
+        } else {
+            while self.series.len() >= self.max_keys {
+                let Some((_, cold)) = self.lru.pop_first() else {
+                    break;
+                };
+                self.series.remove(&cold);
+                self.evicted += 1;
+            }
+            let mut s = Series {
+                buckets: VecDeque::new(),
+                lru_seq: seq,
+            };
+            s.add(bucket, c);
+            self.series.insert(key.to_string(), s);
+        }
+        self.lru.insert(seq, key.to_string());
+    }
+
+    /// Drop buckets (and keys) that fell out of the horizon.
+    pub fn expire(&mut self, now_s: u64) {
+        let oldest = self.oldest_bucket(now_s, self.horizon_s());
+        let mut dead = Vec::new();
+        for (k, s) in self.series.iter_mut() {
+            s.prune(oldest);
+            if s.buckets.is_empty() {
+                dead.push((s.lru_seq, k.clone()));
+            }
+        }
+        for (seq, k) in dead {
+            self.lru.remove(&seq);
+            self.series.remove(&k);
+        }
+    }
+
+    pub fn sum(&self, key: &str, now_s: u64, window_s: u64) -> Counters {
+        let oldest = self.oldest_bucket(now_s, window_s);
+        self.series
+            .get(key)
+            .map(|s| s.sum_since(oldest))
+            .unwrap_or_default()
+    }
+
+    /// The `n` keys with the highest `metric` over the trailing window, highest first.
+    pub fn top(&self, metric: Metric, now_s: u64, window_s: u64, n: usize) -> Vec<(String, u64)> {
+        let oldest = self.oldest_bucket(now_s, window_s);
+        let mut v = self
+            .series
+            .iter()
+            .map(|(k, s)| (k.clone(), s.sum_since(oldest).get(metric)))
+            .filter(|(_, v)| *v > 0)
+            .collect::<Vec<_>>();
+        v.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        v.truncate(n);
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(n: u64) -> Counters {
+        Counters {
+            bytes_in: n,
+            ..Counters::default()
+        }
+    }
+
+    #[test]
+    fn windows_only_count_recent_buckets() {
+        let mut w = Windowed::new(10, 3600, 100);
+        w.add("a", 1_000, &bytes(5));
+        w.add("a", 1_055, &bytes(7));
+        w.add("b", 1_058, &bytes(3));
+
+        // Whole buckets, counting the current (partial) one: 50s at t=1059 covers 1010..1059.
+        assert_eq!(w.sum("a", 1_059, 10).bytes_in, 7);
+        assert_eq!(w.sum("a", 1_059, 50).bytes_in, 7);
+        assert_eq!(w.sum("a", 1_059, 60).bytes_in, 12);
+        assert_eq!(
+            w.top(Metric::BytesIn, 1_059, 50, 10),
+            vec![("a".to_string(), 7), ("b".to_string(), 3)]
+        );
+
+        // An hour later everything has aged out.
+        w.expire(1_059 + 3_600);
+        assert_eq!(w.len(), 0);
+    }
+
+    #[test]
+    fn evicts_the_least_recently_updated_key() {
+        let mut w = Windowed::new(10, 3600, 2);
+        w.add("a", 0, &bytes(1));
+        w.add("b", 1, &bytes(1));
+        w.add("a", 2, &bytes(1));
+        w.add("c", 3, &bytes(1));
+        assert_eq!(w.len(), 2);
+        assert_eq!(w.evicted, 1);
+        assert_eq!(w.sum("b", 3, 60), Counters::default());
+        assert_eq!(w.sum("a", 3, 60).bytes_in, 2);
+    }
+
+    #[test]
+    fn parses_windows() {
+        assert_eq!(parse_window("60s"), Some(60));
+        assert_eq!(parse_window("5m"), Some(300));
+        assert_eq!(parse_window("1h"), Some(3600));
+        assert_eq!(parse_window("90"), Some(90));
+        assert_eq!(parse_window("0s"), None);
+        assert_eq!(parse_window("soon"), None);
+    }
+}
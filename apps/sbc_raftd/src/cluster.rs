@@ -0,0 +1,556 @@
+//! sbc_raftd as a Raft group: the state machine over `PersistedState` and a node handle that
+//! serves `AdminReq`s from any member.
+//!
+//! Each node keeps its log and latest snapshot under its raft dir. Writes received by a follower
+//! are forwarded to the leader; reads (`GetState`) are answered from the local replica and may
+//! trail the leader by a heartbeat.
+
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use fanzylog::rpc::{Network, Proposal, Transport, propose};
+use fanzylog::storage::RaftStateMachine;
+use fanzylog::{
+    BasicNode, Entry, EntryPayload, LogId, Raft, RaftSnapshotBuilder, ServerState, Snapshot,
+    SnapshotMeta, SnapshotPolicy, StorageError, StorageIOError, StoredMembership,
+};
+use sbc_core::{AdminReq, AdminResp, EventEnvelope, RaftMember};
+use tokio::sync::{Mutex, Notify, broadcast};
+
+use crate::state::{Command, PersistedState};
+
+fanzylog::declare_raft_types!(
+    pub TypeConfig:
+        D = Command,
+        R = AdminResp,
+);
+
+pub type NodeId = u64;
+type StorageResult<T> = Result<T, StorageError<NodeId>>;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StoredSnapshot {
+    meta: SnapshotMeta<NodeId, BasicNode>,
+    state: PersistedState,
+}
+
+fn load_snapshot(path: &Path) -> std::io::Result<Option<StoredSnapshot>> {
+    match std::fs::read(path) {
+        Ok(b) => serde_json::from_slice(&b)
+            .map(Some)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn save_snapshot(path: &Path, snap: &StoredSnapshot) -> std::io::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(
+        &tmp,
+        serde_json::to_vec(snap).map_err(std::io::Error::other)?,
+    )?;
+    std::fs::rename(&tmp, path)
+}
+
+pub struct StateMachine {
+    st: Arc<Mutex<PersistedState>>,
+    events: broadcast::Sender<EventEnvelope>,
+    changed: Arc<Notify>,
+    snapshot_path: PathBuf,
+    applied: Option<LogId<NodeId>>,
+    membership: StoredMembership<NodeId, BasicNode>,
+}
+
+impl StateMachine {
+    /// Restores from the latest snapshot in `dir`; the log replays the rest.
+    fn open(
+        dir: &Path,
+        st: Arc<Mutex<PersistedState>>,
+        events: broadcast::Sender<EventEnvelope>,
+        changed: Arc<Notify>,
+    ) -> std::io::Result<Self> {
+        let snapshot_path = dir.join("snapshot.json");
+        let mut sm = Self {
+            st,
+            events,
+            changed,
+            snapshot_path,
+            applied: None,
+            membership: StoredMembership::default(),
+        };
+        if let Some(snap) = load_snapshot(&sm.snapshot_path)? {
+            sm.applied = snap.meta.last_log_id;
+            sm.membership = snap.meta.last_membership;
+            *sm.st.try_lock().expect("state is not shared yet") = snap.state;
+        }
+        Ok(sm)
+    }
+}
+
+pub struct SnapshotBuilder {
+    snap: StoredSnapshot,
+    path: PathBuf,
+}
+
+impl RaftSnapshotBuilder<TypeConfig> for SnapshotBuilder {
+    async fn build_snapshot(&mut self) -> StorageResult<Snapshot<TypeConfig>> {
+        let data = serde_json::to_vec(&self.snap.state)
+            .map_err(|e| StorageIOError::read_state_machine(&e))?;
+        save_snapshot(&self.path, &self.snap)
+            .map_err(|e| StorageIOError::write_snapshot(Some(self.snap.meta.signature()), &e))?;
+        Ok(Snapshot {
+            meta: self.snap.meta.clone(),
+            snapshot: Box::new(Cursor::new(data)),
+        })
+    }
+}
+
+impl RaftStateMachine<TypeConfig> for StateMachine {
+    type SnapshotBuilder = SnapshotBuilder;
+
+    async fn applied_state(
+        &mut self,
+    ) -> StorageResult<(Option<LogId<NodeId>>, StoredMembership<NodeId, BasicNode>)> {
+        Ok((self.applied, self.membership.clone()))
+    }
+
+    async fn apply<I>(&mut self, entries: I) -> StorageResult<Vec<AdminResp>>
+    where
+        I: IntoIterator<Item = Entry<TypeConfig>> + Send,
+        I::IntoIter: Send,
+    {
+        let mut out = Vec::new();
+        let mut st = self.st.lock().await;
+        for ent in entries {
+            self.applied = Some(ent.log_id);
+            let resp = match ent.payload {
+                EntryPayload::Blank => AdminResp::Ok {
+                    index: st.next_index.saturating_sub(1),
+                },
+                EntryPayload::Normal(cmd) => {
+                    let (resp, evs) = st.apply(cmd);
+                    for ev in evs {
+                        // Best-effort: no subscribers is fine.
+                        let _ = self.events.send(ev);
+                    }
+                    resp
+                }
+                EntryPayload::Membership(m) => {
+                    self.membership = StoredMembership::new(Some(ent.log_id), m);
+                    AdminResp::Ok {
+                        index: st.next_index.saturating_sub(1),
+                    }
+                }
+            };
+            out.push(resp);
+        }
+        self.changed.notify_one();
+        Ok(out)
+    }
+
+    async fn get_snapshot_builder(&mut self) -> Self::SnapshotBuilder {
+        let state = self.st.lock().await.clone();
+        let snapshot_id = match self.applied {
+            Some(l) => format!("{}-{}", l.leader_id, l.index),
+            None => "empty".to_string(),
+        };
+        SnapshotBuilder {
+            snap: StoredSnapshot {
+                meta: SnapshotMeta {
+                    last_log_id: self.applied,
+                    last_membership: self.membership.clone(),
+                    snapshot_id,
+                },
+                state,
+            },
+            path: self.snapshot_path.clone(),
+        }
+    }
+
+    async fn begin_receiving_snapshot(&mut self) -> StorageResult<Box<Cursor<Vec<u8>>>> {
+        Ok(Box::new(Cursor::new(Vec::new())))
+    }
+
+    async fn install_snapshot(
+        &mut self,
+        meta: &SnapshotMeta<NodeId, BasicNode>,
+        snapshot: Box<Cursor<Vec<u8>>>,
+    ) -> StorageResult<()> {
+        let state: PersistedState = serde_json::from_slice(snapshot.get_ref())
+            .map_err(|e| StorageIOError::read_snapshot(Some(meta.signature()), &e))?;
+        let snap = StoredSnapshot {
+            meta: meta.clone(),
+            state: state.clone(),
+        };
+        save_snapshot(&self.snapshot_path, &snap)
+            .map_err(|e| StorageIOError::write_snapshot(Some(meta.signature()), &e))?;
+
+        self.applied = meta.last_log_id;
+        self.membership = meta.last_membership.clone();
+        let ev = state.snapshot_event();
+        *self.st.lock().await = state;
+        // Subscribers missed whatever the snapshot covers; hand them the whole picture.
+        let _ = self.events.send(ev);
+        self.changed.notify_one();
+        Ok(())
+    }
+
+    async fn get_current_snapshot(&mut self) -> StorageResult<Option<Snapshot<TypeConfig>>> {
+        let Some(snap) = load_snapshot(&self.snapshot_path)
+            .map_err(|e| StorageIOError::read_snapshot(None, &e))?
+        else {
+            return Ok(None);
+        };
+        let data = serde_json::to_vec(&snap.state)
+            .map_err(|e| StorageIOError::read_snapshot(Some(snap.meta.signature()), &e))?;
+        Ok(Some(Snapshot {
+            meta: snap.meta,
+            snapshot: Box::new(Cursor::new(data)),
+        }))
+    }
+}
+
+pub fn raft_config() -> anyhow::Result<fanzylog::Config> {
+    Ok(fanzylog::Config {
+        cluster_name: "sbc".to_string(),
+        heartbeat_interval: 100,
+        election_timeout_min: 500,
+        election_timeout_max: 1000,
+        snapshot_policy: SnapshotPolicy::LogsSinceLast(1000),
+        // Snapshots travel as JSON; keep chunks small.
+        snapshot_max_chunk_size: 256 * 1024,
+        ..Default::default()
+    }
+    .validate()?)
+}
+
+/// One member of the group.
+pub struct SbcNode<T> {
+    pub id: NodeId,
+    pub raft: Raft<TypeConfig>,
+    pub st: Arc<Mutex<PersistedState>>,
+    /// Signalled whenever committed entries (or a snapshot) change `st`.
+    pub changed: Arc<Notify>,
+    transport: T,
+}
+
+impl<T: Transport<TypeConfig>> SbcNode<T> {
+    /// Open the node's storage in `dir` and start Raft. Committed events are published on
+    /// `events` as they apply.
+    pub async fn start(
+        id: NodeId,
+        dir: &Path,
+        config: Arc<fanzylog::Config>,
+        transport: T,
+        events: broadcast::Sender<EventEnvelope>,
+    ) -> anyhow::Result<Self> {
+        let st = Arc::new(Mutex::new(PersistedState::empty()));
+        let log_store = fanzylog::log_store::FileLogStore::<TypeConfig>::open(dir.join("log"))?;
+        let changed = Arc::new(Notify::new());
+        let sm = StateMachine::open(dir, st.clone(), events, changed.clone())?;
+        let raft = Raft::new(id, config, Network::new(transport.clone()), log_store, sm).await?;
+        Ok(Self {
+            id,
+            raft,
+            st,
+            changed,
+            transport,
+        })
+    }
+
+    /// Form a new cluster from `members` unless this node already has Raft state.
+    pub async fn bootstrap(&self, members: BTreeMap<NodeId, BasicNode>) -> anyhow::Result<bool> {
+        if self.raft.is_initialized().await? {
+            return Ok(false);
+        }
+        self.raft.initialize(members).await?;
+        Ok(true)
+    }
+
+    pub async fn is_leader(&self) -> bool {
+        self.raft.current_leader().await == Some(self.id)
+    }
+
+    pub async fn propose(&self, cmd: Command) -> AdminResp {
+        self.propose_raw(Proposal::Write(cmd)).await
+    }
+
+    async fn propose_raw(&self, p: Proposal<TypeConfig>) -> AdminResp {
+        match propose(&self.raft, &self.transport, p).await {
+            Ok(r) => r.data,
+            Err(e) => AdminResp::Err {
+                message: format!("raft: {e}"),
+            },
+        }
+    }
+
+    /// Serve one admin request. `new_id` names whatever the request creates (a ban or an appeal).
+    pub async fn handle(&self, req: AdminReq, now_unix: u64, new_id: String) -> AdminResp {
+        match req {
+            AdminReq::GetState => {
+                let s = self.st.lock().await;
+                AdminResp::OkState {
+                    index: s.next_index.saturating_sub(1),
+                    bans: s.bans.values().cloned().collect(),
+                    holds: s.holds.values().cloned().collect(),
+                }
+            }
+            AdminReq::GetBanHistory {
+                cidr,
+                ban_id,
+                since_unix,
+                until_unix,
+            } => {
+                let s = self.st.lock().await;
+                match s.ban_history(cidr.as_deref(), ban_id.as_deref(), since_unix, until_unix) {
+                    Ok(entries) => AdminResp::OkBanHistory {
+                        index: s.next_index.saturating_sub(1),
+                        entries,
+                    },
+                    Err(e) => AdminResp::Err {
+                        message: e.to_string(),
+                    },
+                }
+            }
+            AdminReq::ListBanAppeals { ban_id, status } => {
+                let s = self.st.lock().await;
+                AdminResp::OkBanAppeals {
+                    index: s.next_index.saturating_sub(1),
+                    appeals: s.ban_appeals(ban_id.as_deref(), status),
+                }
+            }
+            AdminReq::GetRaftStatus => self.status().await,
+            AdminReq::AddRaftNode { node_id, addr } => {
+                let node = BasicNode { addr };
+                let resp = self
+                    .propose_raw(Proposal::AddVoter { id: node_id, node })
+                    .await;
+                self.membership_resp(resp).await
+            }
+            AdminReq::RemoveRaftNode { node_id } => {
+                let resp = self
+                    .propose_raw(Proposal::RemoveVoter { id: node_id })
+                    .await;
+                self.membership_resp(resp).await
+            }
+            req => {
+                let (new_ban_id, new_appeal_id) = match req {
+                    AdminReq::FileBanAppeal { .. } => (String::new(), new_id),
+                    _ => (new_id, String::new()),
+                };
+                self.propose(Command::Admin {
+                    req,
+                    now_unix,
+                    new_ban_id,
+                    new_appeal_id,
+                })
+                .await
+            }
+        }
+    }
+
+    async fn membership_resp(&self, resp: AdminResp) -> AdminResp {
+        match resp {
+            AdminResp::Err { .. } => resp,
+            _ => self.status().await,
+        }
+    }
+
+    async fn status(&self) -> AdminResp {
+        let m = self.raft.metrics().borrow().clone();
+        let role = match m.state {
+            ServerState::Leader => "leader",
+            ServerState::Follower => "follower",
+            ServerState::Candidate => "candidate",
+            ServerState::Learner => "learner",
+            ServerState::Shutdown => "shutdown",
+        };
+        let membership = m.membership_config.membership();
+        let members = membership
+            .nodes()
+            .map(|(id, node)| RaftMember {
+                node_id: *id,
+                addr: node.addr.clone(),
+                voter: membership.voter_ids().any(|v| v == *id),
+            })
+            .collect();
+        AdminResp::OkRaftStatus {
+            node_id: self.id,
+            leader_id: m.current_leader,
+            role: role.to_string(),
+            term: m.current_term,
+            last_applied: m.last_applied.map_or(0, |l| l.index),
+            members,
+        }
+    }
+
+    /// Leader-only: commit expiry for any bans past their TTL.
+    pub async fn expire_bans(&self, now_unix: u64) {
+        if !self.is_leader().await {
+            return;
+        }
+        if self.st.lock().await.expired_ban_ids(now_unix).is_empty() {
+            return;
+        }
+        if let AdminResp::Err { message } = self.propose(Command::ExpireBans { now_unix }).await {
+            tracing::warn!(err = %message, "ban expiry failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use fanzylog::loopback::Loopback;
+
+    type TestNode = SbcNode<Loopback<TypeConfig>>;
+
+    async fn start(net: &Loopback<TypeConfig>, dir: &Path, id: NodeId) -> TestNode {
+        let (tx, _) = broadcast::channel(64);
+        let node = SbcNode::start(
+            id,
+            &dir.join(format!("n{id}")),
+            Arc::new(raft_config().unwrap()),
+            net.clone(),
+            tx,
+        )
+        .await
+        .unwrap();
+        net.register(id, node.raft.clone());
+        node
+    }
+
+    fn member(id: NodeId) -> BasicNode {
+        BasicNode {
+            addr: format!("n{id}"),
+        }
+    }
+
+    async fn eventually(what: &str, mut f: impl AsyncFnMut() -> bool) {
+        for _ in 0..100 {
+            if f().await {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("timed out waiting for {what}");
+    }
+
+    async fn leader_of(nodes: &[&TestNode]) -> NodeId {
+        let mut leader = None;
+        eventually("a leader", async || {
+            for n in nodes {
+                if n.is_leader().await {
+                    leader = Some(n.id);
+                    return true;
+                }
+            }
+            false
+        })
+        .await;
+        leader.unwrap()
+    }
+
+    fn upsert_ban(cidr: &str) -> AdminReq {
+        AdminReq::UpsertBan {
+            key: cidr.to_string(),
+            ttl_s: 0,
+            created_by: "test".to_string(),
+            reason: "test".to_string(),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn survives_losing_the_leader_and_adds_members() {
+        let dir = std::env::temp_dir().join(format!("sbc_raftd_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let net = Loopback::new();
+
+        let mut nodes = Vec::new();
+        for id in 1..=3 {
+            nodes.push(start(&net, &dir, id).await);
+        }
+        let members = (1..=3)
+            .map(|id| (id, member(id)))
+            .collect::<BTreeMap<_, _>>();
+        assert!(nodes[0].bootstrap(members).await.unwrap());
+
+        // Writes sent to a follower are forwarded to the leader.
+        let leader = leader_of(&nodes.iter().collect::<Vec<_>>()).await;
+        let follower = nodes.iter().find(|n| n.id != leader).unwrap();
+        let resp = follower
+            .handle(upsert_ban("192.0.2.0/24"), 100, "ban-a".to_string())
+            .await;
+        assert!(matches!(resp, AdminResp::OkBan { .. }), "{resp:?}");
+        for n in &nodes {
+            eventually("ban replicated", async || {
+                n.st.lock().await.bans.contains_key("ban-a")
+            })
+            .await;
+        }
+
+        // Lose the leader; the other two elect a new one and keep accepting writes.
+        let idx = nodes.iter().position(|n| n.id == leader).unwrap();
+        let lost = nodes.remove(idx);
+        net.remove(&lost.id);
+        lost.raft.shutdown().await.unwrap();
+        drop(lost);
+
+        let new_leader = leader_of(&nodes.iter().collect::<Vec<_>>()).await;
+        assert_ne!(new_leader, leader);
+        let hold = AdminReq::UpsertLegalHold {
+            name: "Mallory".to_string(),
+            created_by: "test".to_string(),
+            reason: "case 1".to_string(),
+        };
+        let resp = nodes[0].handle(hold, 101, String::new()).await;
+        assert!(matches!(resp, AdminResp::OkLegalHold { .. }), "{resp:?}");
+
+        // The lost node comes back from disk and catches up.
+        let back = start(&net, &dir, leader).await;
+        eventually("restarted node caught up", async || {
+            back.st.lock().await.holds.contains_key("mallory")
+        })
+        .await;
+        nodes.push(back);
+
+        // Compact, then add a fourth member: it has to start from a snapshot.
+        let leader = leader_of(&nodes.iter().collect::<Vec<_>>()).await;
+        let leader = nodes.iter().find(|n| n.id == leader).unwrap();
+        leader.raft.trigger().snapshot().await.unwrap();
+        eventually("snapshot built", async || {
+            leader.raft.metrics().borrow().snapshot.is_some()
+        })
+        .await;
+        let applied = leader.raft.metrics().borrow().last_applied.unwrap().index;
+        leader.raft.trigger().purge_log(applied).await.unwrap();
+
+        let fourth = start(&net, &dir, 4).await;
+        let add = AdminReq::AddRaftNode {
+            node_id: 4,
+            addr: "n4".to_string(),
+        };
+        let resp = nodes[0].handle(add, 102, String::new()).await;
+        let AdminResp::OkRaftStatus { members, .. } = resp else {
+            panic!("add node: {resp:?}");
+        };
+        assert!(members.iter().any(|m| m.node_id == 4 && m.voter));
+        eventually("new member has state from a snapshot", async || {
+            let s = fourth.st.lock().await;
+            s.bans.contains_key("ban-a")
+                && s.holds.contains_key("mallory")
+                && fourth.raft.metrics().borrow().snapshot.is_some()
+        })
+        .await;
+
+        for n in nodes.iter().chain([&fourth]) {
+            n.raft.shutdown().await.unwrap();
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,210 @@
+//! The enforcer fleet as seen from the replicated state: what each node last reported, how far
+//! behind the event stream it is, and which bans it failed to apply.
+
+use std::collections::BTreeMap;
+
+use sbc_core::BanApplyResult;
+
+use crate::state::PersistedState;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Thresholds {
+    /// A node that hasn't reported for this long is stale.
+    pub stale_after_s: u64,
+    /// Alert once a node trails the event stream by more than this many events.
+    pub max_event_lag: u64,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct FleetNode {
+    pub node_id: String,
+    pub enforcement_mode: String,
+    pub backend: String,
+    pub backend_attached: bool,
+    pub dns_name: String,
+    pub dns_enabled: bool,
+    pub dns_last_error: Option<String>,
+    pub reported_at_unix: u64,
+    pub stale: bool,
+    pub event_lag: u64,
+    pub failing_bans: Vec<BanApplyResult>,
+    /// Why this node needs attention; empty when healthy.
+    pub alerts: Vec<String>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct FleetView {
+    pub index: u64,
+    pub now_unix: u64,
+    pub stale_after_s: u64,
+    pub max_event_lag: u64,
+    pub healthy: bool,
+    pub nodes: Vec<FleetNode>,
+}
+
+pub fn fleet_view(st: &PersistedState, now_unix: u64, t: Thresholds) -> FleetView {
+    // Only the latest result per (node, ban, op) is kept; it still matters if the ban is still
+    // supposed to be in that state.
+    let mut failing: BTreeMap<&str, Vec<BanApplyResult>> = BTreeMap::new();
+    for r in st.last_apply_by_node_ban.values() {
+        let wanted = match r.op.as_str() {
+            "upsert" => st.bans.contains_key(&r.ban_id),
+            "delete" => !st.bans.contains_key(&r.ban_id),
+            _ => false,
+        };
+        if r.result == "err" && wanted {
+            failing.entry(&r.node_id).or_default().push(r.clone());
+        }
+    }
+
+    let mut nodes = st
+        .last_status_by_node
+        .values()
+        .map(|s| {
+            let mut failing_bans = failing.remove(s.node_id.as_str()).unwrap_or_default();
+            failing_bans.sort_by(|a, b| a.ban_id.cmp(&b.ban_id).then(a.op.cmp(&b.op)));
+            let age_s = now_unix.saturating_sub(s.reported_at_unix);
+            let stale = age_s > t.stale_after_s;
+            // The report itself took `reported_at_index`; everything before it should have
+            // reached the node.
+            let event_lag = s
+                .reported_at_index
+                .saturating_sub(1)
+                .saturating_sub(s.events_last_index);
+
+            let mut alerts = Vec::new();
+            if stale {
+                alerts.push(format!("no report for {age_s}s"));
+            }
+            if s.dns_enabled && s.enforcement_mode != "enforcing" {
+                alerts.push(format!("dns enabled but mode is {}", s.enforcement_mode));
+            }
+            // No DNS name means enforcement is switched off on purpose.
+            if let Some(e) = s.dns_last_error.as_ref().filter(|_| !s.dns_name.is_empty()) {
+                alerts.push(format!("dns check failing: {e}"));
+            }
+            if event_lag > t.max_event_lag {
+                alerts.push(format!("{event_lag} events behind"));
+            }
+            if !failing_bans.is_empty() {
+                alerts.push(format!("{} bans failing to apply", failing_bans.len()));
+            }
+
+            FleetNode {
+                node_id: s.node_id.clone(),
+                enforcement_mode: s.enforcement_mode.clone(),
+                backend: s.backend.clone(),
+                backend_attached: s.backend_attached,
+                dns_name: s.dns_name.clone(),
+                dns_enabled: s.dns_enabled,
+                dns_last_error: s.dns_last_error.clone(),
+                reported_at_unix: s.reported_at_unix,
+                stale,
+                event_lag,
+                failing_bans,
+                alerts,
+            }
+        })
+        .collect::<Vec<_>>();
+    nodes.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+
+    FleetView {
+        index: st.next_index.saturating_sub(1),
+        now_unix,
+        stale_after_s: t.stale_after_s,
+        max_event_lag: t.max_event_lag,
+        healthy: nodes.iter().all(|n| n.alerts.is_empty()),
+        nodes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sbc_core::{AdminReq, EnforcementStatus};
+
+    use super::*;
+    use crate::state::Command;
+
+    fn admin(st: &mut PersistedState, req: AdminReq, now: u64) {
+        st.apply(Command::Admin {
+            req,
+            now_unix: now,
+            new_ban_id: format!("ban-{now}"),
+            new_appeal_id: String::new(),
+        });
+    }
+
+    fn status(node_id: &str, at: u64, events_last_index: u64) -> AdminReq {
+        AdminReq::ReportEnforcementStatus {
+            status: EnforcementStatus {
+                node_id: node_id.to_string(),
+                dns_name: "enforce.example".to_string(),
+                dns_enabled: true,
+                dns_last_error: None,
+                backend: "nftables".to_string(),
+                backend_attached: true,
+                enforcement_mode: "enforcing".to_string(),
+                reported_at_unix: at,
+                events_last_index,
+                reported_at_index: 0,
+            },
+        }
+    }
+
+    fn apply_result(node_id: &str, ban_id: &str, result: &str) -> AdminReq {
+        AdminReq::ReportBanApplyResult {
+            report: BanApplyResult {
+                node_id: node_id.to_string(),
+                ban_id: ban_id.to_string(),
+                op: "upsert".to_string(),
+                result: result.to_string(),
+                error: (result == "err").then(|| "nft: boom".to_string()),
+                reported_at_unix: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn flags_stale_lagging_and_failing_nodes() {
+        let t = Thresholds {
+            stale_after_s: 90,
+            max_event_lag: 2,
+        };
+        let mut st = PersistedState::empty();
+        let ban = AdminReq::UpsertBan {
+            key: "198.51.100.7/32".to_string(),
+            ttl_s: 0,
+            created_by: "ops".to_string(),
+            reason: "test".to_string(),
+        };
+        admin(&mut st, ban, 1_000); // index 1
+        admin(&mut st, status("a", 1_000, 1), 1_000); // 2: caught up
+        admin(&mut st, apply_result("b", "ban-1000", "err"), 1_000); // 3
+        admin(&mut st, apply_result("a", "ban-1000", "ok"), 1_000); // 4
+        admin(&mut st, status("b", 1_000, 1), 1_000); // 5: missed 2..4
+        admin(&mut st, status("c", 800, 0), 1_000); // 6
+
+        let v = fleet_view(&st, 1_010, t);
+        assert!(!v.healthy);
+        let by_id = |id: &str| v.nodes.iter().find(|n| n.node_id == id).unwrap();
+        assert!(by_id("a").alerts.is_empty(), "{:?}", by_id("a"));
+        let b = by_id("b");
+        assert_eq!((b.event_lag, b.failing_bans.len()), (3, 1));
+        assert_eq!(b.alerts, vec!["3 events behind", "1 bans failing to apply"]);
+        let c = by_id("c");
+        assert!(c.stale);
+        assert_eq!(c.alerts[0], "no report for 210s");
+
+        // A failure on a ban that has since been lifted no longer counts.
+        admin(
+            &mut st,
+            AdminReq::DeleteBan {
+                ban_id: "ban-1000".to_string(),
+                deleted_by: "ops".to_string(),
+                reason: String::new(),
+            },
+            1_020,
+        );
+        assert!(fleet_view(&st, 1_020, t).nodes[1].failing_bans.is_empty());
+    }
+}
@@ -1,28 +1,56 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Context;
-use sbc_core::{
-    AdminReq, AdminResp, BanApplyResult, BanEntry, EnforcementStatus, Event, EventEnvelope,
-    EventsReq, IpPrefix, LegalHoldEntry, SubscribeMode,
-};
+use axum::http::StatusCode;
+use axum::{Json, Router, routing::get};
+use fanzylog::BasicNode;
+use fanzylog::tcp::{PeerKey, TcpTransport};
+use sbc_core::{AdminReq, AdminResp, Event, EventEnvelope, EventsReq, SubscribeMode};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::{UnixListener, UnixStream};
-use tokio::sync::{Mutex, broadcast};
+use tokio::net::{TcpListener, UnixListener, UnixStream};
+use tokio::sync::broadcast;
 use tracing::{Level, info, warn};
 
+mod cluster;
+mod fleet;
+mod state;
+mod sweeper;
+
+use cluster::{NodeId, SbcNode};
+use state::{Command, PersistedState};
+use sweeper::{Clock, Sweeper, SystemClock};
+
+type Node = SbcNode<TcpTransport>;
+
 fn usage_and_exit() -> ! {
     eprintln!(
         "sbc_raftd
 
 USAGE:
   sbc_raftd [--admin-sock PATH] [--events-sock PATH] [--state-path PATH]
+            [--node-id N] [--raft-bind HOST:PORT] [--raft-dir PATH] [--peers SPEC]
+            [--fleet-http HOST:PORT]
 
 ENV:
-  SBC_ADMIN_SOCK   default /run/slopmud/sbc-admin.sock
-  SBC_EVENTS_SOCK  default /run/slopmud/sbc-events.sock
-  SBC_STATE_PATH   default sbc-state.json (in cwd)
+  SBC_ADMIN_SOCK      default /run/slopmud/sbc-admin.sock
+  SBC_EVENTS_SOCK     default /run/slopmud/sbc-events.sock
+  SBC_STATE_PATH      default sbc-state.json (in cwd); imported once by the lowest-id node
+  SBC_RAFT_NODE_ID    default 1
+  SBC_RAFT_BIND       default 127.0.0.1:4970 (peer RPC)
+  SBC_RAFT_KEY        shared secret (min 16 bytes) authenticating peer RPC; the same on every
+                      node. Required unless SBC_RAFT_BIND is a loopback address.
+  SBC_RAFT_DIR        default sbc-raft (log + snapshot for this node)
+  SBC_RAFT_PEERS      optional; id=host:port,... including this node (default: single node)
+                      The lowest id forms the cluster on first start; add nodes later with
+                      the add_raft_node admin request.
+  SBC_FLEET_HTTP      optional; serves the enforcer fleet view at /fleet and /fleet/alerts
+                      (503 while any node needs attention)
+  SBC_FLEET_STALE_S   default 90; enforcers silent for longer are flagged stale
+  SBC_FLEET_MAX_LAG   default 100; enforcers further behind the event stream are flagged
 "
     );
     std::process::exit(2);
@@ -33,6 +61,37 @@ struct Config {
     admin_sock: PathBuf,
     events_sock: PathBuf,
     state_path: PathBuf,
+    node_id: NodeId,
+    raft_bind: SocketAddr,
+    raft_key: Option<PeerKey>,
+    raft_dir: PathBuf,
+    peers: BTreeMap<NodeId, BasicNode>,
+    fleet_http: Option<SocketAddr>,
+    fleet: fleet::Thresholds,
+}
+
+/// Parse `id=host:port,...`.
+fn parse_peers(spec: &str) -> anyhow::Result<BTreeMap<NodeId, BasicNode>> {
+    let mut out = BTreeMap::new();
+    for ent in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let Some((id, addr)) = ent.split_once('=') else {
+            anyhow::bail!("bad peer entry (want id=host:port): {ent}");
+        };
+        let id: NodeId = id
+            .trim()
+            .parse()
+            .with_context(|| format!("bad peer id in {ent}"))?;
+        let addr = addr.trim();
+        addr.parse::<SocketAddr>()
+            .with_context(|| format!("bad peer address in {ent}"))?;
+        out.insert(
+            id,
+            BasicNode {
+                addr: addr.to_string(),
+            },
+        );
+    }
+    Ok(out)
 }
 
 fn parse_args() -> Config {
@@ -45,6 +104,32 @@ fn parse_args() -> Config {
     let mut state_path: PathBuf = std::env::var("SBC_STATE_PATH")
         .unwrap_or_else(|_| "sbc-state.json".to_string())
         .into();
+    let mut node_id: NodeId = std::env::var("SBC_RAFT_NODE_ID")
+        .unwrap_or_else(|_| "1".to_string())
+        .parse()
+        .unwrap_or_else(|_| usage_and_exit());
+    let mut raft_bind: SocketAddr = std::env::var("SBC_RAFT_BIND")
+        .unwrap_or_else(|_| "127.0.0.1:4970".to_string())
+        .parse()
+        .unwrap_or_else(|_| usage_and_exit());
+    let mut raft_dir: PathBuf = std::env::var("SBC_RAFT_DIR")
+        .unwrap_or_else(|_| "sbc-raft".to_string())
+        .into();
+    let mut peers_spec = std::env::var("SBC_RAFT_PEERS").unwrap_or_default();
+    let mut fleet_http: Option<SocketAddr> = std::env::var("SBC_FLEET_HTTP")
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| s.trim().parse().unwrap_or_else(|_| usage_and_exit()));
+    let fleet = fleet::Thresholds {
+        stale_after_s: std::env::var("SBC_FLEET_STALE_S")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(90),
+        max_event_lag: std::env::var("SBC_FLEET_MAX_LAG")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(100),
+    };
 
     let mut it = std::env::args().skip(1);
     while let Some(arg) = it.next() {
@@ -58,70 +143,109 @@ fn parse_args() -> Config {
             "--state-path" => {
                 state_path = it.next().unwrap_or_else(|| usage_and_exit()).into();
             }
+            "--node-id" => {
+                let v = it.next().unwrap_or_else(|| usage_and_exit());
+                node_id = v.parse().unwrap_or_else(|_| usage_and_exit());
+            }
+            "--raft-bind" => {
+                let v = it.next().unwrap_or_else(|| usage_and_exit());
+                raft_bind = v.parse().unwrap_or_else(|_| usage_and_exit());
+            }
+            "--raft-dir" => {
+                raft_dir = it.next().unwrap_or_else(|| usage_and_exit()).into();
+            }
+            "--peers" => {
+                peers_spec = it.next().unwrap_or_else(|| usage_and_exit());
+            }
+            "--fleet-http" => {
+                let v = it.next().unwrap_or_else(|| usage_and_exit());
+                fleet_http = Some(v.parse().unwrap_or_else(|_| usage_and_exit()));
+            }
             "-h" | "--help" => usage_and_exit(),
             _ => usage_and_exit(),
         }
     }
 
+    let mut peers = parse_peers(&peers_spec).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        usage_and_exit()
+    });
+    if peers.is_empty() {
+        peers.insert(
+            node_id,
+            BasicNode {
+                addr: raft_bind.to_string(),
+            },
+        );
+    }
+    if !peers.contains_key(&node_id) {
+        eprintln!("SBC_RAFT_PEERS does not include this node ({node_id})");
+        usage_and_exit();
+    }
+
+    let raft_key = std::env::var("SBC_RAFT_KEY")
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| {
+            PeerKey::new(s.trim().as_bytes()).unwrap_or_else(|e| {
+                eprintln!("SBC_RAFT_KEY: {e}");
+                usage_and_exit()
+            })
+        });
+    if raft_key.is_none() && !raft_bind.ip().is_loopback() {
+        eprintln!("SBC_RAFT_KEY is required when the raft port ({raft_bind}) isn't loopback");
+        usage_and_exit();
+    }
+
     Config {
         admin_sock,
         events_sock,
         state_path,
+        node_id,
+        raft_bind,
+        raft_key,
+        raft_dir,
+        peers,
+        fleet_http,
+        fleet,
     }
 }
 
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
-struct PersistedState {
-    next_index: u64,
-    bans: HashMap<String, BanEntry>,     // ban_id -> entry
-    ban_by_key: HashMap<String, String>, // cidr -> ban_id
-    #[serde(default)]
-    holds: HashMap<String, LegalHoldEntry>, // name_lc -> entry
-    #[serde(default)]
-    last_status_by_node: HashMap<String, EnforcementStatus>,
-    #[serde(default)]
-    last_apply_by_node_ban: HashMap<String, BanApplyResult>,
-}
-
-impl PersistedState {
-    fn empty() -> Self {
-        Self {
-            next_index: 1,
-            bans: HashMap::new(),
-            ban_by_key: HashMap::new(),
-            holds: HashMap::new(),
-            last_status_by_node: HashMap::new(),
-            last_apply_by_node_ban: HashMap::new(),
-        }
-    }
-
-    fn load(path: &Path) -> anyhow::Result<Self> {
-        match std::fs::read_to_string(path) {
-            Ok(s) => Ok(serde_json::from_str(&s)?),
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::empty()),
-            Err(e) => Err(e.into()),
-        }
+fn load_legacy_state(path: &Path) -> anyhow::Result<Option<PersistedState>> {
+    match std::fs::read_to_string(path) {
+        Ok(s) => Ok(Some(serde_json::from_str(&s)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
     }
 }
 
-fn atomic_write_json(path: &Path, value: &PersistedState) -> anyhow::Result<()> {
-    if let Some(dir) = path.parent() {
-        if !dir.as_os_str().is_empty() {
-            std::fs::create_dir_all(dir)?;
+/// Propose `Import` until it commits. The command is a no-op once an import has committed, so
+/// repeating it across restarts is safe.
+async fn import_legacy_state(node: Arc<Node>, state: PersistedState, path: PathBuf) {
+    let mut delay = Duration::from_millis(200);
+    loop {
+        if node.st.lock().await.legacy_imported {
+            return;
+        }
+        let cmd = Command::Import {
+            state: state.clone(),
+        };
+        match node.propose(cmd).await {
+            AdminResp::Err { message } => {
+                warn!(err=%message, path=%path.display(), "legacy state import not committed; retrying");
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(Duration::from_secs(10));
+            }
+            _ => {
+                info!(path=%path.display(), "imported legacy state");
+                return;
+            }
         }
     }
-    let tmp = path.with_extension("json.tmp");
-    let s = serde_json::to_string_pretty(value)?;
-    std::fs::write(&tmp, s)?;
-    std::fs::rename(&tmp, path)?;
-    Ok(())
 }
 
 fn now_unix() -> u64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs()
+    SystemClock.now_unix()
 }
 
 fn rand_hex_16() -> String {
@@ -153,11 +277,7 @@ fn remove_if_exists(path: &Path) -> anyhow::Result<()> {
     }
 }
 
-async fn handle_admin_conn(
-    st: Arc<Mutex<PersistedState>>,
-    tx: broadcast::Sender<EventEnvelope>,
-    mut conn: UnixStream,
-) -> anyhow::Result<()> {
+async fn handle_admin_conn(node: Arc<Node>, mut conn: UnixStream) -> anyhow::Result<()> {
     let (rd, mut wr) = conn.split();
     let mut rd = BufReader::new(rd);
     let mut line = String::new();
@@ -167,201 +287,13 @@ async fn handle_admin_conn(
         return Ok(());
     }
 
-    let req: AdminReq = match serde_json::from_str(line) {
-        Ok(v) => v,
-        Err(e) => {
-            let resp = AdminResp::Err {
-                message: format!("bad json: {e}"),
-            };
-            wr.write_all(serde_json::to_string(&resp)?.as_bytes())
-                .await?;
-            wr.write_all(b"\n").await?;
-            return Ok(());
-        }
+    let resp = match serde_json::from_str::<AdminReq>(line) {
+        Ok(req) => node.handle(req, now_unix(), rand_hex_16()).await,
+        Err(e) => AdminResp::Err {
+            message: format!("bad json: {e}"),
+        },
     };
 
-    let (resp, event_opt) = {
-        let mut s = st.lock().await;
-        match req {
-            AdminReq::UpsertBan {
-                key,
-                ttl_s,
-                created_by,
-                reason,
-            } => {
-                let key_pfx = IpPrefix::parse_cidr(&key)?;
-                let key_cidr = key_pfx.to_cidr_string();
-                let now = now_unix();
-                // ttl_s=0 means "never expires" (until explicitly deleted).
-                let expires_at_unix = if ttl_s == 0 {
-                    0
-                } else {
-                    now.saturating_add(ttl_s)
-                };
-
-                let ban_id = if let Some(existing) = s.ban_by_key.get(&key_cidr) {
-                    existing.clone()
-                } else {
-                    let id = rand_hex_16();
-                    s.ban_by_key.insert(key_cidr.clone(), id.clone());
-                    id
-                };
-
-                let entry = BanEntry {
-                    ban_id: ban_id.clone(),
-                    key: key_pfx,
-                    created_at_unix: now,
-                    created_by,
-                    reason,
-                    expires_at_unix,
-                };
-                s.bans.insert(ban_id.clone(), entry.clone());
-
-                let idx = s.next_index;
-                s.next_index = s.next_index.saturating_add(1);
-                let ev = EventEnvelope {
-                    index: idx,
-                    event: Event::BanUpserted {
-                        entry: entry.clone(),
-                    },
-                };
-                let resp = AdminResp::OkBan { index: idx, entry };
-                (resp, Some(ev))
-            }
-            AdminReq::DeleteBan { ban_id } => {
-                if let Some(entry) = s.bans.remove(&ban_id) {
-                    s.ban_by_key.remove(&entry.key.to_cidr_string());
-                }
-                let idx = s.next_index;
-                s.next_index = s.next_index.saturating_add(1);
-                let ev = EventEnvelope {
-                    index: idx,
-                    event: Event::BanDeleted {
-                        ban_id: ban_id.clone(),
-                    },
-                };
-                let resp = AdminResp::Ok { index: idx };
-                (resp, Some(ev))
-            }
-            AdminReq::UpsertLegalHold {
-                name,
-                created_by,
-                reason,
-            } => {
-                let name_lc = name.trim().to_ascii_lowercase();
-                if name_lc.is_empty() {
-                    let resp = AdminResp::Err {
-                        message: "missing name".to_string(),
-                    };
-                    (resp, None)
-                } else {
-                    let entry = LegalHoldEntry {
-                        name_lc: name_lc.clone(),
-                        created_at_unix: now_unix(),
-                        created_by,
-                        reason,
-                    };
-                    s.holds.insert(name_lc.clone(), entry.clone());
-
-                    let idx = s.next_index;
-                    s.next_index = s.next_index.saturating_add(1);
-                    let ev = EventEnvelope {
-                        index: idx,
-                        event: Event::LegalHoldUpserted {
-                            entry: entry.clone(),
-                        },
-                    };
-                    let resp = AdminResp::OkLegalHold { index: idx, entry };
-                    (resp, Some(ev))
-                }
-            }
-            AdminReq::DeleteLegalHold { name } => {
-                let name_lc = name.trim().to_ascii_lowercase();
-                if name_lc.is_empty() {
-                    let resp = AdminResp::Err {
-                        message: "missing name".to_string(),
-                    };
-                    (resp, None)
-                } else {
-                    s.holds.remove(&name_lc);
-                    let idx = s.next_index;
-                    s.next_index = s.next_index.saturating_add(1);
-                    let ev = EventEnvelope {
-                        index: idx,
-                        event: Event::LegalHoldDeleted { name_lc },
-                    };
-                    let resp = AdminResp::Ok { index: idx };
-                    (resp, Some(ev))
-                }
-            }
-            AdminReq::ReportEnforcementStatus { status } => {
-                s.last_status_by_node
-                    .insert(status.node_id.clone(), status.clone());
-                let idx = s.next_index;
-                s.next_index = s.next_index.saturating_add(1);
-                let ev = EventEnvelope {
-                    index: idx,
-                    event: Event::EnforcementStatus { status },
-                };
-                let resp = AdminResp::Ok { index: idx };
-                (resp, Some(ev))
-            }
-            AdminReq::ReportBanApplyResult { report } => {
-                let k = format!("{}:{}:{}", report.node_id, report.ban_id, report.op);
-                s.last_apply_by_node_ban.insert(k, report.clone());
-                let idx = s.next_index;
-                s.next_index = s.next_index.saturating_add(1);
-                let ev = EventEnvelope {
-                    index: idx,
-                    event: Event::BanApplyResult { report },
-                };
-                let resp = AdminResp::Ok { index: idx };
-                (resp, Some(ev))
-            }
-            AdminReq::GetState => {
-                let idx = s.next_index.saturating_sub(1);
-                let bans = s.bans.values().cloned().collect::<Vec<_>>();
-                let holds = s.holds.values().cloned().collect::<Vec<_>>();
-                let resp = AdminResp::OkState {
-                    index: idx,
-                    bans,
-                    holds,
-                };
-                (resp, None)
-            }
-        }
-    };
-
-    // Persist + broadcast outside the lock.
-    if let Some(ev) = event_opt.clone() {
-        {
-            let s = st.lock().await;
-            let path = {
-                // We only store this in-memory; the caller holds state_path.
-                // The actual write happens in the main task to keep this handler generic.
-                // (We still persist from here by re-reading env if needed.)
-                // For now, do nothing.
-                drop(s);
-                None::<PathBuf>
-            };
-            drop(path);
-        }
-
-        // Broadcast best-effort.
-        let _ = tx.send(ev);
-    }
-
-    // Persist state after the mutation (best-effort).
-    if matches!(
-        &resp,
-        AdminResp::Ok { .. }
-            | AdminResp::OkBan { .. }
-            | AdminResp::OkState { .. }
-            | AdminResp::Err { .. }
-    ) {
-        // no-op: state persistence handled by main loop via a background task.
-    }
-
     wr.write_all(serde_json::to_string(&resp)?.as_bytes())
         .await?;
     wr.write_all(b"\n").await?;
@@ -369,7 +301,7 @@ async fn handle_admin_conn(
 }
 
 async fn handle_events_conn(
-    st: Arc<Mutex<PersistedState>>,
+    node: Arc<Node>,
     tx: broadcast::Sender<EventEnvelope>,
     mut conn: UnixStream,
 ) -> anyhow::Result<()> {
@@ -383,42 +315,96 @@ async fn handle_events_conn(
     }
 
     let req: EventsReq = serde_json::from_str(line)?;
-    let EventsReq::Subscribe { mode } = req;
-
-    if mode == SubscribeMode::Snapshot {
-        let (idx, bans, holds) = {
-            let s = st.lock().await;
-            (
-                s.next_index.saturating_sub(1),
-                s.bans.values().cloned().collect::<Vec<_>>(),
-                s.holds.values().cloned().collect::<Vec<_>>(),
-            )
-        };
-        let snap = EventEnvelope {
-            index: idx,
-            event: Event::Snapshot { bans, holds },
-        };
-        wr.write_all(serde_json::to_string(&snap)?.as_bytes())
-            .await?;
+    let EventsReq::Subscribe { mode, from_index } = req;
+
+    // Subscribe before reading the state so nothing falls in between; live events already
+    // covered by what we send first (up to `covered`) are skipped.
+    let mut rx = tx.subscribe();
+    let (backlog, covered) = {
+        let s = node.st.lock().await;
+        let covered = s.next_index.saturating_sub(1);
+        match from_index.map(|from| s.events_since(from)) {
+            Some(Some(evs)) => (evs, covered),
+            Some(None) => {
+                info!(
+                    from_index,
+                    "events no longer retained; resuming from snapshot"
+                );
+                (vec![s.snapshot_event()], covered)
+            }
+            None if mode == SubscribeMode::Snapshot => (vec![s.snapshot_event()], covered),
+            None => (Vec::new(), 0),
+        }
+    };
+    for ev in &backlog {
+        wr.write_all(serde_json::to_string(ev)?.as_bytes()).await?;
         wr.write_all(b"\n").await?;
     }
 
-    let mut rx = tx.subscribe();
     loop {
         match rx.recv().await {
+            Ok(ev) if ev.index <= covered && !matches!(ev.event, Event::Snapshot { .. }) => {}
             Ok(ev) => {
                 wr.write_all(serde_json::to_string(&ev)?.as_bytes()).await?;
                 wr.write_all(b"\n").await?;
             }
+            // Hang up rather than silently drop events; the subscriber resumes from its
+            // last index.
             Err(broadcast::error::RecvError::Lagged(n)) => {
-                warn!(dropped=%n, "events subscriber lagged");
-                continue;
+                anyhow::bail!("events subscriber lagged by {n} events")
             }
             Err(broadcast::error::RecvError::Closed) => return Ok(()),
         }
     }
 }
 
+async fn fleet_server(
+    bind: SocketAddr,
+    node: Arc<Node>,
+    t: fleet::Thresholds,
+) -> anyhow::Result<()> {
+    type St = axum::extract::State<(Arc<Node>, fleet::Thresholds)>;
+
+    async fn view(st: &(Arc<Node>, fleet::Thresholds)) -> fleet::FleetView {
+        let s = st.0.st.lock().await;
+        fleet::fleet_view(&s, now_unix(), st.1)
+    }
+
+    let app = Router::new()
+        .route("/healthz", get(|| async { "ok\n" }))
+        .route(
+            "/fleet",
+            get(|axum::extract::State(st): St| async move { Json(view(&st).await) }),
+        )
+        .route(
+            "/fleet/alerts",
+            get(|axum::extract::State(st): St| async move {
+                let v = view(&st).await;
+                let mut out = String::new();
+                for n in &v.nodes {
+                    for a in &n.alerts {
+                        out.push_str(&format!("{}: {a}\n", n.node_id));
+                    }
+                }
+                if v.healthy {
+                    (StatusCode::OK, "ok\n".to_string())
+                } else {
+                    (StatusCode::SERVICE_UNAVAILABLE, out)
+                }
+            }),
+        )
+        .with_state((node, t));
+
+    info!(bind=%bind, "fleet http listening");
+    let listener = TcpListener::bind(bind)
+        .await
+        .with_context(|| format!("bind fleet http {bind}"))?;
+    axum::serve(listener, app)
+        .await
+        .context("serve fleet http")?;
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt()
@@ -430,16 +416,55 @@ async fn main() -> anyhow::Result<()> {
 
     let cfg = parse_args();
 
-    // Load persisted state.
-    let state_path = cfg.state_path.clone();
-    let st0 = PersistedState::load(&state_path).with_context(|| {
-        format!(
-            "failed to load state from {}",
-            state_path.as_os_str().to_string_lossy()
-        )
-    })?;
+    // Event bus: committed entries publish here as they apply on this node.
+    let (ev_tx, _ev_rx) = broadcast::channel::<EventEnvelope>(1024);
+
+    let node = Node::start(
+        cfg.node_id,
+        &cfg.raft_dir,
+        Arc::new(cluster::raft_config()?),
+        TcpTransport::new(Duration::from_secs(3)).with_key(cfg.raft_key.clone()),
+        ev_tx.clone(),
+    )
+    .await
+    .with_context(|| format!("failed to start raft node in {}", cfg.raft_dir.display()))?;
+    let node = Arc::new(node);
+
+    let raft_listener = TcpListener::bind(cfg.raft_bind)
+        .await
+        .with_context(|| format!("bind raft rpc {}", cfg.raft_bind))?;
+    tokio::spawn({
+        let raft = node.raft.clone();
+        let key = cfg.raft_key.clone();
+        async move {
+            if let Err(e) = fanzylog::tcp::serve(raft_listener, raft, key).await {
+                warn!(err=%e, "raft rpc listener failed");
+            }
+        }
+    });
 
-    let st = Arc::new(Mutex::new(st0));
+    // The lowest configured id forms the cluster; everyone else waits to be contacted.
+    let first = cfg.peers.keys().next().copied();
+    if first == Some(cfg.node_id) {
+        if node.bootstrap(cfg.peers.clone()).await? {
+            info!(members = cfg.peers.len(), "formed raft cluster");
+        }
+        // Checked on every start, not just the first: a crash before the import committed
+        // would otherwise lose it.
+        let legacy = load_legacy_state(&cfg.state_path).with_context(|| {
+            format!(
+                "failed to load state from {}",
+                cfg.state_path.as_os_str().to_string_lossy()
+            )
+        })?;
+        if let Some(state) = legacy {
+            tokio::spawn(import_legacy_state(
+                node.clone(),
+                state,
+                cfg.state_path.clone(),
+            ));
+        }
+    }
 
     ensure_unix_socket_dir(&cfg.admin_sock)?;
     ensure_unix_socket_dir(&cfg.events_sock)?;
@@ -451,127 +476,66 @@ async fn main() -> anyhow::Result<()> {
     let events_listener = UnixListener::bind(&cfg.events_sock)
         .with_context(|| format!("bind events sock {:?}", cfg.events_sock))?;
 
-    // Event bus.
-    let (ev_tx, _ev_rx) = broadcast::channel::<EventEnvelope>(1024);
-
     info!(
+        node_id = cfg.node_id,
+        raft_bind = %cfg.raft_bind,
+        raft_dir = %cfg.raft_dir.display(),
         admin_sock = %cfg.admin_sock.display(),
         events_sock = %cfg.events_sock.display(),
-        state_path = %cfg.state_path.display(),
         "sbc_raftd listening"
     );
 
-    // Background persister: periodically flush state to disk when index advances.
-    {
-        let st = st.clone();
-        let state_path = state_path.clone();
+    if let Some(bind) = cfg.fleet_http {
+        let node = node.clone();
+        let t = cfg.fleet;
         tokio::spawn(async move {
-            let mut last_persisted = 0u64;
-            loop {
-                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-                let snapshot = { st.lock().await.clone() };
-                let idx = snapshot.next_index;
-                if idx == last_persisted {
-                    continue;
-                }
-                if let Err(e) = tokio::task::spawn_blocking({
-                    let state_path = state_path.clone();
-                    move || atomic_write_json(&state_path, &snapshot)
-                })
-                .await
-                .unwrap_or_else(|e| Err(anyhow::anyhow!("persist task join error: {e}")))
-                {
-                    warn!(err=%e, "failed to persist state");
-                    continue;
-                }
-                last_persisted = idx;
+            if let Err(e) = fleet_server(bind, node, t).await {
+                warn!(err=%e, "fleet http failed");
             }
         });
     }
 
-    // TTL reaper: remove expired bans and emit BanDeleted events.
-    {
-        let st = st.clone();
-        let tx = ev_tx.clone();
-        tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                let now = now_unix();
-                let expired_events = {
-                    let mut s = st.lock().await;
-                    let mut expired_ids = Vec::new();
-                    for (ban_id, entry) in s.bans.iter() {
-                        if entry.expires_at_unix != 0 && entry.expires_at_unix <= now {
-                            expired_ids.push(ban_id.clone());
-                        }
-                    }
-
-                    if expired_ids.is_empty() {
-                        Vec::new()
-                    } else {
-                        let mut evs = Vec::new();
-                        for ban_id in expired_ids {
-                            if let Some(entry) = s.bans.remove(&ban_id) {
-                                s.ban_by_key.remove(&entry.key.to_cidr_string());
-                            }
-                            let idx = s.next_index;
-                            s.next_index = s.next_index.saturating_add(1);
-                            evs.push(EventEnvelope {
-                                index: idx,
-                                event: Event::BanDeleted { ban_id },
-                            });
-                        }
-                        evs
-                    }
-                };
-
-                for ev in expired_events {
-                    let _ = tx.send(ev);
-                }
-            }
-        });
-    }
+    // TTL reaper: the leader commits removal of expired bans (BanDeleted events follow).
+    tokio::spawn(Sweeper::new(node.clone(), Arc::new(SystemClock)).run());
 
     // Serve admin + events in parallel.
-    let st_admin = st.clone();
-    let tx_admin = ev_tx.clone();
+    let node_admin = node.clone();
     let admin_task = tokio::spawn(async move {
         loop {
             match admin_listener.accept().await {
                 Ok((conn, _addr)) => {
-                    let st = st_admin.clone();
-                    let tx = tx_admin.clone();
+                    let node = node_admin.clone();
                     tokio::spawn(async move {
-                        if let Err(e) = handle_admin_conn(st, tx, conn).await {
+                        if let Err(e) = handle_admin_conn(node, conn).await {
                             warn!(err=%e, "admin conn error");
                         }
                     });
                 }
                 Err(e) => {
                     warn!(err=%e, "admin accept error");
-                    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                    tokio::time::sleep(Duration::from_millis(200)).await;
                 }
             }
         }
     });
 
-    let st_events = st.clone();
+    let node_events = node.clone();
     let tx_events = ev_tx.clone();
     let events_task = tokio::spawn(async move {
         loop {
             match events_listener.accept().await {
                 Ok((conn, _addr)) => {
-                    let st = st_events.clone();
+                    let node = node_events.clone();
                     let tx = tx_events.clone();
                     tokio::spawn(async move {
-                        if let Err(e) = handle_events_conn(st, tx, conn).await {
+                        if let Err(e) = handle_events_conn(node, tx, conn).await {
                             warn!(err=%e, "events conn error");
                         }
                     });
                 }
                 Err(e) => {
                     warn!(err=%e, "events accept error");
-                    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                    tokio::time::sleep(Duration::from_millis(200)).await;
                 }
             }
         }
@@ -0,0 +1,671 @@
+//! The replicated SBC state and the commands that change it.
+//!
+//! `apply` must be deterministic: every replica applies the same commands in the same order, so
+//! anything time- or randomness-dependent is decided by the node that proposes the command and
+//! carried inside it.
+
+use std::collections::{HashMap, VecDeque};
+
+use sbc_core::{
+    AdminReq, AdminResp, AppealStatus, BanAction, BanAppeal, BanApplyResult, BanEntry,
+    BanHistoryEntry, EnforcementStatus, Event, EventEnvelope, IpPrefix, LegalHoldEntry,
+};
+
+/// Actor recorded for bans dropped by `ExpireBans`.
+const EXPIRY_ACTOR: &str = "sbc-raftd";
+
+/// Recent events kept for `Subscribe { from_index }` replay.
+const RETAINED_EVENTS: usize = 4096;
+
+/// Ban history is kept for this long, and at most this many entries.
+const HISTORY_MAX_AGE_S: u64 = 90 * 24 * 60 * 60;
+const HISTORY_MAX_ENTRIES: usize = 100_000;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PersistedState {
+    pub next_index: u64,
+    pub bans: HashMap<String, BanEntry>,     // ban_id -> entry
+    pub ban_by_key: HashMap<String, String>, // cidr -> ban_id
+    #[serde(default)]
+    pub holds: HashMap<String, LegalHoldEntry>, // name_lc -> entry
+    #[serde(default)]
+    pub last_status_by_node: HashMap<String, EnforcementStatus>,
+    #[serde(default)]
+    pub last_apply_by_node_ban: HashMap<String, BanApplyResult>,
+    /// Outlives deletes and rides along in snapshots; aged out after `HISTORY_MAX_AGE_S` or
+    /// once it passes `HISTORY_MAX_ENTRIES`.
+    #[serde(default)]
+    pub history: Vec<BanHistoryEntry>,
+    #[serde(default)]
+    pub appeals: HashMap<String, BanAppeal>, // appeal_id -> appeal
+    /// Set by the first committed `Import`, so retried imports are no-ops.
+    #[serde(default)]
+    pub legacy_imported: bool,
+    /// Not persisted: after a restart it refills as the log replays past the snapshot.
+    #[serde(skip)]
+    pub recent: VecDeque<EventEnvelope>,
+}
+
+/// A log entry.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Command {
+    /// An admin mutation, stamped with the proposer's clock and fresh ids: `new_ban_id` is used
+    /// only if the CIDR isn't banned yet, `new_appeal_id` only by `FileBanAppeal`.
+    Admin {
+        req: AdminReq,
+        now_unix: u64,
+        new_ban_id: String,
+        /// Empty in entries logged before appeals had their own id; those used `new_ban_id`.
+        #[serde(default)]
+        new_appeal_id: String,
+    },
+    /// Drop bans that expired at or before `now_unix`.
+    ExpireBans { now_unix: u64 },
+    /// Seed from a pre-cluster `sbc-state.json`; ignored unless the state is still empty, and
+    /// after the first one commits.
+    Import { state: PersistedState },
+}
+
+impl PersistedState {
+    pub fn empty() -> Self {
+        Self {
+            next_index: 1,
+            bans: HashMap::new(),
+            ban_by_key: HashMap::new(),
+            holds: HashMap::new(),
+            last_status_by_node: HashMap::new(),
+            last_apply_by_node_ban: HashMap::new(),
+            history: Vec::new(),
+            appeals: HashMap::new(),
+            legacy_imported: false,
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.next_index <= 1
+    }
+
+    fn push_event(&mut self, event: Event) -> EventEnvelope {
+        let index = self.next_index;
+        self.next_index = self.next_index.saturating_add(1);
+        let ev = EventEnvelope { index, event };
+        if self.recent.len() >= RETAINED_EVENTS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(ev.clone());
+        ev
+    }
+
+    /// The whole current picture, as sent to subscribers that can't replay.
+    pub fn snapshot_event(&self) -> EventEnvelope {
+        EventEnvelope {
+            index: self.next_index.saturating_sub(1),
+            event: Event::Snapshot {
+                bans: self.bans.values().cloned().collect(),
+                holds: self.holds.values().cloned().collect(),
+            },
+        }
+    }
+
+    /// Retained events from `from_index` on, or `None` if some of them are no longer retained
+    /// (or `from_index` is ahead of this replica).
+    pub fn events_since(&self, from_index: u64) -> Option<Vec<EventEnvelope>> {
+        if from_index == self.next_index {
+            return Some(Vec::new());
+        }
+        let first = self.recent.front()?.index;
+        if from_index < first || from_index > self.next_index {
+            return None;
+        }
+        Some(
+            self.recent
+                .iter()
+                .filter(|e| e.index >= from_index)
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Remove a ban (if present) and record why; emits `BanDeleted` either way.
+    fn remove_ban(
+        &mut self,
+        ban_id: String,
+        now: u64,
+        action: BanAction,
+        actor: String,
+        reason: String,
+    ) -> EventEnvelope {
+        let removed = self.bans.remove(&ban_id);
+        if let Some(entry) = &removed {
+            self.ban_by_key.remove(&entry.key.to_cidr_string());
+        }
+        let ev = self.push_event(Event::BanDeleted { ban_id });
+        if let Some(entry) = removed {
+            self.record_history(BanHistoryEntry {
+                index: ev.index,
+                at_unix: now,
+                ban_id: entry.ban_id,
+                key: entry.key,
+                action,
+                actor,
+                reason,
+                expires_at_unix: entry.expires_at_unix,
+            });
+        }
+        ev
+    }
+
+    /// Append to the history, dropping entries that are too old or over the cap. Age is judged
+    /// by the new entry's time, so every replica prunes identically.
+    fn record_history(&mut self, h: BanHistoryEntry) {
+        let cutoff = h.at_unix.saturating_sub(HISTORY_MAX_AGE_S);
+        self.history.push(h);
+        let over = self.history.len().saturating_sub(HISTORY_MAX_ENTRIES);
+        let stale = self
+            .history
+            .iter()
+            .skip(over)
+            .take_while(|h| h.at_unix < cutoff)
+            .count();
+        if over + stale > 0 {
+            self.history.drain(..over + stale);
+        }
+    }
+
+    pub fn ban_history(
+        &self,
+        cidr: Option<&str>,
+        ban_id: Option<&str>,
+        since_unix: Option<u64>,
+        until_unix: Option<u64>,
+    ) -> anyhow::Result<Vec<BanHistoryEntry>> {
+        let pfx = cidr.map(IpPrefix::parse_cidr).transpose()?;
+        Ok(self
+            .history
+            .iter()
+            .filter(|h| {
+                pfx.as_ref()
+                    .is_none_or(|p| p.contains_prefix(&h.key) || h.key.contains_prefix(p))
+            })
+            .filter(|h| ban_id.is_none_or(|id| h.ban_id == id))
+            .filter(|h| since_unix.is_none_or(|t| h.at_unix >= t))
+            .filter(|h| until_unix.is_none_or(|t| h.at_unix <= t))
+            .cloned()
+            .collect())
+    }
+
+    /// Appeals, oldest first.
+    pub fn ban_appeals(
+        &self,
+        ban_id: Option<&str>,
+        status: Option<AppealStatus>,
+    ) -> Vec<BanAppeal> {
+        let mut out = self
+            .appeals
+            .values()
+            .filter(|a| ban_id.is_none_or(|id| a.ban_id == id))
+            .filter(|a| status.is_none_or(|st| a.status == st))
+            .cloned()
+            .collect::<Vec<_>>();
+        out.sort_by(|a, b| (a.filed_at_unix, &a.appeal_id).cmp(&(b.filed_at_unix, &b.appeal_id)));
+        out
+    }
+
+    /// When the earliest expiring ban comes due, if any.
+    pub fn next_expiry(&self) -> Option<u64> {
+        self.bans
+            .values()
+            .map(|e| e.expires_at_unix)
+            .filter(|t| *t != 0)
+            .min()
+    }
+
+    pub fn expired_ban_ids(&self, now_unix: u64) -> Vec<String> {
+        let mut ids = self
+            .bans
+            .iter()
+            .filter(|(_, e)| e.expires_at_unix != 0 && e.expires_at_unix <= now_unix)
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+        // HashMap order differs per replica; event indices must not.
+        ids.sort();
+        ids
+    }
+
+    /// Apply one committed command; returns the client response and the events to publish.
+    pub fn apply(&mut self, cmd: Command) -> (AdminResp, Vec<EventEnvelope>) {
+        match cmd {
+            Command::Admin {
+                req,
+                now_unix,
+                new_ban_id,
+                new_appeal_id,
+            } => {
+                let new_appeal_id = if new_appeal_id.is_empty() {
+                    new_ban_id.clone()
+                } else {
+                    new_appeal_id
+                };
+                let (resp, ev) = self.apply_admin(req, now_unix, new_ban_id, new_appeal_id);
+                (resp, ev.into_iter().collect())
+            }
+            Command::ExpireBans { now_unix } => {
+                let mut evs = Vec::new();
+                for ban_id in self.expired_ban_ids(now_unix) {
+                    evs.push(self.remove_ban(
+                        ban_id,
+                        now_unix,
+                        BanAction::Expired,
+                        EXPIRY_ACTOR.to_string(),
+                        "expired".to_string(),
+                    ));
+                }
+                let index = self.next_index.saturating_sub(1);
+                (AdminResp::Ok { index }, evs)
+            }
+            Command::Import { state } => {
+                if !self.legacy_imported && self.is_empty() {
+                    *self = state;
+                }
+                self.legacy_imported = true;
+                let index = self.next_index.saturating_sub(1);
+                (AdminResp::Ok { index }, Vec::new())
+            }
+        }
+    }
+
+    fn apply_admin(
+        &mut self,
+        req: AdminReq,
+        now: u64,
+        new_ban_id: String,
+        new_appeal_id: String,
+    ) -> (AdminResp, Option<EventEnvelope>) {
+        let err = |message: &str| {
+            (
+                AdminResp::Err {
+                    message: message.to_string(),
+                },
+                None,
+            )
+        };
+        match req {
+            AdminReq::UpsertBan {
+                key,
+                ttl_s,
+                created_by,
+                reason,
+            } => {
+                let key_pfx = match IpPrefix::parse_cidr(&key) {
+                    Ok(p) => p,
+                    Err(e) => return err(&e.to_string()),
+                };
+                let key_cidr = key_pfx.to_cidr_string();
+                // ttl_s=0 means "never expires" (until explicitly deleted).
+                let expires_at_unix = if ttl_s == 0 {
+                    0
+                } else {
+                    now.saturating_add(ttl_s)
+                };
+
+                let ban_id = self
+                    .ban_by_key
+                    .entry(key_cidr)
+                    .or_insert(new_ban_id)
+                    .clone();
+
+                let action = match self.bans.get(&ban_id) {
+                    None => BanAction::Created,
+                    Some(old) if extends(old.expires_at_unix, expires_at_unix) => {
+                        BanAction::Extended
+                    }
+                    Some(_) => BanAction::Updated,
+                };
+                let entry = BanEntry {
+                    ban_id: ban_id.clone(),
+                    key: key_pfx,
+                    created_at_unix: now,
+                    created_by,
+                    reason,
+                    expires_at_unix,
+                };
+                self.bans.insert(ban_id, entry.clone());
+
+                let ev = self.push_event(Event::BanUpserted {
+                    entry: entry.clone(),
+                });
+                self.record_history(BanHistoryEntry {
+                    index: ev.index,
+                    at_unix: now,
+                    ban_id: entry.ban_id.clone(),
+                    key: entry.key.clone(),
+                    action,
+                    actor: entry.created_by.clone(),
+                    reason: entry.reason.clone(),
+                    expires_at_unix,
+                });
+                (
+                    AdminResp::OkBan {
+                        index: ev.index,
+                        entry,
+                    },
+                    Some(ev),
+                )
+            }
+            AdminReq::DeleteBan {
+                ban_id,
+                deleted_by,
+                reason,
+            } => {
+                let ev = self.remove_ban(ban_id, now, BanAction::Deleted, deleted_by, reason);
+                (AdminResp::Ok { index: ev.index }, Some(ev))
+            }
+            AdminReq::FileBanAppeal {
+                ban_id,
+                filed_by,
+                message,
+            } => {
+                let Some(ban) = self.bans.get(&ban_id) else {
+                    return err("no such ban");
+                };
+                if self
+                    .appeals
+                    .values()
+                    .any(|a| a.ban_id == ban_id && a.status == AppealStatus::Open)
+                {
+                    return err("ban already has an open appeal");
+                }
+                let appeal = BanAppeal {
+                    appeal_id: new_appeal_id.clone(),
+                    ban_id,
+                    key: ban.key.clone(),
+                    filed_at_unix: now,
+                    filed_by,
+                    message,
+                    status: AppealStatus::Open,
+                    decided_at_unix: None,
+                    decided_by: None,
+                    decision_note: None,
+                };
+                self.appeals.insert(new_appeal_id, appeal.clone());
+                let index = self.next_index.saturating_sub(1);
+                (AdminResp::OkBanAppeal { index, appeal }, None)
+            }
+            AdminReq::DecideBanAppeal {
+                appeal_id,
+                granted,
+                decided_by,
+                note,
+            } => {
+                let Some(appeal) = self.appeals.get_mut(&appeal_id) else {
+                    return err("no such appeal");
+                };
+                if appeal.status != AppealStatus::Open {
+                    return err("appeal already decided");
+                }
+                appeal.status = if granted {
+                    AppealStatus::Granted
+                } else {
+                    AppealStatus::Denied
+                };
+                appeal.decided_at_unix = Some(now);
+                appeal.decided_by = Some(decided_by.clone());
+                appeal.decision_note = Some(note.clone());
+                let appeal = appeal.clone();
+
+                // The ban may have expired or been deleted while the appeal was open.
+                let ev = (granted && self.bans.contains_key(&appeal.ban_id)).then(|| {
+                    let reason = format!("appeal {appeal_id} granted: {note}");
+                    self.remove_ban(
+                        appeal.ban_id.clone(),
+                        now,
+                        BanAction::Deleted,
+                        decided_by,
+                        reason,
+                    )
+                });
+                let index = self.next_index.saturating_sub(1);
+                (AdminResp::OkBanAppeal { index, appeal }, ev)
+            }
+            AdminReq::UpsertLegalHold {
+                name,
+                created_by,
+                reason,
+            } => {
+                let name_lc = name.trim().to_ascii_lowercase();
+                if name_lc.is_empty() {
+                    return err("missing name");
+                }
+                let entry = LegalHoldEntry {
+                    name_lc: name_lc.clone(),
+                    created_at_unix: now,
+                    created_by,
+                    reason,
+                };
+                self.holds.insert(name_lc, entry.clone());
+                let ev = self.push_event(Event::LegalHoldUpserted {
+                    entry: entry.clone(),
+                });
+                (
+                    AdminResp::OkLegalHold {
+                        index: ev.index,
+                        entry,
+                    },
+                    Some(ev),
+                )
+            }
+            AdminReq::DeleteLegalHold { name } => {
+                let name_lc = name.trim().to_ascii_lowercase();
+                if name_lc.is_empty() {
+                    return err("missing name");
+                }
+                self.holds.remove(&name_lc);
+                let ev = self.push_event(Event::LegalHoldDeleted { name_lc });
+                (AdminResp::Ok { index: ev.index }, Some(ev))
+            }
+            AdminReq::ReportEnforcementStatus { mut status } => {
+                status.reported_at_index = self.next_index;
+                self.last_status_by_node
+                    .insert(status.node_id.clone(), status.clone());
+                let ev = self.push_event(Event::EnforcementStatus { status });
+                (AdminResp::Ok { index: ev.index }, Some(ev))
+            }
+            AdminReq::ReportBanApplyResult { report } => {
+                let k = format!("{}:{}:{}", report.node_id, report.ban_id, report.op);
+                self.last_apply_by_node_ban.insert(k, report.clone());
+                let ev = self.push_event(Event::BanApplyResult { report });
+                (AdminResp::Ok { index: ev.index }, Some(ev))
+            }
+            // Reads and membership changes never reach the log.
+            AdminReq::GetState
+            | AdminReq::AddRaftNode { .. }
+            | AdminReq::RemoveRaftNode { .. }
+            | AdminReq::GetRaftStatus
+            | AdminReq::GetBanHistory { .. }
+            | AdminReq::ListBanAppeals { .. } => err("not a replicated command"),
+        }
+    }
+}
+
+/// Whether moving a ban's expiry from `old` to `new` (0 = never) lengthens it.
+fn extends(old: u64, new: u64) -> bool {
+    old != 0 && (new == 0 || new > old)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn admin(st: &mut PersistedState, req: AdminReq, now: u64, id: &str) -> AdminResp {
+        st.apply(Command::Admin {
+            req,
+            now_unix: now,
+            new_ban_id: id.to_string(),
+            new_appeal_id: id.to_string(),
+        })
+        .0
+    }
+
+    fn ban(cidr: &str, ttl_s: u64, by: &str) -> AdminReq {
+        AdminReq::UpsertBan {
+            key: cidr.to_string(),
+            ttl_s,
+            created_by: by.to_string(),
+            reason: "abuse".to_string(),
+        }
+    }
+
+    #[test]
+    fn history_ages_out() {
+        let mut st = PersistedState::empty();
+        admin(&mut st, ban("192.0.2.0/24", 0, "ops"), 100, "b1");
+        admin(&mut st, ban("192.0.2.0/24", 0, "ops"), 200, "unused");
+        assert_eq!(st.history.len(), 2);
+
+        let later = 150 + HISTORY_MAX_AGE_S;
+        admin(&mut st, ban("198.51.100.0/24", 0, "ops"), later, "b2");
+        let kept = st.history.iter().map(|h| h.at_unix).collect::<Vec<_>>();
+        assert_eq!(kept, vec![200, later]);
+    }
+
+    #[test]
+    fn import_applies_once() {
+        let mut legacy = PersistedState::empty();
+        admin(&mut legacy, ban("192.0.2.0/24", 0, "ops"), 100, "b1");
+
+        let mut st = PersistedState::empty();
+        st.apply(Command::Import {
+            state: legacy.clone(),
+        });
+        assert!(st.legacy_imported);
+        assert!(st.bans.contains_key("b1"));
+
+        // A retry after later changes must not roll them back.
+        admin(&mut st, ban("198.51.100.0/24", 0, "ops"), 110, "b2");
+        st.apply(Command::Import { state: legacy });
+        assert!(st.bans.contains_key("b2"));
+    }
+
+    #[test]
+    fn history_outlives_the_ban() {
+        let mut st = PersistedState::empty();
+        admin(&mut st, ban("192.0.2.0/24", 60, "ops"), 100, "b1");
+        admin(&mut st, ban("192.0.2.0/24", 600, "ops"), 110, "unused");
+        admin(
+            &mut st,
+            ban("198.51.100.7/32", 60, "sbc-deciderd"),
+            120,
+            "b2",
+        );
+        st.apply(Command::ExpireBans { now_unix: 180 });
+        admin(
+            &mut st,
+            AdminReq::DeleteBan {
+                ban_id: "b1".to_string(),
+                deleted_by: "alice".to_string(),
+                reason: "false positive".to_string(),
+            },
+            200,
+            "",
+        );
+        assert!(st.bans.is_empty());
+
+        let actions = |h: Vec<BanHistoryEntry>| h.into_iter().map(|h| h.action).collect::<Vec<_>>();
+        let b1 = st
+            .ban_history(Some("192.0.2.5/32"), None, None, None)
+            .unwrap();
+        assert_eq!(b1.last().unwrap().actor, "alice");
+        assert_eq!(
+            actions(b1),
+            vec![BanAction::Created, BanAction::Extended, BanAction::Deleted]
+        );
+        let b2 = st.ban_history(None, Some("b2"), None, None).unwrap();
+        assert_eq!(actions(b2), vec![BanAction::Created, BanAction::Expired]);
+        let window = st
+            .ban_history(Some("0.0.0.0/0"), None, Some(120), Some(180))
+            .unwrap();
+        assert_eq!(window.len(), 2);
+        assert!(st.ban_history(Some("nope"), None, None, None).is_err());
+    }
+
+    #[test]
+    fn replays_retained_events_from_an_index() {
+        let mut st = PersistedState::empty();
+        for i in 0..RETAINED_EVENTS + 10 {
+            admin(
+                &mut st,
+                ban(&format!("10.0.{}.{}/32", i / 256, i % 256), 0, "ops"),
+                100,
+                &i.to_string(),
+            );
+        }
+        let last = st.next_index - 1;
+        let tail = st.events_since(last - 2).unwrap();
+        assert_eq!(
+            tail.iter().map(|e| e.index).collect::<Vec<_>>(),
+            vec![last - 2, last - 1, last]
+        );
+        assert!(st.events_since(st.next_index).unwrap().is_empty());
+        // Compacted away, or from some other history: the caller falls back to a snapshot.
+        assert!(st.events_since(1).is_none());
+        assert!(st.events_since(st.next_index + 5).is_none());
+        assert_eq!(st.snapshot_event().index, last);
+    }
+
+    #[test]
+    fn granting_an_appeal_lifts_the_ban() {
+        let mut st = PersistedState::empty();
+        admin(&mut st, ban("203.0.113.0/24", 0, "ops"), 100, "b1");
+        let file = || AdminReq::FileBanAppeal {
+            ban_id: "b1".to_string(),
+            filed_by: "portal:bob".to_string(),
+            message: "shared office NAT".to_string(),
+        };
+        let AdminResp::OkBanAppeal { appeal, .. } = admin(&mut st, file(), 110, "a1") else {
+            panic!("appeal not filed");
+        };
+        assert_eq!(appeal.status, AppealStatus::Open);
+        assert!(matches!(
+            admin(&mut st, file(), 111, "a2"),
+            AdminResp::Err { .. }
+        ));
+
+        let decide = AdminReq::DecideBanAppeal {
+            appeal_id: "a1".to_string(),
+            granted: true,
+            decided_by: "carol".to_string(),
+            note: "verified".to_string(),
+        };
+        let (resp, evs) = st.apply(Command::Admin {
+            req: decide.clone(),
+            now_unix: 120,
+            new_ban_id: String::new(),
+            new_appeal_id: String::new(),
+        });
+        assert!(matches!(resp, AdminResp::OkBanAppeal { .. }), "{resp:?}");
+        assert!(matches!(
+            &evs[..],
+            [EventEnvelope {
+                event: Event::BanDeleted { .. },
+                ..
+            }]
+        ));
+        assert!(st.bans.is_empty());
+        let last = st.history.last().unwrap();
+        assert_eq!(
+            (last.action, last.actor.as_str()),
+            (BanAction::Deleted, "carol")
+        );
+        assert!(matches!(
+            admin(&mut st, decide, 130, ""),
+            AdminResp::Err { .. }
+        ));
+        assert_eq!(
+            st.ban_appeals(Some("b1"), Some(AppealStatus::Granted))
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! Ban expiry. The leader commits `ExpireBans` through the log once the earliest ban comes due,
+//! so every replica drops it at the same log position and subscribers see `BanDeleted`.
+//!
+//! Time comes from a `Clock`, so tests can step it by hand.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use fanzylog::rpc::Transport;
+
+use crate::cluster::{SbcNode, TypeConfig};
+
+pub trait Clock: Send + Sync {
+    fn now_unix(&self) -> u64;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_unix(&self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+}
+
+/// Longest nap with nothing due. New bans wake the sweeper early, so this only bounds drift.
+const MAX_IDLE: Duration = Duration::from_secs(60);
+/// Recheck interval while bans are due but this node can't expire them (not the leader, or
+/// the proposal failed).
+const RETRY: Duration = Duration::from_secs(1);
+
+pub struct Sweeper<T> {
+    node: Arc<SbcNode<T>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl<T: Transport<TypeConfig>> Sweeper<T> {
+    pub fn new(node: Arc<SbcNode<T>>, clock: Arc<dyn Clock>) -> Self {
+        Self { node, clock }
+    }
+
+    /// Expire whatever is due now; returns how long until the next pass is needed.
+    pub async fn sweep(&self) -> Duration {
+        let now = self.clock.now_unix();
+        self.node.expire_bans(now).await;
+        match self.node.st.lock().await.next_expiry() {
+            Some(at) if at > now => Duration::from_secs(at - now).min(MAX_IDLE),
+            Some(_) => RETRY,
+            None => MAX_IDLE,
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            let wait = self.sweep().await;
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = self.node.changed.notified() => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use fanzylog::BasicNode;
+    use fanzylog::loopback::Loopback;
+    use sbc_core::{AdminReq, AdminResp, BanAction, Event};
+    use tokio::sync::broadcast;
+
+    use super::*;
+    use crate::cluster::raft_config;
+
+    struct ManualClock(AtomicU64);
+
+    impl Clock for ManualClock {
+        fn now_unix(&self) -> u64 {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn expires_bans_through_the_log_when_due() {
+        let dir = std::env::temp_dir().join(format!("sbc_sweeper_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let net = Loopback::new();
+        let (tx, mut rx) = broadcast::channel(64);
+        let node = SbcNode::start(1, &dir, Arc::new(raft_config().unwrap()), net.clone(), tx)
+            .await
+            .unwrap();
+        net.register(1, node.raft.clone());
+        let members = BTreeMap::from([(
+            1,
+            BasicNode {
+                addr: "n1".to_string(),
+            },
+        )]);
+        node.bootstrap(members).await.unwrap();
+        for _ in 0..100 {
+            if node.is_leader().await {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let node = Arc::new(node);
+
+        let clock = Arc::new(ManualClock(AtomicU64::new(1_000)));
+        let sweeper = Sweeper::new(node.clone(), clock.clone());
+        assert_eq!(sweeper.sweep().await, MAX_IDLE);
+
+        let ban = AdminReq::UpsertBan {
+            key: "198.51.100.7/32".to_string(),
+            ttl_s: 30,
+            created_by: "test".to_string(),
+            reason: "test".to_string(),
+        };
+        let resp = node.handle(ban, 1_000, "b1".to_string()).await;
+        assert!(matches!(resp, AdminResp::OkBan { .. }), "{resp:?}");
+        assert_eq!(sweeper.sweep().await, Duration::from_secs(30));
+
+        clock.0.store(1_029, Ordering::SeqCst);
+        assert_eq!(sweeper.sweep().await, Duration::from_secs(1));
+        assert!(node.st.lock().await.bans.contains_key("b1"));
+
+        clock.0.store(1_030, Ordering::SeqCst);
+        assert_eq!(sweeper.sweep().await, MAX_IDLE);
+        {
+            let st = node.st.lock().await;
+            assert!(st.bans.is_empty());
+            let last = st.history.last().unwrap();
+            assert_eq!((last.action, last.at_unix), (BanAction::Expired, 1_030));
+        }
+        let mut deleted = false;
+        while let Ok(ev) = rx.try_recv() {
+            deleted |= matches!(ev.event, Event::BanDeleted { ref ban_id } if ban_id == "b1");
+        }
+        assert!(deleted);
+
+        node.raft.shutdown().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -341,6 +341,17 @@ impl GroupStore {
         self.groups.get(&id)
     }
 
+    /// The guild `principal` is in (lowest id if several). Guests of a guild aren't in it.
+    pub fn guild_of(&self, principal: &str) -> Option<u64> {
+        let key = principal.trim().to_ascii_lowercase();
+        self.groups
+            .values()
+            .filter(|g| g.kind == GroupKind::Guild)
+            .filter(|g| g.members.get(&key).is_some_and(|r| *r != GroupRole::Guest))
+            .map(|g| g.id)
+            .min()
+    }
+
     pub fn effective_caps_for_principal(
         &self,
         principal: &str,
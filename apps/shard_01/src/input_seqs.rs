@@ -0,0 +1,118 @@
+//! Highest input number taken per session, kept on disk.
+//!
+//! The broker resends every line the shard hasn't acked, so the shard has to remember what it
+//! already ran across its own restarts too, not just across broker reconnects. A number is saved
+//! before its line runs; a crash in between drops that one line instead of running it twice.
+//! Sessions that die while the shard is down never get a detach, so entries untouched for
+//! `STALE_AFTER_S` are dropped on load.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use mudproto::session::SessionId;
+use tracing::warn;
+
+const STALE_AFTER_S: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+struct Entry {
+    seq: u64,
+    at: u64,
+}
+
+#[derive(Debug)]
+pub struct InputSeqs {
+    path: PathBuf,
+    /// Keyed by the session id in hex; JSON object keys have to be strings.
+    seqs: BTreeMap<String, Entry>,
+}
+
+fn key(session: SessionId) -> String {
+    format!("{:032x}", session.0)
+}
+
+fn now_s() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl InputSeqs {
+    pub fn load(path: PathBuf) -> Self {
+        let mut seqs: BTreeMap<String, Entry> = match std::fs::read_to_string(&path) {
+            Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+                warn!(path = %path.display(), err = %e, "bad input seq file; starting empty");
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        let cutoff = now_s().saturating_sub(STALE_AFTER_S);
+        seqs.retain(|_, e| e.at >= cutoff);
+        Self { path, seqs }
+    }
+
+    pub fn last(&self, session: SessionId) -> u64 {
+        self.seqs.get(&key(session)).map_or(0, |e| e.seq)
+    }
+
+    /// Record `seq` for `session`. Returns false if it was already taken (a resend).
+    pub fn take(&mut self, session: SessionId, seq: u64) -> bool {
+        if seq <= self.last(session) {
+            return false;
+        }
+        self.seqs.insert(key(session), Entry { seq, at: now_s() });
+        self.save_or_warn();
+        true
+    }
+
+    pub fn remove(&mut self, session: SessionId) {
+        if self.seqs.remove(&key(session)).is_some() {
+            self.save_or_warn();
+        }
+    }
+
+    fn save_or_warn(&self) {
+        if let Err(e) = self.save() {
+            warn!(path = %self.path.display(), err = %e, "failed to persist input seqs");
+        }
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&self.seqs)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seqs_survive_a_reload() {
+        let path =
+            std::env::temp_dir().join(format!("shard-input-seqs-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let (a, b) = (SessionId(1), SessionId(u128::MAX));
+
+        let mut s = InputSeqs::load(path.clone());
+        assert!(s.take(a, 1));
+        assert!(s.take(a, 2));
+        assert!(!s.take(a, 2));
+        assert!(s.take(b, 7));
+
+        let mut s = InputSeqs::load(path.clone());
+        assert_eq!((s.last(a), s.last(b)), (2, 7));
+        assert!(!s.take(a, 1));
+        s.remove(b);
+
+        let s = InputSeqs::load(path.clone());
+        assert_eq!((s.last(a), s.last(b)), (2, 0));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -7,14 +7,22 @@ use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use anyhow::Context;
+use mudproto::ProtoError;
+use mudproto::assertion::{self, AssertionKey};
+use mudproto::color;
 use mudproto::session::SessionId;
-use mudproto::shard::{RESP_ERR, RESP_OUTPUT, ShardReq};
+use mudproto::shard::{
+    FEATURE_CHAT_SCOPE, FEATURE_HANDOFF, FEATURES as SHARD_FEATURES, PROTO_VERSION,
+    RESP_CHAT_SCOPE, RESP_ERR, RESP_HANDOFF, RESP_HELLO, RESP_INPUT_ACK, RESP_OUTPUT, ShardReq,
+    encode_handoff, encode_hello, encode_input_ack,
+};
 use reqwest::StatusCode;
 use slopio::frame::{FrameReader, FrameWriter};
 use tokio::net::{TcpListener, TcpStream};
 use tracing::{Level, info, warn};
 
 mod groups;
+mod input_seqs;
 mod items;
 mod protoadventure;
 mod raftlog;
@@ -37,6 +45,29 @@ struct AuthBlob {
     oidc_email: Option<String>,
     #[serde(default)]
     caps: Option<Vec<String>>,
+    /// Digest of the handoff snapshot sent with this attach (see `handoff_vouched`).
+    #[serde(default)]
+    snapshot_sha256: Option<String>,
+}
+
+/// Payload of a broker-signed `REQ_PLAYER_OP`. Character names are unique across accounts, so ops
+/// match saved characters by name under any non-guest principal.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum PlayerOp {
+    /// The account (or just this character) was deleted.
+    Forget {
+        name: String,
+    },
+    Rename {
+        name: String,
+        new_name: String,
+    },
+    /// A moderator silenced the character until `until_unix` (in the past lifts it).
+    Mute {
+        name: String,
+        until_unix: u64,
+    },
 }
 
 const OPENAI_API_BASE_DEFAULT: &str = "https://api.openai.com/v1";
@@ -494,17 +525,28 @@ fn command_arg<'a>(line: &'a str, command: &str) -> Option<&'a str> {
 }
 
 fn shout_payload(line: &str, speaker: &str) -> Option<String> {
-    command_arg(line, "shout").map(|msg| format!("{speaker} shouts: {msg}"))
+    command_arg(line, "shout")
+        .map(|msg| format!("{{Y}}{speaker} shouts:{{x}} {}", color::escape(msg)))
 }
 
 fn room_emote_payload(line: &str, speaker: &str, command: &str) -> Option<String> {
-    command_arg(line, command).map(|msg| format!("* {speaker} {msg}"))
+    command_arg(line, command).map(|msg| format!("* {speaker} {}", color::escape(msg)))
 }
 
 fn room_emote_noarg(speaker: &str, verb: &str) -> String {
     format!("* {speaker} {verb}")
 }
 
+/// Commands that put a player's words in front of others; a mute refuses these.
+fn is_talk_command(lc: &str) -> bool {
+    let mut w = lc.split_whitespace();
+    match w.next().unwrap_or("") {
+        "say" | "shout" | "yell" | "emote" | "em" | "me" | "pose" | "tell" | "whisper" => true,
+        "party" => w.next() == Some("say"),
+        _ => false,
+    }
+}
+
 fn is_tavern_sellable(token: &str) -> bool {
     let t = token.trim().to_ascii_lowercase();
     t == ITEM_STENCHPOUCH || t == "stench pouch" || t == "pouch"
@@ -981,7 +1023,7 @@ fn usage_and_exit() -> ! {
     eprintln!(
         "shard_01\n\n\
 USAGE:\n  shard_01 [--bind HOST:PORT]\n\n\
-ENV:\n  SHARD_BIND                  default 127.0.0.1:5000\n  WORLD_SEED                  default 1 (deterministic; replace with raft time/seed later)\n  WORLD_TICK_MS               default 1000\n  BARTENDER_EMOTE_MS          default 30000\n  MOB_WANDER_MS               default 15000\n  SHARD_RAFT_LOG              default var/shard_01_raft.jsonl\n  SHARD_BOOTSTRAP_ADMINS      comma-separated acct names added to admin group (genesis only)\n  SHARD_BOOTSTRAP_ADMIN_SSO   comma-separated principals added to admin group (genesis only)\n                             ex: google_email:rob@caskey.org,google_sub:123,acct:rob\n"
+ENV:\n  SHARD_BIND                  default 127.0.0.1:5000\n  WORLD_SEED                  default 1 (deterministic; replace with raft time/seed later)\n  WORLD_TICK_MS               default 1000\n  BARTENDER_EMOTE_MS          default 30000\n  MOB_WANDER_MS               default 15000\n  SHARD_RAFT_LOG              default var/shard_01_raft.jsonl\n  SHARD_AREAS                 comma-separated area ids this shard owns (default: all)\n                             players walking into other areas are handed off via the broker\n  SHARD_BOOTSTRAP_ADMINS      comma-separated acct names added to admin group (genesis only)\n  SHARD_BOOTSTRAP_ADMIN_SSO   comma-separated principals added to admin group (genesis only)\n                             ex: google_email:rob@caskey.org,google_sub:123,acct:rob\n  SHARD_AUTH_KEYS             comma-separated kid:secret keys accepted for broker-signed attaches\n                             (list old and new while rotating); unsigned attaches are guests\n"
     );
     std::process::exit(2);
 }
@@ -997,6 +1039,10 @@ struct Config {
     players_path: PathBuf,
     bootstrap_admins: Vec<String>,
     bootstrap_admin_sso: Vec<String>,
+    /// `None` = owns every area.
+    areas: Option<HashSet<String>>,
+    /// Broker signing keys; an attach without a valid assertion gets no identity or caps.
+    auth_keys: Vec<AssertionKey>,
 }
 
 fn parse_args() -> Config {
@@ -1049,6 +1095,21 @@ fn parse_args() -> Config {
                 .collect::<Vec<_>>()
         })
         .unwrap_or_default();
+    let areas: Option<HashSet<String>> = std::env::var("SHARD_AREAS")
+        .ok()
+        .map(|v| {
+            v.split(',')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect::<HashSet<_>>()
+        })
+        .filter(|xs| !xs.is_empty());
+
+    let auth_keys = AssertionKey::parse_list(&std::env::var("SHARD_AUTH_KEYS").unwrap_or_default())
+        .unwrap_or_else(|e| {
+            eprintln!("SHARD_AUTH_KEYS: {e}");
+            usage_and_exit()
+        });
 
     let mut it = std::env::args().skip(1);
     while let Some(arg) = it.next() {
@@ -1072,51 +1133,79 @@ fn parse_args() -> Config {
         players_path,
         bootstrap_admins,
         bootstrap_admin_sso,
+        areas,
+        auth_keys,
     }
 }
 
+/// The auth payload the broker vouched for, if `auth` is a valid assertion for this session.
+fn verified_auth<'a>(
+    keys: &[AssertionKey],
+    session: SessionId,
+    auth: Option<&'a [u8]>,
+) -> Option<&'a [u8]> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    match assertion::verify(keys, auth?, session, now) {
+        Ok(payload) => Some(payload),
+        Err(e) => {
+            warn!(session = session.short(), err = %e, "attach auth rejected; treating as guest");
+            None
+        }
+    }
+}
+
+/// Whether the broker vouched for a handoff snapshot: the verified payload has to carry its
+/// digest. Anyone who can reach the shard port could otherwise attach with a made-up character.
+fn handoff_vouched(auth: Option<&[u8]>, snapshot: &[u8]) -> bool {
+    auth.and_then(|raw| serde_json::from_slice::<AuthBlob>(raw).ok())
+        .and_then(|a| a.snapshot_sha256)
+        .is_some_and(|d| d == assertion::digest(snapshot))
+}
+
 fn principal_from_attach(name: &str, auth: Option<&[u8]>) -> String {
-    // `auth` is asserted by the broker. This shard does not validate it; it is used only for
-    // authorization decisions (capabilities/groups).
-    if let Some(raw) = auth {
-        if let Ok(s) = std::str::from_utf8(raw) {
-            if let Ok(a) = serde_json::from_str::<AuthBlob>(s) {
-                if let Some(sub) = a.google_sub.as_deref() {
-                    let sub = sub.trim();
-                    if !sub.is_empty() {
-                        return format!("google_sub:{sub}");
-                    }
-                }
-                if let Some(sub) = a.oidc_sub.as_deref() {
-                    let sub = sub.trim();
-                    if !sub.is_empty() {
-                        return format!("oidc_sub:{sub}");
-                    }
-                }
-                if let Some(email) = a.google_email.as_deref() {
-                    let email = email.trim().to_ascii_lowercase();
-                    if !email.is_empty() {
-                        return format!("google_email:{email}");
-                    }
-                }
-                if let Some(email) = a.oidc_email.as_deref() {
-                    let email = email.trim().to_ascii_lowercase();
-                    if !email.is_empty() {
-                        return format!("oidc_email:{email}");
-                    }
-                }
-                if let Some(acct) = a.acct.as_deref() {
-                    let acct = acct.trim().to_ascii_lowercase();
-                    if !acct.is_empty() {
-                        return format!("acct:{acct}");
-                    }
-                }
-                if let Some(method) = a.method.as_deref() {
-                    let method = method.trim().to_ascii_lowercase();
-                    if !method.is_empty() {
-                        return format!("method:{method}");
-                    }
-                }
+    // `auth` is the verified payload of a broker assertion (see `verified_auth`); without one the
+    // session is a guest and must not be able to claim an account's principal.
+    let Some(raw) = auth else {
+        return format!("guest:{}", name.trim().to_ascii_lowercase());
+    };
+    if let Ok(a) = serde_json::from_slice::<AuthBlob>(raw) {
+        if let Some(sub) = a.google_sub.as_deref() {
+            let sub = sub.trim();
+            if !sub.is_empty() {
+                return format!("google_sub:{sub}");
+            }
+        }
+        if let Some(sub) = a.oidc_sub.as_deref() {
+            let sub = sub.trim();
+            if !sub.is_empty() {
+                return format!("oidc_sub:{sub}");
+            }
+        }
+        if let Some(email) = a.google_email.as_deref() {
+            let email = email.trim().to_ascii_lowercase();
+            if !email.is_empty() {
+                return format!("google_email:{email}");
+            }
+        }
+        if let Some(email) = a.oidc_email.as_deref() {
+            let email = email.trim().to_ascii_lowercase();
+            if !email.is_empty() {
+                return format!("oidc_email:{email}");
+            }
+        }
+        if let Some(acct) = a.acct.as_deref() {
+            let acct = acct.trim().to_ascii_lowercase();
+            if !acct.is_empty() {
+                return format!("acct:{acct}");
+            }
+        }
+        if let Some(method) = a.method.as_deref() {
+            let method = method.trim().to_ascii_lowercase();
+            if !method.is_empty() {
+                return format!("method:{method}");
             }
         }
     }
@@ -1124,8 +1213,8 @@ fn principal_from_attach(name: &str, auth: Option<&[u8]>) -> String {
 }
 
 fn caps_from_attach(auth: Option<&[u8]>) -> HashSet<groups::Capability> {
-    // `auth` is asserted by the broker; treat these caps as additional effective capabilities for
-    // the session's principal. Unknown caps are ignored.
+    // `auth` is the verified payload of a broker assertion; treat these caps as additional
+    // effective capabilities for the session's principal. Unknown caps are ignored.
     let mut out = HashSet::new();
     let Some(raw) = auth else {
         return out;
@@ -1202,6 +1291,9 @@ struct Character {
     bot_ever_since_ms: Option<u64>, // world ms when bot_ever first became true
     bot_mode_changed_ms: u64, // world ms when is_bot last changed
     friends: HashSet<String>, // friend character names (case-insensitive comparisons)
+    ignores: HashSet<String>, // players whose talk this character doesn't see
+    muted_until_unix: u64, // wall clock; talk commands are refused until then
+    handoff_seq: u64, // bumped on every shard handoff; orders copies across shards
     room_id: String,
     autoassist: bool,
     follow_leader: bool,
@@ -1235,6 +1327,12 @@ struct Character {
     equip: Equipment,
 }
 
+impl Character {
+    fn is_ignoring(&self, name: &str) -> bool {
+        !name.is_empty() && self.ignores.iter().any(|n| n.eq_ignore_ascii_case(name))
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Party {
     id: PartyId,
@@ -2341,7 +2439,13 @@ struct World {
     raft_watch: HashSet<CharacterId>,
     groups: groups::GroupStore,
     players_path: PathBuf,
-    players: HashMap<String, PlayerSnapshot>,
+    /// Keyed by `PlayerSnapshot::key`: one account principal can own several characters.
+    players: HashMap<(String, String), PlayerSnapshot>,
+    owned_areas: Option<HashSet<String>>,
+    /// Feature bits from the broker's HELLO (0 until it sends one).
+    peer_features: u64,
+    /// Last chat scope sent to the broker per session; see `sync_chat_scopes`.
+    chat_scopes: HashMap<SessionId, ChatScope>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -2389,6 +2493,10 @@ struct PlayerSnapshot {
     bot_ever_since_ms: Option<u64>,
     bot_mode_changed_ms: u64,
     friends: Vec<String>,
+    #[serde(default)]
+    ignores: Vec<String>,
+    #[serde(default)]
+    muted_until_unix: u64,
     room_id: String,
     autoassist: bool,
     follow_leader: bool,
@@ -2413,14 +2521,29 @@ struct PlayerSnapshot {
     max_stamina: i32,
     pvp_enabled: bool,
     equip: HashMap<String, String>,
+    /// Bumped on every handoff. Shards' clocks can disagree, so this (not a timestamp) decides
+    /// which of two copies is newer.
+    #[serde(default)]
+    handoff_seq: u64,
+}
+
+/// Key into `World::players`: (principal, lowercased character name).
+fn player_key(principal: &str, name: &str) -> (String, String) {
+    (principal.to_string(), name.trim().to_ascii_lowercase())
 }
 
 impl PlayerSnapshot {
+    fn key(&self) -> (String, String) {
+        player_key(&self.principal, &self.name)
+    }
+
     fn from_character(c: &Character) -> Option<Self> {
         let race = c.race?;
         let class = c.class?;
         let mut friends = c.friends.iter().cloned().collect::<Vec<_>>();
         friends.sort();
+        let mut ignores = c.ignores.iter().cloned().collect::<Vec<_>>();
+        ignores.sort();
 
         let mut equip = HashMap::new();
         for &slot in items::EquipSlot::all() {
@@ -2437,6 +2560,8 @@ impl PlayerSnapshot {
             bot_ever_since_ms: c.bot_ever_since_ms,
             bot_mode_changed_ms: c.bot_mode_changed_ms,
             friends,
+            ignores,
+            muted_until_unix: c.muted_until_unix,
             room_id: c.room_id.clone(),
             autoassist: c.autoassist,
             follow_leader: c.follow_leader,
@@ -2461,6 +2586,7 @@ impl PlayerSnapshot {
             max_stamina: c.max_stamina,
             pvp_enabled: c.pvp_enabled,
             equip,
+            handoff_seq: c.handoff_seq,
         })
     }
 }
@@ -2515,6 +2641,9 @@ impl World {
             groups,
             players_path: players_path.clone(),
             players: load_player_snapshots(&players_path),
+            owned_areas: None,
+            peer_features: 0,
+            chat_scopes: HashMap::new(),
         };
 
         w.ensure_genesis_groups(&bootstrap_admins, &bootstrap_admin_sso)?;
@@ -2548,7 +2677,7 @@ impl World {
         let Some(snapshot) = PlayerSnapshot::from_character(c) else {
             return;
         };
-        self.players.insert(snapshot.principal.clone(), snapshot);
+        self.players.insert(snapshot.key(), snapshot);
         if let Err(e) = self.persist_player_snapshots() {
             warn!(err = %e, "failed to persist player snapshot");
         }
@@ -2558,12 +2687,142 @@ impl World {
         let Some(snapshot) = self.chars.get(&cid).and_then(PlayerSnapshot::from_character) else {
             return;
         };
-        self.players.insert(snapshot.principal.clone(), snapshot);
+        self.players.insert(snapshot.key(), snapshot);
         if let Err(e) = self.persist_player_snapshots() {
             warn!(err = %e, "failed to persist player snapshot");
         }
     }
 
+    /// Rooms outside `SHARD_AREAS` belong to another shard. Rooms without an area stay local.
+    fn owns_room(&self, room_id: &str) -> bool {
+        let Some(owned) = self.owned_areas.as_ref() else {
+            return true;
+        };
+        self.rooms
+            .area_id(room_id)
+            .is_none_or(|a| owned.contains(a))
+    }
+
+    /// Take a snapshot handed off by another shard, unless ours is from the same or a later
+    /// handoff (e.g. the broker replaying one we already took). Returns true if used.
+    fn accept_handoff(&mut self, principal: &str, snapshot: &[u8]) -> bool {
+        let snap = match serde_json::from_slice::<PlayerSnapshot>(snapshot) {
+            Ok(s) => s,
+            Err(e) => {
+                warn!(principal, err = %e, "bad handoff snapshot");
+                return false;
+            }
+        };
+        if snap.principal != principal {
+            warn!(principal, snapshot_principal = %snap.principal, "handoff principal mismatch");
+            return false;
+        }
+        if self
+            .players
+            .get(&snap.key())
+            .is_some_and(|cur| cur.handoff_seq >= snap.handoff_seq)
+        {
+            return false;
+        }
+        self.players.insert(snap.key(), snap);
+        true
+    }
+
+    /// A newly created character reusing a deleted one's name starts over.
+    fn forget_player(&mut self, principal: &str, name: &str) {
+        if self.players.remove(&player_key(principal, name)).is_none() {
+            return;
+        }
+        if let Err(e) = self.persist_player_snapshots() {
+            warn!(err = %e, "failed to persist player snapshot");
+        }
+    }
+
+    /// Apply a deletion or rename from the broker to saved characters and everyone's friends and
+    /// ignore lists, or a mute to the character wherever it is.
+    fn apply_player_op(&mut self, op: &PlayerOp) {
+        let (old, new) = match op {
+            PlayerOp::Forget { name } => (name.trim(), None),
+            PlayerOp::Rename { name, new_name } => (name.trim(), Some(new_name.trim())),
+            PlayerOp::Mute { name, until_unix } => {
+                self.apply_mute(name.trim(), *until_unix);
+                return;
+            }
+        };
+        if old.is_empty() || new.is_some_and(str::is_empty) {
+            return;
+        }
+        let keys = self
+            .players
+            .keys()
+            .filter(|(principal, name_lc)| {
+                !principal.starts_with("guest:") && name_lc.eq_ignore_ascii_case(old)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in keys {
+            let Some(mut snap) = self.players.remove(&key) else {
+                continue;
+            };
+            if let Some(new) = new {
+                snap.name = new.to_string();
+                self.players.insert(snap.key(), snap);
+            }
+        }
+
+        let fix = |friends: &mut Vec<String>| {
+            let had = friends.len();
+            friends.retain(|f| !f.eq_ignore_ascii_case(old));
+            if friends.len() != had
+                && let Some(new) = new
+            {
+                friends.push(new.to_string());
+                friends.sort();
+            }
+        };
+        for snap in self.players.values_mut() {
+            fix(&mut snap.friends);
+            fix(&mut snap.ignores);
+        }
+        for c in self.chars.values_mut() {
+            for set in [&mut c.friends, &mut c.ignores] {
+                if set.iter().any(|f| f.eq_ignore_ascii_case(old)) {
+                    let mut names = set.drain().collect::<Vec<_>>();
+                    fix(&mut names);
+                    *set = names.into_iter().collect();
+                }
+            }
+        }
+
+        if let Err(e) = self.persist_player_snapshots() {
+            warn!(err = %e, "failed to persist player snapshots after player op");
+        }
+    }
+
+    fn apply_mute(&mut self, name: &str, until_unix: u64) {
+        if name.is_empty() {
+            return;
+        }
+        let mut changed = false;
+        for snap in self.players.values_mut() {
+            if !snap.principal.starts_with("guest:") && snap.name.eq_ignore_ascii_case(name) {
+                snap.muted_until_unix = until_unix;
+                changed = true;
+            }
+        }
+        for c in self.chars.values_mut() {
+            if c.name.eq_ignore_ascii_case(name) {
+                c.muted_until_unix = until_unix;
+            }
+        }
+        if !changed {
+            return;
+        }
+        if let Err(e) = self.persist_player_snapshots() {
+            warn!(err = %e, "failed to persist player snapshots after mute");
+        }
+    }
+
     fn persist_live_players(&mut self) {
         let snapshots = self
             .chars
@@ -2571,7 +2830,7 @@ impl World {
             .filter_map(PlayerSnapshot::from_character)
             .collect::<Vec<_>>();
         for snapshot in snapshots {
-            self.players.insert(snapshot.principal.clone(), snapshot);
+            self.players.insert(snapshot.key(), snapshot);
         }
         if let Err(e) = self.persist_player_snapshots() {
             warn!(err = %e, "failed to persist live player snapshots");
@@ -2590,7 +2849,7 @@ impl World {
         sex: Sex,
         pronouns: PronounKey,
     ) -> CharacterId {
-        if let Some(snapshot) = self.players.get(&principal).cloned() {
+        if let Some(snapshot) = self.players.get(&player_key(&principal, &name)).cloned() {
             return self.restore_character(session, snapshot, principal, auth_caps);
         }
 
@@ -2638,6 +2897,9 @@ impl World {
             bot_ever_since_ms: snapshot.bot_ever_since_ms,
             bot_mode_changed_ms: snapshot.bot_mode_changed_ms,
             friends: snapshot.friends.into_iter().collect(),
+            ignores: snapshot.ignores.into_iter().collect(),
+            muted_until_unix: snapshot.muted_until_unix,
+            handoff_seq: snapshot.handoff_seq,
             room_id: room_id.clone(),
             autoassist: snapshot.autoassist,
             follow_leader: snapshot.follow_leader,
@@ -2838,13 +3100,17 @@ impl World {
             return format!("group: {group_id} (missing)\r\n");
         };
         let mut s = String::new();
-        s.push_str(&format!("group: {} ({})\r\n", g.name, g.kind.as_str()));
+        s.push_str(&format!(
+            "group: {} ({})\r\n",
+            color::escape(&g.name),
+            g.kind.as_str()
+        ));
         s.push_str(&format!(" - id: {}\r\n", g.id));
 
         let mut members = g
             .members
             .iter()
-            .map(|(k, v)| format!(" - {k}: {}\r\n", v.as_str()))
+            .map(|(k, v)| format!(" - {}: {}\r\n", color::escape(k), v.as_str()))
             .collect::<Vec<_>>();
         members.sort_unstable();
         s.push_str("members:\r\n");
@@ -2859,7 +3125,7 @@ impl World {
         let mut pol = g
             .policies
             .iter()
-            .map(|(k, v)| format!(" - {k}={v}\r\n"))
+            .map(|(k, v)| format!(" - {}={}\r\n", color::escape(k), color::escape(v)))
             .collect::<Vec<_>>();
         pol.sort_unstable();
         s.push_str("policies:\r\n");
@@ -3013,6 +3279,9 @@ impl World {
             bot_ever_since_ms: None,
             bot_mode_changed_ms: self.now_ms,
             friends: HashSet::new(),
+            ignores: HashSet::new(),
+            muted_until_unix: 0,
+            handoff_seq: 0,
             room_id: room_id.clone(),
             autoassist: false,
             follow_leader: false,
@@ -3368,6 +3637,9 @@ impl World {
             bot_ever_since_ms: is_bot.then_some(now_ms),
             bot_mode_changed_ms: now_ms,
             friends: HashSet::new(),
+            ignores: HashSet::new(),
+            muted_until_unix: 0,
+            handoff_seq: 0,
             room_id: room_id.clone(),
             autoassist: true,
             follow_leader: false,
@@ -3424,6 +3696,17 @@ impl World {
         fw: &mut FrameWriter<tokio::net::tcp::OwnedWriteHalf>,
         room_id: &str,
         msg: &str,
+    ) -> std::io::Result<()> {
+        self.broadcast_room_from(fw, room_id, "", msg).await
+    }
+
+    /// `broadcast_room` for something `speaker` said; anyone ignoring them doesn't see it.
+    async fn broadcast_room_from(
+        &self,
+        fw: &mut FrameWriter<tokio::net::tcp::OwnedWriteHalf>,
+        room_id: &str,
+        speaker: &str,
+        msg: &str,
     ) -> std::io::Result<()> {
         let mut b = Vec::with_capacity(msg.len() + 2);
         b.extend_from_slice(msg.as_bytes());
@@ -3438,7 +3721,7 @@ impl World {
             let Some(controller) = c.controller else {
                 continue;
             };
-            if !seen.insert(controller) {
+            if c.is_ignoring(speaker) || !seen.insert(controller) {
                 continue;
             }
             write_resp_async(fw, RESP_OUTPUT, controller, &b).await?;
@@ -3450,12 +3733,27 @@ impl World {
         &self,
         fw: &mut FrameWriter<tokio::net::tcp::OwnedWriteHalf>,
         msg: &str,
+    ) -> std::io::Result<()> {
+        self.broadcast_all_sessions_from(fw, "", msg).await
+    }
+
+    async fn broadcast_all_sessions_from(
+        &self,
+        fw: &mut FrameWriter<tokio::net::tcp::OwnedWriteHalf>,
+        speaker: &str,
+        msg: &str,
     ) -> std::io::Result<()> {
         let mut b = Vec::with_capacity(msg.len() + 2);
         b.extend_from_slice(msg.as_bytes());
         b.extend_from_slice(b"\r\n");
 
         for sid in self.sessions.keys() {
+            if self
+                .active_char(*sid)
+                .is_some_and(|c| c.is_ignoring(speaker))
+            {
+                continue;
+            }
             write_resp_async(fw, RESP_OUTPUT, *sid, &b).await?;
         }
         Ok(())
@@ -3632,6 +3930,16 @@ impl World {
         fw: &mut FrameWriter<tokio::net::tcp::OwnedWriteHalf>,
         pid: PartyId,
         msg: &str,
+    ) -> std::io::Result<()> {
+        self.party_send_from(fw, pid, "", msg).await
+    }
+
+    async fn party_send_from(
+        &self,
+        fw: &mut FrameWriter<tokio::net::tcp::OwnedWriteHalf>,
+        pid: PartyId,
+        speaker: &str,
+        msg: &str,
     ) -> std::io::Result<()> {
         let Some(p) = self.parties.get(&pid) else {
             return Ok(());
@@ -3648,7 +3956,7 @@ impl World {
             let Some(sid) = c.controller else {
                 continue;
             };
-            if !seen.insert(sid) {
+            if c.is_ignoring(speaker) || !seen.insert(sid) {
                 continue;
             }
             write_resp_async(fw, RESP_OUTPUT, sid, &b).await?;
@@ -4161,20 +4469,32 @@ async fn main() -> anyhow::Result<()> {
     let cfg = parse_args();
     let listener = TcpListener::bind(cfg.bind).await?;
     info!(bind = %cfg.bind, "shard_01 listening");
+    if cfg.auth_keys.is_empty() {
+        warn!("SHARD_AUTH_KEYS unset; every session attaches as a guest");
+    }
 
     let rooms = rooms::Rooms::load()?;
+    // Highest input number taken per session. Outlives broker connections and restarts: after a
+    // reconnect the broker resends whatever it hasn't seen acked.
+    let mut input_seqs =
+        input_seqs::InputSeqs::load(cfg.players_path.with_extension("input_seqs.json"));
 
     loop {
         let (stream, peer) = listener.accept().await?;
         info!(peer = %peer, "broker connected");
 
-        if let Err(e) = handle_broker(stream, rooms.clone(), cfg.clone()).await {
+        if let Err(e) = handle_broker(stream, rooms.clone(), cfg.clone(), &mut input_seqs).await {
             warn!(peer = %peer, err = %e, "broker connection ended with error");
         }
     }
 }
 
-async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> anyhow::Result<()> {
+async fn handle_broker(
+    stream: TcpStream,
+    rooms: rooms::Rooms,
+    cfg: Config,
+    input_seqs: &mut input_seqs::InputSeqs,
+) -> anyhow::Result<()> {
     let (rd, wr) = stream.into_split();
     let mut fr = FrameReader::new(rd);
     let mut fw = FrameWriter::new(wr);
@@ -4189,6 +4509,7 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
         cfg.bootstrap_admins.clone(),
         cfg.bootstrap_admin_sso.clone(),
     )?;
+    world.owned_areas = cfg.areas.clone();
     world.schedule_at_ms(0, EventKind::EnsureTavernMob);
     world.schedule_at_ms(0, EventKind::EnsureFirstFightWorm);
     world.schedule_at_ms(0, EventKind::EnsureClassHallMobs);
@@ -4200,6 +4521,7 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
         world.now_ms = start.elapsed().as_millis() as u64;
         world.regen_resources();
         process_due_events(&mut world, &mut fw).await?;
+        sync_chat_scopes(&mut world, &mut fw).await?;
 
         let sleep_ms = match world.events.peek() {
             Some(Reverse(ev)) => ev.due_ms.saturating_sub(world.now_ms()),
@@ -4215,8 +4537,35 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                     Some(f) => f,
                     None => break,
                 };
-                let req = mudproto::shard::parse_req(frame)?;
+                let req = match mudproto::shard::parse_req(frame) {
+                    Ok(r) => r,
+                    // Newer brokers may send types we don't know; skip them instead of hanging up.
+                    Err(ProtoError::UnknownType(t)) => {
+                        warn!(t, "ignoring unknown request type");
+                        continue;
+                    }
+                    Err(e) => return Err(e.into()),
+                };
         match req {
+            ShardReq::Hello {
+                version,
+                features,
+                software,
+            } => {
+                info!(
+                    version,
+                    features,
+                    software = %String::from_utf8_lossy(&software),
+                    "broker hello"
+                );
+                world.peer_features = features;
+                let body = encode_hello(
+                    PROTO_VERSION,
+                    SHARD_FEATURES,
+                    concat!("shard_01/", env!("CARGO_PKG_VERSION")),
+                );
+                write_resp_async(&mut fw, RESP_HELLO, SessionId(0), &body).await?;
+            }
             ShardReq::Attach {
                 session,
                 is_bot,
@@ -4226,14 +4575,27 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                 sex,
                 pronouns,
                 name,
+                snapshot,
+                fresh,
             } => {
                 let name = String::from_utf8_lossy(&name).trim().to_string();
                 if name.is_empty() {
                     let _ = write_resp_async(&mut fw, RESP_ERR, session, b"bad name\r\n").await;
                     continue;
                 }
-                let principal = principal_from_attach(&name, auth.as_deref());
-                let auth_caps = caps_from_attach(auth.as_deref());
+                let auth = verified_auth(&cfg.auth_keys, session, auth.as_deref());
+                let principal = principal_from_attach(&name, auth);
+                let auth_caps = caps_from_attach(auth);
+                let snapshot = snapshot.filter(|snap| {
+                    let ok = handoff_vouched(auth, snap);
+                    if !ok {
+                        warn!(
+                            session = session.short(),
+                            "handoff snapshot not signed by the broker; ignoring"
+                        );
+                    }
+                    ok
+                });
 
                 // If the broker ever re-attaches the same session, drop prior characters first.
                 let removed = world.detach_session(session);
@@ -4242,6 +4604,13 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                     let _ = world.broadcast_room(&mut fw, &c.room_id, &leave_msg).await;
                 }
 
+                if fresh && snapshot.is_none() {
+                    world.forget_player(&principal, &name);
+                }
+                let handed_off = snapshot
+                    .as_deref()
+                    .is_some_and(|snap| world.accept_handoff(&principal, snap));
+
                 let race_tok = race
                     .as_ref()
                     .map(|b| String::from_utf8_lossy(b).trim().to_ascii_lowercase());
@@ -4290,6 +4659,25 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                 let room_id = c.room_id.clone();
                 let build_prompt = render_build_prompt(c);
 
+                // Logged out somewhere another shard owns: send them back there. Never bounce a
+                // handoff, though; if the broker sent it here we keep the character.
+                if snapshot.is_none()
+                    && !world.owns_room(&room_id)
+                    && handoff_session(&mut world, &mut fw, session, cid, &room_id).await?
+                {
+                    continue;
+                }
+
+                if handed_off {
+                    world
+                        .broadcast_room(&mut fw, &room_id, &format!("* {name} arrives"))
+                        .await?;
+                    world.remember_player_by_id(cid);
+                    let s = world.render_room_for(&room_id, session);
+                    write_resp_async(&mut fw, RESP_OUTPUT, session, s.as_bytes()).await?;
+                    continue;
+                }
+
                 let join_msg = format!("* {name} joined");
                 world.broadcast_room(&mut fw, &room_id, &join_msg).await?;
                 world.remember_player_by_id(cid);
@@ -4309,13 +4697,42 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                 write_resp_async(&mut fw, RESP_OUTPUT, session, hi.as_bytes()).await?;
             }
             ShardReq::Detach { session } => {
+                input_seqs.remove(session);
                 let removed = world.detach_session(session);
                 for c in removed {
                     let leave_msg = format!("* {} left", c.name);
                     let _ = world.broadcast_room(&mut fw, &c.room_id, &leave_msg).await;
                 }
             }
-            ShardReq::Input { session, line } => {
+            ShardReq::PlayerOp { session, assertion } => {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                let op = match assertion::verify(&cfg.auth_keys, &assertion, session, now) {
+                    Ok(payload) => serde_json::from_slice::<PlayerOp>(payload),
+                    Err(e) => {
+                        warn!(session = session.short(), err = %e, "player op rejected");
+                        continue;
+                    }
+                };
+                match op {
+                    Ok(op) => {
+                        info!(?op, "player op");
+                        world.apply_player_op(&op);
+                    }
+                    Err(e) => warn!(err = %e, "bad player op"),
+                }
+            }
+            ShardReq::Input { session, line, seq } => {
+                if let Some(seq) = seq {
+                    let fresh = input_seqs.take(session, seq);
+                    let ack = encode_input_ack(input_seqs.last(session));
+                    write_resp_async(&mut fw, RESP_INPUT_ACK, session, &ack).await?;
+                    if !fresh {
+                        continue;
+                    }
+                }
                 let line_s = String::from_utf8_lossy(&line);
                 let line = line_s.trim();
                 if line.is_empty() {
@@ -4690,7 +5107,7 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                             .await;
                     }
 
-                    let msg = format!("group: created {group_id} ({name})\r\n");
+                    let msg = format!("group: created {group_id} ({})\r\n", color::escape(&name));
                     write_resp_async(&mut fw, RESP_OUTPUT, session, msg.as_bytes()).await?;
                     continue;
                 }
@@ -4747,7 +5164,11 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                             &format!("raft[{}] {}", env.index, serde_json::to_string(&env)?),
                         )
                         .await;
-                    let msg = format!("group: {gid} add {member_tok} ({})\r\n", role.as_str());
+                    let msg = format!(
+                        "group: {gid} add {} ({})\r\n",
+                        color::escape(member_tok),
+                        role.as_str()
+                    );
                     write_resp_async(&mut fw, RESP_OUTPUT, session, msg.as_bytes()).await?;
                     continue;
                 }
@@ -4800,7 +5221,7 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                             &format!("raft[{}] {}", env.index, serde_json::to_string(&env)?),
                         )
                         .await;
-                    let msg = format!("group: {gid} remove {member_tok}\r\n");
+                    let msg = format!("group: {gid} remove {}\r\n", color::escape(member_tok));
                     write_resp_async(&mut fw, RESP_OUTPUT, session, msg.as_bytes()).await?;
                     continue;
                 }
@@ -4873,7 +5294,11 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                             &format!("raft[{}] {}", env.index, serde_json::to_string(&env)?),
                         )
                         .await;
-                    let msg = format!("group: {gid} role {member_tok} {}\r\n", role.as_str());
+                    let msg = format!(
+                        "group: {gid} role {} {}\r\n",
+                        color::escape(member_tok),
+                        role.as_str()
+                    );
                     write_resp_async(&mut fw, RESP_OUTPUT, session, msg.as_bytes()).await?;
                     continue;
                 }
@@ -4935,7 +5360,11 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                             &format!("raft[{}] {}", env.index, serde_json::to_string(&env)?),
                         )
                         .await;
-                    let msg = format!("group: {gid} policy set {key_tok}={value_tok}\r\n");
+                    let msg = format!(
+                        "group: {gid} policy set {}={}\r\n",
+                        color::escape(key_tok),
+                        color::escape(value_tok)
+                    );
                     write_resp_async(&mut fw, RESP_OUTPUT, session, msg.as_bytes()).await?;
                     continue;
                 }
@@ -4987,7 +5416,7 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                             &format!("raft[{}] {}", env.index, serde_json::to_string(&env)?),
                         )
                         .await;
-                    let msg = format!("group: {gid} policy del {key_tok}\r\n");
+                    let msg = format!("group: {gid} policy del {}\r\n", color::escape(key_tok));
                     write_resp_async(&mut fw, RESP_OUTPUT, session, msg.as_bytes()).await?;
                     continue;
                 }
@@ -5041,7 +5470,7 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                     let mut caps = Vec::new();
                     for tok in it {
                         let Some(c) = groups::Capability::parse(tok) else {
-                            let msg = format!("huh? (bad capability: {tok})\r\n");
+                            let msg = format!("huh? (bad capability: {})\r\n", color::escape(tok));
                             write_resp_async(&mut fw, RESP_OUTPUT, session, msg.as_bytes()).await?;
                             caps.clear();
                             break;
@@ -5222,7 +5651,7 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                             spawned += 1;
                         }
                     }
-                    let msg = format!("spawned: {} x {}\r\n", spawned, kind);
+                    let msg = format!("spawned: {} x {}\r\n", spawned, color::escape(kind));
                     write_resp_async(&mut fw, RESP_OUTPUT, session, msg.as_bytes()).await?;
                     process_due_events(&mut world, &mut fw).await?;
                     continue;
@@ -6927,11 +7356,26 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                     continue;
                 }
 
+                if is_talk_command(&lc) {
+                    let now_unix = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs();
+                    if p.muted_until_unix > now_unix {
+                        let mins = (p.muted_until_unix - now_unix).div_ceil(60);
+                        let msg = format!("you are muted ({mins}m left)\r\n");
+                        write_resp_async(&mut fw, RESP_OUTPUT, session, msg.as_bytes()).await?;
+                        continue;
+                    }
+                }
+
                 if let Some(msg) = line.strip_prefix("say ") {
                     let msg = msg.trim();
                     if !msg.is_empty() {
-                        let say = format!("{}: {msg}", p.name);
-                        world.broadcast_room(&mut fw, &p.room_id, &say).await?;
+                        let say = format!("{{C}}{}:{{x}} {}", p.name, color::escape(msg));
+                        world
+                            .broadcast_room_from(&mut fw, &p.room_id, &p.name, &say)
+                            .await?;
                     }
                     continue;
                 }
@@ -6957,12 +7401,16 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                     continue;
                 }
                 if let Some(shout) = shout_payload(line, &p.name) {
-                    world.broadcast_all_sessions(&mut fw, &shout).await?;
+                    world
+                        .broadcast_all_sessions_from(&mut fw, &p.name, &shout)
+                        .await?;
                     continue;
                 }
                 if let Some(msg) = command_arg(line, "yell") {
-                    let shout = format!("{} shouts: {}", p.name, msg);
-                    world.broadcast_all_sessions(&mut fw, &shout).await?;
+                    let shout = format!("{{Y}}{} shouts:{{x}} {}", p.name, color::escape(msg));
+                    world
+                        .broadcast_all_sessions_from(&mut fw, &p.name, &shout)
+                        .await?;
                     continue;
                 }
 
@@ -6977,7 +7425,9 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                     continue;
                 }
                 if let Some(emote) = room_emote_payload(line, &p.name, "emote") {
-                    world.broadcast_room(&mut fw, &p.room_id, &emote).await?;
+                    world
+                        .broadcast_room_from(&mut fw, &p.room_id, &p.name, &emote)
+                        .await?;
                     continue;
                 }
                 if lc == "em" {
@@ -6991,7 +7441,9 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                     continue;
                 }
                 if let Some(emote) = room_emote_payload(line, &p.name, "em") {
-                    world.broadcast_room(&mut fw, &p.room_id, &emote).await?;
+                    world
+                        .broadcast_room_from(&mut fw, &p.room_id, &p.name, &emote)
+                        .await?;
                     continue;
                 }
 
@@ -7006,7 +7458,9 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                     continue;
                 }
                 if let Some(emote) = room_emote_payload(line, &p.name, "me") {
-                    world.broadcast_room(&mut fw, &p.room_id, &emote).await?;
+                    world
+                        .broadcast_room_from(&mut fw, &p.room_id, &p.name, &emote)
+                        .await?;
                     continue;
                 }
                 if lc == "pose" {
@@ -7020,7 +7474,9 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                     continue;
                 }
                 if let Some(emote) = room_emote_payload(line, &p.name, "pose") {
-                    world.broadcast_room(&mut fw, &p.room_id, &emote).await?;
+                    world
+                        .broadcast_room_from(&mut fw, &p.room_id, &p.name, &emote)
+                        .await?;
                     continue;
                 }
                 if lc == "dance" {
@@ -7106,9 +7562,19 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                         .get(&tgt_id)
                         .map(|c| c.name.clone())
                         .unwrap_or_else(|| who.to_string());
+                    if world
+                        .chars
+                        .get(&tgt_id)
+                        .is_some_and(|c| c.is_ignoring(&p.name))
+                    {
+                        let msg = format!("tell: {tgt_name} isn't taking tells from you\r\n");
+                        write_resp_async(&mut fw, RESP_OUTPUT, session, msg.as_bytes()).await?;
+                        continue;
+                    }
 
-                    let out_to = format!("{} tells you: {}\r\n", p.name, msg);
-                    let out_from = format!("you tell {}: {}\r\n", tgt_name, msg);
+                    let msg = color::escape(msg);
+                    let out_to = format!("{{M}}{} tells you:{{x}} {}\r\n", p.name, msg);
+                    let out_from = format!("{{M}}you tell {}:{{x}} {}\r\n", tgt_name, msg);
                     let _ = write_resp_async(&mut fw, RESP_OUTPUT, tgt_sid, out_to.as_bytes()).await;
                     write_resp_async(&mut fw, RESP_OUTPUT, session, out_from.as_bytes()).await?;
                     continue;
@@ -7160,14 +7626,105 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                         .get(&tgt_id)
                         .map(|c| c.name.clone())
                         .unwrap_or_else(|| who.to_string());
+                    if world
+                        .chars
+                        .get(&tgt_id)
+                        .is_some_and(|c| c.is_ignoring(&p.name))
+                    {
+                        let msg = format!("tell: {tgt_name} isn't taking tells from you\r\n");
+                        write_resp_async(&mut fw, RESP_OUTPUT, session, msg.as_bytes()).await?;
+                        continue;
+                    }
 
-                    let out_to = format!("{} whispers: {}\r\n", p.name, msg);
-                    let out_from = format!("you whisper {}: {}\r\n", tgt_name, msg);
+                    let msg = color::escape(msg);
+                    let out_to = format!("{{m}}{} whispers:{{x}} {}\r\n", p.name, msg);
+                    let out_from = format!("{{m}}you whisper {}:{{x}} {}\r\n", tgt_name, msg);
                     let _ = write_resp_async(&mut fw, RESP_OUTPUT, tgt_sid, out_to.as_bytes()).await;
                     write_resp_async(&mut fw, RESP_OUTPUT, session, out_from.as_bytes()).await?;
                     continue;
                 }
 
+                if lc == "ignore" || lc == "ignore list" {
+                    let mut names = world
+                        .chars
+                        .get(&p.id)
+                        .map(|c| c.ignores.iter().cloned().collect::<Vec<_>>())
+                        .unwrap_or_default();
+                    names.sort_by_key(|n| n.to_ascii_lowercase());
+                    let mut s = String::new();
+                    s.push_str("ignoring:\r\n");
+                    if names.is_empty() {
+                        s.push_str(" - nobody\r\n");
+                    }
+                    for n in names {
+                        s.push_str(&format!(" - {n}\r\n"));
+                    }
+                    s.push_str("usage: ignore <player> | unignore <player>\r\n");
+                    write_resp_async(&mut fw, RESP_OUTPUT, session, s.as_bytes()).await?;
+                    continue;
+                }
+                if let Some(rest) = command_arg(line, "ignore") {
+                    let Some(tgt_id) = world.find_player_by_prefix(rest) else {
+                        write_resp_async(
+                            &mut fw,
+                            RESP_OUTPUT,
+                            session,
+                            b"ignore: no such player (or ambiguous)\r\n",
+                        )
+                        .await?;
+                        continue;
+                    };
+                    if tgt_id == p.id {
+                        write_resp_async(
+                            &mut fw,
+                            RESP_OUTPUT,
+                            session,
+                            b"ignore: can't ignore yourself\r\n",
+                        )
+                        .await?;
+                        continue;
+                    }
+                    let tgt_name = world
+                        .chars
+                        .get(&tgt_id)
+                        .map(|c| c.name.clone())
+                        .unwrap_or_else(|| rest.to_string());
+                    let added = world.chars.get_mut(&p.id).is_some_and(|c| {
+                        !c.is_ignoring(&tgt_name) && c.ignores.insert(tgt_name.clone())
+                    });
+                    let msg = if added {
+                        format!("ignore: you no longer hear {tgt_name}\r\n")
+                    } else {
+                        format!("ignore: already ignoring {tgt_name}\r\n")
+                    };
+                    write_resp_async(&mut fw, RESP_OUTPUT, session, msg.as_bytes()).await?;
+                    continue;
+                }
+                if lc == "unignore" {
+                    write_resp_async(
+                        &mut fw,
+                        RESP_OUTPUT,
+                        session,
+                        b"usage: unignore <player>\r\n",
+                    )
+                    .await?;
+                    continue;
+                }
+                if let Some(rest) = command_arg(line, "unignore") {
+                    let removed = world.chars.get_mut(&p.id).is_some_and(|c| {
+                        let had = c.ignores.len();
+                        c.ignores.retain(|n| !n.eq_ignore_ascii_case(rest));
+                        c.ignores.len() != had
+                    });
+                    let msg = if removed {
+                        format!("ignore: hearing {} again\r\n", color::escape(rest))
+                    } else {
+                        format!("ignore: not ignoring {}\r\n", color::escape(rest))
+                    };
+                    write_resp_async(&mut fw, RESP_OUTPUT, session, msg.as_bytes()).await?;
+                    continue;
+                }
+
                 if lc == "friends" || lc == "friend" || lc == "friends list" || lc == "friend list"
                 {
                     let mut names = world
@@ -8037,8 +8594,8 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                         .await?;
                         continue;
                     };
-                    let s = format!("[party {pid}] {}: {}", p.name, msg);
-                    let _ = world.party_send(&mut fw, pid, &s).await;
+                    let s = format!("{{G}}[party {pid}] {}:{{x}} {}", p.name, color::escape(msg));
+                    let _ = world.party_send_from(&mut fw, pid, &p.name, &s).await;
                     continue;
                 }
 
@@ -8424,7 +8981,9 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                     let mut xs = p
                         .quest
                         .iter()
-                        .map(|(k, v)| format!(" - {k}={v}\r\n"))
+                        .map(|(k, v)| {
+                            format!(" - {}={}\r\n", color::escape(k), color::escape(v))
+                        })
                         .collect::<Vec<_>>();
                     xs.sort_unstable();
                     let mut s = String::new();
@@ -8448,10 +9007,10 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                         continue;
                     }
                     if let Some(v) = p.quest.get(key) {
-                        let s = format!("quest: {key}={v}\r\n");
+                        let s = format!("quest: {}={}\r\n", color::escape(key), color::escape(v));
                         write_resp_async(&mut fw, RESP_OUTPUT, session, s.as_bytes()).await?;
                     } else {
-                        let s = format!("quest: {key}=(unset)\r\n");
+                        let s = format!("quest: {}=(unset)\r\n", color::escape(key));
                         write_resp_async(&mut fw, RESP_OUTPUT, session, s.as_bytes()).await?;
                     }
                     continue;
@@ -8481,7 +9040,7 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                     if let Some(c) = world.active_char_mut(session) {
                         c.quest.insert(key.to_string(), value.to_string());
                     }
-                    let s = format!("quest: set {key}={value}\r\n");
+                    let s = format!("quest: set {}={}\r\n", color::escape(key), color::escape(value));
                     write_resp_async(&mut fw, RESP_OUTPUT, session, s.as_bytes()).await?;
                     continue;
                 }
@@ -8502,9 +9061,9 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
                         .and_then(|c| c.quest.remove(key))
                         .is_some();
                     let s = if removed {
-                        format!("quest: del {key}\r\n")
+                        format!("quest: del {}\r\n", color::escape(key))
                     } else {
-                        format!("quest: del {key} (missing)\r\n")
+                        format!("quest: del {} (missing)\r\n", color::escape(key))
                     };
                     write_resp_async(&mut fw, RESP_OUTPUT, session, s.as_bytes()).await?;
                     continue;
@@ -8908,7 +9467,7 @@ async fn handle_broker(stream: TcpStream, rooms: rooms::Rooms, cfg: Config) -> a
     Ok(())
 }
 
-fn load_player_snapshots(path: &Path) -> HashMap<String, PlayerSnapshot> {
+fn load_player_snapshots(path: &Path) -> HashMap<(String, String), PlayerSnapshot> {
     let Ok(s) = std::fs::read_to_string(path) else {
         return HashMap::new();
     };
@@ -8922,7 +9481,7 @@ fn load_player_snapshots(path: &Path) -> HashMap<String, PlayerSnapshot> {
         if snapshot.principal.trim().is_empty() {
             continue;
         }
-        out.insert(snapshot.principal.clone(), snapshot);
+        out.insert(snapshot.key(), snapshot);
     }
     out
 }
@@ -9121,7 +9680,7 @@ async fn handle_event(
                 compute_mob_autoattack_damage(world, &att)
             };
 
-            let msg = format!("* {} hits {} for {}.", att.name, tgt.name, dmg);
+            let msg = format!("* {} hits {} for {{R}}{}{{x}}.", att.name, tgt.name, dmg);
             let killed = if tgt_is_player {
                 apply_damage_to_player(world, fw, attacker_id, target_id, dmg, msg).await?
             } else {
@@ -9237,7 +9796,7 @@ async fn handle_event(
                     .map(|c| c.name.clone())
                     .unwrap_or_else(|| "someone".to_string());
                 let dmg = 8;
-                let msg = format!("* grease_king crushes {} for {}.", vname, dmg);
+                let msg = format!("* grease_king crushes {} for {{R}}{}{{x}}.", vname, dmg);
                 let _ = apply_damage_to_player(world, fw, boss_id, vid, dmg, msg).await?;
             }
         }
@@ -9598,6 +10157,114 @@ fn eval_gate_expr(p: &Character, expr: &str) -> bool {
     truthy(lookup(expr))
 }
 
+/// What chatd needs from the shard about a session: its party and guild, and who it ignores.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ChatScope {
+    party: Option<PartyId>,
+    guild: Option<u64>,
+    /// Lowercase, sorted.
+    ignores: Vec<String>,
+}
+
+/// Tell the broker about every session whose party, guild or ignore list changed since last
+/// time, so chatd can scope the party and guild channels to real members and hold back lines
+/// from ignored players.
+async fn sync_chat_scopes(
+    world: &mut World,
+    fw: &mut FrameWriter<tokio::net::tcp::OwnedWriteHalf>,
+) -> anyhow::Result<()> {
+    if world.peer_features & FEATURE_CHAT_SCOPE == 0 {
+        return Ok(());
+    }
+    let sessions = &world.sessions;
+    world
+        .chat_scopes
+        .retain(|sid, _| sessions.contains_key(sid));
+    let mut changed = Vec::new();
+    for (&sid, ss) in &world.sessions {
+        let c = world.chars.get(&ss.active);
+        let mut ignores = c
+            .into_iter()
+            .flat_map(|c| c.ignores.iter().map(|n| n.to_ascii_lowercase()))
+            .collect::<Vec<_>>();
+        ignores.sort();
+        let scope = ChatScope {
+            party: world.party_of.get(&ss.active).copied(),
+            guild: c.and_then(|c| world.groups.guild_of(&c.principal)),
+            ignores,
+        };
+        if world.chat_scopes.get(&sid) != Some(&scope) {
+            changed.push((sid, scope));
+        }
+    }
+    for (sid, scope) in changed {
+        let party = scope.party.map(|p| p.to_string());
+        let guild = scope.guild.map(|g| g.to_string());
+        let body = mudproto::chat::encode_scope(
+            party.as_deref().map(str::as_bytes),
+            guild.as_deref().map(str::as_bytes),
+            &scope.ignores,
+        )?;
+        world.chat_scopes.insert(sid, scope);
+        write_resp_async(fw, RESP_CHAT_SCOPE, sid, &body).await?;
+    }
+    Ok(())
+}
+
+/// Hand the session's character off to whichever shard owns `to`: detach it here and send the
+/// broker a snapshot placed in `to`. Returns false (and changes nothing) if that isn't possible.
+async fn handoff_session(
+    world: &mut World,
+    fw: &mut FrameWriter<tokio::net::tcp::OwnedWriteHalf>,
+    session: SessionId,
+    cid: CharacterId,
+    to: &str,
+) -> anyhow::Result<bool> {
+    if world.peer_features & FEATURE_HANDOFF == 0 {
+        return Ok(false);
+    }
+    let Some(area) = world.rooms.area_id(to).map(str::to_string) else {
+        return Ok(false);
+    };
+    // Party builds control several characters; only a lone character can move between shards.
+    if world
+        .sessions
+        .get(&session)
+        .is_none_or(|ss| ss.controlled.len() != 1)
+    {
+        return Ok(false);
+    }
+    let Some(mut snap) = world
+        .chars
+        .get(&cid)
+        .and_then(PlayerSnapshot::from_character)
+    else {
+        return Ok(false);
+    };
+    snap.room_id = to.to_string();
+    snap.handoff_seq = snap.handoff_seq.saturating_add(1);
+    let body = match serde_json::to_vec(&snap)
+        .map_err(anyhow::Error::from)
+        .and_then(|j| Ok(encode_handoff(&area, &j)?))
+    {
+        Ok(b) => b,
+        Err(e) => {
+            warn!(principal = %snap.principal, err = %e, "cannot encode handoff");
+            return Ok(false);
+        }
+    };
+
+    world.detach_session(session);
+    // Remember where they went so a later login here sends them straight back.
+    world.players.insert(snap.key(), snap);
+    if let Err(e) = world.persist_player_snapshots() {
+        warn!(err = %e, "failed to persist player snapshot");
+    }
+    write_resp_async(fw, RESP_HANDOFF, session, &body).await?;
+    info!(area = %area, room = %to, "handed off character");
+    Ok(true)
+}
+
 async fn try_move(
     world: &mut World,
     fw: &mut FrameWriter<tokio::net::tcp::OwnedWriteHalf>,
@@ -9661,6 +10328,18 @@ async fn try_move(
         return Ok(true);
     }
 
+    if !world.owns_room(next) {
+        let (dir, next) = (dir.to_string(), next.to_string());
+        if handoff_session(world, fw, session, cid, &next).await? {
+            world
+                .broadcast_room(fw, &p.room_id, &format!("* {} goes {dir}", p.name))
+                .await?;
+        } else {
+            write_resp_async(fw, RESP_OUTPUT, session, SEALED_EXIT_MSG).await?;
+        }
+        return Ok(true);
+    }
+
     let from = p.room_id.clone();
     let to = next.to_string();
 
@@ -9787,10 +10466,24 @@ report search <text>\r\n\
 report submit <line_id> <reason> [note...]\r\n\
 report locate <line_id>\r\n\
 report reasons\r\n\
+reports [show|assign|resolve] (moderators)\r\n\
+silence <player> <duration>|off <reason> (moderators)\r\n\
 rules\r\n\
 buildinfo\r\n\
 aiping\r\n\
 uptime\r\n\
+characters (back to character select)\r\n\
+color\r\n\
+color on|off|256\r\n\
+term\r\n\
+term width <n>|auto|off\r\n\
+term height <n>|auto|off\r\n\
+more (at --more--; q to stop)\r\n\
+alias\r\n\
+alias <name> <command>[; <command>...] ($1..$9, $*)\r\n\
+unalias <name>\r\n\
+chat help (channels, if enabled)\r\n\
+ooc|newbie|trade|guild <message>\r\n\
 stats\r\n\
 look\r\n\
 look <thing>\r\n\
@@ -9855,6 +10548,8 @@ kill <mob|player>\r\n\
 pvp on|off\r\n\
 spawn <mob> [n]\r\n\
 tell <player> <msg>\r\n\
+ignore [player]\r\n\
+unignore <player>\r\n\
 friends\r\n\
 friends add <player>\r\n\
 friends del <player>\r\n\
@@ -10020,7 +10715,11 @@ mod tests {
     fn shout_payload_rejects_empty_messages() {
         assert_eq!(
             shout_payload("shout hello everyone", "Alice"),
-            Some("Alice shouts: hello everyone".to_string())
+            Some("{Y}Alice shouts:{x} hello everyone".to_string())
+        );
+        assert_eq!(
+            shout_payload("shout {R}red", "Alice"),
+            Some("{Y}Alice shouts:{x} {{R}red".to_string())
         );
         assert_eq!(shout_payload("shout", "Alice"), None);
         assert_eq!(shout_payload("shout   ", "Alice"), None);
@@ -10041,28 +10740,69 @@ mod tests {
         assert_eq!(parse_tell_args("whisper bob", "whisper"), None);
     }
 
+    #[test]
+    fn talk_commands_cover_what_a_mute_blocks() {
+        for lc in [
+            "say hi",
+            "shout hi",
+            "tell bob hi",
+            "whisper bob hi",
+            "me waves",
+        ] {
+            assert!(is_talk_command(lc), "{lc}");
+        }
+        assert!(is_talk_command("party say hi"));
+        assert!(!is_talk_command("party invite bob"));
+        assert!(!is_talk_command("look"));
+        assert!(!is_talk_command("sayonara"));
+    }
+
     #[test]
     fn room_emote_payload_supports_aliases() {
         assert_eq!(
-            room_emote_payload("emote bows", "Alice"),
+            room_emote_payload("emote bows", "Alice", "emote"),
             Some("* Alice bows".to_string())
         );
         assert_eq!(
-            room_emote_payload("me dances", "Alice"),
+            room_emote_payload("me dances", "Alice", "me"),
             Some("* Alice dances".to_string())
         );
         assert_eq!(
-            room_emote_payload("pose salutes", "Alice"),
+            room_emote_payload("pose salutes", "Alice", "pose"),
             Some("* Alice salutes".to_string())
         );
-        assert_eq!(room_emote_payload("pose", "Alice"), None);
-        assert_eq!(room_emote_payload("pose   ", "Alice"), None);
+        assert_eq!(room_emote_payload("pose", "Alice", "pose"), None);
+        assert_eq!(room_emote_payload("pose   ", "Alice", "pose"), None);
         assert_eq!(
-            room_emote_payload("em grins", "Alice"),
+            room_emote_payload("em grins", "Alice", "em"),
             Some("* Alice grins".to_string())
         );
     }
 
+    #[test]
+    fn handoff_snapshots_need_a_signed_digest() {
+        let key = AssertionKey::parse("k1:0123456789abcdef").unwrap();
+        let sid = SessionId(7);
+        let snap = br#"{"principal":"acct:alice"}"#;
+        let payload = format!(
+            r#"{{"acct":"alice","snapshot_sha256":"{}"}}"#,
+            assertion::digest(snap)
+        );
+        let signed = assertion::sign(&key, sid, u64::MAX, payload.as_bytes());
+        let keys = [key];
+
+        let auth = verified_auth(&keys, sid, Some(&signed));
+        assert!(handoff_vouched(auth, snap));
+        assert!(!handoff_vouched(auth, br#"{"principal":"acct:mallory"}"#));
+
+        // The same payload sent unsigned is a guest attach and can't carry a snapshot.
+        let auth = verified_auth(&keys, sid, Some(payload.as_bytes()));
+        assert!(!handoff_vouched(auth, snap));
+        let signed = assertion::sign(&keys[0], sid, u64::MAX, br#"{"acct":"alice"}"#);
+        let auth = verified_auth(&keys, sid, Some(&signed));
+        assert!(!handoff_vouched(auth, snap));
+    }
+
     #[test]
     fn room_emote_noarg_generates_room_motion() {
         assert_eq!(room_emote_noarg("Alice", "dances"), "* Alice dances");
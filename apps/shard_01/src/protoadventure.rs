@@ -156,6 +156,7 @@ pub fn instantiate_rooms(instance_prefix: &str, plan: &BuildPlan) -> Vec<(String
                 name: r.name.clone(),
                 description: r.description.trim().to_string(),
                 area_name: plan.adventure_id.clone(),
+                area_id: None,
                 exits,
             },
         ));
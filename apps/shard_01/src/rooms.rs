@@ -23,6 +23,8 @@ pub struct RoomDef {
     pub name: String,
     pub description: String,
     pub area_name: String,
+    /// Owning area (zone id); `None` for generated rooms, which always stay on their shard.
+    pub area_id: Option<String>,
     pub exits: Vec<ExitDef>,
 }
 
@@ -89,6 +91,7 @@ impl Rooms {
                             name,
                             description,
                             area_name: area_name.clone(),
+                            area_id: Some(area_id.clone()),
                             exits,
                         },
                     );
@@ -143,6 +146,7 @@ impl Rooms {
                         name: r.name,
                         description: r.desc.unwrap_or_default().trim().to_string(),
                         area_name: area_name.clone(),
+                        area_id: Some(a.zone_id.clone()),
                         exits,
                     },
                 );
@@ -216,6 +220,13 @@ impl Rooms {
         s
     }
 
+    pub fn area_id(&self, room_id: &str) -> Option<&str> {
+        self.dyn_rooms
+            .get(room_id)
+            .or_else(|| self.rooms.get(room_id))
+            .and_then(|r| r.area_id.as_deref())
+    }
+
     pub fn has_room(&self, room_id: &str) -> bool {
         self.dyn_rooms.contains_key(room_id) || self.rooms.contains_key(room_id)
     }
@@ -316,7 +327,7 @@ impl Rooms {
 
         let mut s = String::new();
         s.push_str(&format!(
-            "== {} ({}) [{}] ==\r\n",
+            "{{C}}== {} ({}) [{}] =={{x}}\r\n",
             room.name, room.area_name, room_id
         ));
         if !room.description.is_empty() {
@@ -18,7 +18,14 @@ COMMANDS:\n\
   create-admin <name>   [--password PW]\n\
   promote-admin <name>\n\
   get-account <name>\n\
-  list-accounts\n"
+  list-accounts\n\
+  set-character-limit <name> <n|default>\n\
+  reset-2fa <name>\n\
+  list-reports [--all]\n\
+  show-report <n>\n\
+  assign-report <n> <by> [assignee]\n\
+  resolve-report <n> <by> <dismiss|warn|mute|ban> [--target NAME] [--note TEXT]\n\
+  silence <name> <by> <seconds|off> [--reason TEXT]\n"
     );
     std::process::exit(2);
 }
@@ -51,6 +58,38 @@ enum AdminReq {
         name: String,
     },
     ListAccounts {},
+    SetAccountCharacterLimit {
+        name: String,
+        max_characters: Option<u32>,
+    },
+    ResetAccountTotp {
+        name: String,
+    },
+    SilenceCharacter {
+        name: String,
+        created_by: String,
+        duration_s: u64,
+        reason: String,
+    },
+    ListReports {
+        all: bool,
+    },
+    GetReport {
+        report: String,
+    },
+    AssignReport {
+        report: String,
+        by: String,
+        assignee: Option<String>,
+    },
+    ResolveReport {
+        report: String,
+        by: String,
+        action: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        target: Option<String>,
+        note: String,
+    },
 }
 
 async fn send_admin_req(addr: SocketAddr, req: &AdminReq) -> anyhow::Result<serde_json::Value> {
@@ -178,6 +217,102 @@ async fn main() -> anyhow::Result<()> {
             let resp = send_admin_req(admin_addr, &AdminReq::ListAccounts {}).await?;
             println!("{}", serde_json::to_string_pretty(&resp)?);
         }
+        "set-character-limit" => {
+            if rest.len() != 2 {
+                usage_and_exit();
+            }
+            let name = rest[0].clone();
+            let max_characters = match rest[1].as_str() {
+                "default" => None,
+                n => Some(n.parse().unwrap_or_else(|_| usage_and_exit())),
+            };
+            let resp = send_admin_req(
+                admin_addr,
+                &AdminReq::SetAccountCharacterLimit {
+                    name,
+                    max_characters,
+                },
+            )
+            .await?;
+            println!("{}", serde_json::to_string_pretty(&resp)?);
+        }
+        "reset-2fa" => {
+            if rest.len() != 1 {
+                usage_and_exit();
+            }
+            let name = rest[0].clone();
+            let resp = send_admin_req(admin_addr, &AdminReq::ResetAccountTotp { name }).await?;
+            println!("{}", serde_json::to_string_pretty(&resp)?);
+        }
+        "silence" => {
+            if rest.len() < 3 {
+                usage_and_exit();
+            }
+            let duration_s = match rest[2].as_str() {
+                "off" => 0,
+                n => n.parse().unwrap_or_else(|_| usage_and_exit()),
+            };
+            let resp = send_admin_req(
+                admin_addr,
+                &AdminReq::SilenceCharacter {
+                    name: rest[0].clone(),
+                    created_by: rest[1].clone(),
+                    duration_s,
+                    reason: take_flag_value(&rest[3..], "--reason").unwrap_or_default(),
+                },
+            )
+            .await?;
+            println!("{}", serde_json::to_string_pretty(&resp)?);
+        }
+        "list-reports" => {
+            let all = match rest.as_slice() {
+                [] => false,
+                [f] if f == "--all" => true,
+                _ => usage_and_exit(),
+            };
+            let resp = send_admin_req(admin_addr, &AdminReq::ListReports { all }).await?;
+            println!("{}", serde_json::to_string_pretty(&resp)?);
+        }
+        "show-report" => {
+            if rest.len() != 1 {
+                usage_and_exit();
+            }
+            let report = rest[0].clone();
+            let resp = send_admin_req(admin_addr, &AdminReq::GetReport { report }).await?;
+            println!("{}", serde_json::to_string_pretty(&resp)?);
+        }
+        "assign-report" => {
+            if rest.len() != 2 && rest.len() != 3 {
+                usage_and_exit();
+            }
+            let resp = send_admin_req(
+                admin_addr,
+                &AdminReq::AssignReport {
+                    report: rest[0].clone(),
+                    by: rest[1].clone(),
+                    assignee: rest.get(2).cloned(),
+                },
+            )
+            .await?;
+            println!("{}", serde_json::to_string_pretty(&resp)?);
+        }
+        "resolve-report" => {
+            if rest.len() < 3 {
+                usage_and_exit();
+            }
+            let resp = send_admin_req(
+                admin_addr,
+                &AdminReq::ResolveReport {
+                    report: rest[0].clone(),
+                    by: rest[1].clone(),
+                    action: rest[2].clone(),
+                    target: take_flag_value(&rest[3..], "--target"),
+                    note: take_flag_value(&rest[3..], "--note").unwrap_or_default(),
+                },
+            )
+            .await?;
+            println!("{}", serde_json::to_string_pretty(&resp)?);
+        }
         _ => usage_and_exit(),
     }
 
@@ -7,8 +7,13 @@ use anyhow::Context;
 use bytes::Bytes;
 use futures_util::{SinkExt, StreamExt};
 use getrandom::getrandom;
+use mudproto::color;
 use mudproto::session::SessionId;
-use mudproto::shard::{ShardResp, REQ_ATTACH, REQ_DETACH, REQ_INPUT};
+use mudproto::shard::{
+    downgrade_req, encode_input_v2, AttachFields, ShardResp, FEATURES as SHARD_FEATURES,
+    FEATURE_CHAT_SCOPE, FEATURE_HANDOFF, REQ_ATTACH, REQ_ATTACH_V2, REQ_DETACH, REQ_INPUT_V2,
+};
+use mudproto::shard_client;
 use serde::{Deserialize, Serialize};
 use slopio::frame::{FrameReader, FrameWriter};
 use tokio::net::{TcpListener, TcpStream};
@@ -79,6 +84,7 @@ enum Mode {
 struct ClientInfo {
     mode: Mode,
     tx: mpsc::Sender<Outbound>,
+    attach_t: u8,
     attach_body: Bytes,
 }
 
@@ -143,33 +149,44 @@ fn shard_attach_body_with_build(
     class: Option<&str>,
     sex: Option<&str>,
     pronouns: Option<&str>,
-) -> Bytes {
-    let mut b = Vec::with_capacity(1 + name.len() + 64);
-    let mut flags = 0u8;
-    if is_bot {
-        flags |= 0x01;
-    }
-    let has_build = race.is_some() || class.is_some() || sex.is_some() || pronouns.is_some();
-    if has_build {
-        flags |= 0x04;
-    }
-    b.push(flags);
-
-    if has_build {
-        fn push_field(dst: &mut Vec<u8>, s: Option<&str>) {
-            let t = s.unwrap_or("").trim();
-            let t = if t.len() > 255 { &t[..255] } else { t };
-            dst.push(t.len() as u8);
-            dst.extend_from_slice(t.as_bytes());
-        }
-        push_field(&mut b, race);
-        push_field(&mut b, class);
-        push_field(&mut b, sex);
-        push_field(&mut b, pronouns);
+) -> Result<Bytes, mudproto::ProtoError> {
+    AttachFields {
+        is_bot,
+        auth: None,
+        race: race.map(|s| s.trim().as_bytes()),
+        class: class.map(|s| s.trim().as_bytes()),
+        sex: sex.map(|s| s.trim().as_bytes()),
+        pronouns: pronouns.map(|s| s.trim().as_bytes()),
+        name: name.as_bytes(),
+        snapshot: None,
+        fresh: false,
     }
+    .encode_v2()
+}
 
-    b.extend_from_slice(name.as_bytes());
-    Bytes::from(b)
+/// Send HELLO and wait for the shard's reply; returns the shard's feature bits.
+/// `None` means the shard didn't answer (pre-HELLO shards drop the connection instead).
+async fn shard_hello(
+    fr: &mut FrameReader<tokio::net::tcp::OwnedReadHalf>,
+    fw: &mut FrameWriter<tokio::net::tcp::OwnedWriteHalf>,
+) -> Option<u64> {
+    // Single-shard client without chat: don't offer handoff routing or chat scopes.
+    let ours = SHARD_FEATURES & !FEATURE_HANDOFF & !FEATURE_CHAT_SCOPE;
+    let hello = shard_client::hello(
+        fr,
+        fw,
+        ours,
+        concat!("ws_gateway/", env!("CARGO_PKG_VERSION")),
+        Duration::from_secs(2),
+    )
+    .await?;
+    info!(
+        version = hello.version,
+        features = hello.features,
+        software = %String::from_utf8_lossy(&hello.software),
+        "shard hello"
+    );
+    Some(hello.features & ours)
 }
 
 async fn shard_manager_task(
@@ -178,6 +195,8 @@ async fn shard_manager_task(
     mut rx: mpsc::Receiver<ShardMsg>,
 ) {
     let mut announced_down = false;
+    // Cleared when the shard drops a HELLO; we then speak v1 until the next reconnect.
+    let mut try_hello = true;
 
     loop {
         match TcpStream::connect(shard_addr).await {
@@ -189,15 +208,34 @@ async fn shard_manager_task(
                 let mut fr = FrameReader::new(rd);
                 let mut fw = FrameWriter::new(wr);
 
+                let peer_features = if try_hello {
+                    match shard_hello(&mut fr, &mut fw).await {
+                        Some(f) => f,
+                        None => {
+                            warn!(shard_addr = %shard_addr, "shard did not answer hello; using v1");
+                            try_hello = false;
+                            continue;
+                        }
+                    }
+                } else {
+                    0
+                };
+                try_hello = true;
+
                 // Re-attach all live clients (best-effort).
                 let snapshot = {
                     let m = clients.lock().await;
                     m.iter()
-                        .map(|(sid, ci)| (*sid, ci.attach_body.clone()))
+                        .map(|(sid, ci)| (*sid, ci.attach_t, ci.attach_body.clone()))
                         .collect::<Vec<_>>()
                 };
-                for (sid, body) in snapshot {
-                    let _ = write_req(&mut fw, REQ_ATTACH, sid, &body).await;
+                for (sid, t, body) in snapshot {
+                    match downgrade_req(t, body, peer_features) {
+                        Ok((t, body)) => {
+                            let _ = write_req(&mut fw, t, sid, &body).await;
+                        }
+                        Err(e) => warn!(err=%e, "cannot re-attach client"),
+                    }
                 }
                 let _ = fw.flush().await;
 
@@ -205,7 +243,12 @@ async fn shard_manager_task(
                     tokio::select! {
                         msg = rx.recv() => {
                             let Some(msg) = msg else { return; };
-                            let _ = write_req(&mut fw, msg.t, msg.session, &msg.body).await;
+                            match downgrade_req(msg.t, msg.body, peer_features) {
+                                Ok((t, body)) => {
+                                    let _ = write_req(&mut fw, t, msg.session, &body).await;
+                                }
+                                Err(e) => warn!(err=%e, "dropping unencodable shard request"),
+                            }
                         }
                         res = fr.read_frame() => {
                             let frame = match res {
@@ -246,7 +289,7 @@ async fn route_resp(
             if let Some(ci) = ci {
                 match ci.mode {
                     Mode::Json => {
-                        let text = String::from_utf8_lossy(&line).to_string();
+                        let text = String::from_utf8_lossy(&color::strip(&line)).to_string();
                         let msg = JsonOut::Output { text };
                         if let Ok(s) = serde_json::to_string(&msg) {
                             let _ = ci.tx.send(Outbound::JsonText(s)).await;
@@ -269,7 +312,7 @@ async fn route_resp(
             if let Some(ci) = ci {
                 match ci.mode {
                     Mode::Json => {
-                        let text = String::from_utf8_lossy(&msg).to_string();
+                        let text = String::from_utf8_lossy(&color::strip(&msg)).to_string();
                         let msg = JsonOut::Err { text };
                         if let Ok(s) = serde_json::to_string(&msg) {
                             let _ = ci.tx.send(Outbound::JsonText(s)).await;
@@ -287,6 +330,16 @@ async fn route_resp(
                 }
             }
         }
+        // Only expected during the handshake; a late one carries nothing to route.
+        ShardResp::Hello { .. } => {}
+        // Never offered in our HELLO.
+        ShardResp::Handoff { session, .. } => {
+            warn!(session = %sid_hex(session), "unexpected shard handoff");
+        }
+        // Our input is unnumbered, so nothing is ever acked.
+        ShardResp::InputAck { .. } => {}
+        // Never offered in our HELLO; there's no chat here anyway.
+        ShardResp::ChatScope { .. } => {}
     }
 }
 
@@ -411,27 +464,38 @@ async fn handle_ws_conn(
                         if session.is_some() {
                             continue;
                         }
-                        let sid = new_session_id();
-                        session = Some(sid);
-                        let body = shard_attach_body_with_build(
+                        let Ok(body) = shard_attach_body_with_build(
                             is_bot,
                             name.trim(),
                             race.as_deref(),
                             class.as_deref(),
                             sex.as_deref(),
                             pronouns.as_deref(),
-                        );
+                        ) else {
+                            let _ = tx
+                                .send(Outbound::JsonText(
+                                    serde_json::to_string(&JsonOut::Err {
+                                        text: "attach fields too long".to_string(),
+                                    })
+                                    .unwrap_or_default(),
+                                ))
+                                .await;
+                            continue;
+                        };
+                        let sid = new_session_id();
+                        session = Some(sid);
                         clients.lock().await.insert(
                             sid,
                             ClientInfo {
                                 mode: Mode::Json,
                                 tx: tx.clone(),
+                                attach_t: REQ_ATTACH_V2,
                                 attach_body: body.clone(),
                             },
                         );
                         let _ = shard_tx
                             .send(ShardMsg {
-                                t: REQ_ATTACH,
+                                t: REQ_ATTACH_V2,
                                 session: sid,
                                 body,
                             })
@@ -449,11 +513,22 @@ async fn handle_ws_conn(
                         let Some(sid) = session else {
                             continue;
                         };
+                        let Ok(body) = encode_input_v2(line.as_bytes(), None) else {
+                            let _ = tx
+                                .send(Outbound::JsonText(
+                                    serde_json::to_string(&JsonOut::Err {
+                                        text: "line too long".to_string(),
+                                    })
+                                    .unwrap_or_default(),
+                                ))
+                                .await;
+                            continue;
+                        };
                         let _ = shard_tx
                             .send(ShardMsg {
-                                t: REQ_INPUT,
+                                t: REQ_INPUT_V2,
                                 session: sid,
-                                body: Bytes::from(line.into_bytes()),
+                                body,
                             })
                             .await;
                     }
@@ -508,7 +583,7 @@ async fn handle_ws_conn(
 
                 if session.is_none() {
                     // First message must be attach so we can persist reattach metadata.
-                    if t != REQ_ATTACH {
+                    if t != REQ_ATTACH && t != REQ_ATTACH_V2 {
                         continue;
                     }
                     session = Some(sid);
@@ -517,6 +592,7 @@ async fn handle_ws_conn(
                         ClientInfo {
                             mode: Mode::Fbs,
                             tx: tx.clone(),
+                            attach_t: t,
                             attach_body: body.clone(),
                         },
                     );
@@ -5,5 +5,13 @@
 //! This crate intentionally re-exports the underlying implementation so
 //! consumers depend only on `fanzylog`. That gives us an easy swap point
 //! when we move to the in-house zero-copy implementation.
+//!
+//! On top of that it provides the pieces every replicated service needs: a file-backed log
+//! (`log_store`), peer RPC with leader forwarding (`rpc`), and TCP/in-process transports.
 
 pub use openraft::*;
+
+pub mod log_store;
+pub mod loopback;
+pub mod rpc;
+pub mod tcp;
@@ -0,0 +1,304 @@
+//! File-backed Raft log.
+//!
+//! Layout under the store directory:
+//! - `log.jsonl`: one entry per line, append-only between truncations/purges
+//! - `vote.json`, `committed.json`, `purged.json`: small metadata files, replaced atomically
+//!
+//! Everything is also kept in memory; the log is expected to stay small because the state
+//! machine snapshots and openraft purges behind it. Disk writes run on the blocking pool and
+//! never hold the in-memory lock, so readers don't stall behind an fsync.
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::io::Write;
+use std::ops::RangeBounds;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use openraft::storage::{LogFlushed, RaftLogStorage};
+use openraft::{
+    LogId, LogState, OptionalSend, RaftLogId, RaftLogReader, RaftTypeConfig, StorageError,
+    StorageIOError, Vote,
+};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+struct Inner<C: RaftTypeConfig> {
+    vote: Option<Vote<C::NodeId>>,
+    committed: Option<LogId<C::NodeId>>,
+    last_purged: Option<LogId<C::NodeId>>,
+    log: BTreeMap<u64, C::Entry>,
+}
+
+/// Cheap to clone; clones share the same log (the clone is also the log reader).
+pub struct FileLogStore<C: RaftTypeConfig> {
+    dir: Arc<Path>,
+    inner: Arc<Mutex<Inner<C>>>,
+}
+
+impl<C: RaftTypeConfig> Clone for FileLogStore<C> {
+    fn clone(&self) -> Self {
+        Self {
+            dir: self.dir.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> std::io::Result<Option<T>> {
+    match std::fs::read_to_string(path) {
+        Ok(s) => serde_json::from_str(&s)
+            .map(Some)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Replace `path` with `buf` via a synced temp file.
+fn write_atomic(path: &Path, tmp: &Path, buf: &[u8]) -> std::io::Result<()> {
+    let mut f = std::fs::File::create(tmp)?;
+    f.write_all(buf)?;
+    f.sync_data()?;
+    std::fs::rename(tmp, path)
+}
+
+/// Run file I/O on the blocking pool.
+async fn blocking<T, F>(f: F) -> std::io::Result<T>
+where
+    F: FnOnce() -> std::io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(std::io::Error::other)?
+}
+
+impl<C: RaftTypeConfig> FileLogStore<C> {
+    /// Open (or create) the store in `dir`.
+    pub fn open(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        let vote = read_json(&dir.join("vote.json"))?;
+        let committed =
+            read_json::<Option<LogId<C::NodeId>>>(&dir.join("committed.json"))?.flatten();
+        let last_purged =
+            read_json::<Option<LogId<C::NodeId>>>(&dir.join("purged.json"))?.flatten();
+
+        let mut log = BTreeMap::new();
+        match std::fs::read_to_string(dir.join("log.jsonl")) {
+            Ok(s) => {
+                let total = s.lines().count();
+                for (i, line) in s.lines().enumerate() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let ent: C::Entry = match serde_json::from_str(line) {
+                        Ok(v) => v,
+                        // A torn final line is an append that never completed (and was never
+                        // acknowledged); anything earlier is corruption.
+                        Err(_) if i + 1 == total => break,
+                        Err(e) => {
+                            return Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidData,
+                                format!("bad log line {}: {e}", i + 1),
+                            ));
+                        }
+                    };
+                    log.insert(ent.get_log_id().index, ent);
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(Self {
+            dir: dir.into(),
+            inner: Arc::new(Mutex::new(Inner {
+                vote,
+                committed,
+                last_purged,
+                log,
+            })),
+        })
+    }
+}
+
+impl<C: RaftTypeConfig> Inner<C> {
+    fn encode_log(&self) -> std::io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        for ent in self.log.values() {
+            serde_json::to_writer(&mut buf, ent).map_err(std::io::Error::other)?;
+            buf.push(b'\n');
+        }
+        Ok(buf)
+    }
+}
+
+impl<C: RaftTypeConfig> FileLogStore<C> {
+    /// Replace the metadata file `name` (e.g. `vote.json`) with `v`.
+    async fn write_json<T: Serialize>(&self, name: &str, v: &T) -> std::io::Result<()> {
+        let buf = serde_json::to_vec(v).map_err(std::io::Error::other)?;
+        let path = self.dir.join(name);
+        let tmp = path.with_extension("json.tmp");
+        blocking(move || write_atomic(&path, &tmp, &buf)).await
+    }
+
+    /// Replace `log.jsonl` with `buf` (the encoded in-memory log).
+    async fn rewrite_log(&self, buf: Vec<u8>) -> std::io::Result<()> {
+        let path = self.dir.join("log.jsonl");
+        let tmp = self.dir.join("log.jsonl.tmp");
+        blocking(move || write_atomic(&path, &tmp, &buf)).await
+    }
+}
+
+impl<C: RaftTypeConfig> RaftLogReader<C> for FileLogStore<C>
+where
+    C::Entry: Clone,
+{
+    async fn try_get_log_entries<RB: RangeBounds<u64> + Clone + Debug + OptionalSend>(
+        &mut self,
+        range: RB,
+    ) -> Result<Vec<C::Entry>, StorageError<C::NodeId>> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner.log.range(range).map(|(_, e)| e.clone()).collect())
+    }
+}
+
+impl<C: RaftTypeConfig> RaftLogStorage<C> for FileLogStore<C>
+where
+    C::Entry: Clone,
+{
+    type LogReader = Self;
+
+    async fn get_log_state(&mut self) -> Result<LogState<C>, StorageError<C::NodeId>> {
+        let inner = self.inner.lock().unwrap();
+        let last = inner
+            .log
+            .values()
+            .next_back()
+            .map(|e| e.get_log_id().clone())
+            .or_else(|| inner.last_purged.clone());
+        Ok(LogState {
+            last_purged_log_id: inner.last_purged.clone(),
+            last_log_id: last,
+        })
+    }
+
+    async fn get_log_reader(&mut self) -> Self::LogReader {
+        self.clone()
+    }
+
+    async fn save_vote(&mut self, vote: &Vote<C::NodeId>) -> Result<(), StorageError<C::NodeId>> {
+        self.write_json("vote.json", vote)
+            .await
+            .map_err(|e| StorageIOError::write_vote(&e))?;
+        self.inner.lock().unwrap().vote = Some(vote.clone());
+        Ok(())
+    }
+
+    async fn read_vote(&mut self) -> Result<Option<Vote<C::NodeId>>, StorageError<C::NodeId>> {
+        Ok(self.inner.lock().unwrap().vote.clone())
+    }
+
+    async fn save_committed(
+        &mut self,
+        committed: Option<LogId<C::NodeId>>,
+    ) -> Result<(), StorageError<C::NodeId>> {
+        self.write_json("committed.json", &committed)
+            .await
+            .map_err(|e| StorageIOError::write(&e))?;
+        self.inner.lock().unwrap().committed = committed;
+        Ok(())
+    }
+
+    async fn read_committed(
+        &mut self,
+    ) -> Result<Option<LogId<C::NodeId>>, StorageError<C::NodeId>> {
+        Ok(self.inner.lock().unwrap().committed.clone())
+    }
+
+    async fn append<I>(
+        &mut self,
+        entries: I,
+        callback: LogFlushed<C>,
+    ) -> Result<(), StorageError<C::NodeId>>
+    where
+        I: IntoIterator<Item = C::Entry> + OptionalSend,
+        I::IntoIter: OptionalSend,
+    {
+        let mut buf = Vec::new();
+        let mut appended = Vec::new();
+        let encoded = entries.into_iter().try_for_each(|ent| {
+            serde_json::to_writer(&mut buf, &ent)?;
+            buf.push(b'\n');
+            appended.push(ent);
+            Ok::<_, serde_json::Error>(())
+        });
+        let res = match encoded {
+            Ok(()) => {
+                let path = self.dir.join("log.jsonl");
+                blocking(move || {
+                    let mut f = std::fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(path)?;
+                    f.write_all(&buf)?;
+                    f.sync_data()
+                })
+                .await
+            }
+            Err(e) => Err(std::io::Error::other(e)),
+        };
+        if res.is_ok() {
+            let mut inner = self.inner.lock().unwrap();
+            for ent in appended {
+                inner.log.insert(ent.get_log_id().index, ent);
+            }
+        }
+        match res {
+            Ok(()) => {
+                callback.log_io_completed(Ok(()));
+                Ok(())
+            }
+            Err(e) => {
+                let err = StorageIOError::write_logs(&e).into();
+                callback.log_io_completed(Err(e));
+                Err(err)
+            }
+        }
+    }
+
+    async fn truncate(&mut self, log_id: LogId<C::NodeId>) -> Result<(), StorageError<C::NodeId>> {
+        let buf = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.log.split_off(&log_id.index);
+            inner.encode_log()
+        };
+        let res = match buf {
+            Ok(buf) => self.rewrite_log(buf).await,
+            Err(e) => Err(e),
+        };
+        res.map_err(|e| StorageIOError::write_logs(&e).into())
+    }
+
+    async fn purge(&mut self, log_id: LogId<C::NodeId>) -> Result<(), StorageError<C::NodeId>> {
+        // Record the purge point first: a crash between the two writes leaves extra entries,
+        // which is harmless; the reverse would lose track of what was purged.
+        self.write_json("purged.json", &Some(log_id.clone()))
+            .await
+            .map_err(|e| StorageIOError::write_logs(&e))?;
+        let buf = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.last_purged = Some(log_id.clone());
+            inner.log = inner.log.split_off(&(log_id.index + 1));
+            inner.encode_log()
+        };
+        let res = match buf {
+            Ok(buf) => self.rewrite_log(buf).await,
+            Err(e) => Err(e),
+        };
+        res.map_err(|e| StorageIOError::write_logs(&e).into())
+    }
+}
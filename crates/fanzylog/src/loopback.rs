@@ -0,0 +1,73 @@
+//! In-process transport for running a whole cluster inside one test.
+//!
+//! Requests are round-tripped through JSON so tests exercise the same encoding as the wire.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use openraft::{Raft, RaftTypeConfig};
+
+use crate::rpc::{PeerReq, PeerResp, Transport};
+
+pub struct Loopback<C: RaftTypeConfig> {
+    nodes: Arc<Mutex<BTreeMap<C::NodeId, Raft<C>>>>,
+}
+
+impl<C: RaftTypeConfig> Clone for Loopback<C> {
+    fn clone(&self) -> Self {
+        Self {
+            nodes: self.nodes.clone(),
+        }
+    }
+}
+
+impl<C: RaftTypeConfig> Default for Loopback<C> {
+    fn default() -> Self {
+        Self {
+            nodes: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+}
+
+impl<C: RaftTypeConfig> Loopback<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, id: C::NodeId, raft: Raft<C>) {
+        self.nodes.lock().unwrap().insert(id, raft);
+    }
+
+    /// Make `id` unreachable (e.g. to simulate losing it). Returns its handle, if registered.
+    pub fn remove(&self, id: &C::NodeId) -> Option<Raft<C>> {
+        self.nodes.lock().unwrap().remove(id)
+    }
+}
+
+fn roundtrip<T: serde::Serialize + serde::de::DeserializeOwned>(v: &T) -> std::io::Result<T> {
+    let b = serde_json::to_vec(v).map_err(std::io::Error::other)?;
+    serde_json::from_slice(&b).map_err(std::io::Error::other)
+}
+
+impl<C> Transport<C> for Loopback<C>
+where
+    C: RaftTypeConfig<Responder = openraft::impls::OneshotResponder<C>>,
+    C::SnapshotData: tokio::io::AsyncRead + tokio::io::AsyncWrite + tokio::io::AsyncSeek + Unpin,
+{
+    async fn call(
+        &self,
+        target: C::NodeId,
+        _node: &C::Node,
+        req: PeerReq<C>,
+    ) -> std::io::Result<PeerResp<C>> {
+        let raft = self.nodes.lock().unwrap().get(&target).cloned();
+        let Some(raft) = raft else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                format!("node {target} is down"),
+            ));
+        };
+        let resp = crate::rpc::serve(&raft, roundtrip(&req)?).await;
+        roundtrip(&resp)
+    }
+}
@@ -0,0 +1,249 @@
+//! Peer-to-peer RPC, independent of how bytes move.
+//!
+//! Every node serves [`PeerReq`]s with [`serve`]. A [`Transport`] carries them to a target node
+//! (TCP in production, an in-process [`crate::loopback::Loopback`] in tests), and [`Network`]
+//! adapts any transport to openraft's network traits.
+//!
+//! Writes go through [`propose`], which forwards to the current leader when this node isn't it,
+//! so callers can talk to any member.
+
+use std::future::Future;
+
+use openraft::error::{
+    ClientWriteError, ForwardToLeader, InstallSnapshotError, NetworkError, RPCError, RaftError,
+    RemoteError, Unreachable,
+};
+use openraft::network::RPCOption;
+use openraft::raft::{
+    AppendEntriesRequest, AppendEntriesResponse, ClientWriteResponse, InstallSnapshotRequest,
+    InstallSnapshotResponse, VoteRequest, VoteResponse,
+};
+use openraft::{ChangeMembers, Raft, RaftNetwork, RaftNetworkFactory, RaftTypeConfig};
+use serde::{Deserialize, Serialize};
+
+type WriteResult<C> = Result<
+    ClientWriteResponse<C>,
+    RaftError<
+        <C as RaftTypeConfig>::NodeId,
+        ClientWriteError<<C as RaftTypeConfig>::NodeId, <C as RaftTypeConfig>::Node>,
+    >,
+>;
+
+/// Something that has to be committed through the leader.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub enum Proposal<C: RaftTypeConfig> {
+    Write(C::D),
+    /// Add (or re-add) a voting member; it catches up as a learner first.
+    AddVoter {
+        id: C::NodeId,
+        node: C::Node,
+    },
+    RemoveVoter {
+        id: C::NodeId,
+    },
+}
+
+impl<C: RaftTypeConfig> Clone for Proposal<C>
+where
+    C::D: Clone,
+{
+    fn clone(&self) -> Self {
+        match self {
+            Self::Write(d) => Self::Write(d.clone()),
+            Self::AddVoter { id, node } => Self::AddVoter {
+                id: id.clone(),
+                node: node.clone(),
+            },
+            Self::RemoveVoter { id } => Self::RemoveVoter { id: id.clone() },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub enum PeerReq<C: RaftTypeConfig> {
+    AppendEntries(AppendEntriesRequest<C>),
+    Vote(VoteRequest<C::NodeId>),
+    InstallSnapshot(InstallSnapshotRequest<C>),
+    Propose(Proposal<C>),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub enum PeerResp<C: RaftTypeConfig> {
+    AppendEntries(Result<AppendEntriesResponse<C::NodeId>, RaftError<C::NodeId>>),
+    Vote(Result<VoteResponse<C::NodeId>, RaftError<C::NodeId>>),
+    InstallSnapshot(
+        Result<InstallSnapshotResponse<C::NodeId>, RaftError<C::NodeId, InstallSnapshotError>>,
+    ),
+    Propose(WriteResult<C>),
+}
+
+/// Handle one request against the local node.
+pub async fn serve<C>(raft: &Raft<C>, req: PeerReq<C>) -> PeerResp<C>
+where
+    C: RaftTypeConfig<Responder = openraft::impls::OneshotResponder<C>>,
+    C::SnapshotData: tokio::io::AsyncRead + tokio::io::AsyncWrite + tokio::io::AsyncSeek + Unpin,
+{
+    match req {
+        PeerReq::AppendEntries(r) => PeerResp::AppendEntries(raft.append_entries(r).await),
+        PeerReq::Vote(r) => PeerResp::Vote(raft.vote(r).await),
+        PeerReq::InstallSnapshot(r) => PeerResp::InstallSnapshot(raft.install_snapshot(r).await),
+        PeerReq::Propose(p) => PeerResp::Propose(apply_local(raft, p).await),
+    }
+}
+
+async fn apply_local<C>(raft: &Raft<C>, p: Proposal<C>) -> WriteResult<C>
+where
+    C: RaftTypeConfig<Responder = openraft::impls::OneshotResponder<C>>,
+{
+    match p {
+        Proposal::Write(d) => raft.client_write(d).await,
+        Proposal::AddVoter { id, node } => {
+            raft.add_learner(id.clone(), node, true).await?;
+            raft.change_membership(ChangeMembers::AddVoterIds([id].into()), false)
+                .await
+        }
+        Proposal::RemoveVoter { id } => {
+            raft.change_membership(ChangeMembers::RemoveVoters([id].into()), false)
+                .await
+        }
+    }
+}
+
+/// Commit `p`, forwarding it to the leader if needed.
+pub async fn propose<C, T>(
+    raft: &Raft<C>,
+    transport: &T,
+    p: Proposal<C>,
+) -> anyhow::Result<ClientWriteResponse<C>>
+where
+    C: RaftTypeConfig<Responder = openraft::impls::OneshotResponder<C>>,
+    C::D: Clone,
+    T: Transport<C>,
+{
+    let err = match apply_local(raft, p.clone()).await {
+        Ok(r) => return Ok(r),
+        Err(e) => e,
+    };
+    let Some(ForwardToLeader {
+        leader_id: Some(leader),
+        leader_node: Some(node),
+    }) = err.forward_to_leader().cloned()
+    else {
+        return Err(err.into());
+    };
+    match transport.call(leader, &node, PeerReq::Propose(p)).await? {
+        PeerResp::Propose(r) => Ok(r?),
+        _ => anyhow::bail!("unexpected peer response to propose"),
+    }
+}
+
+/// Delivers a request to `target` and returns its response.
+pub trait Transport<C: RaftTypeConfig>: Clone + Send + Sync + 'static {
+    fn call(
+        &self,
+        target: C::NodeId,
+        node: &C::Node,
+        req: PeerReq<C>,
+    ) -> impl Future<Output = std::io::Result<PeerResp<C>>> + Send;
+}
+
+/// openraft network factory over any [`Transport`].
+#[derive(Clone)]
+pub struct Network<T> {
+    transport: T,
+}
+
+impl<T> Network<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+}
+
+pub struct PeerClient<C: RaftTypeConfig, T> {
+    target: C::NodeId,
+    node: C::Node,
+    transport: T,
+}
+
+impl<C, T> RaftNetworkFactory<C> for Network<T>
+where
+    C: RaftTypeConfig,
+    T: Transport<C>,
+{
+    type Network = PeerClient<C, T>;
+
+    async fn new_client(&mut self, target: C::NodeId, node: &C::Node) -> Self::Network {
+        PeerClient {
+            target,
+            node: node.clone(),
+            transport: self.transport.clone(),
+        }
+    }
+}
+
+fn unexpected<C: RaftTypeConfig, E: std::error::Error>() -> RPCError<C::NodeId, C::Node, E> {
+    let e = std::io::Error::other("unexpected peer response");
+    RPCError::Network(NetworkError::new(&e))
+}
+
+impl<C: RaftTypeConfig, T: Transport<C>> PeerClient<C, T> {
+    async fn call<E: std::error::Error>(
+        &self,
+        req: PeerReq<C>,
+    ) -> Result<PeerResp<C>, RPCError<C::NodeId, C::Node, E>> {
+        self.transport
+            .call(self.target.clone(), &self.node, req)
+            .await
+            .map_err(|e| RPCError::Unreachable(Unreachable::new(&e)))
+    }
+}
+
+impl<C, T> RaftNetwork<C> for PeerClient<C, T>
+where
+    C: RaftTypeConfig,
+    T: Transport<C>,
+{
+    async fn append_entries(
+        &mut self,
+        rpc: AppendEntriesRequest<C>,
+        _option: RPCOption,
+    ) -> Result<AppendEntriesResponse<C::NodeId>, RPCError<C::NodeId, C::Node, RaftError<C::NodeId>>>
+    {
+        match self.call(PeerReq::AppendEntries(rpc)).await? {
+            PeerResp::AppendEntries(r) => {
+                r.map_err(|e| RemoteError::new(self.target.clone(), e).into())
+            }
+            _ => Err(unexpected::<C, _>()),
+        }
+    }
+
+    async fn install_snapshot(
+        &mut self,
+        rpc: InstallSnapshotRequest<C>,
+        _option: RPCOption,
+    ) -> Result<
+        InstallSnapshotResponse<C::NodeId>,
+        RPCError<C::NodeId, C::Node, RaftError<C::NodeId, InstallSnapshotError>>,
+    > {
+        match self.call(PeerReq::InstallSnapshot(rpc)).await? {
+            PeerResp::InstallSnapshot(r) => {
+                r.map_err(|e| RemoteError::new(self.target.clone(), e).into())
+            }
+            _ => Err(unexpected::<C, _>()),
+        }
+    }
+
+    async fn vote(
+        &mut self,
+        rpc: VoteRequest<C::NodeId>,
+        _option: RPCOption,
+    ) -> Result<VoteResponse<C::NodeId>, RPCError<C::NodeId, C::Node, RaftError<C::NodeId>>> {
+        match self.call(PeerReq::Vote(rpc)).await? {
+            PeerResp::Vote(r) => r.map_err(|e| RemoteError::new(self.target.clone(), e).into()),
+            _ => Err(unexpected::<C, _>()),
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! TCP transport: newline-delimited JSON, one request/response pair at a time per connection.
+//!
+//! Connections are pooled per peer address and dropped on any error.
+//!
+//! With a [`PeerKey`] both ends authenticate every frame. On accept the server sends a random
+//! nonce line; after that each line is `<mac hex> <json>`, where the MAC is HMAC-SHA256 over the
+//! direction (`req`/`resp`), the nonce, a per-connection sequence number and the JSON. A frame
+//! that fails to verify closes the connection, so a client without the key can't reach Raft at
+//! all (votes, appends or forwarded proposals). Every member must use the same key.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use openraft::{BasicNode, Raft, RaftTypeConfig};
+use sha2::Sha256;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::rpc::{PeerReq, PeerResp, Transport};
+
+const NONCE_LEN: usize = 16;
+const DIR_REQ: &[u8] = b"req";
+const DIR_RESP: &[u8] = b"resp";
+
+/// Shared secret for peer RPC.
+#[derive(Clone)]
+pub struct PeerKey(Arc<[u8]>);
+
+impl std::fmt::Debug for PeerKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PeerKey(..)")
+    }
+}
+
+impl PeerKey {
+    pub const MIN_LEN: usize = 16;
+
+    pub fn new(secret: &[u8]) -> std::io::Result<Self> {
+        if secret.len() < Self::MIN_LEN {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "peer key too short (min 16 bytes)",
+            ));
+        }
+        Ok(Self(secret.into()))
+    }
+
+    fn mac(&self, dir: &[u8], nonce: &[u8], seq: u64, body: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.0).expect("hmac takes any key");
+        mac.update(dir);
+        mac.update(nonce);
+        mac.update(&seq.to_be_bytes());
+        mac.update(body);
+        mac
+    }
+}
+
+/// Per-connection framing state; without a key frames are bare JSON lines.
+struct Framing {
+    key: Option<PeerKey>,
+    nonce: Vec<u8>,
+    seq: u64,
+}
+
+impl Framing {
+    fn seal(&self, dir: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(body.len() + 66);
+        if let Some(key) = &self.key {
+            let tag = key.mac(dir, &self.nonce, self.seq, body).finalize();
+            out.extend_from_slice(hex(&tag.into_bytes()).as_bytes());
+            out.push(b' ');
+        }
+        out.extend_from_slice(body);
+        out.push(b'\n');
+        out
+    }
+
+    fn open<'a>(&self, dir: &[u8], line: &'a str) -> Option<&'a str> {
+        let line = line.trim_end();
+        let Some(key) = &self.key else {
+            return Some(line);
+        };
+        let (tag, body) = line.split_once(' ')?;
+        key.mac(dir, &self.nonce, self.seq, body.as_bytes())
+            .verify_slice(&unhex(tag)?)
+            .ok()?;
+        Some(body)
+    }
+}
+
+fn hex(b: &[u8]) -> String {
+    b.iter().map(|x| format!("{x:02x}")).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn invalid(msg: &'static str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+struct Conn {
+    io: BufReader<TcpStream>,
+    framing: Framing,
+}
+
+#[derive(Clone)]
+pub struct TcpTransport {
+    timeout: Duration,
+    key: Option<PeerKey>,
+    pool: Arc<Mutex<HashMap<String, Vec<Conn>>>>,
+}
+
+impl TcpTransport {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            key: None,
+            pool: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Authenticate every frame with `key` (the peers' `serve` must use the same key).
+    pub fn with_key(mut self, key: Option<PeerKey>) -> Self {
+        self.key = key;
+        self
+    }
+
+    async fn connect(&self, addr: &str) -> std::io::Result<Conn> {
+        let mut io = BufReader::new(TcpStream::connect(addr).await?);
+        let nonce = match &self.key {
+            Some(_) => {
+                let mut line = String::new();
+                if io.read_line(&mut line).await? == 0 {
+                    return Err(std::io::ErrorKind::UnexpectedEof.into());
+                }
+                unhex(line.trim())
+                    .filter(|n| n.len() == NONCE_LEN)
+                    .ok_or_else(|| invalid("bad peer nonce"))?
+            }
+            None => Vec::new(),
+        };
+        Ok(Conn {
+            io,
+            framing: Framing {
+                key: self.key.clone(),
+                nonce,
+                seq: 0,
+            },
+        })
+    }
+}
+
+async fn read_line(rd: &mut (impl AsyncBufReadExt + Unpin)) -> std::io::Result<String> {
+    let mut line = String::new();
+    if rd.read_line(&mut line).await? == 0 {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(line)
+}
+
+impl<C> Transport<C> for TcpTransport
+where
+    C: RaftTypeConfig<Node = BasicNode>,
+{
+    async fn call(
+        &self,
+        _target: C::NodeId,
+        node: &BasicNode,
+        req: PeerReq<C>,
+    ) -> std::io::Result<PeerResp<C>> {
+        let body = serde_json::to_vec(&req).map_err(std::io::Error::other)?;
+
+        let pooled = self
+            .pool
+            .lock()
+            .unwrap()
+            .get_mut(&node.addr)
+            .and_then(|v| v.pop());
+        let fut = async {
+            let mut conn = match pooled {
+                Some(c) => c,
+                None => self.connect(&node.addr).await?,
+            };
+            let frame = conn.framing.seal(DIR_REQ, &body);
+            conn.io.get_mut().write_all(&frame).await?;
+            let line = read_line(&mut conn.io).await?;
+            Ok::<_, std::io::Error>((conn, line))
+        };
+        let (mut conn, line) = tokio::time::timeout(self.timeout, fut)
+            .await
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+
+        let body = conn
+            .framing
+            .open(DIR_RESP, &line)
+            .ok_or_else(|| invalid("peer response failed authentication"))?;
+        let resp = serde_json::from_str(body).map_err(std::io::Error::other)?;
+        conn.framing.seq += 1;
+        self.pool
+            .lock()
+            .unwrap()
+            .entry(node.addr.clone())
+            .or_default()
+            .push(conn);
+        Ok(resp)
+    }
+}
+
+/// Serve peer requests for `raft` until the listener fails. With `key`, connections that
+/// don't authenticate are dropped before anything reaches Raft.
+pub async fn serve<C>(
+    listener: TcpListener,
+    raft: Raft<C>,
+    key: Option<PeerKey>,
+) -> std::io::Result<()>
+where
+    C: RaftTypeConfig<Responder = openraft::impls::OneshotResponder<C>>,
+    C::SnapshotData: tokio::io::AsyncRead + tokio::io::AsyncWrite + tokio::io::AsyncSeek + Unpin,
+{
+    loop {
+        let (stream, _peer) = listener.accept().await?;
+        let raft = raft.clone();
+        let key = key.clone();
+        tokio::spawn(async move {
+            let (rd, mut wr) = stream.into_split();
+            let mut rd = BufReader::new(rd);
+            let mut framing = Framing {
+                key,
+                nonce: Vec::new(),
+                seq: 0,
+            };
+            if framing.key.is_some() {
+                let mut nonce = vec![0u8; NONCE_LEN];
+                if getrandom::getrandom(&mut nonce).is_err() {
+                    return;
+                }
+                let line = format!("{}\n", hex(&nonce));
+                if wr.write_all(line.as_bytes()).await.is_err() {
+                    return;
+                }
+                framing.nonce = nonce;
+            }
+            loop {
+                let Ok(line) = read_line(&mut rd).await else {
+                    return;
+                };
+                let Some(body) = framing.open(DIR_REQ, &line) else {
+                    return;
+                };
+                let Ok(req) = serde_json::from_str::<PeerReq<C>>(body) else {
+                    return;
+                };
+                let resp = crate::rpc::serve(&raft, req).await;
+                let Ok(out) = serde_json::to_vec(&resp) else {
+                    return;
+                };
+                if wr.write_all(&framing.seal(DIR_RESP, &out)).await.is_err() {
+                    return;
+                }
+                framing.seq += 1;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn framing(key: &[u8], seq: u64) -> Framing {
+        Framing {
+            key: Some(PeerKey::new(key).unwrap()),
+            nonce: vec![7; NONCE_LEN],
+            seq,
+        }
+    }
+
+    #[test]
+    fn frames_verify_only_with_the_same_key_direction_and_seq() {
+        let a = framing(b"0123456789abcdef", 3);
+        let frame = a.seal(DIR_REQ, br#"{"x":1}"#);
+        let line = std::str::from_utf8(&frame).unwrap();
+
+        assert_eq!(a.open(DIR_REQ, line), Some(r#"{"x":1}"#));
+        assert_eq!(a.open(DIR_RESP, line), None);
+        assert_eq!(framing(b"0123456789abcdef", 4).open(DIR_REQ, line), None);
+        assert_eq!(framing(b"fedcba9876543210", 3).open(DIR_REQ, line), None);
+        assert_eq!(a.open(DIR_REQ, r#"{"x":1}"#), None);
+
+        let tampered = line.replace(r#""x":1"#, r#""x":2"#);
+        assert_eq!(a.open(DIR_REQ, &tampered), None);
+        assert!(PeerKey::new(b"short").is_err());
+    }
+}
@@ -0,0 +1,254 @@
+//! Broker-signed auth assertions, carried in the attach `auth` field.
+//!
+//! The shard port is reachable by more than the broker (ws_gateway, tools), so the shard only
+//! trusts an identity the broker vouched for. An assertion is:
+//!
+//! `sa1.<kid>.<session hex>.<expires_unix>.<sig hex>.<payload>`
+//!
+//! where `sig` is HMAC-SHA256 over everything before it (including the trailing `.`) followed by
+//! the payload. The payload is opaque here; the broker puts its JSON auth blob in it.
+//!
+//! Keys are named (`kid`) so they can be rotated: the broker signs with one key while shards
+//! accept a list, old and new, until every broker has moved over.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::ProtoError;
+use crate::session::SessionId;
+
+const PREFIX: &[u8] = b"sa1.";
+const MIN_SECRET_LEN: usize = 16;
+const MAX_KID_LEN: usize = 32;
+
+#[derive(Clone)]
+pub struct AssertionKey {
+    pub kid: String,
+    secret: Vec<u8>,
+}
+
+impl std::fmt::Debug for AssertionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AssertionKey")
+            .field("kid", &self.kid)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AssertionKey {
+    pub fn new(kid: &str, secret: &[u8]) -> Result<Self, ProtoError> {
+        let kid_ok = !kid.is_empty()
+            && kid.len() <= MAX_KID_LEN
+            && kid
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+        if !kid_ok {
+            return Err(ProtoError::Malformed(
+                "bad key id (use [A-Za-z0-9_-], max 32)",
+            ));
+        }
+        if secret.len() < MIN_SECRET_LEN {
+            return Err(ProtoError::Malformed("key secret too short (min 16 bytes)"));
+        }
+        Ok(Self {
+            kid: kid.to_string(),
+            secret: secret.to_vec(),
+        })
+    }
+
+    /// `kid:secret`.
+    pub fn parse(s: &str) -> Result<Self, ProtoError> {
+        let (kid, secret) = s
+            .trim()
+            .split_once(':')
+            .ok_or(ProtoError::Malformed("key must be kid:secret"))?;
+        Self::new(kid.trim(), secret.trim().as_bytes())
+    }
+
+    /// Comma-separated `kid:secret` list; empty entries are skipped.
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, ProtoError> {
+        s.split(',')
+            .filter(|x| !x.trim().is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    fn mac(&self, header: &[u8], payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("hmac takes any key");
+        mac.update(header);
+        mac.update(payload);
+        mac
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssertionError {
+    /// No assertion at all (e.g. a plain JSON blob from a client that isn't the broker).
+    Unsigned,
+    Malformed,
+    UnknownKey,
+    BadSignature,
+    WrongSession,
+    Expired,
+}
+
+impl std::fmt::Display for AssertionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Unsigned => "unsigned",
+            Self::Malformed => "malformed",
+            Self::UnknownKey => "unknown key",
+            Self::BadSignature => "bad signature",
+            Self::WrongSession => "wrong session",
+            Self::Expired => "expired",
+        })
+    }
+}
+
+impl std::error::Error for AssertionError {}
+
+fn hex(b: &[u8]) -> String {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut s = String::with_capacity(b.len() * 2);
+    for &x in b {
+        s.push(HEX[(x >> 4) as usize] as char);
+        s.push(HEX[(x & 0x0f) as usize] as char);
+    }
+    s
+}
+
+fn unhex(s: &[u8]) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    s.chunks(2)
+        .map(|c| u8::from_str_radix(std::str::from_utf8(c).ok()?, 16).ok())
+        .collect()
+}
+
+/// Sign `payload` for `session`, valid until `expires_unix`.
+pub fn sign(key: &AssertionKey, session: SessionId, expires_unix: u64, payload: &[u8]) -> Vec<u8> {
+    let header = format!(
+        "sa1.{}.{}.{expires_unix}.",
+        key.kid,
+        hex(&session.to_be_bytes())
+    );
+    let sig = hex(&key.mac(header.as_bytes(), payload).finalize().into_bytes());
+    let mut out = Vec::with_capacity(header.len() + sig.len() + 1 + payload.len());
+    out.extend_from_slice(header.as_bytes());
+    out.extend_from_slice(sig.as_bytes());
+    out.push(b'.');
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Hex SHA-256 of `data`. The broker puts this in the payload to vouch for data sent next to the
+/// assertion (a handoff snapshot), since only the payload is signed.
+pub fn digest(data: &[u8]) -> String {
+    use sha2::Digest;
+    hex(&Sha256::digest(data))
+}
+
+/// Check an assertion against any of `keys`; returns the payload it vouches for.
+pub fn verify<'a>(
+    keys: &[AssertionKey],
+    token: &'a [u8],
+    session: SessionId,
+    now_unix: u64,
+) -> Result<&'a [u8], AssertionError> {
+    if !token.starts_with(PREFIX) {
+        return Err(AssertionError::Unsigned);
+    }
+    let mut parts = token.splitn(6, |b| *b == b'.');
+    let (Some(_), Some(kid), Some(sid), Some(exp), Some(sig), Some(payload)) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return Err(AssertionError::Malformed);
+    };
+    let header = &token[..PREFIX.len() + kid.len() + sid.len() + exp.len() + 3];
+    let sig = unhex(sig).ok_or(AssertionError::Malformed)?;
+
+    let key = keys
+        .iter()
+        .find(|k| k.kid.as_bytes() == kid)
+        .ok_or(AssertionError::UnknownKey)?;
+    key.mac(header, payload)
+        .verify_slice(&sig)
+        .map_err(|_| AssertionError::BadSignature)?;
+
+    let sid = unhex(sid)
+        .and_then(|b| <[u8; SessionId::LEN]>::try_from(b).ok())
+        .ok_or(AssertionError::Malformed)?;
+    if SessionId::from_be_bytes(sid) != session {
+        return Err(AssertionError::WrongSession);
+    }
+    let exp: u64 = std::str::from_utf8(exp)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or(AssertionError::Malformed)?;
+    if exp <= now_unix {
+        return Err(AssertionError::Expired);
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_rotated_keys_and_rejects_tampering() {
+        let old = AssertionKey::parse("k1:0123456789abcdef").unwrap();
+        let new = AssertionKey::parse("k2:fedcba9876543210").unwrap();
+        let sid = SessionId(0x1234);
+        let payload = br#"{"acct":"alice","caps":["admin.all"]}"#;
+
+        let tok = sign(&old, sid, 1_060, payload);
+        let both = [new, old];
+        assert_eq!(verify(&both, &tok, sid, 1_000), Ok(&payload[..]));
+        assert_eq!(
+            verify(&both[..1], &tok, sid, 1_000),
+            Err(AssertionError::UnknownKey)
+        );
+        assert_eq!(
+            verify(&both, &tok, SessionId(1), 1_000),
+            Err(AssertionError::WrongSession)
+        );
+        assert_eq!(
+            verify(&both, &tok, sid, 1_060),
+            Err(AssertionError::Expired)
+        );
+
+        // Changing the payload, or relabelling it with another kid, breaks the signature.
+        let mut forged = tok.clone();
+        let n = forged.len();
+        forged[n - 3] = b'X';
+        assert_eq!(
+            verify(&both, &forged, sid, 1_000),
+            Err(AssertionError::BadSignature)
+        );
+        let relabelled = [b"sa1.k2".as_slice(), &tok[6..]].concat();
+        assert_eq!(
+            verify(&both, &relabelled, sid, 1_000),
+            Err(AssertionError::BadSignature)
+        );
+
+        assert_eq!(
+            verify(&both, payload, sid, 1_000),
+            Err(AssertionError::Unsigned)
+        );
+        assert!(AssertionKey::parse("k1:short").is_err());
+        assert!(AssertionKey::parse("bad kid:0123456789abcdef").is_err());
+        assert_eq!(
+            AssertionKey::parse_list(" k1:0123456789abcdef, ,k2:fedcba9876543210")
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
@@ -1,26 +1,104 @@
+//! Broker <-> chat daemon messages.
+//!
+//! One connection carries many sessions (like the shard protocol). Frames are
+//! `type (1) + session id (16, u128 big-endian) + body`, in both directions.
+//!
+//! - `REQ_JOIN`: body = display name. Registers the session; idempotent.
+//! - `REQ_LEAVE`: empty body. Forgets the session (its channel settings are kept by name).
+//! - `REQ_SAY`: body = a chat command line, e.g. `ooc hello`, `join trade`, `history ooc`.
+//!   The daemon owns the command grammar so the broker only forwards text.
+//! - `REQ_SCOPE`: TLV body (`crate::tlv`) naming the party and guild the session belongs to
+//!   (`TAG_SCOPE_PARTY`, `TAG_SCOPE_GUILD`; opaque ids, absent = none) and the players it ignores
+//!   (`TAG_SCOPE_IGNORE`, one record per lowercase name). Shards own parties, guilds and ignore
+//!   lists, so only the broker sends this; players can't pick their own scope.
+//! - `EVT_LINE` / `EVT_ERR`: body = text for that session (includes `\r\n`, may contain
+//!   `crate::color` markup).
+
 use bytes::Bytes;
 
 use crate::ProtoError;
 use crate::session::SessionId;
+use crate::tlv::{self, TlvWriter};
 
 pub const REQ_JOIN: u8 = 0x01;
 pub const REQ_LEAVE: u8 = 0x02;
 pub const REQ_SAY: u8 = 0x03;
+pub const REQ_SCOPE: u8 = 0x04;
+
+/// `REQ_SCOPE` tags (also used by the shard's `RESP_CHAT_SCOPE`).
+pub const TAG_SCOPE_PARTY: u8 = 1;
+pub const TAG_SCOPE_GUILD: u8 = 2;
+pub const TAG_SCOPE_IGNORE: u8 = 3;
 
 pub const EVT_LINE: u8 = 0x81;
 pub const EVT_ERR: u8 = 0x82;
 
 #[derive(Debug, Clone)]
 pub enum ChatReq {
-    Join { session: SessionId, name: Bytes },
-    Leave { session: SessionId },
-    Say { session: SessionId, msg: Bytes },
+    Join {
+        session: SessionId,
+        name: Bytes,
+    },
+    Leave {
+        session: SessionId,
+    },
+    Say {
+        session: SessionId,
+        msg: Bytes,
+    },
+    Scope {
+        session: SessionId,
+        party: Option<Bytes>,
+        guild: Option<Bytes>,
+        ignores: Vec<Bytes>,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub enum ChatEvent {
-    Line { line: Bytes },
-    Err { msg: Bytes },
+    Line { session: SessionId, line: Bytes },
+    Err { session: SessionId, msg: Bytes },
+}
+
+/// `REQ_SCOPE` body. Fails if an id or name doesn't fit a TLV record.
+pub fn encode_scope(
+    party: Option<&[u8]>,
+    guild: Option<&[u8]>,
+    ignores: &[impl AsRef<[u8]>],
+) -> Result<Bytes, ProtoError> {
+    let mut w = TlvWriter::new();
+    if let Some(p) = party {
+        w.put(TAG_SCOPE_PARTY, p)?;
+    }
+    if let Some(g) = guild {
+        w.put(TAG_SCOPE_GUILD, g)?;
+    }
+    for name in ignores {
+        w.put(TAG_SCOPE_IGNORE, name.as_ref())?;
+    }
+    Ok(w.finish())
+}
+
+/// A parsed `REQ_SCOPE` body.
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    pub party: Option<Bytes>,
+    pub guild: Option<Bytes>,
+    pub ignores: Vec<Bytes>,
+}
+
+pub fn parse_scope(body: Bytes) -> Result<Scope, ProtoError> {
+    let mut scope = Scope::default();
+    for r in tlv::records(body) {
+        let (tag, v) = r?;
+        match tag {
+            TAG_SCOPE_PARTY => scope.party = Some(v),
+            TAG_SCOPE_GUILD => scope.guild = Some(v),
+            TAG_SCOPE_IGNORE => scope.ignores.push(v),
+            _ => {}
+        }
+    }
+    Ok(scope)
 }
 
 pub fn parse_req(p: Bytes) -> Result<ChatReq, ProtoError> {
@@ -51,19 +129,45 @@ pub fn parse_req(p: Bytes) -> Result<ChatReq, ProtoError> {
             session,
             msg: p.slice(1 + 16..),
         }),
+        REQ_SCOPE => {
+            let Scope {
+                party,
+                guild,
+                ignores,
+            } = parse_scope(p.slice(1 + 16..))?;
+            Ok(ChatReq::Scope {
+                session,
+                party,
+                guild,
+                ignores,
+            })
+        }
         _ => Err(ProtoError::UnknownType(t)),
     }
 }
 
 pub fn parse_event(p: Bytes) -> Result<ChatEvent, ProtoError> {
-    if p.is_empty() {
-        return Err(ProtoError::TooShort { need: 1, got: 0 });
+    if p.len() < 1 + SessionId::LEN {
+        return Err(ProtoError::TooShort {
+            need: 1 + SessionId::LEN,
+            got: p.len(),
+        });
     }
 
     let t = p[0];
+    let mut sid = [0u8; 16];
+    sid.copy_from_slice(&p[1..1 + 16]);
+    let session = SessionId::from_be_bytes(sid);
+
     match t {
-        EVT_LINE => Ok(ChatEvent::Line { line: p.slice(1..) }),
-        EVT_ERR => Ok(ChatEvent::Err { msg: p.slice(1..) }),
+        EVT_LINE => Ok(ChatEvent::Line {
+            session,
+            line: p.slice(1 + 16..),
+        }),
+        EVT_ERR => Ok(ChatEvent::Err {
+            session,
+            msg: p.slice(1 + 16..),
+        }),
         _ => Err(ProtoError::UnknownType(t)),
     }
 }
@@ -0,0 +1,307 @@
+//! Color markup carried inside shard output text.
+//!
+//! The shard never emits raw ANSI escapes. Instead it writes a tiny markup language and the
+//! edge (broker, ws gateway) renders it for the client's terminal, or strips it:
+//! - `{r}` `{g}` `{y}` `{b}` `{m}` `{c}` `{w}` `{d}`: red/green/yellow/blue/magenta/cyan/white/dark
+//! - uppercase (`{R}`, `{G}`, ...): the bright variant
+//! - `{x}`: reset to the default color
+//! - `{#rrggbb}`: 24-bit color (downsampled to the 256 or 16 color palettes as needed)
+//! - `{{`: a literal `{`
+//!
+//! Anything else (including unknown codes) passes through untouched. Markup is plain ASCII, so
+//! rendering works on raw bytes and never splits a UTF-8 sequence. Player-supplied text must go
+//! through [`escape`] before it is placed in markup.
+
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ColorMode {
+    /// Strip all markup.
+    #[default]
+    Off,
+    /// Classic 8 colors + bold for the bright variants.
+    Ansi16,
+    /// xterm 256 color palette.
+    Ansi256,
+    /// 24-bit SGR colors.
+    TrueColor,
+}
+
+impl ColorMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ColorMode::Off => "off",
+            ColorMode::Ansi16 => "16",
+            ColorMode::Ansi256 => "256",
+            ColorMode::TrueColor => "truecolor",
+        }
+    }
+
+    /// Best mode advertised by an MTTS bitvector (`TTYPE` round 3: `MTTS <n>`).
+    pub fn from_mtts(bits: u32) -> Self {
+        if bits & 256 != 0 {
+            ColorMode::TrueColor
+        } else if bits & 8 != 0 {
+            ColorMode::Ansi256
+        } else if bits & 1 != 0 {
+            ColorMode::Ansi16
+        } else {
+            ColorMode::Off
+        }
+    }
+
+    /// Best guess from a plain terminal type name (`xterm-256color`, `ANSI`, `MUDLET`, ...).
+    pub fn from_ttype(name: &str) -> Self {
+        let n = name.trim().to_ascii_uppercase();
+        if n.is_empty() || n == "DUMB" || n == "UNKNOWN" {
+            return ColorMode::Off;
+        }
+        if n.contains("TRUECOLOR") || n.contains("24BIT") {
+            return ColorMode::TrueColor;
+        }
+        if n.contains("256") {
+            return ColorMode::Ansi256;
+        }
+        ColorMode::Ansi16
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Code {
+    Reset,
+    Basic { idx: u8, bright: bool },
+    Rgb(u8, u8, u8),
+}
+
+fn basic_idx(c: u8) -> Option<u8> {
+    match c.to_ascii_lowercase() {
+        b'd' => Some(0),
+        b'r' => Some(1),
+        b'g' => Some(2),
+        b'y' => Some(3),
+        b'b' => Some(4),
+        b'm' => Some(5),
+        b'c' => Some(6),
+        b'w' => Some(7),
+        _ => None,
+    }
+}
+
+fn hex_nibble(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Parse a markup code starting at `src[0] == b'{'`, returning the code and its byte length.
+fn parse_code(src: &[u8]) -> Option<(Code, usize)> {
+    if src.len() >= 3 && src[2] == b'}' {
+        let c = src[1];
+        if c == b'x' || c == b'X' {
+            return Some((Code::Reset, 3));
+        }
+        let idx = basic_idx(c)?;
+        return Some((
+            Code::Basic {
+                idx,
+                bright: c.is_ascii_uppercase(),
+            },
+            3,
+        ));
+    }
+    if src.len() >= 9 && src[1] == b'#' && src[8] == b'}' {
+        let mut rgb = [0u8; 3];
+        for (i, v) in rgb.iter_mut().enumerate() {
+            let hi = hex_nibble(src[2 + i * 2])?;
+            let lo = hex_nibble(src[3 + i * 2])?;
+            *v = (hi << 4) | lo;
+        }
+        return Some((Code::Rgb(rgb[0], rgb[1], rgb[2]), 9));
+    }
+    None
+}
+
+// Approximate RGB values of the 16 color palette (xterm defaults).
+const PALETTE16: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+fn nearest16(r: u8, g: u8, b: u8) -> (u8, bool) {
+    let mut best = 0usize;
+    let mut best_d = u32::MAX;
+    for (i, (pr, pg, pb)) in PALETTE16.iter().enumerate() {
+        let dr = i32::from(r) - i32::from(*pr);
+        let dg = i32::from(g) - i32::from(*pg);
+        let db = i32::from(b) - i32::from(*pb);
+        let d = (dr * dr + dg * dg + db * db) as u32;
+        if d < best_d {
+            best_d = d;
+            best = i;
+        }
+    }
+    ((best % 8) as u8, best >= 8)
+}
+
+fn cube256(r: u8, g: u8, b: u8) -> u8 {
+    let q = |v: u8| -> u8 { ((u16::from(v) * 5 + 127) / 255) as u8 };
+    16 + 36 * q(r) + 6 * q(g) + q(b)
+}
+
+fn push_sgr(out: &mut Vec<u8>, code: Code, mode: ColorMode) {
+    let s = match (code, mode) {
+        (_, ColorMode::Off) => return,
+        (Code::Reset, _) => "\x1b[0m".to_string(),
+        (Code::Basic { idx, bright }, _) => {
+            format!("\x1b[{};{}m", if bright { 1 } else { 0 }, 30 + idx)
+        }
+        (Code::Rgb(r, g, b), ColorMode::TrueColor) => format!("\x1b[38;2;{r};{g};{b}m"),
+        (Code::Rgb(r, g, b), ColorMode::Ansi256) => format!("\x1b[38;5;{}m", cube256(r, g, b)),
+        (Code::Rgb(r, g, b), ColorMode::Ansi16) => {
+            let (idx, bright) = nearest16(r, g, b);
+            format!("\x1b[{};{}m", if bright { 1 } else { 0 }, 30 + idx)
+        }
+    };
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Render markup for a terminal in `mode`. Output that changes color is always terminated by a
+/// reset so colors can't bleed into the prompt or the next message.
+pub fn render(src: &[u8], mode: ColorMode) -> Vec<u8> {
+    let mut out = Vec::with_capacity(src.len() + 16);
+    let mut dirty = false;
+    let mut i = 0;
+    while i < src.len() {
+        let b = src[i];
+        if b != b'{' {
+            out.push(b);
+            i += 1;
+            continue;
+        }
+        if src.get(i + 1) == Some(&b'{') {
+            out.push(b'{');
+            i += 2;
+            continue;
+        }
+        match parse_code(&src[i..]) {
+            Some((code, n)) => {
+                push_sgr(&mut out, code, mode);
+                dirty = code != Code::Reset;
+                i += n;
+            }
+            None => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    if dirty && mode != ColorMode::Off {
+        push_sgr(&mut out, Code::Reset, mode);
+    }
+    out
+}
+
+/// Remove all markup, leaving the plain text.
+pub fn strip(src: &[u8]) -> Vec<u8> {
+    render(src, ColorMode::Off)
+}
+
+/// Double every `{` so text shows literally instead of being read as markup.
+pub fn escape(text: &str) -> Cow<'_, str> {
+    if text.contains('{') {
+        Cow::Owned(text.replace('{', "{{"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// [`escape`] for output that is already bytes.
+pub fn escape_bytes(src: &[u8]) -> Cow<'_, [u8]> {
+    if !src.contains(&b'{') {
+        return Cow::Borrowed(src);
+    }
+    let mut out = Vec::with_capacity(src.len() + 8);
+    for &b in src {
+        out.push(b);
+        if b == b'{' {
+            out.push(b'{');
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// True if `src` contains anything the renderer would rewrite.
+pub fn has_markup(src: &[u8]) -> bool {
+    src.contains(&b'{')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_markup_and_keeps_unknown_codes() {
+        assert_eq!(
+            strip(b"{R}hi{x} {{ok} {q} {#zz0000}"),
+            b"hi {ok} {q} {#zz0000}"
+        );
+    }
+
+    #[test]
+    fn escaped_text_renders_literally() {
+        for text in ["{R}red", "{#ff0000}x", "{x", "a{", "{{"] {
+            let line = format!("{{C}}bob:{{x}} {}", escape(text));
+            assert_eq!(strip(line.as_bytes()), format!("bob: {text}").as_bytes());
+            assert_eq!(
+                render(line.as_bytes(), ColorMode::Ansi16),
+                format!("\x1b[1;36mbob:\x1b[0m {text}").as_bytes()
+            );
+        }
+        assert!(matches!(escape("plain"), Cow::Borrowed("plain")));
+        assert_eq!(strip(&escape_bytes(b"name {R}{{x")), b"name {R}{{x");
+        assert!(matches!(escape_bytes(b"plain"), Cow::Borrowed(b"plain")));
+    }
+
+    #[test]
+    fn renders_per_mode() {
+        assert_eq!(render(b"{r}a{x}", ColorMode::Ansi16), b"\x1b[0;31ma\x1b[0m");
+        assert_eq!(render(b"{G}a", ColorMode::Ansi16), b"\x1b[1;32ma\x1b[0m");
+        assert_eq!(
+            render(b"{#ff0000}a{x}", ColorMode::TrueColor),
+            b"\x1b[38;2;255;0;0ma\x1b[0m"
+        );
+        assert_eq!(
+            render(b"{#ff0000}a{x}", ColorMode::Ansi256),
+            b"\x1b[38;5;196ma\x1b[0m"
+        );
+        assert_eq!(
+            render(b"{#ff0000}a{x}", ColorMode::Ansi16),
+            b"\x1b[1;31ma\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn mode_from_terminal_caps() {
+        assert_eq!(ColorMode::from_mtts(1 | 4 | 8), ColorMode::Ansi256);
+        assert_eq!(ColorMode::from_mtts(2829), ColorMode::TrueColor);
+        assert_eq!(ColorMode::from_ttype("xterm-256color"), ColorMode::Ansi256);
+        assert_eq!(ColorMode::from_ttype("dumb"), ColorMode::Off);
+    }
+}
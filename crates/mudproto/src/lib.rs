@@ -7,9 +7,13 @@
 //! This crate is intentionally "zero-copy" on parse: decoders return `bytes::Bytes`
 //! slices that reference the original frame payload.
 
+pub mod assertion;
 pub mod chat;
+pub mod color;
 pub mod session;
 pub mod shard;
+pub mod shard_client;
+pub mod tlv;
 
 #[derive(Debug, Clone)]
 pub enum ProtoError {
@@ -1,17 +1,123 @@
+//! Broker <-> shard messages.
+//!
+//! Two wire generations coexist:
+//! - v1: hand-packed bodies (`REQ_ATTACH`, `REQ_INPUT`, `RESP_OUTPUT`, ...).
+//! - v2: TLV bodies (`crate::tlv`) for attach/input/output, so fields can be added without
+//!   lockstep upgrades. Parsers accept both; v1 message types stay valid forever.
+//!
+//! A connection starts with the client sending `REQ_HELLO` (nil session) with its protocol
+//! version and feature bits; the shard answers `RESP_HELLO` with its own. Each side then only
+//! sends v2 bodies the other advertised (see `downgrade_req`). A peer that never answers HELLO
+//! is treated as v1-only.
+//!
+//! Handoff (`FEATURE_HANDOFF`): when a character walks into an area its shard doesn't own, the
+//! shard detaches it and sends `RESP_HANDOFF` with the destination area and an opaque snapshot of
+//! the character. The client (the broker) re-routes the session to the shard owning that area and
+//! attaches it there with the snapshot (`TAG_ATTACH_SNAPSHOT`). Snapshot contents are owned by the
+//! shards; the broker only carries them.
+//!
+//! Player ops (`FEATURE_PLAYER_OPS`): account deletion and character renames change state the
+//! shards own (saved characters, friends lists), so the broker sends every shard a
+//! `REQ_PLAYER_OP`. The body is a `crate::assertion` bound to the frame's session, which is a
+//! one-off nonce rather than a live session; its payload is the op, opaque here like attach auth.
+//!
+//! Input acks (`FEATURE_INPUT_ACK`): the broker numbers each session's input lines
+//! (`TAG_INPUT_SEQ`, increasing per session) and keeps them until the shard answers
+//! `RESP_INPUT_ACK` with the highest number it has taken. After a reconnect the broker resends
+//! whatever wasn't acked; the shard skips numbers it has already seen, so a line never runs twice.
+//!
+//! Chat scope (`FEATURE_CHAT_SCOPE`): the shard sends `RESP_CHAT_SCOPE` whenever a session's
+//! party, guild or ignore list changes. The body is a `crate::chat::REQ_SCOPE` body; ids are only unique
+//! within one shard, so the broker qualifies them before passing them on to chatd.
+
 use bytes::Bytes;
 
 use crate::ProtoError;
 use crate::session::SessionId;
+use crate::tlv::{self, TlvWriter};
+
+pub const PROTO_VERSION: u16 = 2;
 
+pub const REQ_HELLO: u8 = 0x00;
 pub const REQ_ATTACH: u8 = 0x01;
 pub const REQ_DETACH: u8 = 0x02;
 pub const REQ_INPUT: u8 = 0x03;
+pub const REQ_ATTACH_V2: u8 = 0x11;
+pub const REQ_INPUT_V2: u8 = 0x13;
+pub const REQ_PLAYER_OP: u8 = 0x14;
 
+pub const RESP_HELLO: u8 = 0x80;
 pub const RESP_OUTPUT: u8 = 0x81;
 pub const RESP_ERR: u8 = 0x82;
+pub const RESP_HANDOFF: u8 = 0x83;
+pub const RESP_INPUT_ACK: u8 = 0x84;
+pub const RESP_CHAT_SCOPE: u8 = 0x85;
+pub const RESP_OUTPUT_V2: u8 = 0x91;
+
+/// HELLO feature bits.
+pub const FEATURE_ATTACH_V2: u64 = 1 << 0;
+pub const FEATURE_INPUT_V2: u64 = 1 << 1;
+pub const FEATURE_OUTPUT_V2: u64 = 1 << 2;
+/// Output text may contain `crate::color` markup.
+pub const FEATURE_COLOR_MARKUP: u64 = 1 << 3;
+/// Client can route `RESP_HANDOFF` to another shard; shard accepts `TAG_ATTACH_SNAPSHOT`.
+pub const FEATURE_HANDOFF: u64 = 1 << 4;
+/// Shard applies broker-signed `REQ_PLAYER_OP`s.
+pub const FEATURE_PLAYER_OPS: u64 = 1 << 5;
+/// Shard acks numbered input with `RESP_INPUT_ACK` and drops numbers it has seen.
+pub const FEATURE_INPUT_ACK: u64 = 1 << 6;
+/// Client forwards `RESP_CHAT_SCOPE` to chatd.
+pub const FEATURE_CHAT_SCOPE: u64 = 1 << 7;
+
+/// Everything this build of the crate understands.
+pub const FEATURES: u64 = FEATURE_ATTACH_V2
+    | FEATURE_INPUT_V2
+    | FEATURE_OUTPUT_V2
+    | FEATURE_COLOR_MARKUP
+    | FEATURE_HANDOFF
+    | FEATURE_PLAYER_OPS
+    | FEATURE_INPUT_ACK
+    | FEATURE_CHAT_SCOPE;
+
+/// `REQ_HELLO` / `RESP_HELLO` tags.
+pub const TAG_HELLO_VERSION: u8 = 1;
+pub const TAG_HELLO_FEATURES: u8 = 2;
+pub const TAG_HELLO_SOFTWARE: u8 = 3;
+
+/// `REQ_ATTACH_V2` tags.
+pub const TAG_ATTACH_NAME: u8 = 1;
+pub const TAG_ATTACH_IS_BOT: u8 = 2;
+pub const TAG_ATTACH_AUTH: u8 = 3;
+pub const TAG_ATTACH_RACE: u8 = 4;
+pub const TAG_ATTACH_CLASS: u8 = 5;
+pub const TAG_ATTACH_SEX: u8 = 6;
+pub const TAG_ATTACH_PRONOUNS: u8 = 7;
+pub const TAG_ATTACH_SNAPSHOT: u8 = 8;
+pub const TAG_ATTACH_FRESH: u8 = 9;
+
+/// `REQ_INPUT_V2` tags.
+pub const TAG_INPUT_LINE: u8 = 1;
+pub const TAG_INPUT_SEQ: u8 = 2;
+
+/// `RESP_OUTPUT_V2` tags. Text longer than one TLV record is split across several
+/// `TAG_OUTPUT_TEXT` records, which the reader joins in order.
+pub const TAG_OUTPUT_TEXT: u8 = 1;
+
+/// `RESP_HANDOFF` tags.
+pub const TAG_HANDOFF_AREA: u8 = 1;
+pub const TAG_HANDOFF_SNAPSHOT: u8 = 2;
+
+/// `RESP_INPUT_ACK` tags.
+pub const TAG_ACK_SEQ: u8 = 1;
 
 #[derive(Debug, Clone)]
 pub enum ShardReq {
+    /// Version/capability handshake (`REQ_HELLO`, TLV body, nil session).
+    Hello {
+        version: u16,
+        features: u64,
+        software: Bytes,
+    },
     /// Attach a session to the shard.
     ///
     /// Encoding:
@@ -43,6 +149,10 @@ pub enum ShardReq {
         sex: Option<Bytes>,
         pronouns: Option<Bytes>,
         name: Bytes,
+        /// Character state handed off from another shard (v2 only).
+        snapshot: Option<Bytes>,
+        /// A newly created character: drop any saved state left under this name (v2 only).
+        fresh: bool,
     },
     Detach {
         session: SessionId,
@@ -50,13 +160,308 @@ pub enum ShardReq {
     Input {
         session: SessionId,
         line: Bytes,
+        /// Broker-assigned sequence number (v2 only); unnumbered input is never acked.
+        seq: Option<u64>,
+    },
+    /// Change saved characters (delete, rename). `assertion` is signed by the broker for
+    /// `session`, which only serves as a nonce.
+    PlayerOp {
+        session: SessionId,
+        assertion: Bytes,
     },
 }
 
 #[derive(Debug, Clone)]
 pub enum ShardResp {
-    Output { session: SessionId, line: Bytes },
-    Err { session: SessionId, msg: Bytes },
+    Hello {
+        version: u16,
+        features: u64,
+        software: Bytes,
+    },
+    Output {
+        session: SessionId,
+        line: Bytes,
+    },
+    Err {
+        session: SessionId,
+        msg: Bytes,
+    },
+    /// The session's character left for `area`; attach it to the owning shard with `snapshot`.
+    Handoff {
+        session: SessionId,
+        area: Bytes,
+        snapshot: Bytes,
+    },
+    /// The shard has taken every input numbered up to `seq` for `session`.
+    InputAck {
+        session: SessionId,
+        seq: u64,
+    },
+    /// The party and guild `session`'s character is in now (shard-local ids) and who it ignores.
+    ChatScope {
+        session: SessionId,
+        party: Option<Bytes>,
+        guild: Option<Bytes>,
+        ignores: Vec<Bytes>,
+    },
+}
+
+/// Attach fields, encodable as either wire generation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AttachFields<'a> {
+    pub is_bot: bool,
+    pub auth: Option<&'a [u8]>,
+    pub race: Option<&'a [u8]>,
+    pub class: Option<&'a [u8]>,
+    pub sex: Option<&'a [u8]>,
+    pub pronouns: Option<&'a [u8]>,
+    pub name: &'a [u8],
+    pub snapshot: Option<&'a [u8]>,
+    pub fresh: bool,
+}
+
+impl AttachFields<'_> {
+    /// `REQ_ATTACH_V2` body. Fails if any field doesn't fit a TLV record; dropping one (say,
+    /// the auth) would change what the attach means.
+    pub fn encode_v2(&self) -> Result<Bytes, ProtoError> {
+        let mut w = TlvWriter::new();
+        w.put(TAG_ATTACH_NAME, self.name)?;
+        w.put_u8(TAG_ATTACH_IS_BOT, u8::from(self.is_bot));
+        if self.fresh {
+            w.put_u8(TAG_ATTACH_FRESH, 1);
+        }
+        for (tag, v) in [
+            (TAG_ATTACH_AUTH, self.auth),
+            (TAG_ATTACH_RACE, self.race),
+            (TAG_ATTACH_CLASS, self.class),
+            (TAG_ATTACH_SEX, self.sex),
+            (TAG_ATTACH_PRONOUNS, self.pronouns),
+            (TAG_ATTACH_SNAPSHOT, self.snapshot),
+        ] {
+            if let Some(v) = v {
+                w.put(tag, v)?;
+            }
+        }
+        Ok(w.finish())
+    }
+
+    /// `REQ_ATTACH` (v1) body; see `ShardReq::Attach` for the layout. v1 has no snapshot or
+    /// fresh flag. Fails like `encode_v2` if the auth or a build field is too long for its
+    /// length prefix.
+    pub fn encode_v1(&self) -> Result<Bytes, ProtoError> {
+        let mut b = Vec::with_capacity(1 + 2 + self.name.len() + 64);
+        let mut flags = 0u8;
+        if self.is_bot {
+            flags |= 0x01;
+        }
+        let auth = self
+            .auth
+            .map(|a| {
+                u16::try_from(a.len())
+                    .map(|n| (n, a))
+                    .map_err(|_| ProtoError::Malformed("attach auth too long"))
+            })
+            .transpose()?;
+        if auth.is_some() {
+            flags |= 0x02;
+        }
+        let has_build = self.race.is_some()
+            || self.class.is_some()
+            || self.sex.is_some()
+            || self.pronouns.is_some();
+        if has_build {
+            flags |= 0x04;
+        }
+        b.push(flags);
+        if let Some((n, a)) = auth {
+            b.extend_from_slice(&n.to_be_bytes());
+            b.extend_from_slice(a);
+        }
+        if has_build {
+            for v in [self.race, self.class, self.sex, self.pronouns] {
+                let v = v.unwrap_or_default();
+                let n = u8::try_from(v.len())
+                    .map_err(|_| ProtoError::Malformed("attach build field too long"))?;
+                b.push(n);
+                b.extend_from_slice(v);
+            }
+        }
+        b.extend_from_slice(self.name);
+        Ok(Bytes::from(b))
+    }
+}
+
+/// `REQ_HELLO` / `RESP_HELLO` body.
+pub fn encode_hello(version: u16, features: u64, software: &str) -> Bytes {
+    let mut w = TlvWriter::new();
+    w.put_u16(TAG_HELLO_VERSION, version);
+    w.put_u64(TAG_HELLO_FEATURES, features);
+    let sw = software.as_bytes();
+    let _ = w.put(TAG_HELLO_SOFTWARE, &sw[..sw.len().min(255)]);
+    w.finish()
+}
+
+/// `REQ_INPUT_V2` body. Fails if the line doesn't fit a TLV record.
+pub fn encode_input_v2(line: &[u8], seq: Option<u64>) -> Result<Bytes, ProtoError> {
+    let mut w = TlvWriter::new();
+    w.put(TAG_INPUT_LINE, line)?;
+    if let Some(seq) = seq {
+        w.put_u64(TAG_INPUT_SEQ, seq);
+    }
+    Ok(w.finish())
+}
+
+/// The sequence number in a `REQ_INPUT_V2` body, if it has a well-formed one.
+pub fn input_seq(body: &Bytes) -> Option<u64> {
+    tlv::records(body.clone())
+        .filter_map(Result::ok)
+        .find(|(tag, _)| *tag == TAG_INPUT_SEQ)
+        .and_then(|(_, v)| tlv::as_u64(&v).ok())
+}
+
+/// `RESP_INPUT_ACK` body.
+pub fn encode_input_ack(seq: u64) -> Bytes {
+    let mut w = TlvWriter::new();
+    w.put_u64(TAG_ACK_SEQ, seq);
+    w.finish()
+}
+
+/// `RESP_OUTPUT_V2` body, split across as many text records as it takes.
+pub fn encode_output_v2(text: &[u8]) -> Bytes {
+    let mut w = TlvWriter::new();
+    // Every record is at most u16::MAX bytes, so none of these puts can fail. Empty text still
+    // gets one (empty) record.
+    if text.is_empty() {
+        let _ = w.put(TAG_OUTPUT_TEXT, text);
+    }
+    for chunk in text.chunks(u16::MAX as usize) {
+        let _ = w.put(TAG_OUTPUT_TEXT, chunk);
+    }
+    w.finish()
+}
+
+/// `RESP_HANDOFF` body. Fails if the snapshot doesn't fit a TLV record.
+pub fn encode_handoff(area: &str, snapshot: &[u8]) -> Result<Bytes, ProtoError> {
+    let mut w = TlvWriter::new();
+    w.put(TAG_HANDOFF_AREA, area.as_bytes())?;
+    w.put(TAG_HANDOFF_SNAPSHOT, snapshot)?;
+    Ok(w.finish())
+}
+
+/// Rewrite a request (type + body) into something a peer advertising `peer_features` accepts.
+/// v2 messages fall back to their v1 equivalent; everything else passes through.
+pub fn downgrade_req(t: u8, body: Bytes, peer_features: u64) -> Result<(u8, Bytes), ProtoError> {
+    match t {
+        REQ_ATTACH_V2 if peer_features & FEATURE_ATTACH_V2 == 0 => {
+            let a = parse_attach_v2(SessionId(0), body)?;
+            let ShardReq::Attach {
+                is_bot,
+                auth,
+                race,
+                class,
+                sex,
+                pronouns,
+                name,
+                ..
+            } = a
+            else {
+                return Err(ProtoError::Malformed("attach"));
+            };
+            let f = AttachFields {
+                is_bot,
+                auth: auth.as_deref(),
+                race: race.as_deref(),
+                class: class.as_deref(),
+                sex: sex.as_deref(),
+                pronouns: pronouns.as_deref(),
+                name: &name,
+                snapshot: None,
+                fresh: false,
+            };
+            Ok((REQ_ATTACH, f.encode_v1()?))
+        }
+        REQ_INPUT_V2 if peer_features & FEATURE_INPUT_V2 == 0 => {
+            let ShardReq::Input { line, .. } = parse_input_v2(SessionId(0), body)? else {
+                return Err(ProtoError::Malformed("input"));
+            };
+            Ok((REQ_INPUT, line))
+        }
+        // Nothing older to fall back to; a shard without the feature keeps the old state.
+        REQ_PLAYER_OP if peer_features & FEATURE_PLAYER_OPS == 0 => {
+            Err(ProtoError::Malformed("peer lacks player ops"))
+        }
+        _ => Ok((t, body)),
+    }
+}
+
+fn parse_hello(body: Bytes) -> Result<(u16, u64, Bytes), ProtoError> {
+    let mut version = None;
+    let mut features = 0;
+    let mut software = Bytes::new();
+    for r in tlv::records(body) {
+        let (tag, v) = r?;
+        match tag {
+            TAG_HELLO_VERSION => version = Some(tlv::as_u16(&v)?),
+            TAG_HELLO_FEATURES => features = tlv::as_u64(&v)?,
+            TAG_HELLO_SOFTWARE => software = v,
+            _ => {}
+        }
+    }
+    let version = version.ok_or(ProtoError::Malformed("hello missing version"))?;
+    Ok((version, features, software))
+}
+
+fn parse_attach_v2(session: SessionId, body: Bytes) -> Result<ShardReq, ProtoError> {
+    let mut name = None;
+    let mut is_bot = false;
+    let (mut auth, mut race, mut class, mut sex, mut pronouns) = (None, None, None, None, None);
+    let mut snapshot = None;
+    let mut fresh = false;
+    for r in tlv::records(body) {
+        let (tag, v) = r?;
+        match tag {
+            TAG_ATTACH_NAME => name = Some(v),
+            TAG_ATTACH_IS_BOT => is_bot = v.first().is_some_and(|b| *b != 0),
+            TAG_ATTACH_AUTH => auth = Some(v),
+            TAG_ATTACH_RACE => race = Some(v),
+            TAG_ATTACH_CLASS => class = Some(v),
+            TAG_ATTACH_SEX => sex = Some(v),
+            TAG_ATTACH_PRONOUNS => pronouns = Some(v),
+            TAG_ATTACH_SNAPSHOT => snapshot = Some(v),
+            TAG_ATTACH_FRESH => fresh = v.first().is_some_and(|b| *b != 0),
+            _ => {}
+        }
+    }
+    Ok(ShardReq::Attach {
+        session,
+        is_bot,
+        auth,
+        race,
+        class,
+        sex,
+        pronouns,
+        name: name.ok_or(ProtoError::Malformed("attach missing name"))?,
+        snapshot,
+        fresh,
+    })
+}
+
+fn parse_input_v2(session: SessionId, body: Bytes) -> Result<ShardReq, ProtoError> {
+    let mut line = None;
+    let mut seq = None;
+    for r in tlv::records(body) {
+        let (tag, v) = r?;
+        match tag {
+            TAG_INPUT_LINE => line = Some(v),
+            TAG_INPUT_SEQ => seq = Some(tlv::as_u64(&v)?),
+            _ => {}
+        }
+    }
+    Ok(ShardReq::Input {
+        session,
+        line: line.ok_or(ProtoError::Malformed("input missing line"))?,
+        seq,
+    })
 }
 
 pub fn parse_req(p: Bytes) -> Result<ShardReq, ProtoError> {
@@ -73,6 +478,16 @@ pub fn parse_req(p: Bytes) -> Result<ShardReq, ProtoError> {
     let session = SessionId::from_be_bytes(sid);
 
     match t {
+        REQ_HELLO => {
+            let (version, features, software) = parse_hello(p.slice(1 + 16..))?;
+            Ok(ShardReq::Hello {
+                version,
+                features,
+                software,
+            })
+        }
+        REQ_ATTACH_V2 => parse_attach_v2(session, p.slice(1 + 16..)),
+        REQ_INPUT_V2 => parse_input_v2(session, p.slice(1 + 16..)),
         REQ_ATTACH => {
             if p.len() < 1 + 16 + 1 {
                 return Err(ProtoError::TooShort {
@@ -166,6 +581,8 @@ pub fn parse_req(p: Bytes) -> Result<ShardReq, ProtoError> {
                 sex,
                 pronouns,
                 name: p.slice(i..),
+                snapshot: None,
+                fresh: false,
             })
         }
         REQ_DETACH => {
@@ -177,6 +594,11 @@ pub fn parse_req(p: Bytes) -> Result<ShardReq, ProtoError> {
         REQ_INPUT => Ok(ShardReq::Input {
             session,
             line: p.slice(1 + 16..),
+            seq: None,
+        }),
+        REQ_PLAYER_OP => Ok(ShardReq::PlayerOp {
+            session,
+            assertion: p.slice(1 + 16..),
         }),
         _ => Err(ProtoError::UnknownType(t)),
     }
@@ -196,6 +618,71 @@ pub fn parse_resp(p: Bytes) -> Result<ShardResp, ProtoError> {
     let session = SessionId::from_be_bytes(sid);
 
     match t {
+        RESP_HELLO => {
+            let (version, features, software) = parse_hello(p.slice(1 + 16..))?;
+            Ok(ShardResp::Hello {
+                version,
+                features,
+                software,
+            })
+        }
+        RESP_OUTPUT_V2 => {
+            let mut parts = Vec::new();
+            for r in tlv::records(p.slice(1 + 16..)) {
+                let (tag, v) = r?;
+                if tag == TAG_OUTPUT_TEXT {
+                    parts.push(v);
+                }
+            }
+            let line = match parts.len() {
+                0 => return Err(ProtoError::Malformed("output missing text")),
+                1 => parts.swap_remove(0),
+                _ => Bytes::from(parts.concat()),
+            };
+            Ok(ShardResp::Output { session, line })
+        }
+        RESP_HANDOFF => {
+            let (mut area, mut snapshot) = (None, None);
+            for r in tlv::records(p.slice(1 + 16..)) {
+                let (tag, v) = r?;
+                match tag {
+                    TAG_HANDOFF_AREA => area = Some(v),
+                    TAG_HANDOFF_SNAPSHOT => snapshot = Some(v),
+                    _ => {}
+                }
+            }
+            Ok(ShardResp::Handoff {
+                session,
+                area: area.ok_or(ProtoError::Malformed("handoff missing area"))?,
+                snapshot: snapshot.ok_or(ProtoError::Malformed("handoff missing snapshot"))?,
+            })
+        }
+        RESP_INPUT_ACK => {
+            let mut seq = None;
+            for r in tlv::records(p.slice(1 + 16..)) {
+                let (tag, v) = r?;
+                if tag == TAG_ACK_SEQ {
+                    seq = Some(tlv::as_u64(&v)?);
+                }
+            }
+            Ok(ShardResp::InputAck {
+                session,
+                seq: seq.ok_or(ProtoError::Malformed("input ack missing seq"))?,
+            })
+        }
+        RESP_CHAT_SCOPE => {
+            let crate::chat::Scope {
+                party,
+                guild,
+                ignores,
+            } = crate::chat::parse_scope(p.slice(1 + 16..))?;
+            Ok(ShardResp::ChatScope {
+                session,
+                party,
+                guild,
+                ignores,
+            })
+        }
         RESP_OUTPUT => Ok(ShardResp::Output {
             session,
             line: p.slice(1 + 16..),
@@ -207,3 +694,155 @@ pub fn parse_resp(p: Bytes) -> Result<ShardResp, ProtoError> {
         _ => Err(ProtoError::UnknownType(t)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(t: u8, session: SessionId, body: &[u8]) -> Bytes {
+        let mut b = vec![t];
+        b.extend_from_slice(&session.to_be_bytes());
+        b.extend_from_slice(body);
+        Bytes::from(b)
+    }
+
+    #[test]
+    fn attach_v2_roundtrips_and_downgrades() {
+        let f = AttachFields {
+            is_bot: true,
+            auth: Some(b"{}"),
+            race: Some(b"elf"),
+            class: Some(b"mage"),
+            sex: Some(b"none"),
+            pronouns: Some(b"they"),
+            name: b"Alice",
+            snapshot: Some(b"{}"),
+            fresh: true,
+        };
+        let (t, v1) = downgrade_req(REQ_ATTACH_V2, f.encode_v2().unwrap(), 0).unwrap();
+        assert_eq!(t, REQ_ATTACH);
+        assert_eq!(v1, f.encode_v1().unwrap());
+        let Ok(ShardReq::Attach {
+            snapshot, fresh, ..
+        }) = parse_req(frame(REQ_ATTACH_V2, SessionId(7), &f.encode_v2().unwrap()))
+        else {
+            panic!("attach did not parse");
+        };
+        assert_eq!(snapshot.as_deref(), Some(&b"{}"[..]));
+        assert!(fresh);
+
+        for (t, body) in [(REQ_ATTACH_V2, f.encode_v2().unwrap()), (REQ_ATTACH, v1)] {
+            let Ok(ShardReq::Attach {
+                is_bot, race, name, ..
+            }) = parse_req(frame(t, SessionId(7), &body))
+            else {
+                panic!("attach did not parse");
+            };
+            assert!(is_bot);
+            assert_eq!(race.as_deref(), Some(&b"elf"[..]));
+            assert_eq!(&name[..], b"Alice");
+        }
+
+        let auth = vec![b'a'; u16::MAX as usize + 1];
+        let f = AttachFields {
+            auth: Some(&auth),
+            ..f
+        };
+        assert!(f.encode_v2().is_err());
+        assert!(f.encode_v1().is_err());
+
+        let race = vec![b'r'; 256];
+        let f = AttachFields {
+            auth: None,
+            race: Some(&race),
+            ..f
+        };
+        let body = f.encode_v2().unwrap();
+        assert!(f.encode_v1().is_err());
+        assert!(downgrade_req(REQ_ATTACH_V2, body, 0).is_err());
+    }
+
+    #[test]
+    fn long_output_is_split_not_truncated() {
+        for len in [0, 5, u16::MAX as usize, 3 * u16::MAX as usize + 7] {
+            let text = (0..len).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+            let body = encode_output_v2(&text);
+            let Ok(ShardResp::Output { line, .. }) =
+                parse_resp(frame(RESP_OUTPUT_V2, SessionId(1), &body))
+            else {
+                panic!("output did not parse");
+            };
+            assert_eq!(&line[..], &text[..]);
+        }
+    }
+
+    #[test]
+    fn player_ops_need_the_feature() {
+        let body = Bytes::from_static(b"sa1.k.00.1.00.{}");
+        assert!(downgrade_req(REQ_PLAYER_OP, body.clone(), FEATURE_HANDOFF).is_err());
+        let (t, b) = downgrade_req(REQ_PLAYER_OP, body.clone(), FEATURES).unwrap();
+        assert_eq!((t, &b[..]), (REQ_PLAYER_OP, &body[..]));
+        let Ok(ShardReq::PlayerOp { session, assertion }) =
+            parse_req(frame(REQ_PLAYER_OP, SessionId(9), &body))
+        else {
+            panic!("player op did not parse");
+        };
+        assert_eq!((session, assertion), (SessionId(9), body));
+    }
+
+    #[test]
+    fn input_seq_survives_v2_only() {
+        let body = encode_input_v2(b"look", Some(42)).unwrap();
+        assert_eq!(input_seq(&body), Some(42));
+        let Ok(ShardReq::Input { line, seq, .. }) =
+            parse_req(frame(REQ_INPUT_V2, SessionId(3), &body))
+        else {
+            panic!("input did not parse");
+        };
+        assert_eq!((&line[..], seq), (&b"look"[..], Some(42)));
+
+        let (t, v1) = downgrade_req(REQ_INPUT_V2, body, 0).unwrap();
+        assert_eq!((t, &v1[..]), (REQ_INPUT, &b"look"[..]));
+        assert_eq!(input_seq(&encode_input_v2(b"look", None).unwrap()), None);
+        assert!(encode_input_v2(&vec![b'x'; u16::MAX as usize + 1], Some(1)).is_err());
+
+        let Ok(ShardResp::InputAck { session, seq }) =
+            parse_resp(frame(RESP_INPUT_ACK, SessionId(3), &encode_input_ack(42)))
+        else {
+            panic!("ack did not parse");
+        };
+        assert_eq!((session, seq), (SessionId(3), 42));
+    }
+
+    #[test]
+    fn chat_scope_roundtrips() {
+        let body = crate::chat::encode_scope(Some(b"7"), None, &["mallory", "eve"]).unwrap();
+        let Ok(ShardResp::ChatScope {
+            session,
+            party,
+            guild,
+            ignores,
+        }) = parse_resp(frame(RESP_CHAT_SCOPE, SessionId(4), &body))
+        else {
+            panic!("chat scope did not parse");
+        };
+        assert_eq!(session, SessionId(4));
+        assert_eq!(party.as_deref(), Some(&b"7"[..]));
+        assert_eq!(guild, None);
+        assert_eq!(ignores, vec![&b"mallory"[..], &b"eve"[..]]);
+    }
+
+    #[test]
+    fn hello_skips_unknown_tags() {
+        let mut body = encode_hello(PROTO_VERSION, FEATURES, "test").to_vec();
+        body.extend_from_slice(&[0xee, 0, 1, 0xff]);
+        let Ok(ShardResp::Hello {
+            version, features, ..
+        }) = parse_resp(frame(RESP_HELLO, SessionId(0), &body))
+        else {
+            panic!("hello did not parse");
+        };
+        assert_eq!(version, PROTO_VERSION);
+        assert_eq!(features, FEATURES);
+    }
+}
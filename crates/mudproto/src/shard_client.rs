@@ -0,0 +1,61 @@
+//! Client side of the shard handshake, shared by everything that dials a shard (broker, ws
+//! gateway).
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use slopio::frame::{FrameReader, FrameWriter};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::session::SessionId;
+use crate::shard::{PROTO_VERSION, REQ_HELLO, ShardResp, encode_hello, parse_resp};
+
+/// The shard's side of the handshake.
+#[derive(Debug, Clone)]
+pub struct PeerHello {
+    pub version: u16,
+    pub features: u64,
+    pub software: Bytes,
+}
+
+/// Send `REQ_HELLO` offering `features` and wait up to `timeout` for the shard's `RESP_HELLO`,
+/// skipping anything else it sends first. `None` means the shard didn't answer (pre-HELLO shards
+/// drop the connection instead), so the caller should reconnect and speak v1.
+pub async fn hello<R, W>(
+    fr: &mut FrameReader<R>,
+    fw: &mut FrameWriter<W>,
+    features: u64,
+    software: &str,
+    timeout: Duration,
+) -> Option<PeerHello>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let body = encode_hello(PROTO_VERSION, features, software);
+    let mut hdr = [0u8; 1 + SessionId::LEN];
+    hdr[0] = REQ_HELLO;
+    hdr[1..].copy_from_slice(&SessionId(0).to_be_bytes());
+    fw.write_frame_parts(&[&hdr, &body]).await.ok()?;
+    fw.flush().await.ok()?;
+
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let frame = tokio::time::timeout_at(deadline, fr.read_frame())
+            .await
+            .ok()?
+            .ok()??;
+        if let Ok(ShardResp::Hello {
+            version,
+            features,
+            software,
+        }) = parse_resp(frame)
+        {
+            return Some(PeerHello {
+                version,
+                features,
+                software,
+            });
+        }
+    }
+}
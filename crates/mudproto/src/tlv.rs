@@ -0,0 +1,125 @@
+//! Tag-length-value records used by v2 message bodies.
+//!
+//! Record encoding:
+//! - tag: 1 byte (meaning is scoped to the enclosing message type)
+//! - len: u16 big-endian
+//! - value: len bytes
+//!
+//! Readers must skip tags they don't know; that's what lets peers add fields independently.
+//! Tags may repeat; single-valued fields take the last occurrence.
+
+use bytes::Bytes;
+
+use crate::ProtoError;
+
+#[derive(Debug, Default)]
+pub struct TlvWriter {
+    buf: Vec<u8>,
+}
+
+impl TlvWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn put(&mut self, tag: u8, v: &[u8]) -> Result<(), ProtoError> {
+        let len =
+            u16::try_from(v.len()).map_err(|_| ProtoError::Malformed("tlv value too long"))?;
+        self.buf.reserve(3 + v.len());
+        self.buf.push(tag);
+        self.buf.extend_from_slice(&len.to_be_bytes());
+        self.buf.extend_from_slice(v);
+        Ok(())
+    }
+
+    pub fn put_u8(&mut self, tag: u8, v: u8) {
+        // Fixed-size values always fit.
+        let _ = self.put(tag, &[v]);
+    }
+
+    pub fn put_u16(&mut self, tag: u8, v: u16) {
+        let _ = self.put(tag, &v.to_be_bytes());
+    }
+
+    pub fn put_u64(&mut self, tag: u8, v: u64) {
+        let _ = self.put(tag, &v.to_be_bytes());
+    }
+
+    pub fn finish(self) -> Bytes {
+        Bytes::from(self.buf)
+    }
+}
+
+/// Iterate records in `p`; values are zero-copy slices of `p`.
+pub fn records(p: Bytes) -> TlvIter {
+    TlvIter { p, i: 0 }
+}
+
+#[derive(Debug, Clone)]
+pub struct TlvIter {
+    p: Bytes,
+    i: usize,
+}
+
+impl Iterator for TlvIter {
+    type Item = Result<(u8, Bytes), ProtoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.i >= self.p.len() {
+            return None;
+        }
+        let i = self.i;
+        if self.p.len() < i + 3 {
+            self.i = self.p.len();
+            return Some(Err(ProtoError::TooShort {
+                need: i + 3,
+                got: self.p.len(),
+            }));
+        }
+        let tag = self.p[i];
+        let len = u16::from_be_bytes([self.p[i + 1], self.p[i + 2]]) as usize;
+        if self.p.len() < i + 3 + len {
+            self.i = self.p.len();
+            return Some(Err(ProtoError::TooShort {
+                need: i + 3 + len,
+                got: self.p.len(),
+            }));
+        }
+        self.i = i + 3 + len;
+        Some(Ok((tag, self.p.slice(i + 3..i + 3 + len))))
+    }
+}
+
+pub fn as_u16(v: &[u8]) -> Result<u16, ProtoError> {
+    let b: [u8; 2] = v
+        .try_into()
+        .map_err(|_| ProtoError::Malformed("expected u16"))?;
+    Ok(u16::from_be_bytes(b))
+}
+
+pub fn as_u64(v: &[u8]) -> Result<u64, ProtoError> {
+    let b: [u8; 8] = v
+        .try_into()
+        .map_err(|_| ProtoError::Malformed("expected u64"))?;
+    Ok(u64::from_be_bytes(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_and_truncation() {
+        let mut w = TlvWriter::new();
+        w.put(1, b"alice").unwrap();
+        w.put_u16(9, 2);
+        let b = w.finish();
+        let recs = records(b.clone()).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(recs.len(), 2);
+        assert_eq!(recs[0], (1, Bytes::from_static(b"alice")));
+        assert_eq!(as_u16(&recs[1].1).unwrap(), 2);
+
+        let cut = b.slice(..b.len() - 1);
+        assert!(records(cut).any(|r| r.is_err()));
+    }
+}
@@ -3,6 +3,10 @@ use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use serde::{Deserialize, Serialize};
 
+mod trie;
+
+pub use trie::{PrefixTrie, aggregate};
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum IpFamily {
@@ -119,24 +123,41 @@ fn mask_v6(addr: Ipv6Addr, prefix_len: u8) -> Ipv6Addr {
     Ipv6Addr::from(u128::from(addr) & v6_mask(prefix_len))
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct ExemptPrefixes {
-    pub prefixes: Vec<IpPrefix>,
+    prefixes: Vec<IpPrefix>,
+    trie: PrefixTrie<()>,
 }
 
 impl ExemptPrefixes {
-    pub fn empty() -> Self {
-        Self {
-            prefixes: Vec::new(),
+    pub fn new(prefixes: Vec<IpPrefix>) -> Self {
+        let mut trie = PrefixTrie::new();
+        for p in &prefixes {
+            trie.insert(p, ());
         }
+        Self { prefixes, trie }
+    }
+
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// As loaded, in file order.
+    pub fn prefixes(&self) -> &[IpPrefix] {
+        &self.prefixes
     }
 
     pub fn contains_ip(&self, ip: IpAddr) -> bool {
-        self.prefixes.iter().any(|p| p.contains_ip(ip))
+        self.trie.contains_ip(ip)
     }
 
     pub fn contains_prefix(&self, pfx: &IpPrefix) -> bool {
-        self.prefixes.iter().any(|p| p.contains_prefix(pfx))
+        self.trie.covering(pfx).is_some()
+    }
+
+    /// Whether `pfx` covers any exempt address at all.
+    pub fn overlaps(&self, pfx: &IpPrefix) -> bool {
+        self.trie.overlaps(pfx)
     }
 
     pub fn load(path: &std::path::Path) -> anyhow::Result<Self> {
@@ -152,7 +173,7 @@ impl ExemptPrefixes {
                 .map_err(|e| anyhow::anyhow!("bad CIDR at {}:{}: {e}", path.display(), i + 1))?;
             out.push(p);
         }
-        Ok(Self { prefixes: out })
+        Ok(Self::new(out))
     }
 }
 
@@ -185,6 +206,12 @@ pub struct EnforcementStatus {
     pub backend_attached: bool,
     pub enforcement_mode: String, // enforcing | fail_open
     pub reported_at_unix: u64,
+    /// Last event index the enforcer had seen when it reported.
+    #[serde(default)]
+    pub events_last_index: u64,
+    /// Index the report committed at; stamped by sbc_raftd.
+    #[serde(default)]
+    pub reported_at_index: u64,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -208,7 +235,13 @@ pub enum SubscribeMode {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum EventsReq {
-    Subscribe { mode: SubscribeMode },
+    /// With `from_index`, replay retained events from that index on (or a `Snapshot` if they
+    /// are no longer retained) and then tail; otherwise `mode` decides how to start.
+    Subscribe {
+        mode: SubscribeMode,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        from_index: Option<u64>,
+    },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -222,6 +255,10 @@ pub enum AdminReq {
     },
     DeleteBan {
         ban_id: String,
+        #[serde(default)]
+        deleted_by: String,
+        #[serde(default)]
+        reason: String,
     },
     UpsertLegalHold {
         name: String,
@@ -238,6 +275,105 @@ pub enum AdminReq {
         report: BanApplyResult,
     },
     GetState,
+    /// Add a voting member to the sbc_raftd cluster (`addr` is its raft RPC endpoint).
+    AddRaftNode {
+        node_id: u64,
+        addr: String,
+    },
+    RemoveRaftNode {
+        node_id: u64,
+    },
+    GetRaftStatus,
+    /// Ban lifecycle history, oldest first. `cidr` matches entries whose key overlaps it
+    /// (contains it or is contained by it); times are inclusive bounds on `at_unix`. Entries
+    /// older than 90 days are dropped.
+    GetBanHistory {
+        #[serde(default)]
+        cidr: Option<String>,
+        #[serde(default)]
+        ban_id: Option<String>,
+        #[serde(default)]
+        since_unix: Option<u64>,
+        #[serde(default)]
+        until_unix: Option<u64>,
+    },
+    /// Attach an appeal to a ban that is currently in force. One open appeal per ban.
+    FileBanAppeal {
+        ban_id: String,
+        filed_by: String,
+        message: String,
+    },
+    /// Close an open appeal. Granting lifts the ban.
+    DecideBanAppeal {
+        appeal_id: String,
+        granted: bool,
+        decided_by: String,
+        #[serde(default)]
+        note: String,
+    },
+    ListBanAppeals {
+        #[serde(default)]
+        ban_id: Option<String>,
+        #[serde(default)]
+        status: Option<AppealStatus>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BanAction {
+    Created,
+    /// Re-upserted with a later (or no) expiry.
+    Extended,
+    /// Re-upserted without extending it (shorter TTL, new reason).
+    Updated,
+    Expired,
+    Deleted,
+}
+
+/// One change to a ban, kept after the ban itself is gone.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BanHistoryEntry {
+    pub index: u64, // event index of the change
+    pub at_unix: u64,
+    pub ban_id: String,
+    pub key: IpPrefix,
+    pub action: BanAction,
+    pub actor: String,
+    pub reason: String,
+    pub expires_at_unix: u64, // as of this change; 0 = never
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AppealStatus {
+    Open,
+    Granted,
+    Denied,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BanAppeal {
+    pub appeal_id: String,
+    pub ban_id: String,
+    pub key: IpPrefix,
+    pub filed_at_unix: u64,
+    pub filed_by: String,
+    pub message: String,
+    pub status: AppealStatus,
+    #[serde(default)]
+    pub decided_at_unix: Option<u64>,
+    #[serde(default)]
+    pub decided_by: Option<String>,
+    #[serde(default)]
+    pub decision_note: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RaftMember {
+    pub node_id: u64,
+    pub addr: String,
+    pub voter: bool,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -259,6 +395,26 @@ pub enum AdminResp {
         bans: Vec<BanEntry>,
         holds: Vec<LegalHoldEntry>,
     },
+    OkRaftStatus {
+        node_id: u64,
+        leader_id: Option<u64>,
+        role: String, // leader | follower | candidate | learner | shutdown
+        term: u64,
+        last_applied: u64,
+        members: Vec<RaftMember>,
+    },
+    OkBanHistory {
+        index: u64,
+        entries: Vec<BanHistoryEntry>,
+    },
+    OkBanAppeal {
+        index: u64,
+        appeal: BanAppeal,
+    },
+    OkBanAppeals {
+        index: u64,
+        appeals: Vec<BanAppeal>,
+    },
     Err {
         message: String,
     },
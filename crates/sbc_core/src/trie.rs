@@ -0,0 +1,363 @@
+//! Binary prefix trie over IPv4 and IPv6, for longest-prefix match and CIDR aggregation.
+//!
+//! One bit per level, one root per family. Removal prunes empty branches, so every node that
+//! exists has a stored prefix somewhere below it.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::{IpFamily, IpPrefix};
+
+#[derive(Clone, Debug)]
+struct Node<T> {
+    value: Option<T>,
+    child: [Option<Box<Node<T>>>; 2],
+}
+
+impl<T> Node<T> {
+    fn new() -> Self {
+        Self {
+            value: None,
+            child: [None, None],
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.value.is_none() && self.child.iter().all(Option::is_none)
+    }
+}
+
+/// Address bits, most significant first, left-aligned in a `u128`.
+fn bits(addr: IpAddr) -> u128 {
+    match addr {
+        IpAddr::V4(a) => u128::from(u32::from(a)) << 96,
+        IpAddr::V6(a) => u128::from(a),
+    }
+}
+
+fn bit(b: u128, depth: u8) -> usize {
+    ((b >> (127 - u32::from(depth))) & 1) as usize
+}
+
+fn with_bit(b: u128, depth: u8, i: usize) -> u128 {
+    b | ((i as u128) << (127 - u32::from(depth)))
+}
+
+/// `b` must have no bits set past `len`.
+fn prefix_at(family: IpFamily, b: u128, len: u8) -> IpPrefix {
+    let addr = match family {
+        IpFamily::V4 => IpAddr::V4(Ipv4Addr::from((b >> 96) as u32)),
+        IpFamily::V6 => IpAddr::V6(Ipv6Addr::from(b)),
+    };
+    IpPrefix {
+        addr,
+        prefix_len: len,
+    }
+}
+
+fn collect<'a, T>(
+    n: &'a Node<T>,
+    family: IpFamily,
+    b: u128,
+    depth: u8,
+    out: &mut Vec<(IpPrefix, &'a T)>,
+) {
+    if let Some(v) = &n.value {
+        out.push((prefix_at(family, b, depth), v));
+    }
+    for (i, c) in n.child.iter().enumerate() {
+        if let Some(c) = c {
+            collect(c, family, with_bit(b, depth, i), depth + 1, out);
+        }
+    }
+}
+
+fn remove_at<T>(n: &mut Node<T>, b: u128, depth: u8, len: u8) -> Option<T> {
+    if depth == len {
+        return n.value.take();
+    }
+    let i = bit(b, depth);
+    let child = n.child[i].as_mut()?;
+    let out = remove_at(child, b, depth + 1, len);
+    if child.is_empty() {
+        n.child[i] = None;
+    }
+    out
+}
+
+#[derive(Clone, Debug)]
+pub struct PrefixTrie<T> {
+    v4: Node<T>,
+    v6: Node<T>,
+    len: usize,
+}
+
+impl<T> Default for PrefixTrie<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> PrefixTrie<T> {
+    pub fn new() -> Self {
+        Self {
+            v4: Node::new(),
+            v6: Node::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn root(&self, family: IpFamily) -> &Node<T> {
+        match family {
+            IpFamily::V4 => &self.v4,
+            IpFamily::V6 => &self.v6,
+        }
+    }
+
+    /// The node for exactly `p`, if any prefix at or below it is stored.
+    fn node(&self, p: &IpPrefix) -> Option<&Node<T>> {
+        let b = bits(p.addr);
+        let mut n = self.root(p.family());
+        for depth in 0..p.prefix_len {
+            n = n.child[bit(b, depth)].as_deref()?;
+        }
+        Some(n)
+    }
+
+    /// Returns the previous value for `p`, if any.
+    pub fn insert(&mut self, p: &IpPrefix, value: T) -> Option<T> {
+        let b = bits(p.addr);
+        let mut n = match p.family() {
+            IpFamily::V4 => &mut self.v4,
+            IpFamily::V6 => &mut self.v6,
+        };
+        for depth in 0..p.prefix_len {
+            n = n.child[bit(b, depth)].get_or_insert_with(|| Box::new(Node::new()));
+        }
+        let old = n.value.replace(value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    pub fn remove(&mut self, p: &IpPrefix) -> Option<T> {
+        let root = match p.family() {
+            IpFamily::V4 => &mut self.v4,
+            IpFamily::V6 => &mut self.v6,
+        };
+        let old = remove_at(root, bits(p.addr), 0, p.prefix_len);
+        if old.is_some() {
+            self.len -= 1;
+        }
+        old
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Exact match.
+    pub fn get(&self, p: &IpPrefix) -> Option<&T> {
+        self.node(p)?.value.as_ref()
+    }
+
+    /// The most specific stored prefix that contains `p` (possibly `p` itself).
+    pub fn covering(&self, p: &IpPrefix) -> Option<(IpPrefix, &T)> {
+        let family = p.family();
+        let b = bits(p.addr);
+        let mut n = self.root(family);
+        let mut best = n.value.as_ref().map(|v| (0, v));
+        for depth in 0..p.prefix_len {
+            match n.child[bit(b, depth)].as_deref() {
+                Some(c) => n = c,
+                None => break,
+            }
+            if let Some(v) = &n.value {
+                best = Some((depth + 1, v));
+            }
+        }
+        best.map(|(len, v)| {
+            let mask = if len == 0 {
+                0
+            } else {
+                u128::MAX << (128 - u32::from(len))
+            };
+            (prefix_at(family, b & mask, len), v)
+        })
+    }
+
+    /// Longest-prefix match for a single address.
+    pub fn longest_match(&self, ip: IpAddr) -> Option<(IpPrefix, &T)> {
+        let host = match ip {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        self.covering(&IpPrefix {
+            addr: ip,
+            prefix_len: host,
+        })
+    }
+
+    pub fn contains_ip(&self, ip: IpAddr) -> bool {
+        self.longest_match(ip).is_some()
+    }
+
+    /// Whether any stored prefix shares addresses with `p` (contains it or lies inside it).
+    pub fn overlaps(&self, p: &IpPrefix) -> bool {
+        // Only the roots can exist while empty.
+        self.covering(p).is_some() || self.node(p).is_some_and(|n| !n.is_empty())
+    }
+
+    /// Stored prefixes inside `p` (including `p` itself), in address order.
+    pub fn within(&self, p: &IpPrefix) -> Vec<(IpPrefix, &T)> {
+        let mut out = Vec::new();
+        if let Some(n) = self.node(p) {
+            collect(n, p.family(), bits(p.addr), p.prefix_len, &mut out);
+        }
+        out
+    }
+
+    /// Everything, IPv4 first, in address order.
+    pub fn entries(&self) -> Vec<(IpPrefix, &T)> {
+        let mut out = Vec::with_capacity(self.len);
+        collect(&self.v4, IpFamily::V4, 0, 0, &mut out);
+        collect(&self.v6, IpFamily::V6, 0, 0, &mut out);
+        out
+    }
+}
+
+/// Returns whether `n` is fully covered; otherwise pushes the covered parts below it.
+fn merge<T>(n: &Node<T>, family: IpFamily, b: u128, depth: u8, out: &mut Vec<IpPrefix>) -> bool {
+    if n.value.is_some() {
+        return true;
+    }
+    let mut full = [false; 2];
+    for (i, c) in n.child.iter().enumerate() {
+        if let Some(c) = c {
+            full[i] = merge(c, family, with_bit(b, depth, i), depth + 1, out);
+        }
+    }
+    if full == [true, true] {
+        return true;
+    }
+    for (i, f) in full.into_iter().enumerate() {
+        if f {
+            out.push(prefix_at(family, with_bit(b, depth, i), depth + 1));
+        }
+    }
+    false
+}
+
+/// The fewest prefixes covering exactly the same addresses: nested prefixes are dropped and
+/// sibling halves merge (two adjacent /24s become a /23), repeatedly. Sorted, IPv4 first.
+pub fn aggregate<'a>(prefixes: impl IntoIterator<Item = &'a IpPrefix>) -> Vec<IpPrefix> {
+    let mut t = PrefixTrie::new();
+    for p in prefixes {
+        t.insert(p, ());
+    }
+    let mut out = Vec::new();
+    for (family, root) in [(IpFamily::V4, &t.v4), (IpFamily::V6, &t.v6)] {
+        if merge(root, family, 0, 0, &mut out) {
+            out.push(prefix_at(family, 0, 0));
+        }
+    }
+    out.sort_by_key(|p| (p.family() == IpFamily::V6, bits(p.addr), p.prefix_len));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(s: &str) -> IpPrefix {
+        IpPrefix::parse_cidr(s).unwrap()
+    }
+
+    fn lm(t: &PrefixTrie<&'static str>, ip: &str) -> Option<(String, &'static str)> {
+        t.longest_match(ip.parse().unwrap())
+            .map(|(k, v)| (k.to_cidr_string(), *v))
+    }
+
+    fn cidrs(v: &[IpPrefix]) -> Vec<String> {
+        v.iter().map(IpPrefix::to_cidr_string).collect()
+    }
+
+    #[test]
+    fn longest_prefix_wins() {
+        let mut t = PrefixTrie::new();
+        t.insert(&p("10.0.0.0/8"), "a");
+        t.insert(&p("10.1.0.0/16"), "b");
+        t.insert(&p("2001:db8::/32"), "c");
+        t.insert(&p("2001:db8:1::/48"), "d");
+        assert_eq!(t.len(), 4);
+
+        assert_eq!(lm(&t, "10.1.2.3"), Some(("10.1.0.0/16".to_string(), "b")));
+        assert_eq!(lm(&t, "10.2.0.1"), Some(("10.0.0.0/8".to_string(), "a")));
+        assert_eq!(lm(&t, "11.0.0.1"), None);
+        assert_eq!(
+            lm(&t, "2001:db8:1::5"),
+            Some(("2001:db8:1::/48".to_string(), "d"))
+        );
+        assert_eq!(
+            lm(&t, "2001:db8:2::5"),
+            Some(("2001:db8::/32".to_string(), "c"))
+        );
+        // Families never mix, even for ::ffff:0:0/96-style lookalikes.
+        assert_eq!(lm(&t, "::a00:1"), None);
+
+        assert!(t.overlaps(&p("10.1.2.0/24")));
+        assert!(t.overlaps(&p("0.0.0.0/0")));
+        assert!(!t.overlaps(&p("192.0.2.0/24")));
+        assert_eq!(
+            t.within(&p("10.0.0.0/7"))
+                .into_iter()
+                .map(|(k, _)| k.to_cidr_string())
+                .collect::<Vec<_>>(),
+            vec!["10.0.0.0/8", "10.1.0.0/16"]
+        );
+
+        assert_eq!(t.remove(&p("10.1.0.0/16")), Some("b"));
+        assert_eq!(t.remove(&p("10.1.0.0/16")), None);
+        assert_eq!(lm(&t, "10.1.2.3"), Some(("10.0.0.0/8".to_string(), "a")));
+        t.remove(&p("10.0.0.0/8"));
+        assert!(!t.overlaps(&p("0.0.0.0/0")));
+        assert_eq!(t.len(), 2);
+    }
+
+    #[test]
+    fn aggregates_adjacent_and_nested_prefixes() {
+        let merged = aggregate(&[
+            p("198.51.100.0/24"),
+            p("198.51.101.0/24"),
+            p("198.51.102.0/24"),
+            p("198.51.100.7/32"),
+            p("203.0.113.0/25"),
+            p("203.0.113.128/25"),
+            p("2001:db8::/33"),
+            p("2001:db8:8000::/33"),
+            p("2001:db8:1::1/128"),
+        ]);
+        assert_eq!(
+            cidrs(&merged),
+            vec![
+                "198.51.100.0/23",
+                "198.51.102.0/24",
+                "203.0.113.0/24",
+                "2001:db8::/32"
+            ]
+        );
+        assert_eq!(
+            cidrs(&aggregate(&[p("0.0.0.0/1"), p("128.0.0.0/1")])),
+            vec!["0.0.0.0/0"]
+        );
+        assert!(aggregate(&[]).is_empty());
+    }
+}
@@ -6,12 +6,30 @@
 //! - `IAC WILL <opt>` => `IAC DONT <opt>`
 //!
 //! It also strips subnegotiation blocks: `IAC SB ... IAC SE`.
+//!
+//! Options registered with `accept_option` are left to the caller: negotiation for them gets no
+//! automatic reply, and their commands/subnegotiation payloads are surfaced via `take_events`.
+
+/// Max subnegotiation payload we keep for an accepted option; the rest is dropped.
+const MAX_SUBNEG_LEN: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TelnetEvent {
+    Will(u8),
+    Wont(u8),
+    Do(u8),
+    Dont(u8),
+    Subneg { opt: u8, data: Vec<u8> },
+}
 
 #[derive(Debug, Default)]
 pub struct IacParser {
     state: State,
     /// If true, emit default refusal replies for DO/WILL.
     refuse_negotiation: bool,
+    /// Options the caller negotiates itself.
+    accepted: Vec<u8>,
+    events: Vec<TelnetEvent>,
 }
 
 #[derive(Debug, Default)]
@@ -25,8 +43,7 @@ enum State {
     Subneg {
         opt: Option<u8>,
         iac_seen: bool,
-        // Keep any subnegotiation payload we might want later (currently unused).
-        _buf: Vec<u8>,
+        buf: Vec<u8>,
     },
 }
 
@@ -35,6 +52,8 @@ impl IacParser {
         Self {
             state: State::Data,
             refuse_negotiation: true,
+            accepted: Vec::new(),
+            events: Vec::new(),
         }
     }
 
@@ -43,6 +62,19 @@ impl IacParser {
         self
     }
 
+    /// Hand negotiation of `opt` to the caller (see `take_events`).
+    pub fn accept_option(mut self, opt: u8) -> Self {
+        if !self.accepted.contains(&opt) {
+            self.accepted.push(opt);
+        }
+        self
+    }
+
+    /// Drain events for accepted options seen by `parse` so far.
+    pub fn take_events(&mut self) -> Vec<TelnetEvent> {
+        std::mem::take(&mut self.events)
+    }
+
     /// Parse a chunk of bytes, returning `(data, replies)`:
     /// - `data`: the stream with IAC sequences removed
     /// - `replies`: bytes to write back to the telnet peer (may be empty)
@@ -83,7 +115,7 @@ impl IacParser {
                             self.state = State::Subneg {
                                 opt: None,
                                 iac_seen: false,
-                                _buf: Vec::new(),
+                                buf: Vec::new(),
                             };
                         }
                         // Other 2-byte IAC commands (NOP, GA, etc.) - ignore.
@@ -94,7 +126,14 @@ impl IacParser {
                 }
                 State::Negotiate { cmd } => {
                     let opt = *b;
-                    if self.refuse_negotiation {
+                    if self.accepted.contains(&opt) {
+                        self.events.push(match *cmd {
+                            DO => TelnetEvent::Do(opt),
+                            DONT => TelnetEvent::Dont(opt),
+                            WILL => TelnetEvent::Will(opt),
+                            _ => TelnetEvent::Wont(opt),
+                        });
+                    } else if self.refuse_negotiation {
                         match *cmd {
                             // "Please do X" => "No thanks".
                             DO => replies.extend_from_slice(&[IAC, WONT, opt]),
@@ -105,11 +144,7 @@ impl IacParser {
                     }
                     self.state = State::Data;
                 }
-                State::Subneg {
-                    opt,
-                    iac_seen,
-                    _buf,
-                } => {
+                State::Subneg { opt, iac_seen, buf } => {
                     if opt.is_none() {
                         *opt = Some(*b);
                         continue;
@@ -118,9 +153,19 @@ impl IacParser {
                     if *iac_seen {
                         // Only SE matters; IAC IAC is escaped literal IAC.
                         if *b == SE {
+                            if let Some(o) = *opt
+                                && self.accepted.contains(&o)
+                            {
+                                self.events.push(TelnetEvent::Subneg {
+                                    opt: o,
+                                    data: std::mem::take(buf),
+                                });
+                            }
                             self.state = State::Data;
                         } else if *b == IAC {
-                            _buf.push(IAC);
+                            if buf.len() < MAX_SUBNEG_LEN {
+                                buf.push(IAC);
+                            }
                             *iac_seen = false;
                         } else {
                             // Unknown IAC within SB; ignore.
@@ -134,7 +179,9 @@ impl IacParser {
                         continue;
                     }
 
-                    _buf.push(*b);
+                    if buf.len() < MAX_SUBNEG_LEN {
+                        buf.push(*b);
+                    }
                 }
             }
         }
@@ -191,4 +238,27 @@ mod tests {
         assert_eq!(d, vec![b'a', b'b']);
         assert!(r.is_empty());
     }
+
+    #[test]
+    fn surfaces_accepted_options() {
+        let mut p = IacParser::new().accept_option(24);
+        // IAC WILL 24, IAC SB 24 IS "ANSI" IAC SE, IAC WILL 3
+        let bytes = [
+            255, 251, 24, 255, 250, 24, 0, b'A', b'N', b'S', b'I', 255, 240, 255, 251, 3,
+        ];
+        let (d, r) = p.parse(&bytes);
+        assert!(d.is_empty());
+        assert_eq!(r, vec![255, 254, 3]);
+        assert_eq!(
+            p.take_events(),
+            vec![
+                TelnetEvent::Will(24),
+                TelnetEvent::Subneg {
+                    opt: 24,
+                    data: b"\0ANSI".to_vec()
+                },
+            ]
+        );
+        assert!(p.take_events().is_empty());
+    }
 }
@@ -0,0 +1,191 @@
+//! Per-account command aliases, expanded by the broker before input reaches a shard.
+//!
+//! `alias hh cast heal $1; say healing $1` makes `hh bob` run two commands. `$1`..`$9` are the
+//! words after the alias name, `$*` is all of them and `$$` is a literal `$`. An expansion may use
+//! other aliases; nesting, the number of resulting commands and their length are capped so a
+//! loop or a pile of nested `$*` fails fast.
+//! A line starting with `\` skips expansion.
+
+use std::collections::BTreeMap;
+
+pub const MAX_ALIASES: usize = 50;
+pub const MAX_NAME_LEN: usize = 20;
+pub const MAX_EXPANSION_LEN: usize = 512;
+const MAX_DEPTH: usize = 5;
+const MAX_COMMANDS: usize = 20;
+/// Longest command an expansion may produce; the same as the longest line a client can send.
+const MAX_COMMAND_LEN: usize = 8 * 1024;
+
+pub type Aliases = BTreeMap<String, String>;
+
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
+pub enum ExpandError {
+    TooDeep(String),
+    TooMany,
+    TooLong,
+}
+
+impl std::fmt::Display for ExpandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpandError::TooDeep(name) => {
+                write!(f, "alias {name} nests too deep (does it use itself?)")
+            }
+            ExpandError::TooMany => write!(f, "expands to more than {MAX_COMMANDS} commands"),
+            ExpandError::TooLong => {
+                write!(f, "expands to a command over {MAX_COMMAND_LEN} characters")
+            }
+        }
+    }
+}
+
+/// Alias names are one lowercase word; `alias` and `unalias` stay reachable.
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+        && name != "alias"
+        && name != "unalias"
+}
+
+/// The commands `line` stands for, or `None` when it isn't an alias (run it as typed).
+pub fn expand(aliases: &Aliases, line: &str) -> Option<Result<Vec<String>, ExpandError>> {
+    let line = line.trim();
+    if line.starts_with('\\') {
+        return None;
+    }
+    let head = line.split_whitespace().next()?.to_ascii_lowercase();
+    aliases.get(&head)?;
+    let mut out = Vec::new();
+    Some(expand_into(aliases, line, 0, &mut out).map(|()| out))
+}
+
+/// Strip the `\` that asks for a line to skip expansion.
+pub fn unescape(line: &str) -> &str {
+    let line = line.trim();
+    line.strip_prefix('\\').unwrap_or(line).trim_start()
+}
+
+fn expand_into(
+    aliases: &Aliases,
+    line: &str,
+    depth: usize,
+    out: &mut Vec<String>,
+) -> Result<(), ExpandError> {
+    let mut words = line.split_whitespace();
+    let head = words.next().unwrap_or("").to_ascii_lowercase();
+    let Some(body) = aliases.get(&head) else {
+        if out.len() >= MAX_COMMANDS {
+            return Err(ExpandError::TooMany);
+        }
+        out.push(line.to_string());
+        return Ok(());
+    };
+    if depth >= MAX_DEPTH {
+        return Err(ExpandError::TooDeep(head));
+    }
+    let args: Vec<&str> = words.collect();
+    for cmd in body.split(';') {
+        let cmd = substitute(cmd, &args)?;
+        let cmd = cmd.trim();
+        if !cmd.is_empty() {
+            expand_into(aliases, cmd, depth + 1, out)?;
+        }
+    }
+    Ok(())
+}
+
+fn substitute(template: &str, args: &[&str]) -> Result<String, ExpandError> {
+    let mut s = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if s.len() > MAX_COMMAND_LEN {
+            return Err(ExpandError::TooLong);
+        }
+        if c != '$' {
+            s.push(c);
+            continue;
+        }
+        match chars.peek().copied() {
+            Some('*') => {
+                chars.next();
+                s.push_str(&args.join(" "));
+            }
+            Some('$') => {
+                chars.next();
+                s.push('$');
+            }
+            Some(d @ '1'..='9') => {
+                chars.next();
+                let i = d as usize - '1' as usize;
+                s.push_str(args.get(i).copied().unwrap_or(""));
+            }
+            _ => s.push('$'),
+        }
+    }
+    if s.len() > MAX_COMMAND_LEN {
+        return Err(ExpandError::TooLong);
+    }
+    Ok(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(pairs: &[(&str, &str)]) -> Aliases {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn substitutes_args_and_splits_macros() {
+        let a = set(&[
+            ("hh", "cast heal $1; say healing $*"),
+            ("pi", "party invite $1"),
+            ("both", "pi $1; pi $2; say $$5"),
+        ]);
+        assert_eq!(expand(&a, "look"), None);
+        assert_eq!(expand(&a, "\\hh bob"), None);
+        assert_eq!(
+            expand(&a, "HH bob now"),
+            Some(Ok(vec![
+                "cast heal bob".to_string(),
+                "say healing bob now".to_string()
+            ]))
+        );
+        assert_eq!(
+            expand(&a, "both ann cy"),
+            Some(Ok(vec![
+                "party invite ann".to_string(),
+                "party invite cy".to_string(),
+                "say $5".to_string()
+            ]))
+        );
+        assert_eq!(unescape("\\hh bob"), "hh bob");
+    }
+
+    #[test]
+    fn stops_loops_and_floods() {
+        let a = set(&[("a", "b"), ("b", "a")]);
+        assert!(matches!(
+            expand(&a, "a"),
+            Some(Err(ExpandError::TooDeep(_)))
+        ));
+        let a = set(&[("x", "y;y;y;y;y"), ("y", "z;z;z;z;z")]);
+        assert_eq!(expand(&a, "x"), Some(Err(ExpandError::TooMany)));
+        let a = set(&[
+            ("a", "b $* $* $* $* $* $* $* $*"),
+            ("b", "c $* $* $* $* $* $* $* $*"),
+            ("c", "d $* $* $* $* $* $* $* $*"),
+            ("d", "say $* $* $* $* $* $* $* $*"),
+        ]);
+        assert_eq!(expand(&a, "a word"), Some(Err(ExpandError::TooLong)));
+        assert!(!valid_name("alias") && !valid_name("Bad") && valid_name("pi2"));
+    }
+}
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 
-use sbc_core::IpPrefix;
+use sbc_core::{IpPrefix, PrefixTrie};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +13,16 @@ pub struct CharacterBan {
     pub reason: String,
 }
 
+/// Keeps a character from talking until `until_unix`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CharacterMute {
+    pub name_lc: String,
+    pub until_unix: u64,
+    pub created_unix: u64,
+    pub created_by: String,
+    pub reason: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IpBan {
     pub cidr: String,
@@ -31,13 +41,16 @@ pub struct BanListFile {
     pub character_bans: Vec<CharacterBan>,
     #[serde(default)]
     pub ip_bans: Vec<IpBan>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub character_mutes: Vec<CharacterMute>,
 }
 
 #[derive(Debug)]
 pub struct BanState {
     path: PathBuf,
     chars: HashMap<String, CharacterBan>,
-    ips: Vec<(IpPrefix, IpBan)>,
+    ips: PrefixTrie<IpBan>,
+    mutes: HashMap<String, CharacterMute>,
     updated_unix: u64,
 }
 
@@ -46,7 +59,8 @@ impl BanState {
         let mut st = Self {
             path,
             chars: HashMap::new(),
-            ips: Vec::new(),
+            ips: PrefixTrie::new(),
+            mutes: HashMap::new(),
             updated_unix: 0,
         };
         let _ = st.reload();
@@ -67,14 +81,23 @@ impl BanState {
         let mut character_bans = self.chars.values().cloned().collect::<Vec<_>>();
         character_bans.sort_by(|a, b| a.name_lc.cmp(&b.name_lc));
 
-        let mut ip_bans = self.ips.iter().map(|(_, b)| b.clone()).collect::<Vec<_>>();
+        let mut ip_bans = self
+            .ips
+            .entries()
+            .into_iter()
+            .map(|(_, b)| b.clone())
+            .collect::<Vec<_>>();
         ip_bans.sort_by(|a, b| a.cidr.cmp(&b.cidr));
 
+        let mut character_mutes = self.mutes.values().cloned().collect::<Vec<_>>();
+        character_mutes.sort_by(|a, b| a.name_lc.cmp(&b.name_lc));
+
         BanListFile {
             version: 1,
             updated_unix: self.updated_unix,
             character_bans,
             ip_bans,
+            character_mutes,
         }
     }
 
@@ -84,6 +107,7 @@ impl BanState {
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
                 self.chars.clear();
                 self.ips.clear();
+                self.mutes.clear();
                 self.updated_unix = 0;
                 return Ok(());
             }
@@ -106,20 +130,28 @@ impl BanState {
             chars.insert(b.name_lc.clone(), b);
         }
 
-        let mut ips = Vec::new();
+        let mut ips = PrefixTrie::new();
         for b in file.ip_bans {
             let cidr = b.cidr.trim();
             if cidr.is_empty() {
                 continue;
             }
             if let Ok(pfx) = parse_ip_prefix(cidr) {
-                ips.push((pfx, b));
+                ips.insert(&pfx, b);
             }
         }
 
+        let mutes = file
+            .character_mutes
+            .into_iter()
+            .filter(|m| !m.name_lc.trim().is_empty())
+            .map(|m| (m.name_lc.clone(), m))
+            .collect();
+
         self.updated_unix = file.updated_unix;
         self.chars = chars;
         self.ips = ips;
+        self.mutes = mutes;
         Ok(())
     }
 
@@ -131,13 +163,43 @@ impl BanState {
         self.chars.get(&k)
     }
 
-    pub fn is_ip_banned(&self, ip: IpAddr) -> Option<&IpBan> {
-        for (pfx, b) in &self.ips {
-            if pfx.contains_ip(ip) {
-                return Some(b);
-            }
+    /// A mute still in force at `now_unix`.
+    pub fn is_char_muted(&self, name: &str, now_unix: u64) -> Option<&CharacterMute> {
+        let k = name.trim().to_ascii_lowercase();
+        self.mutes.get(&k).filter(|m| m.until_unix > now_unix)
+    }
+
+    /// Mute `name` until `until_unix`, replacing any earlier mute. Expired mutes are dropped.
+    pub fn upsert_char_mute(
+        &mut self,
+        name: &str,
+        until_unix: u64,
+        created_unix: u64,
+        created_by: String,
+        reason: String,
+    ) -> anyhow::Result<()> {
+        let name_lc = name.trim().to_ascii_lowercase();
+        if name_lc.is_empty() {
+            anyhow::bail!("empty character name");
         }
-        None
+        self.mutes.retain(|_, m| m.until_unix > created_unix);
+        self.mutes.insert(
+            name_lc.clone(),
+            CharacterMute {
+                name_lc,
+                until_unix,
+                created_unix,
+                created_by,
+                reason,
+            },
+        );
+        self.updated_unix = created_unix.max(self.updated_unix);
+        self.save()
+    }
+
+    /// The most specific ban covering `ip`.
+    pub fn is_ip_banned(&self, ip: IpAddr) -> Option<&IpBan> {
+        self.ips.longest_match(ip).map(|(_, b)| b)
     }
 
     pub fn upsert_char_ban(
@@ -173,6 +235,32 @@ impl BanState {
         Ok(changed)
     }
 
+    /// Move a character ban (and mute) along with a rename, so it follows the character. Returns
+    /// false if `old` had neither.
+    pub fn rename_char_ban(&mut self, old: &str, new: &str, now_unix: u64) -> anyhow::Result<bool> {
+        let old_lc = old.trim().to_ascii_lowercase();
+        let new_lc = new.trim().to_ascii_lowercase();
+        if new_lc.is_empty() {
+            anyhow::bail!("empty character name");
+        }
+        let ban = self.chars.remove(&old_lc);
+        let mute = self.mutes.remove(&old_lc);
+        if ban.is_none() && mute.is_none() {
+            return Ok(false);
+        }
+        if let Some(mut rec) = ban {
+            rec.name_lc = new_lc.clone();
+            self.chars.insert(new_lc.clone(), rec);
+        }
+        if let Some(mut rec) = mute {
+            rec.name_lc = new_lc.clone();
+            self.mutes.insert(new_lc, rec);
+        }
+        self.updated_unix = now_unix.max(self.updated_unix);
+        self.save()?;
+        Ok(true)
+    }
+
     pub fn upsert_ip_ban(
         &mut self,
         cidr: &str,
@@ -195,19 +283,14 @@ impl BanState {
         };
 
         // Replace existing entry if present.
-        let mut changed = true;
-        let mut out = Vec::with_capacity(self.ips.len() + 1);
-        for (p, b) in self.ips.drain(..) {
-            if p == pfx {
-                changed = b.created_unix != rec.created_unix
+        let changed = match self.ips.insert(&pfx, rec.clone()) {
+            Some(b) => {
+                b.created_unix != rec.created_unix
                     || b.created_by != rec.created_by
-                    || b.reason != rec.reason;
-                continue;
+                    || b.reason != rec.reason
             }
-            out.push((p, b));
-        }
-        out.push((pfx.clone(), rec));
-        self.ips = out;
+            None => true,
+        };
         self.updated_unix = created_unix.max(self.updated_unix);
         self.save()?;
         Ok((changed, pfx))
@@ -1,4 +1,16 @@
+//! Outgoing mail for account email verification and password resets.
+
 use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Context;
+use aws_sdk_sesv2::Client as SesClient;
+use aws_sdk_sesv2::types::{Body, Content, Destination, EmailContent, Message};
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message as LettreMessage, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 #[derive(Clone)]
 pub struct EmailConfig {
@@ -43,3 +55,257 @@ impl std::fmt::Debug for EmailConfig {
             .finish()
     }
 }
+
+/// Outgoing mail, built once from `EmailConfig`.
+#[derive(Clone)]
+pub enum Mailer {
+    Disabled,
+    Ses {
+        client: SesClient,
+        from: String,
+    },
+    Smtp {
+        transport: Arc<AsyncSmtpTransport<Tokio1Executor>>,
+        from: String,
+    },
+    /// One JSON file per message in `dir`; tests read them back.
+    File {
+        dir: PathBuf,
+        from: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct OutboxMessage<'a> {
+    created_unix_ms: u128,
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    body: &'a str,
+}
+
+impl Mailer {
+    pub async fn from_config(cfg: &EmailConfig) -> anyhow::Result<Self> {
+        let mode = cfg.mode.trim().to_ascii_lowercase();
+        let from = cfg.from.clone().filter(|s| !s.trim().is_empty());
+        Ok(match mode.as_str() {
+            "" | "disabled" => Self::Disabled,
+            "ses" => {
+                let Some(from) = from else {
+                    anyhow::bail!("SLOPMUD_EMAIL_MODE=ses but missing SLOPMUD_EMAIL_FROM");
+                };
+                let aws_cfg =
+                    aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+                Self::Ses {
+                    client: SesClient::new(&aws_cfg),
+                    from,
+                }
+            }
+            "smtp" => {
+                let Some(from) = from else {
+                    anyhow::bail!("SLOPMUD_EMAIL_MODE=smtp but missing SLOPMUD_EMAIL_FROM");
+                };
+                let Some(host) = cfg.smtp_host.as_deref() else {
+                    anyhow::bail!("SLOPMUD_EMAIL_MODE=smtp but missing SLOPMUD_SMTP_HOST");
+                };
+                let mut builder =
+                    AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?.port(cfg.smtp_port);
+                if !cfg.smtp_username.is_empty() {
+                    builder = builder.credentials(Credentials::new(
+                        cfg.smtp_username.clone(),
+                        cfg.smtp_password.clone(),
+                    ));
+                }
+                Self::Smtp {
+                    transport: Arc::new(builder.build()),
+                    from,
+                }
+            }
+            "file" => {
+                std::fs::create_dir_all(&cfg.file_dir)
+                    .with_context(|| format!("create {}", cfg.file_dir.display()))?;
+                Self::File {
+                    dir: cfg.file_dir.clone(),
+                    from: from.unwrap_or_else(|| "slopmud@localhost".to_string()),
+                }
+            }
+            _ => anyhow::bail!("unknown SLOPMUD_EMAIL_MODE={mode:?}"),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        !matches!(self, Self::Disabled)
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, body: &str) -> anyhow::Result<()> {
+        match self {
+            Self::Disabled => anyhow::bail!("email sender disabled"),
+            Self::Ses { client, from } => {
+                let dest = Destination::builder().to_addresses(to).build();
+                let subj = Content::builder().data(subject).charset("UTF-8").build()?;
+                let body = Content::builder().data(body).charset("UTF-8").build()?;
+                let msg = Message::builder()
+                    .subject(subj)
+                    .body(Body::builder().text(body).build())
+                    .build();
+                client
+                    .send_email()
+                    .from_email_address(from)
+                    .destination(dest)
+                    .content(EmailContent::builder().simple(msg).build())
+                    .send()
+                    .await?;
+                Ok(())
+            }
+            Self::Smtp { transport, from } => {
+                let msg = LettreMessage::builder()
+                    .from(from.parse::<Mailbox>()?)
+                    .to(to.parse::<Mailbox>()?)
+                    .subject(subject)
+                    .body(body.to_string())?;
+                transport.send(msg).await.map_err(|e| anyhow::anyhow!(e))?;
+                Ok(())
+            }
+            Self::File { dir, from } => {
+                let now_ms = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+                let mut r = [0u8; 4];
+                getrandom::getrandom(&mut r).expect("getrandom");
+                let stem = format!("{now_ms}-{:08x}", u32::from_be_bytes(r));
+                let msg = OutboxMessage {
+                    created_unix_ms: now_ms,
+                    from,
+                    to,
+                    subject,
+                    body,
+                };
+                // Write then rename, so readers never see half a message.
+                let tmp = dir.join(format!(".{stem}.tmp"));
+                tokio::fs::write(&tmp, serde_json::to_vec_pretty(&msg)?).await?;
+                tokio::fs::rename(&tmp, dir.join(format!("{stem}.json"))).await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Mailer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Disabled => "Mailer::Disabled",
+            Self::Ses { .. } => "Mailer::Ses",
+            Self::Smtp { .. } => "Mailer::Smtp",
+            Self::File { .. } => "Mailer::File",
+        })
+    }
+}
+
+const CODE_DIGITS: usize = 8;
+const CODE_MAX_ATTEMPTS: u32 = 5;
+
+/// A one-time code sent by email (address verification, password reset). Only a hash is kept,
+/// and a handful of wrong guesses burns it.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EmailCode {
+    hash: String,
+    #[serde(default)]
+    sent_unix: u64,
+    expires_unix: u64,
+    #[serde(default)]
+    attempts: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeCheck {
+    Ok,
+    Wrong,
+    /// Expired or out of attempts; the caller should drop it.
+    Spent,
+}
+
+fn hash_code(code: &str) -> String {
+    let d = Sha256::digest(code.as_bytes());
+    d.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+impl EmailCode {
+    /// A fresh code: (the digits to mail, what to store).
+    pub fn new(now_unix: u64, ttl_s: u64) -> (String, Self) {
+        let mut b = [0u8; 8];
+        getrandom::getrandom(&mut b).expect("getrandom");
+        let n = u64::from_be_bytes(b) % 10u64.pow(CODE_DIGITS as u32);
+        let code = format!("{n:0CODE_DIGITS$}");
+        let rec = Self {
+            hash: hash_code(&code),
+            sent_unix: now_unix,
+            expires_unix: now_unix.saturating_add(ttl_s),
+            attempts: 0,
+        };
+        (code, rec)
+    }
+
+    /// Whether this code went out less than `cooldown_s` ago (so don't mail another yet).
+    pub fn sent_within(&self, now_unix: u64, cooldown_s: u64) -> bool {
+        now_unix < self.sent_unix.saturating_add(cooldown_s)
+    }
+
+    pub fn check(&mut self, code: &str, now_unix: u64) -> CodeCheck {
+        if now_unix >= self.expires_unix || self.attempts >= CODE_MAX_ATTEMPTS {
+            return CodeCheck::Spent;
+        }
+        let code: String = code
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .collect();
+        if code.len() == CODE_DIGITS && hash_code(&code) == self.hash {
+            return CodeCheck::Ok;
+        }
+        self.attempts += 1;
+        if self.attempts >= CODE_MAX_ATTEMPTS {
+            CodeCheck::Spent
+        } else {
+            CodeCheck::Wrong
+        }
+    }
+}
+
+/// `alice@example.com` -> `a***@example.com`, for telling a player where a code went.
+pub fn mask_address(addr: &str) -> String {
+    match addr.split_once('@') {
+        Some((local, domain)) => {
+            let first = local.chars().next().unwrap_or('*');
+            format!("{first}***@{domain}")
+        }
+        None => "***".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_expire_and_burn_after_wrong_guesses() {
+        let (code, mut rec) = EmailCode::new(1_000, 600);
+        assert_eq!(code.len(), CODE_DIGITS);
+        assert!(rec.sent_within(1_059, 60) && !rec.sent_within(1_060, 60));
+        assert_eq!(rec.clone().check(&code, 1_600), CodeCheck::Spent);
+        let spaced = format!("{} {}", &code[..4], &code[4..]);
+        assert_eq!(rec.clone().check(&spaced, 1_001), CodeCheck::Ok);
+
+        let wrong = if code == "00000000" {
+            "11111111"
+        } else {
+            "00000000"
+        };
+        for _ in 1..CODE_MAX_ATTEMPTS {
+            assert_eq!(rec.check(wrong, 1_001), CodeCheck::Wrong);
+        }
+        assert_eq!(rec.check(wrong, 1_001), CodeCheck::Spent);
+        assert_eq!(rec.check(&code, 1_001), CodeCheck::Spent);
+
+        assert_eq!(mask_address("alice@example.com"), "a***@example.com");
+    }
+}
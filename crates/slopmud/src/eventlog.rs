@@ -121,6 +121,16 @@ impl EventLog {
         }
     }
 
+    /// Write out buffered lines now (before a hotboot exec, which skips destructors).
+    pub async fn flush(&self) {
+        if !self.cfg.enabled {
+            return;
+        }
+        if let Err(e) = self.inner.lock().await.flush_all() {
+            warn!(err=%e, "eventlog flush failed");
+        }
+    }
+
     pub fn public_s3_key(&self, relpath: &str) -> Option<(String, String)> {
         let bucket = self.cfg.s3_bucket.as_ref()?.clone();
         let key = join_prefix(&self.cfg.s3_prefix, relpath);
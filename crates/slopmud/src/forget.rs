@@ -0,0 +1,89 @@
+//! Character deletions the shards haven't confirmed yet.
+//!
+//! A `Forget` player op sent while a shard is down is dropped, and nothing would ever resend it,
+//! leaving a deleted character's snapshot on the shard. Names wait here until the players file no
+//! longer has them; every shard connect replays the list. While a name is pending it can't be
+//! taken again, so a replay never hits a new character of the same name.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use tracing::warn;
+
+#[derive(Debug)]
+pub struct PendingForgets {
+    path: PathBuf,
+    names: BTreeSet<String>,
+}
+
+impl PendingForgets {
+    pub fn load(path: PathBuf) -> Self {
+        let names = match std::fs::read_to_string(&path) {
+            Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+                warn!(path = %path.display(), err = %e, "bad pending forget list; starting empty");
+                BTreeSet::new()
+            }),
+            Err(_) => BTreeSet::new(),
+        };
+        Self { path, names }
+    }
+
+    pub fn add(&mut self, name: &str) -> anyhow::Result<()> {
+        if self.names.insert(name.to_ascii_lowercase()) {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains(&name.to_ascii_lowercase())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.names.iter().cloned().collect()
+    }
+
+    /// Drop names `done` says the shards have forgotten.
+    pub fn prune(&mut self, done: impl Fn(&str) -> bool) -> anyhow::Result<()> {
+        let before = self.names.len();
+        self.names.retain(|n| !done(n));
+        if self.names.len() != before {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&self.names)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_persist_until_pruned() {
+        let path = std::env::temp_dir().join(format!("slopmud-forget-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut f = PendingForgets::load(path.clone());
+        f.add("Bob").unwrap();
+        f.add("carol").unwrap();
+        assert!(f.contains("BOB"));
+
+        let mut f = PendingForgets::load(path.clone());
+        assert_eq!(f.names(), vec!["bob", "carol"]);
+        f.prune(|n| n == "bob").unwrap();
+
+        let f = PendingForgets::load(path.clone());
+        assert_eq!(f.names(), vec!["carol"]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
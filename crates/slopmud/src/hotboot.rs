@@ -0,0 +1,330 @@
+//! Hotboot: replace the broker binary without dropping telnet connections.
+//!
+//! On SIGUSR2 (systemd `ExecReload`) the broker stops accepting, asks every connection to park,
+//! and execs itself with `--hotboot <state file>`. A parked connection gives back its socket with
+//! close-on-exec cleared, plus what it needs to carry on: who is logged in, terminal settings and
+//! scrollback. The new process takes over the listening socket and the client sockets and
+//! restores sessions and routes before its shard and chat connections come up; those re-attach
+//! everyone the same way they do after a shard restart.
+//!
+//! Connections that weren't in the world yet keep their socket but start the login over.
+//!
+//! If the exec fails the broker takes the parked sockets back and keeps running.
+
+use std::ffi::OsString;
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::term::TermCaps;
+
+const STATE_VERSION: u32 = 1;
+pub const ARG: &str = "--hotboot";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct State {
+    pub version: u32,
+    pub listener_fd: RawFd,
+    pub conns: Vec<Conn>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScrollbackLine {
+    pub id: u64,
+    pub ts_unix_ms: u64,
+    pub text: String,
+}
+
+/// One parked connection.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Conn {
+    pub fd: RawFd,
+    pub session: u128,
+    pub peer: SocketAddr,
+    /// After PROXY rewriting.
+    pub peer_ip: IpAddr,
+    pub peer_port: u16,
+    /// Only sessions in the world resume; the rest start at the name prompt.
+    pub in_world: bool,
+    pub account: Option<String>,
+    pub name: Option<String>,
+    pub auth_method: Option<String>,
+    /// The unsigned auth blob; it's re-signed for every attach.
+    pub auth_blob: Option<String>,
+    pub google_sub: Option<String>,
+    pub google_email: Option<String>,
+    pub oidc_sub: Option<String>,
+    pub oidc_email: Option<String>,
+    pub is_bot: Option<bool>,
+    pub race: Option<String>,
+    pub class: Option<String>,
+    pub sex: Option<String>,
+    pub pronouns: Option<String>,
+    pub held: bool,
+    pub term: TermCaps,
+    pub width_override: Option<u16>,
+    pub height_override: Option<u16>,
+    pub color_pref: String,
+    /// A partial line the client had typed.
+    pub pending_input: Vec<u8>,
+    /// Last shard input number; shards skip anything at or below it.
+    #[serde(default)]
+    pub input_seq: u64,
+    pub scrollback: Vec<ScrollbackLine>,
+    /// Filled in by the broker from its routing table.
+    #[serde(default)]
+    pub shard: Option<SocketAddr>,
+    #[serde(default)]
+    pub handoff_snapshot: Option<String>,
+}
+
+/// Handed to each connection task: `trigger` flips to true when it should park, and the parked
+/// connection goes back on `parked`.
+pub struct Hooks {
+    pub trigger: tokio::sync::watch::Receiver<bool>,
+    pub parked: tokio::sync::mpsc::Sender<Conn>,
+}
+
+/// One round of parking: hands out `Hooks` to connections and collects them when triggered.
+/// A round that ends in a failed exec is replaced by a fresh one.
+pub struct Parking {
+    trigger: tokio::sync::watch::Sender<bool>,
+    parked_tx: Option<tokio::sync::mpsc::Sender<Conn>>,
+    parked_rx: tokio::sync::mpsc::Receiver<Conn>,
+}
+
+impl Parking {
+    pub fn new() -> Self {
+        let (trigger, _) = tokio::sync::watch::channel(false);
+        let (parked_tx, parked_rx) = tokio::sync::mpsc::channel(64);
+        Self {
+            trigger,
+            parked_tx: Some(parked_tx),
+            parked_rx,
+        }
+    }
+
+    pub fn hooks(&self) -> Hooks {
+        Hooks {
+            trigger: self.trigger.subscribe(),
+            parked: self
+                .parked_tx
+                .clone()
+                .expect("hooks handed out after parking"),
+        }
+    }
+
+    /// Ask every connection to park and collect them. Connections stuck in a slow step (an
+    /// OAuth poll, say) are left behind once `deadline` passes.
+    pub async fn park_all(&mut self, deadline: tokio::time::Instant) -> Vec<Conn> {
+        self.trigger.send_replace(true);
+        self.parked_tx = None;
+        let mut conns = Vec::new();
+        while let Ok(Some(c)) = tokio::time::timeout_at(deadline, self.parked_rx.recv()).await {
+            conns.push(c);
+        }
+        conns
+    }
+}
+
+/// A connection to pick up where it was parked, by the previous process or by this one after a
+/// failed exec. `ready` is dropped once the session is registered.
+pub struct Resume {
+    pub conn: Conn,
+    pub ready: tokio::sync::mpsc::Sender<()>,
+}
+
+fn clear_cloexec(fd: RawFd) -> std::io::Result<()> {
+    // SAFETY: fcntl on an fd we own; no memory is passed.
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: as above.
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, flags & !libc::FD_CLOEXEC) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Detach a client socket from this process so it survives the exec. The returned fd is not
+/// closed by anything here.
+pub fn park(stream: std::net::TcpStream) -> std::io::Result<RawFd> {
+    let fd = stream.into_raw_fd();
+    clear_cloexec(fd)?;
+    Ok(fd)
+}
+
+/// Adopt a socket parked by the previous process.
+pub fn unpark(fd: RawFd) -> std::io::Result<tokio::net::TcpStream> {
+    // Fails on an fd that isn't open, before we claim ownership of it.
+    // SAFETY: fcntl takes no memory.
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: the previous process handed this fd over in the state file and nothing else in
+    // this process knows about it.
+    let s = unsafe { std::net::TcpStream::from_raw_fd(fd) };
+    s.set_nonblocking(true)?;
+    tokio::net::TcpStream::from_std(s)
+}
+
+pub fn unpark_listener(fd: RawFd) -> std::io::Result<tokio::net::TcpListener> {
+    // SAFETY: fcntl takes no memory.
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: as in `unpark`.
+    let l = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+    l.set_nonblocking(true)?;
+    tokio::net::TcpListener::from_std(l)
+}
+
+/// Read and remove the state file. It holds session identities, so it doesn't outlive the
+/// handover.
+pub fn take_state(path: &Path) -> anyhow::Result<State> {
+    let raw = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+    let _ = std::fs::remove_file(path);
+    let st: State = serde_json::from_slice(&raw).context("parse hotboot state")?;
+    if st.version != STATE_VERSION {
+        anyhow::bail!(
+            "hotboot state version {} (want {STATE_VERSION})",
+            st.version
+        );
+    }
+    Ok(st)
+}
+
+impl State {
+    pub fn new(listener: &tokio::net::TcpListener, conns: Vec<Conn>) -> Self {
+        Self {
+            version: STATE_VERSION,
+            listener_fd: listener.as_raw_fd(),
+            conns,
+        }
+    }
+}
+
+/// Arguments for the next process: ours, minus any earlier `--hotboot <path>`, plus this one.
+fn relaunch_args(args: impl IntoIterator<Item = OsString>, path: &Path) -> Vec<OsString> {
+    let mut args = args.into_iter();
+    let mut keep = Vec::new();
+    while let Some(a) = args.next() {
+        if a == ARG {
+            let _ = args.next();
+            continue;
+        }
+        keep.push(a);
+    }
+    keep.push(ARG.into());
+    keep.push(path.into());
+    keep
+}
+
+/// Hand the listener and parked connections in `st` to a fresh copy of this binary. Only returns
+/// on failure, after removing the state file; the caller still owns every fd in `st`.
+pub fn exec(path: &Path, st: &State) -> anyhow::Error {
+    let run = || -> anyhow::Result<std::io::Error> {
+        clear_cloexec(st.listener_fd).context("listener fd")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("create {}", parent.display()))?;
+        }
+        let mut f = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)
+            .with_context(|| format!("open {}", path.display()))?;
+        f.write_all(&serde_json::to_vec(st)?)
+            .with_context(|| format!("write {}", path.display()))?;
+
+        // After a deploy, /proc/self/exe names the replaced (deleted) file; argv[0] is how the
+        // service was started, which now finds the new binary.
+        let mut args = std::env::args_os();
+        let exe = match args.next() {
+            Some(a) => a,
+            None => std::env::current_exe().context("locate own binary")?.into(),
+        };
+        Ok(std::process::Command::new(exe)
+            .args(relaunch_args(args, path))
+            .exec())
+    };
+    let err = match run() {
+        Ok(e) => anyhow::Error::new(e).context("exec"),
+        Err(e) => e,
+    };
+    let _ = std::fs::remove_file(path);
+    err
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conn(fd: RawFd) -> Conn {
+        serde_json::from_value(serde_json::json!({
+            "fd": fd,
+            "session": 7,
+            "peer": "192.0.2.1:5000",
+            "peer_ip": "198.51.100.2",
+            "peer_port": 6000,
+            "in_world": true,
+            "account": "alice",
+            "name": "Alice",
+            "auth_method": "password",
+            "auth_blob": null,
+            "google_sub": null,
+            "google_email": null,
+            "oidc_sub": null,
+            "oidc_email": null,
+            "is_bot": false,
+            "race": "human",
+            "class": "fighter",
+            "sex": "female",
+            "pronouns": "she",
+            "held": false,
+            "term": TermCaps::default(),
+            "width_override": null,
+            "height_override": null,
+            "color_pref": "auto",
+            "pending_input": [108, 111],
+            "scrollback": [{"id": 3, "ts_unix_ms": 10, "text": "hello"}],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn state_round_trips() {
+        let st = State {
+            version: STATE_VERSION,
+            listener_fd: 3,
+            conns: vec![conn(9)],
+        };
+        let back: State = serde_json::from_slice(&serde_json::to_vec(&st).unwrap()).unwrap();
+        assert_eq!(back.listener_fd, 3);
+        let c = &back.conns[0];
+        assert_eq!((c.fd, c.session, c.peer_port), (9, 7, 6000));
+        assert_eq!(c.name.as_deref(), Some("Alice"));
+        assert_eq!(c.pending_input, b"lo");
+        assert_eq!(c.scrollback[0].text, "hello");
+        // Fields added later default when an older broker wrote the file.
+        assert_eq!((c.input_seq, c.shard), (0, None));
+    }
+
+    #[test]
+    fn relaunch_replaces_the_hotboot_arg() {
+        let args = ["--bind", "0.0.0.0:4000", ARG, "/old/state.json", "-v"].map(OsString::from);
+        assert_eq!(
+            relaunch_args(args, Path::new("/new/state.json")),
+            ["--bind", "0.0.0.0:4000", "-v", ARG, "/new/state.json"].map(OsString::from)
+        );
+    }
+}
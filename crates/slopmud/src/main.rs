@@ -10,8 +10,15 @@ use bytes::Bytes;
 use chrono::{TimeZone, Utc};
 use compliance::LogStream;
 use memchr::memchr;
+use mudproto::assertion::AssertionKey;
+use mudproto::chat::ChatEvent;
+use mudproto::color::{self, ColorMode};
 use mudproto::session::SessionId;
-use mudproto::shard::{REQ_ATTACH, REQ_DETACH, REQ_INPUT, ShardResp};
+use mudproto::shard::{
+    AttachFields, FEATURE_INPUT_ACK, FEATURES as SHARD_FEATURES, REQ_ATTACH_V2, REQ_DETACH,
+    REQ_INPUT_V2, REQ_PLAYER_OP, ShardResp, downgrade_req, encode_input_v2, input_seq,
+};
+use mudproto::shard_client;
 use password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
 use serde::{Deserialize, Serialize};
 use slopio::frame::{FrameReader, FrameWriter};
@@ -21,11 +28,21 @@ use tokio::net::{TcpListener, TcpStream, UnixStream};
 use tracing::{Level, info, warn};
 use zeroize::Zeroize;
 
+mod aliases;
 mod ban;
 mod email;
 mod eventlog;
+mod forget;
 mod hold;
+mod hotboot;
 mod nearline;
+mod pager;
+mod reports;
+mod shard_auth;
+mod shards;
+mod statsd;
+mod term;
+mod totp;
 
 const LOGIN_BACKOFF_BASE: Duration = Duration::from_secs(1);
 const LOGIN_BACKOFF_MAX: Duration = Duration::from_secs(30);
@@ -47,6 +64,9 @@ const REPORT_LAST_MAX: usize = 200;
 const REPORT_SEARCH_LIMIT: usize = 20;
 const REPORT_CONTEXT_LINES: usize = 3;
 const REPORT_NOTE_MAX_CHARS: usize = 500;
+const REPORT_MUTE_S: u64 = 24 * 60 * 60;
+const REPORTS_LIST_MAX: usize = 50;
+const SILENCE_MAX_S: u64 = 30 * 24 * 60 * 60;
 
 const REPORT_REASONS: &[(&str, &str)] = &[
     ("bullying", "Bullying / harassment"),
@@ -250,7 +270,7 @@ fn usage_and_exit() -> ! {
     eprintln!(
         "slopmud (session broker)\n\n\
 USAGE:\n  slopmud [--bind HOST:PORT] [--shard-addr HOST:PORT]\n\n\
-ENV:\n  SLOPMUD_BIND               default 0.0.0.0:4000\n  SHARD_ADDR                 default 127.0.0.1:5000\n  NODE_ID                    optional (for logs only)\n  SLOPMUD_ACCOUNTS_PATH       optional; default accounts.json (in WorkingDirectory)\n  SLOPMUD_LOCALE              optional; default en\n  SLOPMUD_ADMIN_BIND          optional; default 127.0.0.1:4011 (local admin JSON)\n  SLOPMUD_BANS_PATH           optional; default locks/bans.json\n  SBC_ADMIN_SOCK              optional; default /run/slopmud/sbc-admin.sock\n  SBC_EVENTS_SOCK             optional; default /run/slopmud/sbc-events.sock\n  SLOPMUD_EMAIL_MODE          optional; default disabled (disabled | ses | smtp | file)\n  SLOPMUD_EMAIL_FROM          required for ses/smtp; optional for file\n  SLOPMUD_SMTP_HOST           required for smtp\n  SLOPMUD_SMTP_PORT           optional; default 587\n  SLOPMUD_SMTP_USERNAME       optional\n  SLOPMUD_SMTP_PASSWORD       optional\n  SLOPMUD_EMAIL_FILE_DIR      optional; default /tmp/slopmud_email_outbox\n  SLOPMUD_EVENTLOG_ENABLED    optional; default 0\n  SLOPMUD_EVENTLOG_SPOOL_DIR  optional; default locks/eventlog\n  SLOPMUD_EVENTLOG_FLUSH_INTERVAL_S optional; default 60\n  SLOPMUD_EVENTLOG_S3_BUCKET  optional; if set, uploads target this bucket\n  SLOPMUD_EVENTLOG_S3_PREFIX  optional; default slopmud/eventlog\n  SLOPMUD_EVENTLOG_UPLOAD_ENABLED optional; default 0\n  SLOPMUD_EVENTLOG_UPLOAD_DELETE_LOCAL optional; default 1\n  SLOPMUD_EVENTLOG_UPLOAD_SCAN_INTERVAL_S optional; default 600\n  SLOPMUD_NEARLINE_ENABLED    optional; default 1\n  SLOPMUD_NEARLINE_DIR        optional; default locks/nearline_scrollback\n  SLOPMUD_NEARLINE_MAX_SEGMENTS optional; default 12\n  SLOPMUD_NEARLINE_SEGMENT_MAX_BYTES optional; default 2000000\n  SLOPMUD_GOOGLE_OAUTH_DIR    optional; default locks/google_oauth (shared with static_web)\n  SLOPMUD_GOOGLE_AUTH_BASE_URL optional; default http://127.0.0.1:8080 (where to open OAuth in browser)\n  SLOPMUD_OIDC_TOKEN_URL      optional; if set, mint a session token at login\n  SLOPMUD_OIDC_CLIENT_ID      required if token url set\n  SLOPMUD_OIDC_CLIENT_SECRET  required if token url set\n  SLOPMUD_OIDC_SCOPE          optional; default slopmud:session\n"
+ENV:\n  SLOPMUD_BIND               default 0.0.0.0:4000\n  SHARD_ADDR                 default 127.0.0.1:5000\n  SHARD_DIRECTORY            optional; area=host:port,... (areas not listed use SHARD_ADDR)\n  CHAT_ADDR                  optional; chatd host:port (channels are disabled if unset)\n  SLOPMUD_INPUT_QUEUE_MAX    optional; default 32 (commands held per session while its shard is down)\n  NODE_ID                    optional (for logs only)\n  SLOPMUD_ACCOUNTS_PATH       optional; default accounts.json (in WorkingDirectory)\n  SLOPMUD_MAX_CHARACTERS      optional; default 5 (characters per account; admins can override per account)\n  SLOPMUD_ACCOUNT_DELETE_GRACE_S optional; default 604800 (time to change your mind after account delete)\n  SLOPMUD_RENAME_COOLDOWN_S   optional; default 2592000 (minimum time between character renames per account)\n  SLOPMUD_FORGET_PATH         optional; default locks/forget.json (character deletions the shards have not confirmed)\n  SLOPMUD_HOTBOOT_PATH        optional; default locks/hotboot.json (SIGUSR2 re-execs the broker, keeping connections)\n  SLOPMUD_LOCALE              optional; default en\n  SLOPMUD_ADMIN_BIND          optional; default 127.0.0.1:4011 (local admin JSON)\n  SLOPMUD_BANS_PATH           optional; default locks/bans.json\n  SLOPMUD_REPORTS_PATH        optional; default locks/reports.json (abuse report queue)\n  SBC_ADMIN_SOCK              optional; default /run/slopmud/sbc-admin.sock\n  SBC_EVENTS_SOCK             optional; default /run/slopmud/sbc-events.sock\n  SBC_STATSD_ADDR             optional; sbc_metricsd statsd host:port (connects, login failures, account creations, reports per client IP)\n  SLOPMUD_SHARD_AUTH_KEY      kid:secret used to sign shard attach assertions (shards must list it in SHARD_AUTH_KEYS)\n  SLOPMUD_SHARD_AUTH_TTL_S    optional; default 300 (assertion lifetime)\n  SLOPMUD_EMAIL_MODE          optional; default disabled (disabled | ses | smtp | file)\n  SLOPMUD_EMAIL_FROM          required for ses/smtp; optional for file\n  SLOPMUD_SMTP_HOST           required for smtp\n  SLOPMUD_SMTP_PORT           optional; default 587\n  SLOPMUD_SMTP_USERNAME       optional\n  SLOPMUD_SMTP_PASSWORD       optional\n  SLOPMUD_EMAIL_FILE_DIR      optional; default /tmp/slopmud_email_outbox\n  SLOPMUD_EVENTLOG_ENABLED    optional; default 0\n  SLOPMUD_EVENTLOG_SPOOL_DIR  optional; default locks/eventlog\n  SLOPMUD_EVENTLOG_FLUSH_INTERVAL_S optional; default 60\n  SLOPMUD_EVENTLOG_S3_BUCKET  optional; if set, uploads target this bucket\n  SLOPMUD_EVENTLOG_S3_PREFIX  optional; default slopmud/eventlog\n  SLOPMUD_EVENTLOG_UPLOAD_ENABLED optional; default 0\n  SLOPMUD_EVENTLOG_UPLOAD_DELETE_LOCAL optional; default 1\n  SLOPMUD_EVENTLOG_UPLOAD_SCAN_INTERVAL_S optional; default 600\n  SLOPMUD_NEARLINE_ENABLED    optional; default 1\n  SLOPMUD_NEARLINE_DIR        optional; default locks/nearline_scrollback\n  SLOPMUD_NEARLINE_MAX_SEGMENTS optional; default 12\n  SLOPMUD_NEARLINE_SEGMENT_MAX_BYTES optional; default 2000000\n  SLOPMUD_GOOGLE_OAUTH_DIR    optional; default locks/google_oauth (shared with static_web)\n  SLOPMUD_GOOGLE_AUTH_BASE_URL optional; default http://127.0.0.1:8080 (where to open OAuth in browser)\n  SLOPMUD_OIDC_TOKEN_URL      optional; if set, mint a session token at login\n  SLOPMUD_OIDC_CLIENT_ID      required if token url set\n  SLOPMUD_OIDC_CLIENT_SECRET  required if token url set\n  SLOPMUD_OIDC_SCOPE          optional; default slopmud:session\n"
     );
     std::process::exit(2);
 }
@@ -259,11 +279,22 @@ ENV:\n  SLOPMUD_BIND               default 0.0.0.0:4000\n  SHARD_ADDR
 struct Config {
     bind: SocketAddr,
     shard_addr: SocketAddr,
+    shard_directory: shards::ShardDirectory,
+    chat_addr: Option<SocketAddr>,
+    // Unacked/queued input per session per shard connection.
+    input_queue_max: usize,
     node_id: Option<String>,
     // Accounts DB (stores only password hashes, never raw passwords).
     accounts_path: String,
     // Shared player snapshot file written by shard_01.
     players_path: PathBuf,
+    // Characters an account may own unless its record says otherwise.
+    max_characters: u32,
+    // How long a deleted account lingers (and can be restored) before it is purged.
+    account_delete_grace_s: u64,
+    rename_cooldown_s: u64,
+    // Deleted characters whose snapshots the shards may still hold (see `forget`).
+    forget_path: PathBuf,
     // Directory used for cross-process OAuth handoffs (static_web writes results here).
     google_oauth_dir: String,
     // Base URL for the user to open in a browser for OAuth (points at static_web).
@@ -282,12 +313,20 @@ struct Config {
 
     admin_bind: SocketAddr,
     bans_path: PathBuf,
+    reports_path: PathBuf,
     sbc_admin_sock: PathBuf,
     sbc_events_sock: PathBuf,
-    #[allow(dead_code)]
+    statsd_addr: Option<SocketAddr>,
+    // Signs the auth blob sent to shards on attach; shards ignore unsigned identities.
+    shard_auth_key: Option<AssertionKey>,
+    shard_auth_ttl_s: u64,
     email: email::EmailConfig,
     eventlog: eventlog::EventLogConfig,
     nearline: nearline::NearlineConfig,
+    // Where a hotboot leaves state for the next process.
+    hotboot_path: PathBuf,
+    // Set (via `--hotboot`) only in the process a hotboot exec'd.
+    hotboot_from: Option<PathBuf>,
 }
 
 fn parse_args() -> Config {
@@ -301,12 +340,52 @@ fn parse_args() -> Config {
         .parse()
         .unwrap_or_else(|_| usage_and_exit());
 
+    let chat_addr: Option<SocketAddr> = std::env::var("CHAT_ADDR")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| v.trim().parse().unwrap_or_else(|_| usage_and_exit()));
+
+    let statsd_addr: Option<SocketAddr> = std::env::var("SBC_STATSD_ADDR")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| v.trim().parse().unwrap_or_else(|_| usage_and_exit()));
+
+    let shard_auth_key: Option<AssertionKey> = std::env::var("SLOPMUD_SHARD_AUTH_KEY")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| {
+            AssertionKey::parse(&v).unwrap_or_else(|e| {
+                eprintln!("SLOPMUD_SHARD_AUTH_KEY: {e}");
+                usage_and_exit()
+            })
+        });
+    let shard_auth_ttl_s: u64 = std::env::var("SLOPMUD_SHARD_AUTH_TTL_S")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(300);
+
     let node_id = std::env::var("NODE_ID").ok();
     let accounts_path =
         std::env::var("SLOPMUD_ACCOUNTS_PATH").unwrap_or_else(|_| "accounts.json".to_string());
     let players_path: PathBuf = std::env::var("SLOPMUD_PLAYERS_PATH")
         .unwrap_or_else(|_| "var/shard_01_players.json".to_string())
         .into();
+    let forget_path: PathBuf = std::env::var("SLOPMUD_FORGET_PATH")
+        .unwrap_or_else(|_| "locks/forget.json".to_string())
+        .into();
+    let max_characters: u32 = std::env::var("SLOPMUD_MAX_CHARACTERS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5)
+        .max(1);
+    let account_delete_grace_s: u64 = std::env::var("SLOPMUD_ACCOUNT_DELETE_GRACE_S")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(7 * 24 * 3600);
+    let rename_cooldown_s: u64 = std::env::var("SLOPMUD_RENAME_COOLDOWN_S")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(30 * 24 * 3600);
     let google_oauth_dir = std::env::var("SLOPMUD_GOOGLE_OAUTH_DIR")
         .unwrap_or_else(|_| "locks/google_oauth".to_string());
     let google_auth_base_url = std::env::var("SLOPMUD_GOOGLE_AUTH_BASE_URL")
@@ -325,6 +404,9 @@ fn parse_args() -> Config {
     let bans_path: PathBuf = std::env::var("SLOPMUD_BANS_PATH")
         .unwrap_or_else(|_| "locks/bans.json".to_string())
         .into();
+    let reports_path: PathBuf = std::env::var("SLOPMUD_REPORTS_PATH")
+        .unwrap_or_else(|_| "locks/reports.json".to_string())
+        .into();
 
     let sbc_admin_sock: PathBuf = std::env::var("SBC_ADMIN_SOCK")
         .unwrap_or_else(|_| "/run/slopmud/sbc-admin.sock".to_string())
@@ -399,9 +481,25 @@ fn parse_args() -> Config {
         .and_then(|v| v.parse().ok())
         .unwrap_or(nearline.segment_max_bytes);
 
+    let input_queue_max: usize = std::env::var("SLOPMUD_INPUT_QUEUE_MAX")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(32);
+
+    let hotboot_path: PathBuf = std::env::var("SLOPMUD_HOTBOOT_PATH")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| "locks/hotboot.json".to_string())
+        .into();
+    let mut hotboot_from: Option<PathBuf> = None;
+
     let mut it = std::env::args().skip(1);
     while let Some(arg) = it.next() {
         match arg.as_str() {
+            hotboot::ARG => {
+                let v = it.next().unwrap_or_else(|| usage_and_exit());
+                hotboot_from = Some(v.into());
+            }
             "--bind" => {
                 let v = it.next().unwrap_or_else(|| usage_and_exit());
                 bind = v.parse().unwrap_or_else(|_| usage_and_exit());
@@ -415,12 +513,31 @@ fn parse_args() -> Config {
         }
     }
 
+    let shard_directory = shards::ShardDirectory::parse(
+        shard_addr,
+        &std::env::var("SHARD_DIRECTORY").unwrap_or_default(),
+    )
+    .unwrap_or_else(|e| {
+        eprintln!("{e}");
+        usage_and_exit()
+    });
+
     Config {
         bind,
         shard_addr,
+        shard_directory,
+        chat_addr,
+        input_queue_max,
+        statsd_addr,
+        shard_auth_key,
+        shard_auth_ttl_s,
         node_id,
         accounts_path,
         players_path,
+        max_characters,
+        account_delete_grace_s,
+        rename_cooldown_s,
+        forget_path,
         google_oauth_dir,
         google_auth_base_url,
         oidc_token_url,
@@ -430,11 +547,14 @@ fn parse_args() -> Config {
         locale,
         admin_bind,
         bans_path,
+        reports_path,
         sbc_admin_sock,
         sbc_events_sock,
         email,
         eventlog,
         nearline,
+        hotboot_path,
+        hotboot_from,
     }
 }
 
@@ -674,7 +794,8 @@ impl Scrollback {
 }
 
 fn extract_output_lines(b: &[u8]) -> Vec<String> {
-    let s = escape_log_text(b);
+    // Scrollback, nearline and logs keep plain text; color markup is a render-time concern.
+    let s = escape_log_text(&color::strip(b));
     let mut out = Vec::new();
     for raw in s.split('\n') {
         let line = raw.trim_end_matches('\r').trim();
@@ -708,6 +829,24 @@ fn fmt_hhmmss(ts_unix_ms: u64) -> String {
     dt.format("%H:%M:%S").to_string()
 }
 
+fn fmt_unix(ts_unix: u64) -> String {
+    let dt = Utc
+        .timestamp_opt(i64::try_from(ts_unix).unwrap_or(0), 0)
+        .single()
+        .unwrap_or_default();
+    dt.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+/// Coarse, for player-facing notices ("7d", "12h").
+fn fmt_duration_s(secs: u64) -> String {
+    match secs {
+        s if s >= 86_400 => format!("{}d", s / 86_400),
+        s if s >= 3_600 => format!("{}h", s / 3_600),
+        s if s >= 60 => format!("{}m", s / 60),
+        s => format!("{s}s"),
+    }
+}
+
 fn report_usage_text() -> String {
     let mut s = String::new();
     s.push_str("report:\r\n");
@@ -782,15 +921,21 @@ fn clamp_chars(s: &str, max: usize) -> String {
 }
 
 async fn handle_report_command(
-    sessions: &Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
-    holds: &Arc<tokio::sync::Mutex<hold::HoldCache>>,
-    nearline: &Arc<nearline::NearlineRing>,
-    eventlog: &Arc<eventlog::EventLog>,
+    shared: &Shared,
     session: SessionId,
     peer_ip: IpAddr,
     name: &str,
     line: &str,
 ) -> String {
+    let Shared {
+        sessions,
+        holds,
+        reports,
+        nearline,
+        eventlog,
+        statsd,
+        ..
+    } = shared;
     let scrollback = {
         let si = { sessions.lock().await.get(&session).cloned() };
         match si {
@@ -992,6 +1137,10 @@ async fn handle_report_command(
                 return s;
             };
             let note = clamp_chars(&it.collect::<Vec<_>>().join(" "), REPORT_NOTE_MAX_CHARS);
+            let now_unix = u64::try_from(Utc::now().timestamp()).unwrap_or(0);
+            if let Err(e) = reports.lock().await.admit(name, &id_str, now_unix) {
+                return format!("report submit: {e}\r\n\r\n> ");
+            }
 
             let mut source = "hot";
             let target_ts_unix_ms: u64;
@@ -1094,10 +1243,51 @@ async fn handle_report_command(
             if !name.trim().is_empty() {
                 eventlog.log_line(LogStream::Character(name), &entry).await;
             }
+            statsd.incr(statsd::REPORTS, peer_ip);
+
+            let queued = reports.lock().await.add(reports::Report {
+                no: 0,
+                id: report_id.clone(),
+                created_unix: u64::try_from(now.timestamp()).unwrap_or(0),
+                reporter: name.to_string(),
+                line_id: id_str.clone(),
+                line_ts_unix_ms: target_ts_unix_ms,
+                text: target_text_log.clone(),
+                redacted: held,
+                reason: reason.to_string(),
+                note: note_log.clone(),
+                context: context_lines
+                    .iter()
+                    .map(|(cid, cts_ms, ctext)| reports::ContextLine {
+                        id: cid.clone(),
+                        ts_unix_ms: *cts_ms,
+                        text: if held {
+                            redact_pii(ctext)
+                        } else {
+                            ctext.clone()
+                        },
+                    })
+                    .collect(),
+                assignee: None,
+                resolution: None,
+                notify_pending: false,
+            });
+            let report_no = match queued {
+                Ok(no) => Some(no),
+                Err(e) => {
+                    warn!(report_id = %report_id, err = %e, "failed to queue abuse report");
+                    None
+                }
+            };
 
             let mut s = String::new();
             s.push_str("report submitted:\r\n");
             s.push_str(&format!(" - report_id: {report_id}\r\n"));
+            if let Some(no) = report_no {
+                s.push_str(&format!(
+                    " - report: #{no} (you'll hear back once a moderator looks)\r\n"
+                ));
+            }
             s.push_str(&format!(
                 " - line: [{id} {}] {}\r\n",
                 fmt_hhmmss(target_ts_unix_ms),
@@ -1114,185 +1304,1224 @@ async fn handle_report_command(
     }
 }
 
-fn account_usage_text() -> String {
+fn reports_usage_text() -> String {
     let mut s = String::new();
-    s.push_str("account:\r\n");
-    s.push_str("use:\r\n");
-    s.push_str(" - account email\r\n");
-    s.push_str(" - account email set <addr>\r\n");
-    s.push_str(" - account email clear\r\n");
+    s.push_str("reports:\r\n");
+    s.push_str(" - reports [list] [all]\r\n");
+    s.push_str(" - reports show <n>\r\n");
+    s.push_str(" - reports assign <n> [name|none]\r\n");
+    s.push_str(" - reports resolve <n> dismiss [note...]\r\n");
+    s.push_str(" - reports resolve <n> <warn|mute|ban> <player> [note...]\r\n");
+    s.push_str("\r\n");
+    s.push_str(&format!(
+        "notes:\r\n - mute lasts {}; ban is a character ban\r\n",
+        fmt_duration_s(REPORT_MUTE_S)
+    ));
+    s.push_str(" - the reporter is told whether action was taken, not what\r\n");
     s.push_str("\r\n> ");
     s
 }
 
-fn accounthold_usage_text() -> String {
-    let mut s = String::new();
-    s.push_str("accounthold:\r\n");
-    s.push_str("use:\r\n");
-    s.push_str(" - accounthold list\r\n");
-    s.push_str(" - accounthold show <name>\r\n");
-    s.push_str(" - accounthold add <name> [reason...]\r\n");
-    s.push_str(" - accounthold del <name>\r\n");
-    s.push_str("\r\n> ");
+/// Moderator commands check one `mod.*` cap; `admin.all` has them all.
+async fn account_has_mod_cap(
+    accounts: &Arc<tokio::sync::Mutex<Accounts>>,
+    account: &str,
+    cap: &str,
+) -> bool {
+    let a = accounts.lock().await;
+    a.by_name.get(account).is_some_and(|r| {
+        r.caps
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .any(|c| c == "admin.all" || c == cap)
+    })
+}
+
+fn report_summary_line(r: &reports::Report) -> String {
+    let mut s = format!(
+        " #{no} [{ts}] {reason} from {reporter}: {text}",
+        no = r.no,
+        ts = fmt_hhmmss(r.line_ts_unix_ms),
+        reason = r.reason,
+        reporter = r.reporter,
+        text = clamp_chars(&r.text, 80),
+    );
+    if let Some(res) = r.resolution.as_ref() {
+        s.push_str(&format!(" ({} by {})", res.action.as_str(), res.by));
+    } else if let Some(a) = r.assignee.as_deref() {
+        s.push_str(&format!(" (assigned: {a})"));
+    }
+    s.push_str("\r\n");
     s
 }
 
-async fn sbc_send_admin_req(
-    sock: &PathBuf,
-    req: &sbc_core::AdminReq,
-) -> anyhow::Result<sbc_core::AdminResp> {
-    let mut stream = UnixStream::connect(sock)
+/// The report with the reporter's scrollback around the line, read fresh from nearline when it's
+/// still there. Reports from a held reporter keep the redacted copy taken at submit time.
+async fn report_with_context(
+    nearline: &nearline::NearlineRing,
+    mut r: reports::Report,
+) -> reports::Report {
+    if r.redacted {
+        return r;
+    }
+    if let Some((_, context)) = nearline
+        .find_with_context(&r.reporter, &r.line_id, REPORT_CONTEXT_LINES)
         .await
-        .map_err(|e| anyhow::anyhow!("connect sbc admin sock {}: {e}", sock.display()))?;
-    stream
-        .write_all(serde_json::to_string(req)?.as_bytes())
-        .await?;
-    stream.write_all(b"\n").await?;
-    let (rd, _) = stream.into_split();
-    let mut rd = BufReader::new(rd);
-    let mut line = String::new();
-    rd.read_line(&mut line).await?;
-    let raw = line.trim();
-    if raw.is_empty() {
-        return Err(anyhow::anyhow!("empty sbc admin response"));
+    {
+        r.context = context
+            .into_iter()
+            .map(|l| reports::ContextLine {
+                id: l.id,
+                ts_unix_ms: l.ts_unix_ms,
+                text: l.text,
+            })
+            .collect();
     }
-    Ok(serde_json::from_str(raw)?)
+    r
 }
 
-async fn sbc_holds_events_task(
-    events_sock: PathBuf,
-    holds: Arc<tokio::sync::Mutex<hold::HoldCache>>,
-    sessions: Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
-) {
-    let sub = sbc_core::EventsReq::Subscribe {
-        mode: sbc_core::SubscribeMode::Snapshot,
-    };
-
-    loop {
-        let mut stream = match UnixStream::connect(&events_sock).await {
-            Ok(s) => s,
-            Err(e) => {
-                warn!(err=%e, path=%events_sock.display(), "sbc events connect failed");
-                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-                continue;
-            }
-        };
-
-        if let Err(e) = stream
-            .write_all(serde_json::to_string(&sub).unwrap().as_bytes())
-            .await
-        {
-            warn!(err=%e, "failed to subscribe to sbc events");
-            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-            continue;
+fn render_report(r: &reports::Report) -> String {
+    let mut s = String::new();
+    s.push_str(&format!("report #{}:\r\n", r.no));
+    s.push_str(&format!(" - report_id: {}\r\n", r.id));
+    s.push_str(&format!(" - reporter: {}\r\n", r.reporter));
+    s.push_str(&format!(" - reason: {}\r\n", r.reason));
+    if !r.note.is_empty() {
+        s.push_str(&format!(" - note: {}\r\n", r.note));
+    }
+    s.push_str(&format!(
+        " - line: [{} {}] {}\r\n",
+        r.line_id,
+        fmt_hhmmss(r.line_ts_unix_ms),
+        r.text
+    ));
+    if let Some(a) = r.assignee.as_deref() {
+        s.push_str(&format!(" - assigned: {a}\r\n"));
+    }
+    if let Some(res) = r.resolution.as_ref() {
+        s.push_str(&format!(
+            " - resolved: {} by {}",
+            res.action.as_str(),
+            res.by
+        ));
+        if let Some(t) = res.target.as_deref() {
+            s.push_str(&format!(" (player: {t})"));
         }
-        let _ = stream.write_all(b"\n").await;
-
-        let (rd, _) = stream.into_split();
-        let mut rd = BufReader::new(rd);
-        let mut line = String::new();
-
-        loop {
-            line.clear();
-            match rd.read_line(&mut line).await {
-                Ok(0) => break,
-                Ok(_) => {}
-                Err(e) => {
-                    warn!(err=%e, "sbc events read failed");
-                    break;
-                }
-            }
-            let raw = line.trim();
-            if raw.is_empty() {
-                continue;
-            }
-            let env: sbc_core::EventEnvelope = match serde_json::from_str(raw) {
-                Ok(v) => v,
-                Err(e) => {
-                    warn!(err=%e, line=%raw, "bad sbc event json");
-                    continue;
-                }
-            };
-
-            match env.event {
-                sbc_core::Event::Snapshot { holds: h, .. } => {
-                    let held_names = h
-                        .iter()
-                        .map(|e| e.name_lc.trim().to_ascii_lowercase())
-                        .filter(|k| !k.is_empty())
-                        .collect::<std::collections::HashSet<_>>();
-
-                    holds.lock().await.apply_snapshot(env.index, h);
-
-                    let mut m = sessions.lock().await;
-                    for si in m.values_mut() {
-                        let k = si.name.trim().to_ascii_lowercase();
-                        si.held = held_names.contains(&k);
-                    }
-                }
-                sbc_core::Event::LegalHoldUpserted { entry } => {
-                    let name_lc = entry.name_lc.trim().to_ascii_lowercase();
-                    holds.lock().await.apply_upsert(env.index, entry);
-
-                    if !name_lc.is_empty() {
-                        let mut m = sessions.lock().await;
-                        for si in m.values_mut() {
-                            if si.name.trim().to_ascii_lowercase() == name_lc {
-                                si.held = true;
-                            }
-                        }
-                    }
-                }
-                sbc_core::Event::LegalHoldDeleted { name_lc } => {
-                    let name_lc = name_lc.trim().to_ascii_lowercase();
-                    holds.lock().await.apply_delete(env.index, &name_lc);
-
-                    if !name_lc.is_empty() {
-                        let mut m = sessions.lock().await;
-                        for si in m.values_mut() {
-                            if si.name.trim().to_ascii_lowercase() == name_lc {
-                                si.held = false;
-                            }
-                        }
-                    }
-                }
-                _ => {}
-            }
+        if !res.note.is_empty() {
+            s.push_str(&format!(": {}", res.note));
+        }
+        s.push_str("\r\n");
+    }
+    if !r.context.is_empty() {
+        s.push_str("context:\r\n");
+        for l in &r.context {
+            let mark = if l.id == r.line_id { '>' } else { ' ' };
+            s.push_str(&format!(
+                "{mark}[{} {}] {}\r\n",
+                l.id,
+                fmt_hhmmss(l.ts_unix_ms),
+                l.text
+            ));
         }
+    }
+    s.push_str("\r\n> ");
+    s
+}
 
-        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+/// What the reporter hears once their report is closed.
+fn report_notice_text(r: &reports::Report) -> String {
+    let taken = r
+        .resolution
+        .as_ref()
+        .is_some_and(|res| res.action != reports::Action::Dismiss);
+    if taken {
+        format!(
+            "\r\n# your report #{} was reviewed and action was taken. thanks for reporting.\r\n",
+            r.no
+        )
+    } else {
+        format!(
+            "\r\n# your report #{} was reviewed; no action was taken.\r\n",
+            r.no
+        )
     }
 }
 
-async fn handle_accounthold_command(
+/// Send `msg` to every session playing `name`; returns how many got it.
+async fn write_to_char(
     sessions: &Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
-    holds: &Arc<tokio::sync::Mutex<hold::HoldCache>>,
-    sbc_admin_sock: &PathBuf,
-    eventlog: &Arc<eventlog::EventLog>,
-    peer_ip: IpAddr,
-    session: SessionId,
-    actor: &str,
-    line: &str,
-) -> String {
-    if !peer_ip.is_loopback() {
-        return "accounthold: permission denied\r\n\r\n> ".to_string();
+    name: &str,
+    msg: Bytes,
+) -> u64 {
+    let targets = {
+        let m = sessions.lock().await;
+        m.values()
+            .filter(|s| s.name.eq_ignore_ascii_case(name.trim()))
+            .map(|s| s.write_tx.clone())
+            .collect::<Vec<_>>()
+    };
+    for tx in &targets {
+        let _ = tx.send(msg.clone()).await;
     }
+    targets.len() as u64
+}
 
-    let mut it = line.split_whitespace();
-    let _ = it.next(); // "accounthold"
-    let sub = it.next().unwrap_or("").to_ascii_lowercase();
+/// Tell `name` how any of their reports closed while they were away.
+async fn deliver_report_notices(
+    reports: &Arc<tokio::sync::Mutex<reports::ReportQueue>>,
+    write_tx: &WriteTx,
+    name: &str,
+) {
+    let mut q = reports.lock().await;
+    for r in q.pending_notices(name) {
+        let _ = write_tx.send(Bytes::from(report_notice_text(&r))).await;
+        if let Err(e) = q.mark_notified(r.no) {
+            warn!(report_id = %r.id, err = %e, "failed to save report notice");
+        }
+    }
+}
+
+async fn assign_report(
+    reports: &Arc<tokio::sync::Mutex<reports::ReportQueue>>,
+    eventlog: &eventlog::EventLog,
+    by: &str,
+    key: &str,
+    assignee: Option<String>,
+) -> anyhow::Result<reports::Report> {
+    let r = reports.lock().await.assign(key, assignee)?;
+    let entry = format!(
+        "ts={} kind=report_assigned report_id={} report_no={} by={} assignee={}",
+        logfmt_str(&Utc::now().to_rfc3339()),
+        logfmt_str(&r.id),
+        logfmt_str(&r.no.to_string()),
+        logfmt_str(by),
+        logfmt_str(r.assignee.as_deref().unwrap_or("")),
+    );
+    eventlog.log_line(LogStream::Reports, &entry).await;
+    eventlog.log_line(LogStream::All, &entry).await;
+    Ok(r)
+}
 
+/// Close a report: apply the action to `target`, record who did it and let the reporter know.
+/// Returns the report and how many sessions a ban kicked.
+async fn resolve_report(
+    shared: &Shared,
+    by: &str,
+    key: &str,
+    action: reports::Action,
+    target: Option<&str>,
+    note: &str,
+) -> anyhow::Result<(reports::Report, u64)> {
+    let Shared {
+        reports,
+        bans,
+        sessions,
+        eventlog,
+        ..
+    } = shared;
+    let target = match (action, target.map(sanitize_name)) {
+        (reports::Action::Dismiss, _) => None,
+        (_, Some(t)) if !t.is_empty() => Some(t),
+        _ => anyhow::bail!("{} needs a player name", action.as_str()),
+    };
+    // Claimed before acting, so a second moderator can't apply another action to the same report.
+    let no = reports.lock().await.claim(key)?;
+    let note = clamp_chars(note, REPORT_NOTE_MAX_CHARS);
     let now_unix = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default()
         .as_secs();
+    let reason = if note.is_empty() {
+        format!("report #{no}")
+    } else {
+        format!("report #{no}: {note}")
+    };
 
-    match sub.as_str() {
-        "" | "help" => accounthold_usage_text(),
-        "list" => {
-            let entries = { holds.lock().await.snapshot() };
-            let mut s = String::new();
-            s.push_str("accounthold list:\r\n");
+    let applied = async {
+        let mut kicked = 0;
+        match (action, target.as_deref()) {
+            (reports::Action::Warn, Some(t)) => {
+                let msg = if note.is_empty() {
+                    "please keep to the rules (see `rules`)".to_string()
+                } else {
+                    note.clone()
+                };
+                let msg = format!("\r\n# moderator warning: {msg}\r\n");
+                write_to_char(sessions, t, Bytes::from(msg)).await;
+            }
+            (reports::Action::Mute, Some(t)) => {
+                set_char_mute(shared, t, now_unix + REPORT_MUTE_S, now_unix, by, reason).await?;
+                let msg = format!(
+                    "\r\n# a moderator has muted you for {}.\r\n",
+                    fmt_duration_s(REPORT_MUTE_S)
+                );
+                write_to_char(sessions, t, Bytes::from(msg)).await;
+            }
+            (reports::Action::Ban, Some(t)) => {
+                bans.lock()
+                    .await
+                    .upsert_char_ban(t, now_unix, by.to_string(), reason)?;
+                kicked = kick_by_char(sessions, t).await;
+            }
+            _ => {}
+        }
+        anyhow::Ok(kicked)
+    };
+    let kicked = match applied.await {
+        Ok(kicked) => kicked,
+        Err(e) => {
+            reports.lock().await.release(no);
+            return Err(e);
+        }
+    };
+
+    let resolved = reports.lock().await.resolve(
+        key,
+        reports::Resolution {
+            by: by.to_string(),
+            unix: now_unix,
+            action,
+            target: target.clone(),
+            note: note.clone(),
+        },
+    );
+    let r = match resolved {
+        Ok(r) => r,
+        Err(e) => {
+            reports.lock().await.release(no);
+            return Err(e);
+        }
+    };
+
+    let entry = format!(
+        "ts={} kind=report_resolved report_id={} report_no={} by={} action={} target={} note={}",
+        logfmt_str(&Utc::now().to_rfc3339()),
+        logfmt_str(&r.id),
+        logfmt_str(&r.no.to_string()),
+        logfmt_str(by),
+        logfmt_str(action.as_str()),
+        logfmt_str(target.as_deref().unwrap_or("")),
+        logfmt_str(&note),
+    );
+    eventlog.log_line(LogStream::Reports, &entry).await;
+    eventlog.log_line(LogStream::All, &entry).await;
+    if let Some(t) = target.as_deref() {
+        eventlog.log_line(LogStream::Character(t), &entry).await;
+    }
+
+    if write_to_char(sessions, &r.reporter, Bytes::from(report_notice_text(&r))).await > 0 {
+        reports.lock().await.mark_notified(r.no)?;
+    }
+    Ok((r, kicked))
+}
+
+async fn handle_reports_command(shared: &Shared, account: &str, name: &str, line: &str) -> String {
+    let Shared {
+        accounts,
+        reports,
+        nearline,
+        eventlog,
+        ..
+    } = shared;
+    if !account_has_mod_cap(accounts, account, "mod.reports").await {
+        return "nope: mod.reports\r\n\r\n> ".to_string();
+    }
+
+    let mut it = line.split_whitespace();
+    let _ = it.next(); // "reports"
+    let sub = it.next().unwrap_or("list").to_ascii_lowercase();
+
+    match sub.as_str() {
+        "list" | "all" => {
+            let all = sub == "all" || it.next().is_some_and(|w| w.eq_ignore_ascii_case("all"));
+            let q = reports.lock().await;
+            let list = q.list(all);
+            let mut s = String::new();
+            s.push_str(&format!(
+                "reports ({}):\r\n",
+                if all { "all" } else { "open" }
+            ));
+            let start = list.len().saturating_sub(REPORTS_LIST_MAX);
+            for r in &list[start..] {
+                s.push_str(&report_summary_line(r));
+            }
+            if list.is_empty() {
+                s.push_str(" (none)\r\n");
+            }
+            s.push_str("\r\n> ");
+            s
+        }
+        "show" => {
+            let Some(key) = it.next() else {
+                return "reports show: missing report number\r\n\r\n> ".to_string();
+            };
+            let r = reports.lock().await.get(key).cloned();
+            match r {
+                Some(r) => render_report(&report_with_context(nearline, r).await),
+                None => format!("reports: no report {key}\r\n\r\n> "),
+            }
+        }
+        "assign" => {
+            let Some(key) = it.next() else {
+                return "reports assign: missing report number\r\n\r\n> ".to_string();
+            };
+            let assignee = match it.next() {
+                None => Some(name.to_string()),
+                Some(w) if w.eq_ignore_ascii_case("none") => None,
+                Some(w) => Some(sanitize_name(w)).filter(|n| !n.is_empty()),
+            };
+            match assign_report(reports, eventlog, name, key, assignee).await {
+                Ok(r) => format!(
+                    "report #{} assigned to {}\r\n\r\n> ",
+                    r.no,
+                    r.assignee.as_deref().unwrap_or("nobody")
+                ),
+                Err(e) => format!("reports assign: {e}\r\n\r\n> "),
+            }
+        }
+        "resolve" => {
+            let (Some(key), Some(action)) = (it.next(), it.next()) else {
+                return reports_usage_text();
+            };
+            let Some(action) = reports::Action::parse(action) else {
+                return "reports resolve: action is one of dismiss, warn, mute, ban\r\n\r\n> "
+                    .to_string();
+            };
+            let target = match action {
+                reports::Action::Dismiss => None,
+                _ => it.next(),
+            };
+            let note = it.collect::<Vec<_>>().join(" ");
+            match resolve_report(shared, name, key, action, target, &note).await {
+                Ok((r, kicked)) => {
+                    let mut s = format!("report #{} resolved: {}", r.no, action.as_str());
+                    if let Some(t) = r.resolution.as_ref().and_then(|res| res.target.as_deref()) {
+                        s.push_str(&format!(" {t}"));
+                    }
+                    if kicked > 0 {
+                        s.push_str(&format!(" (kicked {kicked})"));
+                    }
+                    s.push_str("\r\n\r\n> ");
+                    s
+                }
+                Err(e) => format!("reports resolve: {e}\r\n\r\n> "),
+            }
+        }
+        _ => reports_usage_text(),
+    }
+}
+
+fn silence_usage_text() -> String {
+    let mut s = String::new();
+    s.push_str("silence:\r\n");
+    s.push_str(" - silence (who is silenced)\r\n");
+    s.push_str(" - silence <player> <duration> <reason...>\r\n");
+    s.push_str(" - silence <player> off [reason...]\r\n");
+    s.push_str("\r\n");
+    s.push_str(&format!(
+        "notes:\r\n - duration like 30m, 2h, 7d (at most {})\r\n",
+        fmt_duration_s(SILENCE_MAX_S)
+    ));
+    s.push_str(" - a silenced player can't say, shout, tell, emote or post to channels\r\n");
+    s.push_str("\r\n> ");
+    s
+}
+
+/// `90s`, `30m`, `2h`, `7d`; a bare number is minutes.
+fn parse_duration_s(s: &str) -> Option<u64> {
+    let s = s.trim().to_ascii_lowercase();
+    let (n, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s.as_str(), "m"),
+    };
+    let n = n.parse::<u64>().ok().filter(|n| *n > 0)?;
+    let mult = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        _ => return None,
+    };
+    n.checked_mul(mult)
+}
+
+/// Mute `name` until `until_unix` (`now_unix` lifts it) and tell the shards, which refuse the
+/// character's talk commands until then.
+async fn set_char_mute(
+    shared: &Shared,
+    name: &str,
+    until_unix: u64,
+    now_unix: u64,
+    by: &str,
+    reason: String,
+) -> anyhow::Result<()> {
+    let Shared {
+        bans,
+        shard_tx,
+        shard_signer,
+        ..
+    } = shared;
+    bans.lock()
+        .await
+        .upsert_char_mute(name, until_unix, now_unix, by.to_string(), reason)?;
+    send_player_op(shard_tx, shard_signer, PlayerOp::Mute { name, until_unix }).await;
+    Ok(())
+}
+
+/// Silence `target` for `duration_s` (0 lifts it), log it and let them know. Returns the mute's
+/// end.
+async fn silence_char(
+    shared: &Shared,
+    by: &str,
+    target: &str,
+    duration_s: u64,
+    reason: &str,
+) -> anyhow::Result<u64> {
+    let Shared {
+        sessions, eventlog, ..
+    } = shared;
+    let now_unix = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let duration_s = duration_s.min(SILENCE_MAX_S);
+    let until_unix = now_unix + duration_s;
+    let reason = clamp_chars(reason, 200);
+    set_char_mute(shared, target, until_unix, now_unix, by, reason.clone()).await?;
+
+    let entry = format!(
+        "ts={} kind=silence action={} by={} target={} until_unix={} reason={}",
+        logfmt_str(&Utc::now().to_rfc3339()),
+        if duration_s == 0 { "lift" } else { "set" },
+        logfmt_str(by),
+        logfmt_str(target),
+        logfmt_str(&until_unix.to_string()),
+        logfmt_str(&reason),
+    );
+    eventlog.log_line(LogStream::All, &entry).await;
+    eventlog
+        .log_line(LogStream::Character(target), &entry)
+        .await;
+    if !by.trim().is_empty() && !by.eq_ignore_ascii_case(target) {
+        eventlog.log_line(LogStream::Character(by), &entry).await;
+    }
+
+    let msg = if duration_s == 0 {
+        "\r\n# you can talk again.\r\n".to_string()
+    } else if reason.is_empty() {
+        format!(
+            "\r\n# a moderator has silenced you for {}.\r\n",
+            fmt_duration_s(duration_s)
+        )
+    } else {
+        format!(
+            "\r\n# a moderator has silenced you for {}: {reason}\r\n",
+            fmt_duration_s(duration_s)
+        )
+    };
+    write_to_char(sessions, target, Bytes::from(msg)).await;
+    Ok(until_unix)
+}
+
+async fn handle_silence_command(shared: &Shared, account: &str, name: &str, line: &str) -> String {
+    let Shared { accounts, bans, .. } = shared;
+    if !account_has_mod_cap(accounts, account, "mod.silence").await {
+        return "nope: mod.silence\r\n\r\n> ".to_string();
+    }
+
+    let mut it = line.split_whitespace();
+    let _ = it.next(); // "silence"
+    let Some(target) = it.next() else {
+        let now_unix = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mutes = bans.lock().await.snapshot_file().character_mutes;
+        let mut s = String::new();
+        s.push_str("silenced:\r\n");
+        let mut any = false;
+        for m in mutes.iter().filter(|m| m.until_unix > now_unix) {
+            any = true;
+            s.push_str(&format!(
+                " - {} ({} left, by {}): {}\r\n",
+                m.name_lc,
+                fmt_duration_s(m.until_unix - now_unix),
+                m.created_by,
+                m.reason
+            ));
+        }
+        if !any {
+            s.push_str(" - nobody\r\n");
+        }
+        s.push_str("\r\n> ");
+        return s;
+    };
+    let target = sanitize_name(target);
+    if target.is_empty() {
+        return "silence: bad name\r\n\r\n> ".to_string();
+    }
+    let Some(dur) = it.next() else {
+        return silence_usage_text();
+    };
+    let duration_s = if dur.eq_ignore_ascii_case("off") {
+        0
+    } else {
+        match parse_duration_s(dur) {
+            Some(d) => d,
+            None => return format!("silence: bad duration {dur}\r\n\r\n> "),
+        }
+    };
+    let reason = it.collect::<Vec<_>>().join(" ");
+    if duration_s > 0 && reason.trim().is_empty() {
+        return "silence: give a reason (it goes in the audit log)\r\n\r\n> ".to_string();
+    }
+
+    match silence_char(shared, name, &target, duration_s, &reason).await {
+        Ok(_) if duration_s == 0 => format!("ok: {target} can talk again\r\n\r\n> "),
+        Ok(until) => format!(
+            "ok: {target} silenced for {} (until {})\r\n\r\n> ",
+            fmt_duration_s(duration_s.min(SILENCE_MAX_S)),
+            fmt_unix(until)
+        ),
+        Err(e) => format!("silence: {e}\r\n\r\n> "),
+    }
+}
+
+fn account_usage_text() -> String {
+    let mut s = String::new();
+    s.push_str("account:\r\n");
+    s.push_str("use:\r\n");
+    s.push_str(" - account email\r\n");
+    s.push_str(" - account email set <addr>\r\n");
+    s.push_str(" - account email verify <code>\r\n");
+    s.push_str(" - account email resend\r\n");
+    s.push_str(" - account email clear\r\n");
+    s.push_str(" - account 2fa\r\n");
+    s.push_str(" - account 2fa enable\r\n");
+    s.push_str(" - account 2fa verify <code>\r\n");
+    s.push_str(" - account 2fa recovery <code>\r\n");
+    s.push_str(" - account 2fa disable <code>\r\n");
+    s.push_str(" - account export\r\n");
+    s.push_str(" - account export email\r\n");
+    s.push_str(" - account delete\r\n");
+    s.push_str(" - account delete confirm\r\n");
+    s.push_str(" - account delete cancel\r\n");
+    s.push_str("\r\n> ");
+    s
+}
+
+const EXPORT_FORMAT: &str = "slopmud-account-export/1";
+// Per character; older lines may already have rotated out of the nearline ring.
+const EXPORT_SCROLLBACK_LINES: usize = 1000;
+
+/// Everything we keep about an account that its owner may take with them: the account record
+/// (minus secrets), each character's saved snapshot, and recent scrollback.
+async fn account_export_json(
+    cfg: &Config,
+    accounts: &Arc<tokio::sync::Mutex<Accounts>>,
+    nearline: &nearline::NearlineRing,
+    name: &str,
+) -> Option<String> {
+    let (rec, characters) = {
+        let a = accounts.lock().await;
+        let characters = a.characters(name);
+        (a.by_name.get(name).cloned()?, characters)
+    };
+
+    let snapshots = std::fs::read_to_string(&cfg.players_path)
+        .ok()
+        .and_then(|raw| serde_json::from_str::<Vec<serde_json::Value>>(&raw).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|p| {
+            p.get("name")
+                .and_then(|n| n.as_str())
+                .is_some_and(|n| characters.iter().any(|c| c.eq_ignore_ascii_case(n)))
+        })
+        .collect::<Vec<_>>();
+
+    let mut scrollback = serde_json::Map::new();
+    for c in &characters {
+        let mut lines = nearline.last_n(c, EXPORT_SCROLLBACK_LINES).await;
+        lines.reverse();
+        scrollback.insert(c.clone(), serde_json::to_value(lines).unwrap_or_default());
+    }
+
+    let now_unix = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let v = serde_json::json!({
+        "format": EXPORT_FORMAT,
+        "exported_unix": now_unix,
+        "account": {
+            "name": rec.name,
+            "created_unix": rec.created_unix,
+            "auth": {
+                "password": rec.pw_hash.is_some(),
+                "google_email": rec.google_email,
+                "oidc_email": rec.oidc_email,
+                "totp_enabled": rec.totp_secret.is_some(),
+            },
+            "email": rec.email,
+            "email_verified": rec.email_verified,
+            "color": rec.color,
+            "aliases": rec.aliases,
+            "caps": rec.caps,
+            "characters": characters,
+            "max_characters": rec.max_characters,
+            "renamed_unix": rec.renamed_unix,
+            "delete_after_unix": rec.delete_after_unix,
+        },
+        "characters": snapshots,
+        "scrollback": scrollback,
+    });
+    serde_json::to_string_pretty(&v).ok()
+}
+
+async fn handle_account_export_command(
+    cfg: &Config,
+    accounts: &Arc<tokio::sync::Mutex<Accounts>>,
+    mailer: &email::Mailer,
+    nearline: &nearline::NearlineRing,
+    name: &str,
+    action: &str,
+) -> String {
+    let Some(json) = account_export_json(cfg, accounts, nearline, name).await else {
+        return "account: not found\r\n\r\n> ".to_string();
+    };
+    match action {
+        "" => {
+            // Scrollback text carries color markup; show it literally.
+            let mut s = String::new();
+            s.push_str("account export (json; save everything between the markers):\r\n");
+            s.push_str("-----BEGIN SLOPMUD EXPORT-----\r\n");
+            s.push_str(&json.replace('{', "{{").replace('\n', "\r\n"));
+            s.push_str("\r\n-----END SLOPMUD EXPORT-----\r\n\r\n> ");
+            s
+        }
+        "email" => {
+            let addr = {
+                let a = accounts.lock().await;
+                a.by_name
+                    .get(name)
+                    .filter(|r| r.email_verified)
+                    .and_then(|r| r.email.clone())
+            };
+            let Some(addr) = addr else {
+                return "account export email: needs a verified email (account email)\r\n\r\n> "
+                    .to_string();
+            };
+            if !mailer.enabled() {
+                return "account export email: email is not configured on this server\r\n\r\n> "
+                    .to_string();
+            }
+            match mailer.send(&addr, "slopmud account export", &json).await {
+                Ok(()) => format!(
+                    "ok: export sent to {}\r\n\r\n> ",
+                    email::mask_address(&addr)
+                ),
+                Err(e) => {
+                    warn!(name = %name, err = %e, "export email failed");
+                    "account export email: failed to send; try again later\r\n\r\n> ".to_string()
+                }
+            }
+        }
+        _ => account_usage_text(),
+    }
+}
+
+async fn handle_account_delete_command(
+    cfg: &Config,
+    accounts: &Arc<tokio::sync::Mutex<Accounts>>,
+    name: &str,
+    action: &str,
+) -> String {
+    let now_unix = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut a = accounts.lock().await;
+    let Some(r) = a.by_name.get_mut(name) else {
+        return "account: not found\r\n\r\n> ".to_string();
+    };
+    let out = match (action, r.delete_after_unix) {
+        ("", Some(t)) => {
+            return format!(
+                "account delete: scheduled for {} (account delete cancel to keep it)\r\n\r\n> ",
+                fmt_unix(t)
+            );
+        }
+        ("", None) => {
+            return format!(
+                "account delete: removes this account and all its characters after a {} grace period.\r\n\
+                 you can still log in and cancel until then. consider `account export` first.\r\n\
+                 type: account delete confirm\r\n\r\n> ",
+                fmt_duration_s(cfg.account_delete_grace_s)
+            );
+        }
+        ("confirm", Some(t)) => {
+            return format!(
+                "account delete: already scheduled for {}\r\n\r\n> ",
+                fmt_unix(t)
+            );
+        }
+        ("confirm", None) => {
+            let t = now_unix.saturating_add(cfg.account_delete_grace_s);
+            r.delete_after_unix = Some(t);
+            format!(
+                "ok: account scheduled for deletion at {} (account delete cancel to keep it)\r\n\r\n> ",
+                fmt_unix(t)
+            )
+        }
+        ("cancel", Some(_)) => {
+            r.delete_after_unix = None;
+            "ok: account deletion cancelled\r\n\r\n> ".to_string()
+        }
+        ("cancel", None) => {
+            return "ok: account is not scheduled for deletion\r\n\r\n> ".to_string();
+        }
+        _ => return account_usage_text(),
+    };
+    if let Err(e) = a.save() {
+        warn!(name = %name, err = %e, "accounts save failed");
+        return "account delete: failed to save\r\n\r\n> ".to_string();
+    }
+    out
+}
+
+fn recovery_codes_text(codes: &[String]) -> String {
+    let mut s = String::new();
+    s.push_str("recovery codes (each works once in place of a 2fa code; save them now):\r\n");
+    for c in codes {
+        s.push_str(&format!(" - {c}\r\n"));
+    }
+    s
+}
+
+/// Mail a fresh verification code for `addr`. Returns what to tell the player.
+async fn send_email_verify_code(
+    accounts: &Arc<tokio::sync::Mutex<Accounts>>,
+    mailer: &email::Mailer,
+    name: &str,
+    addr: &str,
+) -> String {
+    if !mailer.enabled() {
+        return "(unverified: this server doesn't send email)\r\n".to_string();
+    }
+    let now_unix = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let code = {
+        let mut a = accounts.lock().await;
+        let Some(r) = a.by_name.get_mut(name) else {
+            return "account: not found\r\n".to_string();
+        };
+        if r.email_verify
+            .as_ref()
+            .is_some_and(|c| c.sent_within(now_unix, EMAIL_CODE_COOLDOWN_S))
+        {
+            return "a code went out less than a minute ago; check your inbox\r\n".to_string();
+        }
+        let (code, rec) = email::EmailCode::new(now_unix, EMAIL_VERIFY_TTL_S);
+        r.email_verify = Some(rec);
+        if let Err(e) = a.save() {
+            warn!(name = %name, err = %e, "accounts save failed");
+            return "account email: failed to save\r\n".to_string();
+        }
+        code
+    };
+
+    let body = format!(
+        "Your slopmud verification code for account {name} is:\n\n    {code}\n\n\
+         In game, type: account email verify {code}\n\n\
+         It expires in 24 hours. If you didn't ask for this, you can ignore this message.\n"
+    );
+    match mailer.send(addr, "slopmud: verify your email", &body).await {
+        Ok(()) => format!("sent a code to {addr}; confirm with: account email verify <code>\r\n"),
+        Err(e) => {
+            warn!(name = %name, err = %e, "verification email failed");
+            "couldn't send the verification email; try account email resend later\r\n".to_string()
+        }
+    }
+}
+
+/// Mail a password reset code if the account has a verified address. Returns whether a code is
+/// waiting (just sent, or sent moments ago) and what to tell the player.
+async fn start_password_reset(
+    accounts: &Arc<tokio::sync::Mutex<Accounts>>,
+    mailer: &email::Mailer,
+    name: &str,
+) -> (bool, String) {
+    if !mailer.enabled() {
+        return (
+            false,
+            "password reset by email isn't available on this server\r\n".to_string(),
+        );
+    }
+    let now_unix = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (addr, code) = {
+        let mut a = accounts.lock().await;
+        let Some(r) = a.by_name.get_mut(name) else {
+            return (false, "account: not found\r\n".to_string());
+        };
+        let addr = match (&r.email, r.email_verified) {
+            (Some(e), true) => e.clone(),
+            _ => {
+                return (
+                    false,
+                    "this account has no verified email, so it can't be reset by email\r\n"
+                        .to_string(),
+                );
+            }
+        };
+        if r.password_reset
+            .as_ref()
+            .is_some_and(|c| c.sent_within(now_unix, EMAIL_CODE_COOLDOWN_S))
+        {
+            let msg = format!(
+                "a reset code went to {} less than a minute ago\r\n",
+                email::mask_address(&addr)
+            );
+            return (true, msg);
+        }
+        let (code, rec) = email::EmailCode::new(now_unix, PASSWORD_RESET_TTL_S);
+        r.password_reset = Some(rec);
+        if let Err(e) = a.save() {
+            warn!(name = %name, err = %e, "accounts save failed");
+            return (false, "password reset: failed to save\r\n".to_string());
+        }
+        (addr, code)
+    };
+
+    let body = format!(
+        "Someone (hopefully you) asked to reset the password for slopmud account {name}.\n\n\
+         Your reset code is:\n\n    {code}\n\n\
+         Enter it at the reset code prompt. It expires in 15 minutes. If this wasn't you, ignore \
+         this message; your password stays the same.\n"
+    );
+    match mailer
+        .send(&addr, "slopmud: password reset code", &body)
+        .await
+    {
+        Ok(()) => (
+            true,
+            format!("sent a reset code to {}\r\n", email::mask_address(&addr)),
+        ),
+        Err(e) => {
+            warn!(name = %name, err = %e, "password reset email failed");
+            (
+                false,
+                "couldn't send the reset email; try again later\r\n".to_string(),
+            )
+        }
+    }
+}
+
+async fn handle_account_2fa_command(
+    accounts: &Arc<tokio::sync::Mutex<Accounts>>,
+    name: &str,
+    action: &str,
+    code: &str,
+) -> String {
+    let now_unix = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut a = accounts.lock().await;
+    let Some(r) = a.by_name.get_mut(name) else {
+        return "account: not found\r\n\r\n> ".to_string();
+    };
+
+    let out = match action {
+        "" | "show" | "status" => {
+            return match r.totp_secret {
+                Some(_) => format!(
+                    "2fa: on ({} recovery codes left)\r\n\r\n> ",
+                    r.totp_recovery.len()
+                ),
+                None => "2fa: off\r\nuse: account 2fa enable\r\n\r\n> ".to_string(),
+            };
+        }
+        "enable" => {
+            if r.pw_hash.is_none() {
+                return "account 2fa: only password logins use a second factor\r\n\r\n> "
+                    .to_string();
+            }
+            if r.totp_secret.is_some() {
+                return "account 2fa: already on\r\n\r\n> ".to_string();
+            }
+            let secret = totp::new_secret();
+            let mut s = String::new();
+            s.push_str("add this to your authenticator app:\r\n");
+            s.push_str(&format!(
+                " - {}\r\n",
+                totp::provisioning_uri("slopmud", &r.name, &secret)
+            ));
+            s.push_str(&format!(" - or enter the key by hand: {secret}\r\n"));
+            s.push_str("then confirm with: account 2fa verify <code>\r\n\r\n> ");
+            r.totp_pending = Some(secret);
+            s
+        }
+        "verify" => {
+            let Some(pending) = r.totp_pending.clone() else {
+                return "account 2fa: nothing to verify (account 2fa enable first)\r\n\r\n> "
+                    .to_string();
+            };
+            let Some(step) = totp::verify(&pending, code, now_unix, None) else {
+                return "account 2fa: wrong code; check your device's clock and try again\r\n\r\n> "
+                    .to_string();
+            };
+            let (shown, hashes) = totp::new_recovery_codes();
+            r.totp_secret = Some(pending);
+            r.totp_pending = None;
+            r.totp_last_step = Some(step);
+            r.totp_recovery = hashes;
+            format!("ok: 2fa on\r\n{}\r\n> ", recovery_codes_text(&shown))
+        }
+        "recovery" => {
+            if r.check_second_factor(code, now_unix).is_none() {
+                return "account 2fa recovery: needs a current code\r\n\r\n> ".to_string();
+            }
+            let (shown, hashes) = totp::new_recovery_codes();
+            r.totp_recovery = hashes;
+            format!(
+                "ok: old recovery codes no longer work\r\n{}\r\n> ",
+                recovery_codes_text(&shown)
+            )
+        }
+        "disable" | "off" => {
+            if r.check_second_factor(code, now_unix).is_none() {
+                return "account 2fa disable: needs a current code or a recovery code\r\n\r\n> "
+                    .to_string();
+            }
+            r.clear_totp();
+            "ok: 2fa off\r\n\r\n> ".to_string()
+        }
+        _ => return account_usage_text(),
+    };
+
+    if let Err(e) = a.save() {
+        warn!(name = %name, err = %e, "accounts save failed");
+        return "account 2fa: failed to save\r\n\r\n> ".to_string();
+    }
+    out
+}
+
+fn color_usage_text() -> String {
+    let mut s = String::new();
+    s.push_str("color:\r\n");
+    s.push_str("use:\r\n");
+    s.push_str(" - color\r\n");
+    s.push_str(" - color on|off|256\r\n");
+    s.push_str(" - color auto (follow what your client reports)\r\n");
+    s.push_str("\r\n> ");
+    s
+}
+
+fn alias_usage_text() -> String {
+    let mut s = String::new();
+    s.push_str("alias:\r\n");
+    s.push_str("use:\r\n");
+    s.push_str(" - alias (list)\r\n");
+    s.push_str(
+        " - alias <name> <command>[; <command>...] ($1..$9 are arguments, $* all of them)\r\n",
+    );
+    s.push_str(" - unalias <name>\r\n");
+    s.push_str(" - \\<command> runs a command without alias expansion\r\n");
+    s.push_str("\r\n> ");
+    s
+}
+
+fn term_usage_text() -> String {
+    let mut s = String::new();
+    s.push_str("term:\r\n");
+    s.push_str("use:\r\n");
+    s.push_str(" - term\r\n");
+    s.push_str(" - term width <n>|auto|off\r\n");
+    s.push_str(" - term height <n>|auto|off (paging)\r\n");
+    s.push_str("\r\n> ");
+    s
+}
+
+fn accounthold_usage_text() -> String {
+    let mut s = String::new();
+    s.push_str("accounthold:\r\n");
+    s.push_str("use:\r\n");
+    s.push_str(" - accounthold list\r\n");
+    s.push_str(" - accounthold show <name>\r\n");
+    s.push_str(" - accounthold add <name> [reason...]\r\n");
+    s.push_str(" - accounthold del <name>\r\n");
+    s.push_str("\r\n> ");
+    s
+}
+
+async fn sbc_send_admin_req(
+    sock: &PathBuf,
+    req: &sbc_core::AdminReq,
+) -> anyhow::Result<sbc_core::AdminResp> {
+    let mut stream = UnixStream::connect(sock)
+        .await
+        .map_err(|e| anyhow::anyhow!("connect sbc admin sock {}: {e}", sock.display()))?;
+    stream
+        .write_all(serde_json::to_string(req)?.as_bytes())
+        .await?;
+    stream.write_all(b"\n").await?;
+    let (rd, _) = stream.into_split();
+    let mut rd = BufReader::new(rd);
+    let mut line = String::new();
+    rd.read_line(&mut line).await?;
+    let raw = line.trim();
+    if raw.is_empty() {
+        return Err(anyhow::anyhow!("empty sbc admin response"));
+    }
+    Ok(serde_json::from_str(raw)?)
+}
+
+async fn sbc_holds_events_task(
+    events_sock: PathBuf,
+    holds: Arc<tokio::sync::Mutex<hold::HoldCache>>,
+    sessions: Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
+) {
+    // Index to resume from after a reconnect.
+    let mut next_index: Option<u64> = None;
+
+    loop {
+        let sub = sbc_core::EventsReq::Subscribe {
+            mode: sbc_core::SubscribeMode::Snapshot,
+            from_index: next_index,
+        };
+
+        let mut stream = match UnixStream::connect(&events_sock).await {
+            Ok(s) => s,
+            Err(e) => {
+                warn!(err=%e, path=%events_sock.display(), "sbc events connect failed");
+                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                continue;
+            }
+        };
+
+        if let Err(e) = stream
+            .write_all(serde_json::to_string(&sub).unwrap().as_bytes())
+            .await
+        {
+            warn!(err=%e, "failed to subscribe to sbc events");
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            continue;
+        }
+        let _ = stream.write_all(b"\n").await;
+
+        let (rd, _) = stream.into_split();
+        let mut rd = BufReader::new(rd);
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            match rd.read_line(&mut line).await {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => {
+                    warn!(err=%e, "sbc events read failed");
+                    break;
+                }
+            }
+            let raw = line.trim();
+            if raw.is_empty() {
+                continue;
+            }
+            let env: sbc_core::EventEnvelope = match serde_json::from_str(raw) {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!(err=%e, line=%raw, "bad sbc event json");
+                    continue;
+                }
+            };
+            next_index = Some(env.index + 1);
+
+            match env.event {
+                sbc_core::Event::Snapshot { holds: h, .. } => {
+                    let held_names = h
+                        .iter()
+                        .map(|e| e.name_lc.trim().to_ascii_lowercase())
+                        .filter(|k| !k.is_empty())
+                        .collect::<std::collections::HashSet<_>>();
+
+                    holds.lock().await.apply_snapshot(env.index, h);
+
+                    let mut m = sessions.lock().await;
+                    for si in m.values_mut() {
+                        let k = si.name.trim().to_ascii_lowercase();
+                        si.held = held_names.contains(&k);
+                    }
+                }
+                sbc_core::Event::LegalHoldUpserted { entry } => {
+                    let name_lc = entry.name_lc.trim().to_ascii_lowercase();
+                    holds.lock().await.apply_upsert(env.index, entry);
+
+                    if !name_lc.is_empty() {
+                        let mut m = sessions.lock().await;
+                        for si in m.values_mut() {
+                            if si.name.trim().to_ascii_lowercase() == name_lc {
+                                si.held = true;
+                            }
+                        }
+                    }
+                }
+                sbc_core::Event::LegalHoldDeleted { name_lc } => {
+                    let name_lc = name_lc.trim().to_ascii_lowercase();
+                    holds.lock().await.apply_delete(env.index, &name_lc);
+
+                    if !name_lc.is_empty() {
+                        let mut m = sessions.lock().await;
+                        for si in m.values_mut() {
+                            if si.name.trim().to_ascii_lowercase() == name_lc {
+                                si.held = false;
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    }
+}
+
+async fn handle_accounthold_command(
+    sessions: &Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
+    holds: &Arc<tokio::sync::Mutex<hold::HoldCache>>,
+    sbc_admin_sock: &PathBuf,
+    eventlog: &Arc<eventlog::EventLog>,
+    peer_ip: IpAddr,
+    session: SessionId,
+    actor: &str,
+    line: &str,
+) -> String {
+    if !peer_ip.is_loopback() {
+        return "accounthold: permission denied\r\n\r\n> ".to_string();
+    }
+
+    let mut it = line.split_whitespace();
+    let _ = it.next(); // "accounthold"
+    let sub = it.next().unwrap_or("").to_ascii_lowercase();
+
+    let now_unix = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    match sub.as_str() {
+        "" | "help" => accounthold_usage_text(),
+        "list" => {
+            let entries = { holds.lock().await.snapshot() };
+            let mut s = String::new();
+            s.push_str("accounthold list:\r\n");
             if entries.is_empty() {
                 s.push_str(" (none)\r\n");
             } else {
@@ -1477,18 +2706,240 @@ async fn handle_accounthold_command(
                 eventlog.log_line(LogStream::Character(actor), &entry).await;
             }
 
-            if existed {
-                format!("ok: account hold removed for {name_lc}\r\n\r\n> ")
+            if existed {
+                format!("ok: account hold removed for {name_lc}\r\n\r\n> ")
+            } else {
+                format!("ok: account hold not present for {name_lc}\r\n\r\n> ")
+            }
+        }
+        _ => accounthold_usage_text(),
+    }
+}
+
+async fn handle_color_command(
+    accounts: &Arc<tokio::sync::Mutex<Accounts>>,
+    name: &str,
+    line: &str,
+    term_caps: &term::TermCaps,
+    pref: &mut term::ColorPref,
+) -> String {
+    let mut it = line.split_whitespace();
+    let _ = it.next(); // "color"
+    let arg = it.next().unwrap_or("").to_ascii_lowercase();
+
+    if arg.is_empty() || arg == "show" {
+        let detected = term_caps.detected_color();
+        let mut s = String::new();
+        s.push_str("color:\r\n");
+        s.push_str(&format!(" - setting: {}\r\n", pref.as_str()));
+        s.push_str(&format!(
+            " - effective: {}\r\n",
+            pref.effective(detected).as_str()
+        ));
+        s.push_str(&format!(
+            " - client: {} (detected {})\r\n",
+            color::escape(&term_caps.describe()),
+            detected.as_str()
+        ));
+        s.push_str(
+            " - sample: {R}red{x} {G}green{x} {Y}yellow{x} {B}blue{x} {M}magenta{x} {C}cyan{x}\r\n",
+        );
+        s.push_str("\r\n> ");
+        return s;
+    }
+    if arg == "help" {
+        return color_usage_text();
+    }
+    let Some(want) = term::ColorPref::parse(&arg) else {
+        return color_usage_text();
+    };
+    *pref = want;
+
+    let mut a = accounts.lock().await;
+    if let Some(r) = a.by_name.get_mut(name) {
+        let stored = match want {
+            term::ColorPref::Auto => None,
+            p => Some(p.as_str().to_string()),
+        };
+        if r.color != stored {
+            r.color = stored;
+            if let Err(e) = a.save() {
+                warn!(name = %name, err = %e, "accounts save failed");
+                return "color: set for this session (failed to save)\r\n\r\n> ".to_string();
+            }
+        }
+    }
+    format!(
+        "ok: color {} ({})\r\n\r\n> ",
+        want.as_str(),
+        want.effective(term_caps.detected_color()).as_str()
+    )
+}
+
+async fn load_aliases(
+    accounts: &Arc<tokio::sync::Mutex<Accounts>>,
+    account: Option<&str>,
+) -> aliases::Aliases {
+    let Some(nm) = account else {
+        return aliases::Aliases::new();
+    };
+    let a = accounts.lock().await;
+    a.by_name
+        .get(nm)
+        .map(|r| r.aliases.clone())
+        .unwrap_or_default()
+}
+
+/// `alias` / `unalias`. `current` is the session's copy; the account store is updated alongside.
+async fn handle_alias_command(
+    accounts: &Arc<tokio::sync::Mutex<Accounts>>,
+    name: &str,
+    line: &str,
+    current: &mut aliases::Aliases,
+) -> String {
+    let line = line.trim();
+    let (cmd, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let (alias, body) = rest
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((rest.trim(), ""));
+    let alias = alias.to_ascii_lowercase();
+    let body = body.trim();
+    // Expansions are player text; keep `{` from reading as color markup.
+    let show = |b: &str| b.replace('{', "{{");
+
+    if cmd.eq_ignore_ascii_case("unalias") {
+        if alias.is_empty() {
+            return alias_usage_text();
+        }
+        if current.remove(&alias).is_none() {
+            return format!("unalias: no alias {alias}\r\n\r\n> ");
+        }
+    } else if alias.is_empty() || alias == "help" {
+        if alias.is_empty() && !current.is_empty() {
+            let mut s = format!("aliases ({}/{}):\r\n", current.len(), aliases::MAX_ALIASES);
+            for (k, v) in current.iter() {
+                s.push_str(&format!(" - {k} = {}\r\n", show(v)));
+            }
+            s.push_str("\r\n> ");
+            return s;
+        }
+        return alias_usage_text();
+    } else if body.is_empty() {
+        return match current.get(&alias) {
+            Some(v) => format!("alias {alias} = {}\r\n\r\n> ", show(v)),
+            None => format!("alias: no alias {alias}\r\n\r\n> "),
+        };
+    } else {
+        if !aliases::valid_name(&alias) {
+            return format!(
+                "alias: names are one word of a-z, 0-9, _ or - (max {}), not alias/unalias\r\n\r\n> ",
+                aliases::MAX_NAME_LEN
+            );
+        }
+        if body.len() > aliases::MAX_EXPANSION_LEN {
+            return format!(
+                "alias: expansion too long (max {} characters)\r\n\r\n> ",
+                aliases::MAX_EXPANSION_LEN
+            );
+        }
+        if !current.contains_key(&alias) && current.len() >= aliases::MAX_ALIASES {
+            return format!(
+                "alias: you have {} aliases already; unalias one first\r\n\r\n> ",
+                aliases::MAX_ALIASES
+            );
+        }
+        current.insert(alias.clone(), body.to_string());
+    }
+
+    let done = if cmd.eq_ignore_ascii_case("unalias") {
+        format!("ok: unalias {alias}")
+    } else {
+        format!("ok: alias {alias} = {}", show(body))
+    };
+    let mut a = accounts.lock().await;
+    if let Some(r) = a.by_name.get_mut(name) {
+        r.aliases = current.clone();
+        if let Err(e) = a.save() {
+            warn!(name = %name, err = %e, "accounts save failed");
+            return format!("{done} (this session only; failed to save)\r\n\r\n> ");
+        }
+    }
+    format!("{done}\r\n\r\n> ")
+}
+
+/// Effective wrap width / page height: explicit `term` setting, else NAWS, else off.
+fn term_size(
+    caps: &term::TermCaps,
+    width_override: Option<u16>,
+    height_override: Option<u16>,
+    is_bot: Option<bool>,
+) -> (Option<u16>, Option<u16>) {
+    let width = width_override.or(caps.width).filter(|w| *w > 0);
+    let height = if is_bot == Some(true) {
+        None
+    } else {
+        height_override.or(caps.height).filter(|h| *h > 0)
+    };
+    (width, height)
+}
+
+fn handle_term_command(
+    line: &str,
+    caps: &term::TermCaps,
+    width_override: &mut Option<u16>,
+    height_override: &mut Option<u16>,
+) -> String {
+    let mut it = line.split_whitespace();
+    let _ = it.next(); // "term"
+    let sub = it.next().unwrap_or("").to_ascii_lowercase();
+    let arg = it.next().unwrap_or("").to_ascii_lowercase();
+
+    fn parse_dim(arg: &str) -> Option<Option<u16>> {
+        match arg {
+            "auto" => Some(None),
+            "off" | "0" => Some(Some(0)),
+            n => n.parse::<u16>().ok().filter(|v| *v <= 1000).map(Some),
+        }
+    }
+    fn show_dim(v: Option<u16>) -> String {
+        match v {
+            None => "auto".to_string(),
+            Some(0) => "off".to_string(),
+            Some(n) => n.to_string(),
+        }
+    }
+
+    match sub.as_str() {
+        "" | "show" => {
+            let mut s = String::new();
+            s.push_str("term:\r\n");
+            s.push_str(&format!(" - width: {}\r\n", show_dim(*width_override)));
+            s.push_str(&format!(" - height: {}\r\n", show_dim(*height_override)));
+            s.push_str(&format!(" - client: {}\r\n", caps.describe()));
+            s.push_str("\r\n> ");
+            s
+        }
+        "width" | "height" => {
+            let Some(v) = parse_dim(&arg) else {
+                return term_usage_text();
+            };
+            if sub == "width" {
+                *width_override = v;
             } else {
-                format!("ok: account hold not present for {name_lc}\r\n\r\n> ")
+                *height_override = v;
             }
+            format!("ok: term {sub} {}\r\n\r\n> ", show_dim(v))
         }
-        _ => accounthold_usage_text(),
+        _ => term_usage_text(),
     }
 }
 
 async fn handle_account_command(
+    cfg: &Config,
     accounts: &Arc<tokio::sync::Mutex<Accounts>>,
+    mailer: &email::Mailer,
+    nearline: &nearline::NearlineRing,
     name: &str,
     line: &str,
 ) -> String {
@@ -1503,26 +2954,33 @@ async fn handle_account_command(
 
             match action.as_str() {
                 "" | "show" => {
-                    let (email, google_email) = {
+                    let (email, verified, google_email) = {
                         let a = accounts.lock().await;
                         match a.by_name.get(name) {
-                            Some(r) => (r.email.clone(), r.google_email.clone()),
-                            None => (None, None),
+                            Some(r) => (r.email.clone(), r.email_verified, r.google_email.clone()),
+                            None => (None, false, None),
                         }
                     };
 
                     let mut s = String::new();
                     s.push_str("account email:\r\n");
-                    s.push_str(&format!(
-                        " - configured: {}\r\n",
-                        email.as_deref().unwrap_or("(none)")
-                    ));
+                    match email.as_deref() {
+                        Some(e) if verified => {
+                            s.push_str(&format!(" - configured: {e} (verified)\r\n"))
+                        }
+                        Some(e) => s.push_str(&format!(" - configured: {e} (unverified)\r\n")),
+                        None => s.push_str(" - configured: (none)\r\n"),
+                    }
                     s.push_str(&format!(
                         " - google: {}\r\n",
                         google_email.as_deref().unwrap_or("(none)")
                     ));
                     s.push_str("use:\r\n");
                     s.push_str(" - account email set <addr>\r\n");
+                    if email.is_some() && !verified {
+                        s.push_str(" - account email verify <code>\r\n");
+                        s.push_str(" - account email resend\r\n");
+                    }
                     s.push_str(" - account email clear\r\n");
                     s.push_str("\r\n> ");
                     s
@@ -1539,18 +2997,87 @@ async fn handle_account_command(
                         return "account: not found\r\n\r\n> ".to_string();
                     };
 
-                    let changed = r.email.as_deref() != Some(email.as_str());
+                    if r.email.as_deref() == Some(email.as_str()) {
+                        return if r.email_verified {
+                            format!("ok: email already set to {email}\r\n\r\n> ")
+                        } else {
+                            format!(
+                                "ok: email already set to {email} (unverified; account email resend)\r\n\r\n> "
+                            )
+                        };
+                    }
+                    // A new address starts unverified, and codes sent to the old one stop working.
                     r.email = Some(email.clone());
+                    r.email_verified = false;
+                    r.email_verify = None;
+                    r.password_reset = None;
+                    if let Err(e) = a.save() {
+                        warn!(name = %name, err = %e, "accounts save failed");
+                        return "account email: failed to save\r\n\r\n> ".to_string();
+                    }
+                    drop(a);
 
-                    if changed {
-                        if let Err(e) = a.save() {
-                            warn!(name = %name, err = %e, "accounts save failed");
-                            return "account email: failed to save\r\n\r\n> ".to_string();
+                    let sent = send_email_verify_code(accounts, mailer, name, &email).await;
+                    format!("ok: email set to {email}\r\n{sent}\r\n> ")
+                }
+                "verify" => {
+                    let code = it.collect::<String>();
+                    let now_unix = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs();
+                    let mut a = accounts.lock().await;
+                    let Some(r) = a.by_name.get_mut(name) else {
+                        return "account: not found\r\n\r\n> ".to_string();
+                    };
+                    if r.email_verified {
+                        return "ok: email already verified\r\n\r\n> ".to_string();
+                    }
+                    let Some(pending) = r.email_verify.as_mut() else {
+                        return "account email verify: no code pending (account email resend)\r\n\r\n> "
+                            .to_string();
+                    };
+                    let out = match pending.check(&code, now_unix) {
+                        email::CodeCheck::Ok => {
+                            r.email_verified = true;
+                            r.email_verify = None;
+                            format!(
+                                "ok: {} verified\r\n\r\n> ",
+                                r.email.as_deref().unwrap_or("email")
+                            )
                         }
-                        format!("ok: email set to {email}\r\n\r\n> ")
-                    } else {
-                        format!("ok: email already set to {email}\r\n\r\n> ")
+                        email::CodeCheck::Wrong => {
+                            "account email verify: wrong code\r\n\r\n> ".to_string()
+                        }
+                        email::CodeCheck::Spent => {
+                            r.email_verify = None;
+                            "account email verify: that code expired or had too many wrong tries; account email resend\r\n\r\n> "
+                                .to_string()
+                        }
+                    };
+                    if let Err(e) = a.save() {
+                        warn!(name = %name, err = %e, "accounts save failed");
+                        return "account email: failed to save\r\n\r\n> ".to_string();
                     }
+                    out
+                }
+                "resend" => {
+                    let email = {
+                        let a = accounts.lock().await;
+                        match a.by_name.get(name) {
+                            None => return "account: not found\r\n\r\n> ".to_string(),
+                            Some(r) if r.email_verified => {
+                                return "ok: email already verified\r\n\r\n> ".to_string();
+                            }
+                            Some(r) => r.email.clone(),
+                        }
+                    };
+                    let Some(email) = email else {
+                        return "account email resend: no email set (account email set <addr>)\r\n\r\n> "
+                            .to_string();
+                    };
+                    let sent = send_email_verify_code(accounts, mailer, name, &email).await;
+                    format!("{sent}\r\n> ")
                 }
                 "clear" | "unset" | "remove" => {
                     let mut a = accounts.lock().await;
@@ -1562,6 +3089,9 @@ async fn handle_account_command(
                         return "ok: email already clear\r\n\r\n> ".to_string();
                     }
                     r.email = None;
+                    r.email_verified = false;
+                    r.email_verify = None;
+                    r.password_reset = None;
                     if let Err(e) = a.save() {
                         warn!(name = %name, err = %e, "accounts save failed");
                         return "account email: failed to save\r\n\r\n> ".to_string();
@@ -1571,10 +3101,50 @@ async fn handle_account_command(
                 _ => account_usage_text(),
             }
         }
+        "2fa" => {
+            let action = it.next().unwrap_or("").to_ascii_lowercase();
+            let code = it.next().unwrap_or("");
+            handle_account_2fa_command(accounts, name, &action, code).await
+        }
+        "export" => {
+            let action = it.next().unwrap_or("").to_ascii_lowercase();
+            handle_account_export_command(cfg, accounts, mailer, nearline, name, &action).await
+        }
+        "delete" => {
+            let action = it.next().unwrap_or("").to_ascii_lowercase();
+            handle_account_delete_command(cfg, accounts, name, &action).await
+        }
         _ => account_usage_text(),
     }
 }
 
+/// One write for a connection's writer task. Only shard and chatd output (which escape player
+/// text) and the broker's own color samples carry markup; everything else shows as typed.
+#[derive(Debug)]
+enum Out {
+    Text(Bytes),
+    Markup(Bytes),
+}
+
+impl From<Bytes> for Out {
+    fn from(b: Bytes) -> Self {
+        Out::Text(b)
+    }
+}
+
+/// Sending half of a connection's writer queue.
+#[derive(Debug, Clone)]
+struct WriteTx(tokio::sync::mpsc::Sender<Out>);
+
+impl WriteTx {
+    async fn send(
+        &self,
+        out: impl Into<Out>,
+    ) -> Result<(), tokio::sync::mpsc::error::SendError<Out>> {
+        self.0.send(out.into()).await
+    }
+}
+
 #[derive(Debug, Clone)]
 struct SessionInfo {
     name: String,
@@ -1586,7 +3156,7 @@ struct SessionInfo {
     sex: String,
     pronouns: String,
     peer_ip: IpAddr,
-    write_tx: tokio::sync::mpsc::Sender<Bytes>,
+    write_tx: WriteTx,
     disconnect_tx: tokio::sync::watch::Sender<bool>,
     scrollback: Arc<tokio::sync::Mutex<Scrollback>>,
 }
@@ -1655,18 +3225,64 @@ fn make_shard_auth_blob(
     Bytes::from(serde_json::to_vec(&b).expect("serialize shard auth blob"))
 }
 
-fn load_saved_player(path: &Path, name: &str) -> Option<SavedPlayerSnapshot> {
+/// A change to saved characters that the shards apply (see `mudproto::shard::REQ_PLAYER_OP`).
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum PlayerOp<'a> {
+    Forget { name: &'a str },
+    Rename { name: &'a str, new_name: &'a str },
+    Mute { name: &'a str, until_unix: u64 },
+}
+
+fn player_op_frame(shard_signer: &shard_auth::Signer, op: &PlayerOp<'_>) -> (SessionId, Bytes) {
+    // The session is only a nonce for the assertion; no route is created for it.
+    let session = new_session_id();
+    let body = shard_signer.seal(
+        session,
+        &serde_json::to_vec(op).expect("serialize player op"),
+    );
+    (session, body)
+}
+
+async fn send_player_op(
+    shard_tx: &tokio::sync::mpsc::Sender<ShardMsg>,
+    shard_signer: &shard_auth::Signer,
+    op: PlayerOp<'_>,
+) {
+    let (session, body) = player_op_frame(shard_signer, &op);
+    let _ = shard_tx
+        .send(ShardMsg {
+            t: REQ_PLAYER_OP,
+            session,
+            body,
+        })
+        .await;
+}
+
+fn load_saved_players(path: &Path) -> Vec<SavedPlayerSnapshot> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn find_saved_player<'a>(
+    snapshots: &'a [SavedPlayerSnapshot],
+    name: &str,
+) -> Option<&'a SavedPlayerSnapshot> {
     let target = sanitize_name(name);
     if target.is_empty() {
         return None;
     }
-    let raw = std::fs::read_to_string(path).ok()?;
-    let snapshots = serde_json::from_str::<Vec<SavedPlayerSnapshot>>(&raw).ok()?;
     snapshots
-        .into_iter()
+        .iter()
         .find(|p| sanitize_name(&p.name).eq_ignore_ascii_case(&target))
 }
 
+fn load_saved_player(path: &Path, name: &str) -> Option<SavedPlayerSnapshot> {
+    find_saved_player(&load_saved_players(path), name).cloned()
+}
+
 async fn wait_for_saved_player(
     path: &Path,
     name: &str,
@@ -1695,13 +3311,19 @@ async fn attach_authenticated_session(
     pronouns: &str,
     shard_auth: Bytes,
     auth_method: &str,
-    write_tx: &tokio::sync::mpsc::Sender<Bytes>,
+    write_tx: &WriteTx,
     disconnect_tx: &tokio::sync::watch::Sender<bool>,
-    sessions: &Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
-    holds: &Arc<tokio::sync::Mutex<hold::HoldCache>>,
-    eventlog: &Arc<eventlog::EventLog>,
-    shard_tx: &tokio::sync::mpsc::Sender<ShardMsg>,
+    shared: &Shared,
+    fresh: bool,
 ) -> anyhow::Result<()> {
+    let Shared {
+        sessions,
+        holds,
+        eventlog,
+        shard_tx,
+        shard_signer,
+        ..
+    } = shared;
     let held = { holds.lock().await.is_held(name).is_some() };
 
     {
@@ -1745,6 +3367,8 @@ async fn attach_authenticated_session(
     }
 
     let body = attach_body(
+        session,
+        shard_signer,
         bot,
         Some(shard_auth.as_ref()),
         race,
@@ -1752,10 +3376,13 @@ async fn attach_authenticated_session(
         sex,
         pronouns,
         name.as_bytes(),
-    );
+        None,
+        fresh,
+    )
+    .map_err(|e| anyhow::anyhow!("encode shard attach: {e}"))?;
     let _ = shard_tx
         .send(ShardMsg {
-            t: REQ_ATTACH,
+            t: REQ_ATTACH_V2,
             session,
             body,
         })
@@ -1763,6 +3390,170 @@ async fn attach_authenticated_session(
     Ok(())
 }
 
+const TOTP_PROMPT: &[u8] = b"2fa code (or a recovery code): ";
+
+const EMAIL_VERIFY_TTL_S: u64 = 24 * 3600;
+const PASSWORD_RESET_TTL_S: u64 = 15 * 60;
+// Minimum gap between mailed codes, so the broker can't be used to flood someone's inbox.
+const EMAIL_CODE_COOLDOWN_S: u64 = 60;
+const RESET_CODE_PROMPT: &[u8] = b"reset code (from your email; blank to go back): ";
+
+const CHARACTER_CREATION_PROMPT: &[u8] =
+    b"\r\ncharacter creation (step 2/4)\r\nare you using automation?\r\ntype: human | bot\r\n> ";
+
+fn character_menu_text(players_path: &Path, characters: &[String], limit: u32) -> String {
+    let mut s = format!("\r\ncharacter select ({}/{limit}):\r\n", characters.len());
+    let saved = load_saved_players(players_path);
+    for c in characters {
+        match find_saved_player(&saved, c) {
+            Some(p) => s.push_str(&format!(" - {c} ({} {})\r\n", p.race, p.class)),
+            None => s.push_str(&format!(" - {c} (not created yet)\r\n")),
+        }
+    }
+    s.push_str("type: <name> | new <name> | rename <name> <new name> | delete <name>\r\n> ");
+    s
+}
+
+/// Enter the world as `character`, or start its creation if it has never been saved.
+#[allow(clippy::too_many_arguments)]
+async fn start_character(
+    session: SessionId,
+    peer_ip: IpAddr,
+    character: &str,
+    saved: Option<SavedPlayerSnapshot>,
+    shard_auth: Bytes,
+    auth_method: &str,
+    write_tx: &WriteTx,
+    disconnect_tx: &tokio::sync::watch::Sender<bool>,
+    shared: &Shared,
+) -> anyhow::Result<ConnState> {
+    let Some(saved) = saved else {
+        let _ = write_tx
+            .send(Bytes::from_static(CHARACTER_CREATION_PROMPT))
+            .await;
+        return Ok(ConnState::NeedBotDisclosure);
+    };
+    attach_authenticated_session(
+        session,
+        peer_ip,
+        character,
+        saved.is_bot,
+        &saved.race,
+        &saved.class,
+        &saved.sex,
+        &saved.pronouns,
+        shard_auth,
+        auth_method,
+        write_tx,
+        disconnect_tx,
+        shared,
+        false,
+    )
+    .await?;
+    Ok(ConnState::InWorld)
+}
+
+/// Where a successful login goes next. An account with one character goes straight back into
+/// it; otherwise the player picks from the character menu. Returns the next state and the
+/// character, if one was picked.
+#[allow(clippy::too_many_arguments)]
+async fn enter_account(
+    shared: &Shared,
+    session: SessionId,
+    peer_ip: IpAddr,
+    account: &str,
+    shard_auth: Bytes,
+    auth_method: &str,
+    write_tx: &WriteTx,
+    disconnect_tx: &tokio::sync::watch::Sender<bool>,
+) -> anyhow::Result<(ConnState, Option<String>)> {
+    let Shared { cfg, accounts, .. } = shared;
+    let (characters, limit, delete_after) = {
+        let a = accounts.lock().await;
+        (
+            a.characters(account),
+            a.character_limit(account, cfg.max_characters),
+            a.by_name.get(account).and_then(|r| r.delete_after_unix),
+        )
+    };
+    if let Some(t) = delete_after {
+        let msg = format!(
+            "\r\nthis account is scheduled for deletion at {} (account delete cancel to keep it)\r\n",
+            fmt_unix(t)
+        );
+        let _ = write_tx.send(Bytes::from(msg)).await;
+    }
+    let [only] = characters.as_slice() else {
+        let menu = character_menu_text(&cfg.players_path, &characters, limit);
+        let _ = write_tx.send(Bytes::from(menu)).await;
+        return Ok((ConnState::NeedCharacter, None));
+    };
+    // Just-finished sessions may still be flushing their snapshot.
+    let saved = wait_for_saved_player(&cfg.players_path, only, Duration::from_secs(2)).await;
+    let next = start_character(
+        session,
+        peer_ip,
+        only,
+        saved,
+        shard_auth,
+        auth_method,
+        write_tx,
+        disconnect_tx,
+        shared,
+    )
+    .await?;
+    Ok((next, Some(only.clone())))
+}
+
+/// Take the session's character out of the world, keeping the connection. Returns false if it
+/// never got there.
+async fn leave_world(
+    session: SessionId,
+    sessions: &Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
+    eventlog: &Arc<eventlog::EventLog>,
+    shard_tx: &tokio::sync::mpsc::Sender<ShardMsg>,
+    chat_tx: Option<&tokio::sync::mpsc::Sender<ShardMsg>>,
+) -> bool {
+    let removed = { sessions.lock().await.remove(&session) };
+    let Some(si) = removed else {
+        return false;
+    };
+    {
+        let ts = Utc::now().to_rfc3339();
+        let sid = session_hex(session);
+        let entry = format!(
+            "ts={} kind=logout session={} ip={} name={}",
+            logfmt_str(&ts),
+            logfmt_str(&sid),
+            logfmt_str(&si.peer_ip.to_string()),
+            logfmt_str(&si.name),
+        );
+        eventlog.log_line(LogStream::All, &entry).await;
+        eventlog
+            .log_line(LogStream::Character(&si.name), &entry)
+            .await;
+        eventlog.log_line(LogStream::Login, &entry).await;
+    }
+
+    let _ = shard_tx
+        .send(ShardMsg {
+            t: REQ_DETACH,
+            session,
+            body: Bytes::new(),
+        })
+        .await;
+    if let Some(chat_tx) = chat_tx {
+        let _ = chat_tx
+            .send(ShardMsg {
+                t: mudproto::chat::REQ_LEAVE,
+                session,
+                body: Bytes::new(),
+            })
+            .await;
+    }
+    true
+}
+
 #[derive(Debug, Clone)]
 struct ShardMsg {
     t: u8,
@@ -1776,7 +3567,11 @@ enum ConnState {
     NeedAuthMethod,
     NeedPasswordCreate,
     NeedPasswordLogin,
+    NeedTotp,
+    NeedResetCode,
+    NeedResetPassword,
     NeedGoogleWait,
+    NeedCharacter,
     NeedBotDisclosure,
     NeedPublicAck,
     NeedCocAck,
@@ -1802,12 +3597,88 @@ struct AccountRec {
     oidc_email: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     caps: Option<Vec<String>>,
-    // User-configured email address for notifications. Not used for auth.
+    // User-configured email address for notifications and password resets. Not used for login.
     #[serde(default)]
     email: Option<String>,
+    // Set once a code mailed to `email` comes back; only verified addresses get reset codes.
+    #[serde(default)]
+    email_verified: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    email_verify: Option<email::EmailCode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    password_reset: Option<email::EmailCode>,
+    // Player color setting (`color on|off|256`); None means follow the client's TTYPE.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    // `alias` definitions, name -> expansion.
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    aliases: aliases::Aliases,
+    // Character names, in creation order. Accounts from before multi-character support have an
+    // empty list; their one character is named after the account (see `Accounts::characters`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    characters: Vec<String>,
+    // Per-account override of SLOPMUD_MAX_CHARACTERS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_characters: Option<u32>,
+    // TOTP second factor for password logins (base32 secret). Set once `account 2fa verify`
+    // confirms the pending secret.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    totp_secret: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    totp_pending: Option<String>,
+    // Last TOTP step accepted, so a code can't be used twice.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    totp_last_step: Option<u64>,
+    // SHA-256 hashes of unused recovery codes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    totp_recovery: Vec<String>,
+    // Set by `account delete confirm`; the account is purged once this passes (see
+    // `account_purge_task`) unless the player cancels first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    delete_after_unix: Option<u64>,
+    // Last character rename, for SLOPMUD_RENAME_COOLDOWN_S.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    renamed_unix: Option<u64>,
     created_unix: u64,
 }
 
+impl AccountRec {
+    /// Character names; accounts from before multi-character support have an empty list and one
+    /// character named after the account.
+    fn character_names(&self) -> Vec<String> {
+        if self.characters.is_empty() {
+            vec![self.name.clone()]
+        } else {
+            self.characters.clone()
+        }
+    }
+
+    /// The character list for editing, with a legacy account's implicit character made explicit.
+    fn characters_mut(&mut self) -> &mut Vec<String> {
+        if self.characters.is_empty() {
+            self.characters.push(self.name.clone());
+        }
+        &mut self.characters
+    }
+
+    /// Check a TOTP or recovery code, using it up. Returns which one matched.
+    fn check_second_factor(&mut self, code: &str, now_unix: u64) -> Option<&'static str> {
+        let secret = self.totp_secret.as_deref()?;
+        if let Some(step) = totp::verify(secret, code, now_unix, self.totp_last_step) {
+            self.totp_last_step = Some(step);
+            return Some("totp");
+        }
+        totp::take_recovery_code(&mut self.totp_recovery, code).then_some("recovery")
+    }
+
+    fn clear_totp(&mut self) {
+        self.totp_secret = None;
+        self.totp_pending = None;
+        self.totp_last_step = None;
+        self.totp_recovery.clear();
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct GoogleOAuthPending {
     code: String,
@@ -1847,10 +3718,11 @@ struct WebAuthReq {
 struct Accounts {
     path: String,
     by_name: HashMap<String, AccountRec>,
+    forgets: forget::PendingForgets,
 }
 
 impl Accounts {
-    fn load(path: String) -> Self {
+    fn load(path: String, forget_path: PathBuf) -> Self {
         let mut by_name = HashMap::new();
         if let Ok(s) = std::fs::read_to_string(&path) {
             if let Ok(v) = serde_json::from_str::<Vec<AccountRec>>(&s) {
@@ -1859,7 +3731,53 @@ impl Accounts {
                 }
             }
         }
-        Self { path, by_name }
+        Self {
+            path,
+            by_name,
+            forgets: forget::PendingForgets::load(forget_path),
+        }
+    }
+
+    /// The account's characters (see `AccountRec::character_names`).
+    fn characters(&self, account: &str) -> Vec<String> {
+        self.by_name
+            .get(account)
+            .map(AccountRec::character_names)
+            .unwrap_or_default()
+    }
+
+    /// The account owning character `name`, if any.
+    fn owner_of(&self, name: &str) -> Option<&AccountRec> {
+        self.by_name.values().find(|r| {
+            r.characters.iter().any(|c| c.eq_ignore_ascii_case(name))
+                || (r.characters.is_empty() && r.name.eq_ignore_ascii_case(name))
+        })
+    }
+
+    /// Account and character names share one namespace so a login name never lands on someone
+    /// else's character. Deleted characters stay taken until the shards have forgotten them.
+    fn name_taken(&self, name: &str) -> bool {
+        self.by_name.keys().any(|n| n.eq_ignore_ascii_case(name))
+            || self.owner_of(name).is_some()
+            || self.forgets.contains(name)
+    }
+
+    /// Record that `name`'s snapshot must go; see `forget::PendingForgets`.
+    fn forget_character(&mut self, name: &str) {
+        if let Err(e) = self.forgets.add(name) {
+            warn!(name, err = %e, "pending forget save failed");
+        }
+    }
+
+    fn character_limit(&self, account: &str, default: u32) -> u32 {
+        self.by_name
+            .get(account)
+            .and_then(|r| r.max_characters)
+            .unwrap_or(default)
+    }
+
+    fn at_character_limit(&self, account: &str, default: u32) -> bool {
+        self.characters(account).len() as u32 >= self.character_limit(account, default)
     }
 
     fn save(&self) -> anyhow::Result<()> {
@@ -1873,6 +3791,27 @@ impl Accounts {
     }
 }
 
+/// Broker-wide state that connection tasks, shard managers and the admin socket share. Every field
+/// is a handle, so cloning is cheap.
+#[derive(Clone)]
+struct Shared {
+    cfg: Arc<Config>,
+    server_info: Arc<ServerInfo>,
+    sessions: Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
+    shard_tx: tokio::sync::mpsc::Sender<ShardMsg>,
+    chat_tx: Option<tokio::sync::mpsc::Sender<ShardMsg>>,
+    accounts: Arc<tokio::sync::Mutex<Accounts>>,
+    login_throttle: Arc<tokio::sync::Mutex<LoginThrottle>>,
+    bans: Arc<tokio::sync::Mutex<ban::BanState>>,
+    reports: Arc<tokio::sync::Mutex<reports::ReportQueue>>,
+    holds: Arc<tokio::sync::Mutex<hold::HoldCache>>,
+    nearline: Arc<nearline::NearlineRing>,
+    eventlog: Arc<eventlog::EventLog>,
+    statsd: Arc<statsd::Statsd>,
+    mailer: Arc<email::Mailer>,
+    shard_signer: Arc<shard_auth::Signer>,
+}
+
 fn new_session_id() -> SessionId {
     let mut b = [0u8; 16];
     getrandom::getrandom(&mut b).expect("getrandom");
@@ -1891,7 +3830,15 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     let cfg = Arc::new(parse_args());
-    let listener = TcpListener::bind(cfg.bind).await?;
+    // After a hotboot the previous process hands over the listener and client sockets.
+    let resumed = match cfg.hotboot_from.as_deref() {
+        Some(path) => Some(hotboot::take_state(path)?),
+        None => None,
+    };
+    let listener = match resumed.as_ref() {
+        Some(st) => hotboot::unpark_listener(st.listener_fd)?,
+        None => TcpListener::bind(cfg.bind).await?,
+    };
 
     let server_info = Arc::new(ServerInfo {
         started_instant: std::time::Instant::now(),
@@ -1906,13 +3853,17 @@ async fn main() -> anyhow::Result<()> {
     let sessions: Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>> =
         Arc::new(tokio::sync::Mutex::new(HashMap::new()));
     let accounts: Arc<tokio::sync::Mutex<Accounts>> = Arc::new(tokio::sync::Mutex::new(
-        Accounts::load(cfg.accounts_path.clone()),
+        Accounts::load(cfg.accounts_path.clone(), cfg.forget_path.clone()),
     ));
     let login_throttle: Arc<tokio::sync::Mutex<LoginThrottle>> =
         Arc::new(tokio::sync::Mutex::new(LoginThrottle::default()));
     let bans: Arc<tokio::sync::Mutex<ban::BanState>> = Arc::new(tokio::sync::Mutex::new(
         ban::BanState::load(cfg.bans_path.clone()),
     ));
+    let mut report_queue = reports::ReportQueue::load(cfg.reports_path.clone());
+    report_queue.write_in_background();
+    let reports: Arc<tokio::sync::Mutex<reports::ReportQueue>> =
+        Arc::new(tokio::sync::Mutex::new(report_queue));
     let holds: Arc<tokio::sync::Mutex<hold::HoldCache>> =
         Arc::new(tokio::sync::Mutex::new(hold::HoldCache::new()));
     let eventlog = Arc::new(eventlog::EventLog::new(cfg.eventlog.clone()).await);
@@ -1920,24 +3871,150 @@ async fn main() -> anyhow::Result<()> {
         LineIdGen::new(cfg.node_id.as_deref()),
     ));
     let nearline = Arc::new(nearline::NearlineRing::new(cfg.nearline.clone()).await);
+    let statsd = Arc::new(statsd::Statsd::new(cfg.statsd_addr));
+    let mailer = Arc::new(email::Mailer::from_config(&cfg.email).await?);
+    info!(mailer = ?mailer, "email");
+    let shard_signer = Arc::new(shard_auth::Signer::new(
+        cfg.shard_auth_key.clone(),
+        cfg.shard_auth_ttl_s,
+    ));
+    if cfg.shard_auth_key.is_none() {
+        warn!("SLOPMUD_SHARD_AUTH_KEY unset; shards will treat every session as a guest");
+    }
+
+    let routes: Arc<tokio::sync::Mutex<HashMap<SessionId, shards::Route>>> =
+        Arc::new(tokio::sync::Mutex::new(HashMap::new()));
+    let (shard_tx, shard_rx) = tokio::sync::mpsc::channel::<ShardMsg>(4096);
+    let (chat_tx, chat_rx) = match cfg.chat_addr {
+        Some(_) => {
+            let (tx, rx) = tokio::sync::mpsc::channel::<ShardMsg>(4096);
+            (Some(tx), Some(rx))
+        }
+        None => (None, None),
+    };
+
+    let shared = Shared {
+        cfg: cfg.clone(),
+        server_info,
+        sessions: sessions.clone(),
+        shard_tx: shard_tx.clone(),
+        chat_tx: chat_tx.clone(),
+        accounts: accounts.clone(),
+        login_throttle,
+        bans: bans.clone(),
+        reports,
+        holds: holds.clone(),
+        nearline: nearline.clone(),
+        eventlog: eventlog.clone(),
+        statsd,
+        mailer,
+        shard_signer: shard_signer.clone(),
+    };
+
+    let mut parking = hotboot::Parking::new();
+    let spawn_conn = |stream: TcpStream,
+                      peer: SocketAddr,
+                      resume: Option<hotboot::Resume>,
+                      hooks: hotboot::Hooks| {
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_conn(stream, peer, shared, hooks, resume).await {
+                warn!(peer = %peer, err = %e, "connection ended with error");
+            }
+        });
+    };
+
+    // Resumed sessions must be registered before the shard and chat connections come up, since
+    // those re-attach whatever is in `sessions` when they connect.
+    if let Some(st) = resumed {
+        let endpoints = cfg.shard_directory.endpoints();
+        let (ready_tx, mut ready_rx) = tokio::sync::mpsc::channel::<()>(1);
+        let count = st.conns.len();
+        for conn in st.conns {
+            if conn.in_world {
+                let shard = conn
+                    .shard
+                    .filter(|s| endpoints.contains(s))
+                    .unwrap_or_else(|| cfg.shard_directory.default_shard());
+                routes.lock().await.insert(
+                    SessionId(conn.session),
+                    shards::Route {
+                        shard,
+                        snapshot: conn.handoff_snapshot.clone().map(Bytes::from),
+                    },
+                );
+            }
+            let stream = match hotboot::unpark(conn.fd) {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!(fd = conn.fd, err = %e, "hotboot: lost a connection");
+                    routes.lock().await.remove(&SessionId(conn.session));
+                    continue;
+                }
+            };
+            let peer = conn.peer;
+            spawn_conn(
+                stream,
+                peer,
+                Some(hotboot::Resume {
+                    conn,
+                    ready: ready_tx.clone(),
+                }),
+                parking.hooks(),
+            );
+        }
+        drop(ready_tx);
+        let _ = ready_rx.recv().await;
+        info!(conns = count, "hotboot: resumed");
+    }
 
-    let (shard_tx, shard_rx) = tokio::sync::mpsc::channel::<ShardMsg>(4096);
-    tokio::spawn(shard_manager_task(
-        cfg.shard_addr,
+    let (handoff_tx, handoff_rx) = tokio::sync::mpsc::channel::<shards::ShardHandoff>(256);
+    let mut shard_txs = HashMap::new();
+    for addr in cfg.shard_directory.endpoints() {
+        let (tx, rx) = tokio::sync::mpsc::channel::<ShardMsg>(4096);
+        shard_txs.insert(addr, tx);
+        tokio::spawn(shard_manager_task(
+            addr,
+            shared.clone(),
+            routes.clone(),
+            handoff_tx.clone(),
+            line_ids.clone(),
+            rx,
+        ));
+    }
+    tokio::spawn(shard_router_task(
+        cfg.shard_directory.clone(),
         sessions.clone(),
-        line_ids.clone(),
-        nearline.clone(),
-        eventlog.clone(),
+        routes.clone(),
+        shard_txs,
+        shard_signer.clone(),
         shard_rx,
+        handoff_rx,
     ));
 
-    tokio::spawn(admin_server_task(
-        cfg.admin_bind,
-        bans.clone(),
-        sessions.clone(),
+    if let (Some(addr), Some(rx)) = (cfg.chat_addr, chat_rx) {
+        tokio::spawn(chat_manager_task(
+            addr,
+            sessions.clone(),
+            line_ids.clone(),
+            nearline.clone(),
+            eventlog.clone(),
+            rx,
+        ));
+    }
+
+    tokio::spawn(account_purge_task(
         accounts.clone(),
+        holds.clone(),
+        sessions.clone(),
+        eventlog.clone(),
+        shard_tx.clone(),
+        shard_signer.clone(),
+        cfg.players_path.clone(),
     ));
 
+    tokio::spawn(admin_server_task(cfg.admin_bind, shared.clone()));
+
     tokio::spawn(sbc_holds_events_task(
         cfg.sbc_events_sock.clone(),
         holds.clone(),
@@ -1950,55 +4027,313 @@ async fn main() -> anyhow::Result<()> {
         node_id = %cfg.node_id.as_deref().unwrap_or("-"),
         admin_bind = %cfg.admin_bind,
         bans_path = %cfg.bans_path.display(),
+        reports_path = %cfg.reports_path.display(),
         sbc_admin_sock = %cfg.sbc_admin_sock.display(),
         sbc_events_sock = %cfg.sbc_events_sock.display(),
         "session broker listening"
     );
 
+    let mut hotboot_signal =
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined2())?;
     loop {
-        let (stream, peer) = listener.accept().await?;
-        let sessions = sessions.clone();
-        let shard_tx = shard_tx.clone();
-        let server_info = server_info.clone();
-        let cfg = cfg.clone();
-        let accounts = accounts.clone();
-        let login_throttle = login_throttle.clone();
-        let bans = bans.clone();
-        let holds = holds.clone();
-        let nearline = nearline.clone();
-        let eventlog = eventlog.clone();
-        tokio::spawn(async move {
-            if let Err(e) = handle_conn(
-                stream,
-                peer,
-                sessions,
-                shard_tx,
-                server_info,
-                cfg,
-                accounts,
-                login_throttle,
-                bans,
-                holds,
-                nearline,
-                eventlog,
-            )
-            .await
+        let (stream, peer) = tokio::select! {
+            res = listener.accept() => res?,
+            _ = hotboot_signal.recv() => {
+                let conns = park_for_hotboot(&mut parking, &routes, &eventlog).await;
+                let st = hotboot::State::new(&listener, conns);
+                info!(conns = st.conns.len(), path = %cfg.hotboot_path.display(), "hotboot: exec");
+                let err = hotboot::exec(&cfg.hotboot_path, &st);
+
+                // Still here: take the connections back and carry on as before.
+                warn!(err = %format!("{err:#}"), "hotboot failed; resuming connections");
+                parking = hotboot::Parking::new();
+                let (ready_tx, _ready_rx) = tokio::sync::mpsc::channel::<()>(1);
+                for conn in st.conns {
+                    let stream = match hotboot::unpark(conn.fd) {
+                        Ok(s) => s,
+                        Err(e) => {
+                            warn!(fd = conn.fd, err = %e, "hotboot: lost a connection");
+                            routes.lock().await.remove(&SessionId(conn.session));
+                            continue;
+                        }
+                    };
+                    let peer = conn.peer;
+                    let resume = hotboot::Resume {
+                        conn,
+                        ready: ready_tx.clone(),
+                    };
+                    spawn_conn(stream, peer, Some(resume), parking.hooks());
+                }
+                continue;
+            }
+        };
+        spawn_conn(stream, peer, None, parking.hooks());
+    }
+}
+
+/// Hotboot: park every connection, noting the shard each in-world session is on.
+async fn park_for_hotboot(
+    parking: &mut hotboot::Parking,
+    routes: &Arc<tokio::sync::Mutex<HashMap<SessionId, shards::Route>>>,
+    eventlog: &eventlog::EventLog,
+) -> Vec<hotboot::Conn> {
+    info!("hotboot: parking connections");
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    let mut conns = parking.park_all(deadline).await;
+    {
+        let r = routes.lock().await;
+        for c in conns.iter_mut() {
+            if let Some(route) = r.get(&SessionId(c.session)) {
+                c.shard = Some(route.shard);
+                c.handoff_snapshot = route
+                    .snapshot
+                    .as_ref()
+                    .map(|b| String::from_utf8_lossy(b).into_owned());
+            }
+        }
+    }
+    let ts = Utc::now().to_rfc3339();
+    let entry = format!(
+        "ts={} kind=hotboot conns={}",
+        logfmt_str(&ts),
+        logfmt_str(&conns.len().to_string()),
+    );
+    eventlog.log_line(LogStream::All, &entry).await;
+    eventlog.flush().await;
+    conns
+}
+
+/// Removes accounts whose `account delete` grace period has run out, and tells the shards to
+/// forget their characters. Accounts under a legal hold (on the account or any character) wait
+/// until the hold is lifted; so do accounts with a character in the world.
+async fn account_purge_task(
+    accounts: Arc<tokio::sync::Mutex<Accounts>>,
+    holds: Arc<tokio::sync::Mutex<hold::HoldCache>>,
+    sessions: Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
+    eventlog: Arc<eventlog::EventLog>,
+    shard_tx: tokio::sync::mpsc::Sender<ShardMsg>,
+    shard_signer: Arc<shard_auth::Signer>,
+    players_path: PathBuf,
+) {
+    // Log a held account once, not every tick.
+    let mut held_logged: HashSet<String> = HashSet::new();
+    let mut tick = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        tick.tick().await;
+        {
+            // Forgets the shards have carried out (the snapshot is gone).
+            let saved = load_saved_players(&players_path);
+            let mut a = accounts.lock().await;
+            if let Err(e) = a.forgets.prune(|n| find_saved_player(&saved, n).is_none()) {
+                warn!(err = %e, "pending forget save failed");
+            }
+        }
+        let now_unix = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let due = {
+            let a = accounts.lock().await;
+            a.by_name
+                .values()
+                .filter(|r| r.delete_after_unix.is_some_and(|t| t <= now_unix))
+                .map(|r| (r.name.clone(), r.character_names()))
+                .collect::<Vec<_>>()
+        };
+        held_logged.retain(|n| due.iter().any(|(acct, _)| acct == n));
+
+        for (acct, characters) in due {
+            let held = {
+                let h = holds.lock().await;
+                std::iter::once(&acct)
+                    .chain(&characters)
+                    .find(|n| h.is_held(n).is_some())
+                    .cloned()
+            };
+            if let Some(held) = held {
+                if held_logged.insert(acct.clone()) {
+                    info!(account = %acct, held = %held, "account deletion waiting on legal hold");
+                }
+                continue;
+            }
+            let online = {
+                let m = sessions.lock().await;
+                m.values()
+                    .any(|si| characters.iter().any(|c| c.eq_ignore_ascii_case(&si.name)))
+            };
+            if online {
+                continue;
+            }
+
             {
-                warn!(peer = %peer, err = %e, "connection ended with error");
+                let mut a = accounts.lock().await;
+                // Cancelled since we looked.
+                let still_due = a
+                    .by_name
+                    .get(&acct)
+                    .is_some_and(|r| r.delete_after_unix.is_some_and(|t| t <= now_unix));
+                if !still_due {
+                    continue;
+                }
+                let Some(rec) = a.by_name.remove(&acct) else {
+                    continue;
+                };
+                if let Err(e) = a.save() {
+                    warn!(account = %acct, err = %e, "accounts save failed; keeping account");
+                    a.by_name.insert(acct.clone(), rec);
+                    continue;
+                }
+                for c in &characters {
+                    a.forget_character(c);
+                }
             }
-        });
+            for c in &characters {
+                send_player_op(&shard_tx, &shard_signer, PlayerOp::Forget { name: c }).await;
+            }
+
+            let ts = Utc::now().to_rfc3339();
+            let entry = format!(
+                "ts={} kind=account_deleted name={} characters={}",
+                logfmt_str(&ts),
+                logfmt_str(&acct),
+                logfmt_str(&characters.join(",")),
+            );
+            eventlog.log_line(LogStream::All, &entry).await;
+            eventlog.log_line(LogStream::Login, &entry).await;
+            info!(account = %acct, characters = characters.len(), "account deleted");
+        }
+    }
+}
+
+/// Fans broker traffic out to the shard each session currently lives on, and moves sessions
+/// between shards on handoff.
+async fn shard_router_task(
+    directory: shards::ShardDirectory,
+    sessions: Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
+    routes: Arc<tokio::sync::Mutex<HashMap<SessionId, shards::Route>>>,
+    shard_txs: HashMap<SocketAddr, tokio::sync::mpsc::Sender<ShardMsg>>,
+    shard_signer: Arc<shard_auth::Signer>,
+    mut rx: tokio::sync::mpsc::Receiver<ShardMsg>,
+    mut handoff_rx: tokio::sync::mpsc::Receiver<shards::ShardHandoff>,
+) {
+    loop {
+        tokio::select! {
+            msg = rx.recv() => {
+                let Some(msg) = msg else {
+                    return;
+                };
+                // Player ops aren't tied to a session; every shard may hold the character.
+                if msg.t == REQ_PLAYER_OP {
+                    for tx in shard_txs.values() {
+                        let _ = tx.send(msg.clone()).await;
+                    }
+                    continue;
+                }
+                let shard = {
+                    let mut r = routes.lock().await;
+                    let shard = r
+                        .entry(msg.session)
+                        .or_insert_with(|| shards::Route {
+                            shard: directory.default_shard(),
+                            snapshot: None,
+                        })
+                        .shard;
+                    if msg.t == REQ_DETACH {
+                        r.remove(&msg.session);
+                    }
+                    shard
+                };
+                if let Some(tx) = shard_txs.get(&shard) {
+                    let _ = tx.send(msg).await;
+                }
+            }
+            h = handoff_rx.recv() => {
+                let Some(h) = h else {
+                    return;
+                };
+                let to = directory.lookup(&h.area);
+                if to == h.from {
+                    // The shard doesn't own the area but the directory says it does. Attaching the
+                    // snapshot back makes it keep the character rather than loop.
+                    warn!(area = %h.area, shard_addr = %to, "handoff area maps to the same shard");
+                }
+                let si = { sessions.lock().await.get(&h.session).cloned() };
+                let Some(si) = si else {
+                    continue;
+                };
+                {
+                    let mut r = routes.lock().await;
+                    if !r.contains_key(&h.session) {
+                        // Detached while the handoff was in flight.
+                        continue;
+                    }
+                    r.insert(
+                        h.session,
+                        shards::Route {
+                            shard: to,
+                            snapshot: Some(h.snapshot.clone()),
+                        },
+                    );
+                }
+                info!(area = %h.area, from = %h.from, to = %to, "shard handoff");
+                let body = attach_body(
+                    h.session,
+                    &shard_signer,
+                    si.is_bot,
+                    si.auth.as_deref(),
+                    &si.race,
+                    &si.class,
+                    &si.sex,
+                    &si.pronouns,
+                    si.name.as_bytes(),
+                    Some(&h.snapshot),
+                    false,
+                );
+                let body = match body {
+                    Ok(b) => b,
+                    Err(e) => {
+                        warn!(area = %h.area, err = %e, "cannot encode handoff attach");
+                        continue;
+                    }
+                };
+                if let Some(tx) = shard_txs.get(&to) {
+                    let _ = tx
+                        .send(ShardMsg {
+                            t: REQ_ATTACH_V2,
+                            session: h.session,
+                            body,
+                        })
+                        .await;
+                }
+            }
+        }
     }
 }
 
 async fn shard_manager_task(
     shard_addr: SocketAddr,
-    sessions: Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
+    shared: Shared,
+    routes: Arc<tokio::sync::Mutex<HashMap<SessionId, shards::Route>>>,
+    handoff_tx: tokio::sync::mpsc::Sender<shards::ShardHandoff>,
     line_ids: Arc<tokio::sync::Mutex<LineIdGen>>,
-    nearline: Arc<nearline::NearlineRing>,
-    eventlog: Arc<eventlog::EventLog>,
     mut rx: tokio::sync::mpsc::Receiver<ShardMsg>,
 ) {
+    let Shared {
+        cfg,
+        sessions,
+        chat_tx,
+        accounts,
+        bans,
+        nearline,
+        eventlog,
+        shard_signer,
+        ..
+    } = &shared;
+    let input_queue_max = cfg.input_queue_max;
     let mut announced_down = false;
+    // Cleared when the shard drops a HELLO; we then speak v1 until the next reconnect.
+    let mut try_hello = true;
+    let mut input_queue = shards::InputQueue::new(input_queue_max);
 
     loop {
         match TcpStream::connect(shard_addr).await {
@@ -2010,11 +4345,48 @@ async fn shard_manager_task(
                 let mut fr = FrameReader::new(rd);
                 let mut fw = FrameWriter::new(wr);
 
-                // Re-attach all live sessions.
+                let peer_features = if try_hello {
+                    match shard_hello(&mut fr, &mut fw).await {
+                        Some(f) => f,
+                        None => {
+                            warn!(shard_addr = %shard_addr, "shard did not answer hello; using v1");
+                            try_hello = false;
+                            continue;
+                        }
+                    }
+                } else {
+                    0
+                };
+                try_hello = true;
+
+                // Mutes set or lifted while the shard was away; it keeps its own copy from here.
+                let mutes = bans.lock().await.snapshot_file().character_mutes;
+                for m in &mutes {
+                    let op = PlayerOp::Mute {
+                        name: &m.name_lc,
+                        until_unix: m.until_unix,
+                    };
+                    let (sid, body) = player_op_frame(shard_signer, &op);
+                    if let Ok((t, body)) = downgrade_req(REQ_PLAYER_OP, body, peer_features) {
+                        let _ = write_req(&mut fw, t, sid, &body).await;
+                    }
+                }
+                // Deletions this shard may have missed while it was away.
+                let forgets = accounts.lock().await.forgets.names();
+                for name in &forgets {
+                    let (sid, body) = player_op_frame(shard_signer, &PlayerOp::Forget { name });
+                    if let Ok((t, body)) = downgrade_req(REQ_PLAYER_OP, body, peer_features) {
+                        let _ = write_req(&mut fw, t, sid, &body).await;
+                    }
+                }
+
+                // Re-attach the live sessions that live on this shard.
+                let routed = routed_sessions(&routes, shard_addr).await;
                 let snapshot = {
                     let m = sessions.lock().await;
                     m.iter()
-                        .map(|(sid, s)| {
+                        .filter_map(|(sid, s)| Some((sid, s, routed.get(sid)?.clone())))
+                        .map(|(sid, s, handoff)| {
                             (
                                 *sid,
                                 s.is_bot,
@@ -2024,31 +4396,251 @@ async fn shard_manager_task(
                                 s.sex.clone(),
                                 s.pronouns.clone(),
                                 s.name.clone(),
+                                handoff,
                             )
                         })
                         .collect::<Vec<_>>()
                 };
-                for (sid, is_bot, auth, race, class, sex, pronouns, name) in snapshot {
-                    let body = attach_body(
-                        is_bot,
-                        auth.as_deref(),
-                        &race,
-                        &class,
-                        &sex,
-                        &pronouns,
-                        name.as_bytes(),
-                    );
-                    let _ = write_req(&mut fw, REQ_ATTACH, sid, &body).await;
+                for (sid, is_bot, auth, race, class, sex, pronouns, name, handoff) in snapshot {
+                    let body = attach_body(
+                        sid,
+                        shard_signer,
+                        is_bot,
+                        auth.as_deref(),
+                        &race,
+                        &class,
+                        &sex,
+                        &pronouns,
+                        name.as_bytes(),
+                        handoff.as_deref(),
+                        false,
+                    )
+                    .and_then(|body| downgrade_req(REQ_ATTACH_V2, body, peer_features));
+                    match body {
+                        Ok((t, body)) => {
+                            let _ = write_req(&mut fw, t, sid, &body).await;
+                        }
+                        Err(e) => warn!(err = %e, "cannot re-attach session"),
+                    }
+                }
+
+                // Resend input the shard never acked or that was typed while it was down. A shard
+                // that acks skips numbers it already ran; one that doesn't gets each line once.
+                let acks = peer_features & FEATURE_INPUT_ACK != 0;
+                for (sid, pending) in input_queue.drain() {
+                    if !routed.contains_key(&sid) {
+                        continue;
+                    }
+                    let note = format!(
+                        "# shard back; sending {} queued command(s)\r\n",
+                        pending.len()
+                    );
+                    notify_one(sessions, sid, Bytes::from(note)).await;
+                    for (_, body) in &pending {
+                        if let Ok((t, body)) =
+                            downgrade_req(REQ_INPUT_V2, body.clone(), peer_features)
+                        {
+                            let _ = write_req(&mut fw, t, sid, &body).await;
+                        }
+                    }
+                    if acks {
+                        input_queue.restore(sid, pending);
+                    }
+                }
+                let _ = fw.flush().await;
+
+                // Connection loop.
+                loop {
+                    tokio::select! {
+                        msg = rx.recv() => {
+                            let Some(msg) = msg else {
+                                return;
+                            };
+                            if msg.t == REQ_DETACH {
+                                input_queue.forget(msg.session);
+                            }
+                            if let (REQ_INPUT_V2, true, Some(seq)) =
+                                (msg.t, acks, input_seq(&msg.body))
+                            {
+                                // A full queue means the shard is badly behind; lines past that
+                                // are sent but not resent.
+                                let _ = input_queue.push(msg.session, seq, msg.body.clone());
+                            }
+                            match downgrade_req(msg.t, msg.body, peer_features) {
+                                Ok((t, body)) => {
+                                    let _ = write_req(&mut fw, t, msg.session, &body).await;
+                                }
+                                Err(e) => warn!(err=%e, "dropping unencodable shard request"),
+                            }
+                        }
+                        res = fr.read_frame() => {
+                            let frame = match res {
+                                Ok(Some(f)) => f,
+                                Ok(None) => break,
+                                Err(_) => break,
+                            };
+                            match mudproto::shard::parse_resp(frame) {
+                                Ok(ShardResp::Handoff { session, area, snapshot }) => {
+                                    let h = shards::ShardHandoff {
+                                        from: shard_addr,
+                                        session,
+                                        area: String::from_utf8_lossy(&area).into_owned(),
+                                        snapshot,
+                                    };
+                                    let _ = handoff_tx.send(h).await;
+                                }
+                                Ok(ShardResp::InputAck { session, seq }) => {
+                                    input_queue.ack(session, seq);
+                                }
+                                Ok(ShardResp::ChatScope { session, party, guild, ignores }) => {
+                                    if let Some(chat_tx) = chat_tx.as_ref() {
+                                        forward_chat_scope(
+                                            chat_tx, shard_addr, session, party, guild, &ignores,
+                                        )
+                                        .await;
+                                    }
+                                }
+                                Ok(resp) => {
+                                    route_resp(resp, sessions, &line_ids, nearline, eventlog)
+                                        .await
+                                }
+                                Err(e) => {
+                                    warn!(err=%e, "bad shard response");
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // Shard connection dropped.
+                warn!(shard_addr = %shard_addr, "shard disconnected; reconnecting");
+                notify_routed(
+                    sessions,
+                    &routes,
+                    shard_addr,
+                    b"# shard disconnected; reconnecting (commands are queued)...\r\n",
+                )
+                .await;
+            }
+            Err(e) => {
+                // A dying shard can drop our HELLO on its way out; whatever comes back next may
+                // well speak it, so ask again.
+                try_hello = true;
+                if !announced_down {
+                    announced_down = true;
+                    warn!(shard_addr = %shard_addr, err=%e, "shard offline; retrying");
+                    notify_routed(
+                        sessions,
+                        &routes,
+                        shard_addr,
+                        b"# shard offline; retrying (commands are queued)...\r\n",
+                    )
+                    .await;
+                }
+
+                // Hold input for replay (bounded); everything else is redone on reconnect.
+                while let Ok(msg) = rx.try_recv() {
+                    match msg.t {
+                        REQ_INPUT_V2 => {
+                            let session = msg.session;
+                            let queued = input_seq(&msg.body)
+                                .is_some_and(|seq| input_queue.push(session, seq, msg.body));
+                            let note = if queued {
+                                format!(
+                                    "# shard offline; queued ({}/{})\r\n",
+                                    input_queue.len(session),
+                                    input_queue.max()
+                                )
+                            } else {
+                                "# shard offline; queue full, input dropped\r\n".to_string()
+                            };
+                            notify_one(sessions, session, Bytes::from(note)).await;
+                        }
+                        REQ_DETACH => input_queue.forget(msg.session),
+                        _ => {}
+                    }
+                }
+
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+        }
+    }
+}
+
+/// Pass a shard's party/guild ids and ignore list for `session` on to chatd. The ids are only
+/// unique within one shard, so they're qualified with its address; names are global already.
+async fn forward_chat_scope(
+    chat_tx: &tokio::sync::mpsc::Sender<ShardMsg>,
+    shard: SocketAddr,
+    session: SessionId,
+    party: Option<Bytes>,
+    guild: Option<Bytes>,
+    ignores: &[Bytes],
+) {
+    let qualify = |id: Option<Bytes>| {
+        id.map(|id| format!("{shard}/{}", String::from_utf8_lossy(&id)).into_bytes())
+    };
+    let (party, guild) = (qualify(party), qualify(guild));
+    match mudproto::chat::encode_scope(party.as_deref(), guild.as_deref(), ignores) {
+        Ok(body) => {
+            let _ = chat_tx
+                .send(ShardMsg {
+                    t: mudproto::chat::REQ_SCOPE,
+                    session,
+                    body,
+                })
+                .await;
+        }
+        Err(e) => warn!(err = %e, "cannot encode chat scope"),
+    }
+}
+
+/// Bridges in-world sessions to chatd. Chat lines are delivered like shard output, so they
+/// land in scrollback and the event log too.
+async fn chat_manager_task(
+    chat_addr: SocketAddr,
+    sessions: Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
+    line_ids: Arc<tokio::sync::Mutex<LineIdGen>>,
+    nearline: Arc<nearline::NearlineRing>,
+    eventlog: Arc<eventlog::EventLog>,
+    mut rx: tokio::sync::mpsc::Receiver<ShardMsg>,
+) {
+    let mut announced_down = false;
+    // Latest scope per session; chatd loses them with the connection, like joins.
+    let mut scopes: HashMap<SessionId, Bytes> = HashMap::new();
+
+    loop {
+        match TcpStream::connect(chat_addr).await {
+            Ok(stream) => {
+                announced_down = false;
+                info!(chat_addr = %chat_addr, "connected to chat");
+
+                let (rd, wr) = stream.into_split();
+                let mut fr = FrameReader::new(rd);
+                let mut fw = FrameWriter::new(wr);
+
+                // chatd forgets sessions when a broker connection drops; re-join everyone.
+                let joined = {
+                    let m = sessions.lock().await;
+                    m.iter()
+                        .map(|(sid, s)| (*sid, s.name.clone()))
+                        .collect::<Vec<_>>()
+                };
+                for (sid, name) in joined {
+                    let _ =
+                        write_req(&mut fw, mudproto::chat::REQ_JOIN, sid, name.as_bytes()).await;
+                    if let Some(scope) = scopes.get(&sid) {
+                        let _ = write_req(&mut fw, mudproto::chat::REQ_SCOPE, sid, scope).await;
+                    }
                 }
-                let _ = fw.flush().await;
 
-                // Connection loop.
                 loop {
                     tokio::select! {
                         msg = rx.recv() => {
                             let Some(msg) = msg else {
                                 return;
                             };
+                            remember_chat_scope(&mut scopes, &msg);
                             let _ = write_req(&mut fw, msg.t, msg.session, &msg.body).await;
                         }
                         res = fr.read_frame() => {
@@ -2057,37 +4649,41 @@ async fn shard_manager_task(
                                 Ok(None) => break,
                                 Err(_) => break,
                             };
-                            match mudproto::shard::parse_resp(frame) {
-                                Ok(resp) => {
-                                    route_resp(resp, &sessions, &line_ids, &nearline, &eventlog)
-                                        .await
-                                }
+                            let (session, line) = match mudproto::chat::parse_event(frame) {
+                                Ok(ChatEvent::Line { session, line }) => (session, line),
+                                Ok(ChatEvent::Err { session, msg }) => (session, msg),
                                 Err(e) => {
-                                    warn!(err=%e, "bad shard response");
+                                    warn!(err=%e, "bad chat event");
+                                    continue;
                                 }
-                            }
+                            };
+                            route_resp(
+                                ShardResp::Output { session, line },
+                                &sessions,
+                                &line_ids,
+                                &nearline,
+                                &eventlog,
+                            )
+                            .await;
                         }
                     }
                 }
 
-                // Shard connection dropped.
-                warn!(shard_addr = %shard_addr, "shard disconnected; reconnecting");
-                notify_all(&sessions, b"# shard disconnected; reconnecting...\r\n").await;
+                warn!(chat_addr = %chat_addr, "chat disconnected; reconnecting");
             }
             Err(e) => {
                 if !announced_down {
                     announced_down = true;
-                    warn!(shard_addr = %shard_addr, err=%e, "shard offline; retrying");
-                    notify_all(&sessions, b"# shard offline; retrying...\r\n").await;
+                    warn!(chat_addr = %chat_addr, err=%e, "chat offline; retrying");
                 }
 
-                // Don't let the outbound queue grow unbounded while offline.
                 while let Ok(msg) = rx.try_recv() {
-                    if msg.t == REQ_INPUT {
+                    remember_chat_scope(&mut scopes, &msg);
+                    if msg.t == mudproto::chat::REQ_SAY {
                         notify_one(
                             &sessions,
                             msg.session,
-                            b"# shard offline; input dropped\r\n",
+                            Bytes::from_static(b"# chat offline; message dropped\r\n"),
                         )
                         .await;
                     }
@@ -2099,6 +4695,18 @@ async fn shard_manager_task(
     }
 }
 
+fn remember_chat_scope(scopes: &mut HashMap<SessionId, Bytes>, msg: &ShardMsg) {
+    match msg.t {
+        mudproto::chat::REQ_SCOPE => {
+            scopes.insert(msg.session, msg.body.clone());
+        }
+        mudproto::chat::REQ_LEAVE => {
+            scopes.remove(&msg.session);
+        }
+        _ => {}
+    }
+}
+
 async fn route_resp(
     resp: ShardResp,
     sessions: &Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
@@ -2163,7 +4771,7 @@ async fn route_resp(
                     eventlog.log_line(LogStream::Character(&name), &entry).await;
                 }
 
-                let _ = si.write_tx.send(line).await;
+                let _ = si.write_tx.send(Out::Markup(line)).await;
             }
         }
         ShardResp::Err { session, msg } => {
@@ -2221,9 +4829,13 @@ async fn route_resp(
                     eventlog.log_line(LogStream::Character(&name), &entry).await;
                 }
 
-                let _ = si.write_tx.send(msg).await;
+                let _ = si.write_tx.send(Out::Markup(msg)).await;
             }
         }
+        // Only expected during the handshake; a late one carries nothing to route.
+        ShardResp::Hello { .. } => {}
+        // Handled by shard_manager_task before routing.
+        ShardResp::Handoff { .. } | ShardResp::InputAck { .. } | ShardResp::ChatScope { .. } => {}
     }
 }
 
@@ -2240,6 +4852,14 @@ enum AdminReq {
         created_by: String,
         reason: String,
     },
+    /// Mute a character for `duration_s`; 0 lifts it. Listed with the bans.
+    SilenceCharacter {
+        name: String,
+        created_by: String,
+        duration_s: u64,
+        #[serde(default)]
+        reason: String,
+    },
     ListBans {},
     ListSessions {},
     CreateAccountPassword {
@@ -2260,6 +4880,42 @@ enum AdminReq {
         name: String,
     },
     ListAccounts {},
+    /// `None` goes back to SLOPMUD_MAX_CHARACTERS.
+    SetAccountCharacterLimit {
+        name: String,
+        #[serde(default)]
+        max_characters: Option<u32>,
+    },
+    /// Turn off 2fa for a player who lost both their device and recovery codes.
+    ResetAccountTotp {
+        name: String,
+    },
+    /// Open abuse reports; `all` includes resolved ones still on file.
+    ListReports {
+        #[serde(default)]
+        all: bool,
+    },
+    /// `report` is the number (`12`) or the full report id.
+    GetReport {
+        report: String,
+    },
+    /// `None` unassigns.
+    AssignReport {
+        report: String,
+        by: String,
+        #[serde(default)]
+        assignee: Option<String>,
+    },
+    ResolveReport {
+        report: String,
+        by: String,
+        /// dismiss | warn | mute | ban
+        action: String,
+        #[serde(default)]
+        target: Option<String>,
+        #[serde(default)]
+        note: String,
+    },
 }
 
 #[derive(Debug, Serialize)]
@@ -2280,10 +4936,22 @@ enum AdminResp {
         has_password: bool,
         #[serde(default, skip_serializing_if = "Vec::is_empty")]
         caps: Vec<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        characters: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_characters: Option<u32>,
+        totp_enabled: bool,
     },
     OkAccounts {
         names: Vec<String>,
     },
+    OkReports {
+        reports: Vec<reports::Report>,
+    },
+    OkReport {
+        report: Box<reports::Report>,
+        kicked: u64,
+    },
     Err {
         message: String,
     },
@@ -2324,34 +4992,31 @@ fn normalize_caps_list(caps: &[String]) -> Vec<String> {
     out
 }
 
-async fn admin_server_task(
-    bind: SocketAddr,
-    bans: Arc<tokio::sync::Mutex<ban::BanState>>,
-    sessions: Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
-    accounts: Arc<tokio::sync::Mutex<Accounts>>,
-) -> anyhow::Result<()> {
+async fn admin_server_task(bind: SocketAddr, shared: Shared) -> anyhow::Result<()> {
     let listener = TcpListener::bind(bind).await?;
     info!(bind=%bind, "admin server listening");
 
     loop {
         let (stream, peer) = listener.accept().await?;
-        let bans = bans.clone();
-        let sessions = sessions.clone();
-        let accounts = accounts.clone();
+        let shared = shared.clone();
         tokio::spawn(async move {
-            if let Err(e) = handle_admin_conn(stream, bans, sessions, accounts).await {
+            if let Err(e) = handle_admin_conn(stream, &shared).await {
                 warn!(peer=%peer, err=%e, "admin request failed");
             }
         });
     }
 }
 
-async fn handle_admin_conn(
-    stream: TcpStream,
-    bans: Arc<tokio::sync::Mutex<ban::BanState>>,
-    sessions: Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
-    accounts: Arc<tokio::sync::Mutex<Accounts>>,
-) -> anyhow::Result<()> {
+async fn handle_admin_conn(stream: TcpStream, shared: &Shared) -> anyhow::Result<()> {
+    let Shared {
+        sessions,
+        accounts,
+        bans,
+        reports,
+        nearline,
+        eventlog,
+        ..
+    } = shared;
     let (rd, mut wr) = stream.into_split();
     let mut rd = BufReader::new(rd);
 
@@ -2396,7 +5061,7 @@ async fn handle_admin_conn(
                     let mut b = bans.lock().await;
                     b.upsert_char_ban(&nm, now_unix, created_by, reason)?
                 };
-                let kicked = kick_by_char(&sessions, &nm).await;
+                let kicked = kick_by_char(sessions, &nm).await;
                 AdminResp::Ok { kicked }
             }
         }
@@ -2410,15 +5075,89 @@ async fn handle_admin_conn(
                 let (_changed, pfx) = b.upsert_ip_ban(&cidr, now_unix, created_by, reason)?;
                 pfx
             };
-            let kicked = kick_by_ip(&sessions, &pfx).await;
+            let kicked = kick_by_ip(sessions, &pfx).await;
             AdminResp::Ok { kicked }
         }
+        AdminReq::SilenceCharacter {
+            name,
+            created_by,
+            duration_s,
+            reason,
+        } => {
+            let nm = sanitize_name(&name);
+            if nm.is_empty() {
+                AdminResp::Err {
+                    message: "bad name".to_string(),
+                }
+            } else {
+                silence_char(shared, &created_by, &nm, duration_s, &reason).await?;
+                AdminResp::Ok { kicked: 0 }
+            }
+        }
         AdminReq::ListBans {} => {
             let b = bans.lock().await;
             AdminResp::OkBans {
                 bans: b.snapshot_file(),
             }
         }
+        AdminReq::ListReports { all } => {
+            let q = reports.lock().await;
+            AdminResp::OkReports {
+                reports: q.list(all).into_iter().cloned().collect(),
+            }
+        }
+        AdminReq::GetReport { report } => {
+            let r = reports.lock().await.get(&report).cloned();
+            match r {
+                Some(r) => AdminResp::OkReport {
+                    report: Box::new(report_with_context(nearline, r).await),
+                    kicked: 0,
+                },
+                None => AdminResp::Err {
+                    message: "report not found".to_string(),
+                },
+            }
+        }
+        AdminReq::AssignReport {
+            report,
+            by,
+            assignee,
+        } => {
+            let assignee = assignee
+                .map(|a| sanitize_name(&a))
+                .filter(|a| !a.is_empty());
+            match assign_report(reports, eventlog, &by, &report, assignee).await {
+                Ok(report) => AdminResp::OkReport {
+                    report: Box::new(report),
+                    kicked: 0,
+                },
+                Err(e) => AdminResp::Err {
+                    message: e.to_string(),
+                },
+            }
+        }
+        AdminReq::ResolveReport {
+            report,
+            by,
+            action,
+            target,
+            note,
+        } => match reports::Action::parse(&action) {
+            None => AdminResp::Err {
+                message: "action must be dismiss, warn, mute or ban".to_string(),
+            },
+            Some(action) => {
+                match resolve_report(shared, &by, &report, action, target.as_deref(), &note).await {
+                    Ok((report, kicked)) => AdminResp::OkReport {
+                        report: Box::new(report),
+                        kicked,
+                    },
+                    Err(e) => AdminResp::Err {
+                        message: e.to_string(),
+                    },
+                }
+            }
+        },
         AdminReq::ListSessions {} => {
             let snapshot = {
                 let m = sessions.lock().await;
@@ -2471,7 +5210,7 @@ async fn handle_admin_conn(
 
                 let created = {
                     let mut a = accounts.lock().await;
-                    if a.by_name.contains_key(&uname) {
+                    if a.name_taken(&uname) {
                         false
                     } else {
                         a.by_name.insert(
@@ -2485,6 +5224,19 @@ async fn handle_admin_conn(
                                 oidc_email: None,
                                 caps,
                                 email: None,
+                                color: None,
+                                aliases: Default::default(),
+                                characters: vec![uname.clone()],
+                                max_characters: None,
+                                totp_secret: None,
+                                totp_pending: None,
+                                totp_last_step: None,
+                                totp_recovery: Vec::new(),
+                                email_verified: false,
+                                email_verify: None,
+                                password_reset: None,
+                                delete_after_unix: None,
+                                renamed_unix: None,
                                 created_unix: now_unix,
                             },
                         );
@@ -2499,9 +5251,12 @@ async fn handle_admin_conn(
                     }
                 } else {
                     AdminResp::OkAccount {
+                        characters: vec![uname.clone()],
                         name: uname,
                         has_password: true,
                         caps: caps_vec,
+                        max_characters: None,
+                        totp_enabled: false,
                     }
                 }
             }
@@ -2530,11 +5285,17 @@ async fn handle_admin_conn(
                         let has_password =
                             r.pw_hash.as_deref().map(|s| !s.is_empty()).unwrap_or(false);
                         let caps = r.caps.clone().unwrap_or_default();
+                        let characters = r.character_names();
+                        let max_characters = r.max_characters;
+                        let totp_enabled = r.totp_secret.is_some();
                         a.save()?;
                         AdminResp::OkAccount {
                             name: uname,
                             has_password,
                             caps,
+                            characters,
+                            max_characters,
+                            totp_enabled,
                         }
                     } else {
                         AdminResp::Err {
@@ -2572,11 +5333,17 @@ async fn handle_admin_conn(
                             };
                             let has_password =
                                 r.pw_hash.as_deref().map(|s| !s.is_empty()).unwrap_or(false);
+                            let characters = r.character_names();
+                            let max_characters = r.max_characters;
+                            let totp_enabled = r.totp_secret.is_some();
                             a.save()?;
                             AdminResp::OkAccount {
                                 name: uname,
                                 has_password,
                                 caps: merged,
+                                characters,
+                                max_characters,
+                                totp_enabled,
                             }
                         } else {
                             AdminResp::Err {
@@ -2604,13 +5371,66 @@ async fn handle_admin_conn(
                         message: "account not found".to_string(),
                     },
                     Some(r) => AdminResp::OkAccount {
-                        name: r.name,
                         has_password: r.pw_hash.as_deref().map(|s| !s.is_empty()).unwrap_or(false),
+                        totp_enabled: r.totp_secret.is_some(),
+                        characters: r.character_names(),
+                        name: r.name,
                         caps: r.caps.unwrap_or_default(),
+                        max_characters: r.max_characters,
                     },
                 }
             }
         }
+        AdminReq::SetAccountCharacterLimit {
+            name,
+            max_characters,
+        } => {
+            let uname = sanitize_name(&name);
+            let mut a = accounts.lock().await;
+            match a.by_name.get_mut(&uname) {
+                None => AdminResp::Err {
+                    message: "account not found".to_string(),
+                },
+                Some(_) if max_characters == Some(0) => AdminResp::Err {
+                    message: "max_characters must be at least 1".to_string(),
+                },
+                Some(r) => {
+                    r.max_characters = max_characters;
+                    let resp = AdminResp::OkAccount {
+                        name: r.name.clone(),
+                        has_password: r.pw_hash.as_deref().map(|s| !s.is_empty()).unwrap_or(false),
+                        caps: r.caps.clone().unwrap_or_default(),
+                        characters: r.character_names(),
+                        max_characters: r.max_characters,
+                        totp_enabled: r.totp_secret.is_some(),
+                    };
+                    a.save()?;
+                    resp
+                }
+            }
+        }
+        AdminReq::ResetAccountTotp { name } => {
+            let uname = sanitize_name(&name);
+            let mut a = accounts.lock().await;
+            match a.by_name.get_mut(&uname) {
+                None => AdminResp::Err {
+                    message: "account not found".to_string(),
+                },
+                Some(r) => {
+                    r.clear_totp();
+                    let resp = AdminResp::OkAccount {
+                        name: r.name.clone(),
+                        has_password: r.pw_hash.as_deref().map(|s| !s.is_empty()).unwrap_or(false),
+                        caps: r.caps.clone().unwrap_or_default(),
+                        characters: r.character_names(),
+                        max_characters: r.max_characters,
+                        totp_enabled: false,
+                    };
+                    a.save()?;
+                    resp
+                }
+            }
+        }
         AdminReq::ListAccounts {} => {
             let mut names = {
                 let a = accounts.lock().await;
@@ -2676,13 +5496,31 @@ async fn kick_by_ip(
     targets.len() as u64
 }
 
-async fn notify_all(
+/// Sessions currently routed to `shard`, with their last handoff snapshot.
+async fn routed_sessions(
+    routes: &Arc<tokio::sync::Mutex<HashMap<SessionId, shards::Route>>>,
+    shard: SocketAddr,
+) -> HashMap<SessionId, Option<Bytes>> {
+    let r = routes.lock().await;
+    r.iter()
+        .filter(|(_, route)| route.shard == shard)
+        .map(|(sid, route)| (*sid, route.snapshot.clone()))
+        .collect()
+}
+
+async fn notify_routed(
     sessions: &Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
+    routes: &Arc<tokio::sync::Mutex<HashMap<SessionId, shards::Route>>>,
+    shard: SocketAddr,
     msg: &'static [u8],
 ) {
+    let routed = routed_sessions(routes, shard).await;
     let txs = {
         let m = sessions.lock().await;
-        m.values().map(|s| s.write_tx.clone()).collect::<Vec<_>>()
+        m.iter()
+            .filter(|(sid, _)| routed.contains_key(sid))
+            .map(|(_, s)| s.write_tx.clone())
+            .collect::<Vec<_>>()
     };
     for tx in txs {
         let _ = tx.send(Bytes::from_static(msg)).await;
@@ -2692,14 +5530,14 @@ async fn notify_all(
 async fn notify_one(
     sessions: &Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
     session: SessionId,
-    msg: &'static [u8],
+    msg: Bytes,
 ) {
     let tx = {
         let m = sessions.lock().await;
         m.get(&session).map(|s| s.write_tx.clone())
     };
     if let Some(tx) = tx {
-        let _ = tx.send(Bytes::from_static(msg)).await;
+        let _ = tx.send(msg).await;
     }
 }
 
@@ -2715,7 +5553,10 @@ async fn write_req(
     fw.write_frame_parts(&[&hdr, body]).await
 }
 
+#[allow(clippy::too_many_arguments)]
 fn attach_body(
+    session: SessionId,
+    signer: &shard_auth::Signer,
     is_bot: bool,
     auth: Option<&[u8]>,
     race: &str,
@@ -2723,55 +5564,46 @@ fn attach_body(
     sex: &str,
     pronouns: &str,
     name: &[u8],
-) -> Bytes {
-    let mut b = Vec::with_capacity(
-        1 + 2
-            + auth.map(|a| a.len()).unwrap_or(0)
-            + 1
-            + race.len()
-            + 1
-            + class.len()
-            + 1
-            + sex.len()
-            + 1
-            + pronouns.len()
-            + name.len(),
+    snapshot: Option<&[u8]>,
+    fresh: bool,
+) -> Result<Bytes, mudproto::ProtoError> {
+    let auth = auth.map(|a| signer.seal_attach(session, a, snapshot));
+    // Build info is always included by the broker.
+    AttachFields {
+        is_bot,
+        auth: auth.as_deref(),
+        race: Some(race.as_bytes()),
+        class: Some(class.as_bytes()),
+        sex: Some(sex.as_bytes()),
+        pronouns: Some(pronouns.as_bytes()),
+        name,
+        snapshot,
+        fresh,
+    }
+    .encode_v2()
+}
+
+/// Send HELLO and wait for the shard's reply; returns the shard's feature bits.
+/// `None` means the shard didn't answer (pre-HELLO shards drop the connection instead).
+async fn shard_hello(
+    fr: &mut FrameReader<tokio::net::tcp::OwnedReadHalf>,
+    fw: &mut FrameWriter<tokio::net::tcp::OwnedWriteHalf>,
+) -> Option<u64> {
+    let hello = shard_client::hello(
+        fr,
+        fw,
+        SHARD_FEATURES,
+        concat!("slopmud/", env!("CARGO_PKG_VERSION")),
+        Duration::from_secs(2),
+    )
+    .await?;
+    info!(
+        version = hello.version,
+        features = hello.features,
+        software = %String::from_utf8_lossy(&hello.software),
+        "shard hello"
     );
-    let mut flags = 0u8;
-    if is_bot {
-        flags |= 0x01;
-    }
-    if auth.is_some() {
-        flags |= 0x02;
-    }
-    flags |= 0x04; // build info always included by broker
-    b.push(flags);
-    if let Some(a) = auth {
-        if a.len() <= u16::MAX as usize {
-            let len = a.len() as u16;
-            b.extend_from_slice(&len.to_be_bytes());
-            b.extend_from_slice(a);
-        } else {
-            // Too long to encode; drop auth rather than truncating.
-            warn!(len = a.len(), "auth token too long; dropping");
-            b[0] &= !0x02;
-        }
-    }
-    // race/class tokens (u8 length + bytes)
-    let r = race.as_bytes();
-    let c = class.as_bytes();
-    let sx = sex.as_bytes();
-    let pr = pronouns.as_bytes();
-    b.push(r.len().min(255) as u8);
-    b.extend_from_slice(&r[..r.len().min(255)]);
-    b.push(c.len().min(255) as u8);
-    b.extend_from_slice(&c[..c.len().min(255)]);
-    b.push(sx.len().min(255) as u8);
-    b.extend_from_slice(&sx[..sx.len().min(255)]);
-    b.push(pr.len().min(255) as u8);
-    b.extend_from_slice(&pr[..pr.len().min(255)]);
-    b.extend_from_slice(name);
-    Bytes::from(b)
+    Some(hello.features & SHARD_FEATURES)
 }
 
 #[allow(dead_code)]
@@ -2825,7 +5657,7 @@ fn logfmt_str(s: &str) -> String {
 }
 
 fn redact_input_for_logs(line: &str) -> Cow<'_, str> {
-    // Avoid leaking email addresses into logs by redacting the one command that carries it.
+    // Avoid leaking email addresses and 2fa codes into logs.
     let mut it = line.split_whitespace();
     let Some(cmd) = it.next() else {
         return Cow::Borrowed(line);
@@ -2836,6 +5668,14 @@ fn redact_input_for_logs(line: &str) -> Cow<'_, str> {
     let Some(sub) = it.next() else {
         return Cow::Borrowed(line);
     };
+    if sub.eq_ignore_ascii_case("2fa") {
+        return match it.next() {
+            Some(action) if it.next().is_some() => {
+                Cow::Owned(format!("account 2fa {action} <redacted>"))
+            }
+            _ => Cow::Borrowed(line),
+        };
+    }
     if !sub.eq_ignore_ascii_case("email") {
         return Cow::Borrowed(line);
     }
@@ -2845,6 +5685,9 @@ fn redact_input_for_logs(line: &str) -> Cow<'_, str> {
     if action.eq_ignore_ascii_case("set") {
         return Cow::Borrowed("account email set <redacted>");
     }
+    if action.eq_ignore_ascii_case("verify") {
+        return Cow::Borrowed("account email verify <redacted>");
+    }
     Cow::Borrowed(line)
 }
 
@@ -2888,21 +5731,70 @@ async fn mint_internal_oidc_token(
     Ok(Some(Bytes::from(t.access_token)))
 }
 
+/// `ooc hi`, `trade wts sword`: shorthand for `chat <channel> <message>`. `party` stays with
+/// the shard, which owns real parties.
+fn is_chat_channel_line(lc: &str) -> bool {
+    let w = lc.split_whitespace().next().unwrap_or("");
+    matches!(w, "ooc" | "newbie" | "trade" | "guild")
+}
+
+/// A chatd command line that posts to a channel (`ooc hi`, `party omw`), as opposed to `join`,
+/// `list` and the like.
+fn is_chat_post(cmdline: &str) -> bool {
+    let mut words = cmdline.split_whitespace();
+    let ch = words.next().unwrap_or("").to_ascii_lowercase();
+    matches!(ch.as_str(), "ooc" | "newbie" | "trade" | "party" | "guild") && words.next().is_some()
+}
+
+/// What a muted player gets instead of their chat post going out. chatd doesn't know about mutes,
+/// so the broker refuses posts with the same message shards give for the rest of their talk.
+fn chat_mute_refusal(
+    bans: &ban::BanState,
+    name: &str,
+    cmdline: &str,
+    now_unix: u64,
+) -> Option<String> {
+    if !is_chat_post(cmdline) {
+        return None;
+    }
+    let until = bans.is_char_muted(name, now_unix)?.until_unix;
+    let mins = until.saturating_sub(now_unix).div_ceil(60);
+    Some(format!("you are muted ({mins}m left)\r\n"))
+}
+
 async fn handle_conn(
     stream: TcpStream,
     peer: SocketAddr,
-    sessions: Arc<tokio::sync::Mutex<HashMap<SessionId, SessionInfo>>>,
-    shard_tx: tokio::sync::mpsc::Sender<ShardMsg>,
-    server_info: Arc<ServerInfo>,
-    cfg: Arc<Config>,
-    accounts: Arc<tokio::sync::Mutex<Accounts>>,
-    login_throttle: Arc<tokio::sync::Mutex<LoginThrottle>>,
-    bans: Arc<tokio::sync::Mutex<ban::BanState>>,
-    holds: Arc<tokio::sync::Mutex<hold::HoldCache>>,
-    nearline: Arc<nearline::NearlineRing>,
-    eventlog: Arc<eventlog::EventLog>,
+    shared: Shared,
+    hooks: hotboot::Hooks,
+    resume: Option<hotboot::Resume>,
 ) -> anyhow::Result<()> {
-    let session = new_session_id();
+    let Shared {
+        cfg,
+        server_info,
+        sessions,
+        shard_tx,
+        chat_tx,
+        accounts,
+        login_throttle,
+        bans,
+        reports,
+        holds,
+        nearline,
+        eventlog,
+        statsd,
+        mailer,
+        shard_signer,
+    } = &shared;
+    let session = match resume.as_ref() {
+        Some(r) => SessionId(r.conn.session),
+        None => new_session_id(),
+    };
+    let hotboot::Hooks {
+        trigger: mut hotboot_trigger,
+        parked: hotboot_parked,
+    } = hooks;
+    let mut parking = false;
     let mut peer_ip = peer.ip();
     let mut peer_port = peer.port();
     let trusted_proxy_peer = peer_ip.is_loopback();
@@ -2910,17 +5802,70 @@ async fn handle_conn(
 
     let (disconnect_tx, mut disconnect_rx) = tokio::sync::watch::channel(false);
 
-    let (write_tx, mut write_rx) = tokio::sync::mpsc::channel::<Bytes>(128);
+    // Effective color mode for this connection; shard output markup is rendered on the way out.
+    let (color_tx, color_rx) = tokio::sync::watch::channel(ColorMode::Off);
+    // Wrapping/paging lives in the writer; the read loop drives it and watches `paging`.
+    let (pager_tx, mut pager_rx) = tokio::sync::mpsc::channel::<pager::Ctl>(32);
+    let (paging_tx, paging_rx) = tokio::sync::watch::channel(false);
+
+    let (write_tx, mut write_rx) = tokio::sync::mpsc::channel::<Out>(128);
+    let write_tx = WriteTx(write_tx);
+    // Hotboot: write out what's queued, then give the socket back.
+    let (writer_stop_tx, mut writer_stop_rx) = tokio::sync::oneshot::channel::<()>();
     let writer = tokio::spawn(async move {
-        while let Some(b) = write_rx.recv().await {
-            if wr.write_all(&b[..]).await.is_err() {
+        let mut pg = pager::Pager::default();
+        let mut stopping = false;
+        loop {
+            let out = tokio::select! {
+                _ = &mut writer_stop_rx, if !stopping => {
+                    stopping = true;
+                    write_rx.close();
+                    Vec::new()
+                }
+                out = write_rx.recv() => match out {
+                    None => break,
+                    // Telnet negotiation bypasses the pager.
+                    Some(Out::Text(b)) if b.first() == Some(&TELNET_IAC) => b.to_vec(),
+                    Some(Out::Text(b)) => pg.push(&color::escape_bytes(&b)),
+                    Some(Out::Markup(b)) => pg.push(&b),
+                },
+                ctl = pager_rx.recv() => match ctl {
+                    Some(pager::Ctl::Resize { width, height }) => {
+                        pg.resize(width, height);
+                        Vec::new()
+                    }
+                    Some(pager::Ctl::LineEntered) => {
+                        pg.line_entered();
+                        Vec::new()
+                    }
+                    Some(pager::Ctl::More) => pg.next_page(),
+                    Some(pager::Ctl::Quit) => pg.quit(),
+                    None => Vec::new(),
+                },
+            };
+            paging_tx.send_if_modified(|v| {
+                let changed = *v != pg.is_paging();
+                *v = pg.is_paging();
+                changed
+            });
+            if out.is_empty() {
+                continue;
+            }
+            let res = if color::has_markup(&out) {
+                let mode = *color_rx.borrow();
+                wr.write_all(&color::render(&out, mode)).await
+            } else {
+                wr.write_all(&out).await
+            };
+            if res.is_err() {
                 break;
             }
         }
+        wr
     });
 
     // Log connect early (prior to optional proxy protocol rewriting).
-    {
+    if resume.is_none() {
         let ts = Utc::now().to_rfc3339();
         let sid = session_hex(session);
         let entry = format!(
@@ -2933,9 +5878,28 @@ async fn handle_conn(
         eventlog.log_line(LogStream::All, &entry).await;
     }
 
-    let mut iac = IacParser::new();
+    let mut iac = IacParser::new()
+        .accept_option(term::TELNET_OPT_TTYPE)
+        .accept_option(term::TELNET_OPT_NAWS);
+    let mut term_caps = term::TermCaps::default();
+    // `term width|height` overrides for this session (Some(0) = off).
+    let mut width_override: Option<u16> = None;
+    let mut height_override: Option<u16> = None;
+    let mut color_pref = term::ColorPref::Auto;
+    let mut color_pref_loaded = false;
     let mut linebuf: Vec<u8> = Vec::with_capacity(8 * 1024);
+    // Numbers this session's shard input; shards use it to skip lines resent after a reconnect.
+    let mut input_seq: u64 = 0;
+    // The account's aliases, and commands an alias expanded to that haven't run yet.
+    let mut aliases = aliases::Aliases::new();
+    let mut alias_queue: VecDeque<Vec<u8>> = VecDeque::new();
+    // `account` is the login name; `name` is the character being created or played.
+    let mut account: Option<String> = None;
     let mut name: Option<String> = None;
+    // Set by `new <name>` at the character menu, so the shard drops any snapshot a deleted
+    // character of the same name left behind.
+    let mut fresh_character = false;
+    let mut pending_delete: Option<String> = None;
     let mut is_bot: Option<bool> = None;
     let mut auth_method: Option<String> = None;
     // Small JSON blob asserted to the shard for permissions (groups/capabilities).
@@ -2985,33 +5949,150 @@ async fn handle_conn(
         "please type: password | google\r\n> "
     };
 
-    write_tx
-        .send(Bytes::from_static(
-            b"slopmud (alpha)\r\ncharacter creation (step 1/4)\r\nname: ",
-        ))
-        .await
-        .ok();
+    if let Some(hotboot::Resume { conn, ready }) = resume {
+        // The client already negotiated and, if in the world, stays there.
+        peer_ip = conn.peer_ip;
+        peer_port = conn.peer_port;
+        proxy_checked = true;
+        term_caps = conn.term;
+        width_override = conn.width_override;
+        height_override = conn.height_override;
+        linebuf = conn.pending_input;
+        input_seq = conn.input_seq;
+        if let (true, Some(nm)) = (conn.in_world, conn.name) {
+            account = conn.account;
+            auth_method = conn.auth_method;
+            auth_blob = conn.auth_blob.map(Bytes::from);
+            google_sub = conn.google_sub;
+            google_email = conn.google_email;
+            oidc_sub = conn.oidc_sub;
+            oidc_email = conn.oidc_email;
+            is_bot = conn.is_bot;
+            race = conn.race;
+            class = conn.class;
+            sex = conn.sex;
+            pronouns = conn.pronouns;
+            color_pref = term::ColorPref::parse(&conn.color_pref).unwrap_or_default();
+            color_pref_loaded = true;
+            aliases = load_aliases(accounts, account.as_deref()).await;
+            state = ConnState::InWorld;
+
+            let mut sb = Scrollback::new(SCROLLBACK_MAX_LINES);
+            for l in conn.scrollback {
+                sb.push_line(LineId(l.id), l.ts_unix_ms, l.text);
+            }
+            sessions.lock().await.insert(
+                session,
+                SessionInfo {
+                    name: nm.clone(),
+                    held: conn.held,
+                    is_bot: is_bot.unwrap_or(false),
+                    auth: auth_blob.clone(),
+                    race: race.clone().unwrap_or_default(),
+                    class: class.clone().unwrap_or_default(),
+                    sex: sex.clone().unwrap_or_default(),
+                    pronouns: pronouns.clone().unwrap_or_default(),
+                    peer_ip,
+                    write_tx: write_tx.clone(),
+                    disconnect_tx: disconnect_tx.clone(),
+                    scrollback: Arc::new(tokio::sync::Mutex::new(sb)),
+                },
+            );
+            name = Some(nm);
+        } else {
+            write_tx
+                .send(Bytes::from_static(
+                    b"\r\nthe server restarted; please log in again\r\nname: ",
+                ))
+                .await
+                .ok();
+        }
+        drop(ready);
+        color_tx.send_replace(color_pref.effective(term_caps.detected_color()));
+        let (width, height) = term_size(&term_caps, width_override, height_override, is_bot);
+        let _ = pager_tx.send(pager::Ctl::Resize { width, height }).await;
+    } else {
+        write_tx
+            .send(Bytes::from(term::TermCaps::negotiate_start().to_vec()))
+            .await
+            .ok();
+        write_tx
+            .send(Bytes::from_static(
+                b"slopmud (alpha)\r\ncharacter creation (step 1/4)\r\nname: ",
+            ))
+            .await
+            .ok();
+    }
 
     let mut buf = [0u8; 4096];
     'read: loop {
         let n = tokio::select! {
             res = rd.read(&mut buf) => res?,
             _ = disconnect_rx.changed() => 0usize,
+            Ok(()) = hotboot_trigger.changed() => {
+                parking = true;
+                0usize
+            }
         };
         if n == 0 {
             break;
         }
 
-        let (data, replies) = iac.parse(&buf[..n]);
+        let (data, mut replies) = iac.parse(&buf[..n]);
+        let events = iac.take_events();
+        for ev in &events {
+            replies.extend_from_slice(&term_caps.on_event(ev));
+        }
         if !replies.is_empty() {
             let _ = write_tx.send(Bytes::from(replies)).await;
         }
+        if !events.is_empty() {
+            color_tx.send_if_modified(|m| {
+                let want = color_pref.effective(term_caps.detected_color());
+                let changed = *m != want;
+                *m = want;
+                changed
+            });
+            let (width, height) = term_size(&term_caps, width_override, height_override, is_bot);
+            let _ = pager_tx.send(pager::Ctl::Resize { width, height }).await;
+        }
         if data.is_empty() {
             continue;
         }
 
         linebuf.extend_from_slice(&data);
-        while let Some(mut line_bytes) = try_pop_line(&mut linebuf) {
+        while let Some((mut line_bytes, from_alias)) = alias_queue
+            .pop_front()
+            .map(|l| (l, true))
+            .or_else(|| try_pop_line(&mut linebuf).map(|l| (l, false)))
+        {
+            if from_alias && state != ConnState::InWorld {
+                // An alias left the world (e.g. `characters`); the rest was meant for it.
+                alias_queue.clear();
+                continue;
+            }
+            if !from_alias {
+                let _ = pager_tx.send(pager::Ctl::LineEntered).await;
+            }
+            if !from_alias && *paging_rx.borrow() {
+                let l = String::from_utf8_lossy(&line_bytes)
+                    .trim()
+                    .to_ascii_lowercase();
+                match l.as_str() {
+                    "" | "m" | "more" => {
+                        let _ = pager_tx.send(pager::Ctl::More).await;
+                        continue;
+                    }
+                    "q" => {
+                        let _ = pager_tx.send(pager::Ctl::Quit).await;
+                        continue;
+                    }
+                    // Anything else drops the rest of the page and runs as usual.
+                    _ => {
+                        let _ = pager_tx.send(pager::Ctl::Quit).await;
+                    }
+                }
+            }
             if line_bytes.is_empty() {
                 continue;
             }
@@ -3042,6 +6123,7 @@ async fn handle_conn(
                                 logfmt_str(&peer_port.to_string()),
                             );
                             eventlog.log_line(LogStream::All, &entry).await;
+                            statsd.incr(statsd::CONNS, peer_ip);
 
                             // Apply IP bans immediately after proxy rewrite.
                             if let Some(b) = { bans.lock().await.is_ip_banned(peer_ip).cloned() } {
@@ -3073,6 +6155,7 @@ async fn handle_conn(
                     // Apply IP bans for direct clients (non-proxied).
                     if !proxy_checked {
                         proxy_checked = true;
+                        statsd.incr(statsd::CONNS, peer_ip);
                         if let Some(b) = { bans.lock().await.is_ip_banned(peer_ip).cloned() } {
                             let ts = Utc::now().to_rfc3339();
                             let sid = session_hex(session);
@@ -3246,6 +6329,19 @@ async fn handle_conn(
                                 break 'read;
                             }
 
+                            let taken = {
+                                let a = accounts.lock().await;
+                                !a.by_name.contains_key(&uname) && a.name_taken(&uname)
+                            };
+                            if taken {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(
+                                        b"web_auth: name already taken\r\nname: ",
+                                    ))
+                                    .await;
+                                continue;
+                            }
+
                             let ok = match (action.as_str(), method.as_str()) {
                                 ("create", "password") => {
                                     let pw = req.password.as_deref().unwrap_or("").as_bytes();
@@ -3294,10 +6390,24 @@ async fn handle_conn(
                                                     oidc_email: None,
                                                     caps: None,
                                                     email: None,
+                                                    color: None,
+                                                    aliases: Default::default(),
+                                                    characters: vec![uname.clone()],
+                                                    max_characters: None,
+                                                    totp_secret: None,
+                                                    totp_pending: None,
+                                                    totp_last_step: None,
+                                                    totp_recovery: Vec::new(),
+                                                    email_verified: false,
+                                                    email_verify: None,
+                                                    password_reset: None,
+                                                    delete_after_unix: None,
+                                                    renamed_unix: None,
                                                     created_unix: now_unix,
                                                 },
                                             );
                                             a.save()?;
+                                            statsd.incr(statsd::ACCOUNTS_CREATED, peer_ip);
                                         }
 
                                         auth_method = Some("password".to_string());
@@ -3451,10 +6561,24 @@ async fn handle_conn(
                                                         oidc_email: None,
                                                         caps: None,
                                                         email: None,
+                                                        color: None,
+                                                        aliases: Default::default(),
+                                                        characters: vec![uname.clone()],
+                                                        max_characters: None,
+                                                        totp_secret: None,
+                                                        totp_pending: None,
+                                                        totp_last_step: None,
+                                                        totp_recovery: Vec::new(),
+                                                        email_verified: false,
+                                                        email_verify: None,
+                                                        password_reset: None,
+                                                        delete_after_unix: None,
+                                                        renamed_unix: None,
                                                         created_unix: now_unix,
                                                     },
                                                 );
                                                 a.save()?;
+                                                statsd.incr(statsd::ACCOUNTS_CREATED, peer_ip);
                                             }
 
                                             google_sub = Some(sub.to_string());
@@ -3552,10 +6676,24 @@ async fn handle_conn(
                                                         oidc_email: email.clone(),
                                                         caps: None,
                                                         email: None,
+                                                        color: None,
+                                                        aliases: Default::default(),
+                                                        characters: vec![uname.clone()],
+                                                        max_characters: None,
+                                                        totp_secret: None,
+                                                        totp_pending: None,
+                                                        totp_last_step: None,
+                                                        totp_recovery: Vec::new(),
+                                                        email_verified: false,
+                                                        email_verify: None,
+                                                        password_reset: None,
+                                                        delete_after_unix: None,
+                                                        renamed_unix: None,
                                                         created_unix: now_unix,
                                                     },
                                                 );
                                                 a.save()?;
+                                                statsd.incr(statsd::ACCOUNTS_CREATED, peer_ip);
                                             }
 
                                             oidc_sub = Some(sub.to_string());
@@ -3587,42 +6725,34 @@ async fn handle_conn(
 
                             if ok {
                                 let uname = name.as_deref().unwrap_or("");
-                                if let Some(saved) = wait_for_saved_player(
-                                    &cfg.players_path,
-                                    uname,
-                                    Duration::from_secs(2),
-                                )
-                                .await
-                                {
-                                    let shard_auth = auth_blob.clone().expect("auth blob set");
-                                    attach_authenticated_session(
-                                        session,
-                                        peer_ip,
-                                        uname,
-                                        saved.is_bot,
-                                        &saved.race,
-                                        &saved.class,
-                                        &saved.sex,
-                                        &saved.pronouns,
-                                        shard_auth,
-                                        auth_method.as_deref().unwrap_or("unknown"),
-                                        &write_tx,
-                                        &disconnect_tx,
-                                        &sessions,
-                                        &holds,
-                                        &eventlog,
-                                        &shard_tx,
-                                    )
-                                    .await?;
-                                    state = ConnState::InWorld;
-                                } else {
-                                    state = ConnState::NeedBotDisclosure;
-                                    let _ = write_tx
-                                        .send(Bytes::from_static(
-                                            b"\r\ncharacter creation (step 2/4)\r\nare you using automation?\r\ntype: human | bot\r\n> ",
-                                        ))
-                                        .await;
+                                let acct = uname.to_string();
+                                // The web login only proves the password; the second factor is
+                                // asked for in-band like a telnet login.
+                                let totp_on = method == "password" && {
+                                    let a = accounts.lock().await;
+                                    a.by_name
+                                        .get(&acct)
+                                        .is_some_and(|r| r.totp_secret.is_some())
+                                };
+                                if totp_on {
+                                    state = ConnState::NeedTotp;
+                                    let _ = write_tx.send(Bytes::from_static(TOTP_PROMPT)).await;
+                                    continue;
                                 }
+                                let (next, character) = enter_account(
+                                    &shared,
+                                    session,
+                                    peer_ip,
+                                    &acct,
+                                    auth_blob.clone().expect("auth blob set"),
+                                    auth_method.as_deref().unwrap_or("unknown"),
+                                    &write_tx,
+                                    &disconnect_tx,
+                                )
+                                .await?;
+                                account = Some(acct);
+                                name = character;
+                                state = next;
                             }
                             continue;
                         }
@@ -3658,6 +6788,19 @@ async fn handle_conn(
                         break 'read;
                     }
 
+                    // Logins use the account name; another account's character name (or a case
+                    // variant of an account name) can't start a new account either.
+                    let taken = {
+                        let a = accounts.lock().await;
+                        !a.by_name.contains_key(&n) && a.name_taken(&n)
+                    };
+                    if taken {
+                        let _ = write_tx
+                            .send(Bytes::from_static(b"name already taken\r\nname: "))
+                            .await;
+                        continue;
+                    }
+
                     if let Some(pending) = pending_auto_webauth.clone() {
                         match pending {
                             PendingAutoWebAuth::Google { sub, email, caps } => {
@@ -3687,44 +6830,22 @@ async fn handle_conn(
                                             None,
                                             caps.as_deref(),
                                         ));
-                                        name = Some(n.clone());
                                         pending_auto_webauth = None;
-                                        if let Some(saved) = wait_for_saved_player(
-                                            &cfg.players_path,
-                                            &n,
-                                            Duration::from_secs(2),
+                                        let acct = n.clone();
+                                        let (next, character) = enter_account(
+                                            &shared,
+                                            session,
+                                            peer_ip,
+                                            &acct,
+                                            auth_blob.clone().expect("auth blob set"),
+                                            auth_method.as_deref().unwrap_or("unknown"),
+                                            &write_tx,
+                                            &disconnect_tx,
                                         )
-                                        .await
-                                        {
-                                            let shard_auth = auth_blob.clone().expect("auth blob set");
-                                            attach_authenticated_session(
-                                                session,
-                                                peer_ip,
-                                                &n,
-                                                saved.is_bot,
-                                                &saved.race,
-                                                &saved.class,
-                                                &saved.sex,
-                                                &saved.pronouns,
-                                                shard_auth,
-                                                auth_method.as_deref().unwrap_or("unknown"),
-                                                &write_tx,
-                                                &disconnect_tx,
-                                                &sessions,
-                                                &holds,
-                                                &eventlog,
-                                                &shard_tx,
-                                            )
-                                            .await?;
-                                            state = ConnState::InWorld;
-                                        } else {
-                                            state = ConnState::NeedBotDisclosure;
-                                            let _ = write_tx
-                                                .send(Bytes::from_static(
-                                                    b"\r\ncharacter creation (step 2/4)\r\nare you using automation?\r\ntype: human | bot\r\n> ",
-                                                ))
-                                                .await;
-                                        }
+                                        .await?;
+                                        account = Some(acct);
+                                        name = character;
+                                        state = next;
                                         continue;
                                     }
                                     None => {
@@ -3745,10 +6866,24 @@ async fn handle_conn(
                                                     oidc_email: None,
                                                     caps: None,
                                                     email: None,
+                                                    color: None,
+                                                    aliases: Default::default(),
+                                                    characters: vec![n.clone()],
+                                                    max_characters: None,
+                                                    totp_secret: None,
+                                                    totp_pending: None,
+                                                    totp_last_step: None,
+                                                    totp_recovery: Vec::new(),
+                                                    email_verified: false,
+                                                    email_verify: None,
+                                                    password_reset: None,
+                                                    delete_after_unix: None,
+                                                    renamed_unix: None,
                                                     created_unix: now_unix,
                                                 },
                                             );
                                             a.save()?;
+                                            statsd.incr(statsd::ACCOUNTS_CREATED, peer_ip);
                                         }
                                         google_sub = Some(sub.clone());
                                         google_email = email.clone();
@@ -3762,44 +6897,22 @@ async fn handle_conn(
                                             None,
                                             caps.as_deref(),
                                         ));
-                                        name = Some(n.clone());
                                         pending_auto_webauth = None;
-                                        if let Some(saved) = wait_for_saved_player(
-                                            &cfg.players_path,
-                                            &n,
-                                            Duration::from_secs(2),
+                                        let acct = n.clone();
+                                        let (next, character) = enter_account(
+                                            &shared,
+                                            session,
+                                            peer_ip,
+                                            &acct,
+                                            auth_blob.clone().expect("auth blob set"),
+                                            auth_method.as_deref().unwrap_or("unknown"),
+                                            &write_tx,
+                                            &disconnect_tx,
                                         )
-                                        .await
-                                        {
-                                            let shard_auth = auth_blob.clone().expect("auth blob set");
-                                            attach_authenticated_session(
-                                                session,
-                                                peer_ip,
-                                                &n,
-                                                saved.is_bot,
-                                                &saved.race,
-                                                &saved.class,
-                                                &saved.sex,
-                                                &saved.pronouns,
-                                                shard_auth,
-                                                auth_method.as_deref().unwrap_or("unknown"),
-                                                &write_tx,
-                                                &disconnect_tx,
-                                                &sessions,
-                                                &holds,
-                                                &eventlog,
-                                                &shard_tx,
-                                            )
-                                            .await?;
-                                            state = ConnState::InWorld;
-                                        } else {
-                                            state = ConnState::NeedBotDisclosure;
-                                            let _ = write_tx
-                                                .send(Bytes::from_static(
-                                                    b"\r\ncharacter creation (step 2/4)\r\nare you using automation?\r\ntype: human | bot\r\n> ",
-                                                ))
-                                                .await;
-                                        }
+                                        .await?;
+                                        account = Some(acct);
+                                        name = character;
+                                        state = next;
                                         continue;
                                     }
                                 }
@@ -3831,44 +6944,22 @@ async fn handle_conn(
                                             oidc_email.as_deref(),
                                             caps.as_deref(),
                                         ));
-                                        name = Some(n.clone());
-                                        pending_auto_webauth = None;
-                                        if let Some(saved) = wait_for_saved_player(
-                                            &cfg.players_path,
-                                            &n,
-                                            Duration::from_secs(2),
-                                        )
-                                        .await
-                                        {
-                                            let shard_auth = auth_blob.clone().expect("auth blob set");
-                                            attach_authenticated_session(
-                                                session,
-                                                peer_ip,
-                                                &n,
-                                                saved.is_bot,
-                                                &saved.race,
-                                                &saved.class,
-                                                &saved.sex,
-                                                &saved.pronouns,
-                                                shard_auth,
-                                                auth_method.as_deref().unwrap_or("unknown"),
-                                                &write_tx,
-                                                &disconnect_tx,
-                                                &sessions,
-                                                &holds,
-                                                &eventlog,
-                                                &shard_tx,
-                                            )
-                                            .await?;
-                                            state = ConnState::InWorld;
-                                        } else {
-                                            state = ConnState::NeedBotDisclosure;
-                                            let _ = write_tx
-                                                .send(Bytes::from_static(
-                                                    b"\r\ncharacter creation (step 2/4)\r\nare you using automation?\r\ntype: human | bot\r\n> ",
-                                                ))
-                                                .await;
-                                        }
+                                        pending_auto_webauth = None;
+                                        let acct = n.clone();
+                                        let (next, character) = enter_account(
+                                            &shared,
+                                            session,
+                                            peer_ip,
+                                            &acct,
+                                            auth_blob.clone().expect("auth blob set"),
+                                            auth_method.as_deref().unwrap_or("unknown"),
+                                            &write_tx,
+                                            &disconnect_tx,
+                                        )
+                                        .await?;
+                                        account = Some(acct);
+                                        name = character;
+                                        state = next;
                                         continue;
                                     }
                                     None => {
@@ -3889,10 +6980,24 @@ async fn handle_conn(
                                                     oidc_email: email.clone(),
                                                     caps: None,
                                                     email: None,
+                                                    color: None,
+                                                    aliases: Default::default(),
+                                                    characters: vec![n.clone()],
+                                                    max_characters: None,
+                                                    totp_secret: None,
+                                                    totp_pending: None,
+                                                    totp_last_step: None,
+                                                    totp_recovery: Vec::new(),
+                                                    email_verified: false,
+                                                    email_verify: None,
+                                                    password_reset: None,
+                                                    delete_after_unix: None,
+                                                    renamed_unix: None,
                                                     created_unix: now_unix,
                                                 },
                                             );
                                             a.save()?;
+                                            statsd.incr(statsd::ACCOUNTS_CREATED, peer_ip);
                                         }
                                         oidc_sub = Some(sub.clone());
                                         oidc_email = email.clone();
@@ -3906,44 +7011,22 @@ async fn handle_conn(
                                             oidc_email.as_deref(),
                                             caps.as_deref(),
                                         ));
-                                        name = Some(n.clone());
                                         pending_auto_webauth = None;
-                                        if let Some(saved) = wait_for_saved_player(
-                                            &cfg.players_path,
-                                            &n,
-                                            Duration::from_secs(2),
+                                        let acct = n.clone();
+                                        let (next, character) = enter_account(
+                                            &shared,
+                                            session,
+                                            peer_ip,
+                                            &acct,
+                                            auth_blob.clone().expect("auth blob set"),
+                                            auth_method.as_deref().unwrap_or("unknown"),
+                                            &write_tx,
+                                            &disconnect_tx,
                                         )
-                                        .await
-                                        {
-                                            let shard_auth = auth_blob.clone().expect("auth blob set");
-                                            attach_authenticated_session(
-                                                session,
-                                                peer_ip,
-                                                &n,
-                                                saved.is_bot,
-                                                &saved.race,
-                                                &saved.class,
-                                                &saved.sex,
-                                                &saved.pronouns,
-                                                shard_auth,
-                                                auth_method.as_deref().unwrap_or("unknown"),
-                                                &write_tx,
-                                                &disconnect_tx,
-                                                &sessions,
-                                                &holds,
-                                                &eventlog,
-                                                &shard_tx,
-                                            )
-                                            .await?;
-                                            state = ConnState::InWorld;
-                                        } else {
-                                            state = ConnState::NeedBotDisclosure;
-                                            let _ = write_tx
-                                                .send(Bytes::from_static(
-                                                    b"\r\ncharacter creation (step 2/4)\r\nare you using automation?\r\ntype: human | bot\r\n> ",
-                                                ))
-                                                .await;
-                                        }
+                                        .await?;
+                                        account = Some(acct);
+                                        name = character;
+                                        state = next;
                                         continue;
                                     }
                                 }
@@ -4222,10 +7305,24 @@ async fn handle_conn(
                                             oidc_email: None,
                                             caps: None,
                                             email: None,
+                                            color: None,
+                                            aliases: Default::default(),
+                                            characters: vec![uname.clone()],
+                                            max_characters: None,
+                                            totp_secret: None,
+                                            totp_pending: None,
+                                            totp_last_step: None,
+                                            totp_recovery: Vec::new(),
+                                            email_verified: false,
+                                            email_verify: None,
+                                            password_reset: None,
+                                            delete_after_unix: None,
+                                            renamed_unix: None,
                                             created_unix: now_unix,
                                         },
                                     );
                                     a.save()?;
+                                    statsd.incr(statsd::ACCOUNTS_CREATED, peer_ip);
                                 }
                             }
 
@@ -4242,42 +7339,21 @@ async fn handle_conn(
                             let _ = std::fs::remove_file(&path);
                             google_oauth_code = None;
                             let uname = name.as_deref().unwrap_or("");
-                            if let Some(saved) = wait_for_saved_player(
-                                &cfg.players_path,
-                                uname,
-                                Duration::from_secs(2),
+                            let acct = uname.to_string();
+                            let (next, character) = enter_account(
+                                &shared,
+                                session,
+                                peer_ip,
+                                &acct,
+                                auth_blob.clone().expect("auth blob set"),
+                                auth_method.as_deref().unwrap_or("unknown"),
+                                &write_tx,
+                                &disconnect_tx,
                             )
-                            .await
-                            {
-                                let shard_auth = auth_blob.clone().expect("auth blob set");
-                                attach_authenticated_session(
-                                    session,
-                                    peer_ip,
-                                    uname,
-                                    saved.is_bot,
-                                    &saved.race,
-                                    &saved.class,
-                                    &saved.sex,
-                                    &saved.pronouns,
-                                    shard_auth,
-                                    auth_method.as_deref().unwrap_or("unknown"),
-                                    &write_tx,
-                                    &disconnect_tx,
-                                    &sessions,
-                                    &holds,
-                                    &eventlog,
-                                    &shard_tx,
-                                )
-                                .await?;
-                                state = ConnState::InWorld;
-                            } else {
-                                state = ConnState::NeedBotDisclosure;
-                                let _ = write_tx
-                                    .send(Bytes::from_static(
-                                        b"\r\ncharacter creation (step 2/4)\r\nare you using automation?\r\ntype: human | bot\r\n> ",
-                                    ))
-                                    .await;
-                            }
+                            .await?;
+                            account = Some(acct);
+                            name = character;
+                            state = next;
                             continue;
                         }
                         _ => {
@@ -4333,251 +7409,837 @@ async fn handle_conn(
                             let mut t = login_throttle.lock().await;
                             t.note_failure(peer_ip, uname, now)
                         };
+                        statsd.incr(statsd::LOGIN_FAILURES, peer_ip);
+                        let delay_s = wait_seconds(delay);
+
+                        // Re-disable echo for retry.
+                        password_echo_disabled = true;
+                        let mut b = Vec::new();
+                        b.extend_from_slice(b"\r\npassword too short; retry in ");
+                        b.extend_from_slice(delay_s.to_string().as_bytes());
+                        b.extend_from_slice(b"s\r\n");
+                        b.extend_from_slice(telnet_will(TELNET_OPT_ECHO).as_slice());
+                        b.extend_from_slice(b"set password (min 8 chars): ");
+                        let _ = write_tx.send(Bytes::from(b)).await;
+                        line_bytes.zeroize();
+                        continue;
+                    }
+
+                    let _ = write_tx.send(Bytes::from_static(b"\r\n")).await;
+
+                    // Store only a salted hash, never the raw password.
+                    let salt = SaltString::generate(&mut password_hash::rand_core::OsRng);
+                    let hash = Argon2::default()
+                        .hash_password(pw, &salt)
+                        .map_err(|e| anyhow::anyhow!("hash_password failed: {e}"))?
+                        .to_string();
+
+                    let now_unix = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs();
+                    {
+                        let mut a = accounts.lock().await;
+                        let uname = name.as_ref().expect("name set").clone();
+                        a.by_name.insert(
+                            uname.clone(),
+                            AccountRec {
+                                name: uname.clone(),
+                                pw_hash: Some(hash),
+                                google_sub: None,
+                                google_email: None,
+                                oidc_sub: None,
+                                oidc_email: None,
+                                caps: None,
+                                email: None,
+                                color: None,
+                                aliases: Default::default(),
+                                characters: vec![uname],
+                                max_characters: None,
+                                totp_secret: None,
+                                totp_pending: None,
+                                totp_last_step: None,
+                                totp_recovery: Vec::new(),
+                                email_verified: false,
+                                email_verify: None,
+                                password_reset: None,
+                                delete_after_unix: None,
+                                renamed_unix: None,
+                                created_unix: now_unix,
+                            },
+                        );
+                        a.save()?;
+                        statsd.incr(statsd::ACCOUNTS_CREATED, peer_ip);
+                    }
+
+                    {
+                        let mut t = login_throttle.lock().await;
+                        t.note_success(peer_ip, uname);
+                    }
+
+                    // For password auth, the shard principal is acct:<name> (via the auth blob).
+                    auth_blob = Some(make_shard_auth_blob(
+                        name.as_deref().unwrap_or(""),
+                        "password",
+                        None,
+                        None,
+                        None,
+                        None,
+                        None,
+                    ));
+
+                    line_bytes.zeroize();
+                    let uname = name.as_deref().unwrap_or("");
+                    let acct = uname.to_string();
+                    let (next, character) = enter_account(
+                        &shared,
+                        session,
+                        peer_ip,
+                        &acct,
+                        auth_blob.clone().expect("auth blob set"),
+                        auth_method.as_deref().unwrap_or("unknown"),
+                        &write_tx,
+                        &disconnect_tx,
+                    )
+                    .await?;
+                    account = Some(acct);
+                    name = character;
+                    state = next;
+                    continue;
+                }
+                ConnState::NeedPasswordLogin => {
+                    let uname = name.as_deref().expect("name set");
+                    let now = std::time::Instant::now();
+                    let wait = {
+                        let mut t = login_throttle.lock().await;
+                        t.wait(peer_ip, uname, now)
+                    };
+                    if !wait.is_zero() {
+                        let wait_s = wait_seconds(wait);
+                        let mut b = Vec::new();
+                        b.extend_from_slice(b"\r\nrate limit: retry in ");
+                        b.extend_from_slice(wait_s.to_string().as_bytes());
+                        b.extend_from_slice(b"s\r\n");
+                        if !password_echo_disabled {
+                            password_echo_disabled = true;
+                            b.extend_from_slice(telnet_will(TELNET_OPT_ECHO).as_slice());
+                        }
+                        b.extend_from_slice(b"password: ");
+                        let _ = write_tx.send(Bytes::from(b)).await;
+                        line_bytes.zeroize();
+                        continue;
+                    }
+
+                    if trim_ascii_ws(&line_bytes).eq_ignore_ascii_case(b"forgot") {
+                        line_bytes.zeroize();
+                        let (sent, msg) = start_password_reset(accounts, mailer, uname).await;
+                        let mut b = Vec::new();
+                        b.extend_from_slice(b"\r\n");
+                        b.extend_from_slice(msg.as_bytes());
+                        if sent {
+                            if password_echo_disabled {
+                                b.extend_from_slice(telnet_wont(TELNET_OPT_ECHO).as_slice());
+                                password_echo_disabled = false;
+                            }
+                            b.extend_from_slice(RESET_CODE_PROMPT);
+                            state = ConnState::NeedResetCode;
+                        } else {
+                            if !password_echo_disabled {
+                                password_echo_disabled = true;
+                                b.extend_from_slice(telnet_will(TELNET_OPT_ECHO).as_slice());
+                            }
+                            b.extend_from_slice(b"password: ");
+                        }
+                        let _ = write_tx.send(Bytes::from(b)).await;
+                        continue;
+                    }
+
+                    let pw = trim_ascii_ws(&line_bytes);
+                    let rec = {
+                        let a = accounts.lock().await;
+                        a.by_name.get(uname).cloned()
+                    };
+                    let (hash, caps, totp_on) = match rec {
+                        Some(r) => (r.pw_hash, r.caps, r.totp_secret.is_some()),
+                        None => (None, None, false),
+                    };
+
+                    if hash.as_deref().is_none() {
+                        // Re-enable echo (best-effort).
+                        if password_echo_disabled {
+                            let _ = write_tx
+                                .send(Bytes::from(telnet_wont(TELNET_OPT_ECHO).to_vec()))
+                                .await;
+                            password_echo_disabled = false;
+                        }
+                        let _ = write_tx
+                            .send(Bytes::from_static(
+                                b"\r\naccount has no password set\r\nbye\r\n",
+                            ))
+                            .await;
+                        line_bytes.zeroize();
+                        break 'read;
+                    }
+
+                    let ok = if let Some(hash) = hash {
+                        if let Ok(ph) = PasswordHash::new(&hash) {
+                            Argon2::default().verify_password(pw, &ph).is_ok()
+                        } else {
+                            false
+                        }
+                    } else {
+                        false
+                    };
+
+                    // Re-enable echo (best-effort).
+                    if password_echo_disabled {
+                        let _ = write_tx
+                            .send(Bytes::from(telnet_wont(TELNET_OPT_ECHO).to_vec()))
+                            .await;
+                        password_echo_disabled = false;
+                    }
+
+                    if !ok {
+                        let delay = {
+                            let mut t = login_throttle.lock().await;
+                            t.note_failure(peer_ip, uname, now)
+                        };
+                        statsd.incr(statsd::LOGIN_FAILURES, peer_ip);
                         let delay_s = wait_seconds(delay);
 
-                        // Re-disable echo for retry.
-                        password_echo_disabled = true;
+                        password_echo_disabled = true;
+                        let mut b = Vec::new();
+                        b.extend_from_slice(b"\r\nbad password; retry in ");
+                        b.extend_from_slice(delay_s.to_string().as_bytes());
+                        b.extend_from_slice(b"s (forgot it? type: forgot)\r\n");
+                        b.extend_from_slice(telnet_will(TELNET_OPT_ECHO).as_slice());
+                        b.extend_from_slice(b"password: ");
+                        let _ = write_tx.send(Bytes::from(b)).await;
+                        line_bytes.zeroize();
+                        continue;
+                    }
+
+                    let _ = write_tx.send(Bytes::from_static(b"\r\n")).await;
+
+                    // For password auth, the shard principal is acct:<name> (via the auth blob).
+                    auth_blob = Some(make_shard_auth_blob(
+                        name.as_deref().unwrap_or(""),
+                        "password",
+                        None,
+                        None,
+                        None,
+                        None,
+                        caps.as_deref(),
+                    ));
+
+                    line_bytes.zeroize();
+                    if totp_on {
+                        state = ConnState::NeedTotp;
+                        let _ = write_tx.send(Bytes::from_static(TOTP_PROMPT)).await;
+                        continue;
+                    }
+
+                    {
+                        let mut t = login_throttle.lock().await;
+                        t.note_success(peer_ip, uname);
+                    }
+
+                    let acct = uname.to_string();
+                    let (next, character) = enter_account(
+                        &shared,
+                        session,
+                        peer_ip,
+                        &acct,
+                        auth_blob.clone().expect("auth blob set"),
+                        auth_method.as_deref().unwrap_or("unknown"),
+                        &write_tx,
+                        &disconnect_tx,
+                    )
+                    .await?;
+                    account = Some(acct);
+                    name = character;
+                    state = next;
+                    continue;
+                }
+                ConnState::NeedResetCode => {
+                    let uname = name.clone().expect("name set");
+                    let now = std::time::Instant::now();
+                    let wait = {
+                        let mut t = login_throttle.lock().await;
+                        t.wait(peer_ip, &uname, now)
+                    };
+                    if !wait.is_zero() {
+                        let mut b = format!("rate limit: retry in {}s\r\n", wait_seconds(wait))
+                            .into_bytes();
+                        b.extend_from_slice(RESET_CODE_PROMPT);
+                        let _ = write_tx.send(Bytes::from(b)).await;
+                        line_bytes.zeroize();
+                        continue;
+                    }
+
+                    let mut code = String::from_utf8_lossy(&line_bytes).trim().to_string();
+                    line_bytes.zeroize();
+                    if code.is_empty() {
+                        state = ConnState::NeedPasswordLogin;
+                        password_echo_disabled = true;
+                        let mut b = telnet_will(TELNET_OPT_ECHO).to_vec();
+                        b.extend_from_slice(b"password: ");
+                        let _ = write_tx.send(Bytes::from(b)).await;
+                        continue;
+                    }
+                    let now_unix = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs();
+                    let check = {
+                        let mut a = accounts.lock().await;
+                        let check = match a.by_name.get_mut(&uname) {
+                            Some(r) => {
+                                let check = match r.password_reset.as_mut() {
+                                    Some(c) => c.check(&code, now_unix),
+                                    None => email::CodeCheck::Spent,
+                                };
+                                // A code gets one successful use.
+                                if check != email::CodeCheck::Wrong {
+                                    r.password_reset = None;
+                                }
+                                check
+                            }
+                            None => email::CodeCheck::Spent,
+                        };
+                        a.save()?;
+                        check
+                    };
+                    code.zeroize();
+
+                    let mut b = Vec::new();
+                    match check {
+                        email::CodeCheck::Ok => {
+                            state = ConnState::NeedResetPassword;
+                            password_echo_disabled = true;
+                            b.extend_from_slice(telnet_will(TELNET_OPT_ECHO).as_slice());
+                            b.extend_from_slice(
+                                b"new password (never logged/echoed; min 8 chars): ",
+                            );
+                        }
+                        email::CodeCheck::Wrong => {
+                            let delay = {
+                                let mut t = login_throttle.lock().await;
+                                t.note_failure(peer_ip, &uname, now)
+                            };
+                            statsd.incr(statsd::LOGIN_FAILURES, peer_ip);
+                            b.extend_from_slice(
+                                format!("bad code; retry in {}s\r\n", wait_seconds(delay))
+                                    .as_bytes(),
+                            );
+                            b.extend_from_slice(RESET_CODE_PROMPT);
+                        }
+                        email::CodeCheck::Spent => {
+                            state = ConnState::NeedPasswordLogin;
+                            password_echo_disabled = true;
+                            b.extend_from_slice(
+                                b"that code expired or had too many wrong tries; type forgot for a new one\r\n",
+                            );
+                            b.extend_from_slice(telnet_will(TELNET_OPT_ECHO).as_slice());
+                            b.extend_from_slice(b"password: ");
+                        }
+                    }
+                    let _ = write_tx.send(Bytes::from(b)).await;
+                    continue;
+                }
+                ConnState::NeedResetPassword => {
+                    let uname = name.clone().expect("name set");
+                    // Never convert to String and never log (same as NeedPasswordCreate). Echo
+                    // stays off: every way out of here prompts for a password again.
+                    let pw = trim_ascii_ws(&line_bytes);
+
+                    if pw.len() < 8 {
+                        line_bytes.zeroize();
                         let mut b = Vec::new();
-                        b.extend_from_slice(b"\r\npassword too short; retry in ");
-                        b.extend_from_slice(delay_s.to_string().as_bytes());
-                        b.extend_from_slice(b"s\r\n");
-                        b.extend_from_slice(telnet_will(TELNET_OPT_ECHO).as_slice());
-                        b.extend_from_slice(b"set password (min 8 chars): ");
+                        b.extend_from_slice(b"\r\npassword too short\r\n");
+                        b.extend_from_slice(b"new password (min 8 chars): ");
                         let _ = write_tx.send(Bytes::from(b)).await;
-                        line_bytes.zeroize();
                         continue;
                     }
 
-                    let _ = write_tx.send(Bytes::from_static(b"\r\n")).await;
-
-                    // Store only a salted hash, never the raw password.
                     let salt = SaltString::generate(&mut password_hash::rand_core::OsRng);
                     let hash = Argon2::default()
                         .hash_password(pw, &salt)
                         .map_err(|e| anyhow::anyhow!("hash_password failed: {e}"))?
                         .to_string();
-
-                    let now_unix = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_secs();
+                    line_bytes.zeroize();
                     {
                         let mut a = accounts.lock().await;
-                        let uname = name.as_ref().expect("name set").clone();
-                        a.by_name.insert(
-                            uname.clone(),
-                            AccountRec {
-                                name: uname,
-                                pw_hash: Some(hash),
-                                google_sub: None,
-                                google_email: None,
-                                oidc_sub: None,
-                                oidc_email: None,
-                                caps: None,
-                                email: None,
-                                created_unix: now_unix,
-                            },
-                        );
+                        if let Some(r) = a.by_name.get_mut(&uname) {
+                            r.pw_hash = Some(hash);
+                        }
                         a.save()?;
                     }
-
                     {
-                        let mut t = login_throttle.lock().await;
-                        t.note_success(peer_ip, uname);
+                        let ts = Utc::now().to_rfc3339();
+                        let sid = session_hex(session);
+                        let entry = format!(
+                            "ts={} kind=password_reset session={} ip={} name={} via=email",
+                            logfmt_str(&ts),
+                            logfmt_str(&sid),
+                            logfmt_str(&peer_ip.to_string()),
+                            logfmt_str(&uname),
+                        );
+                        eventlog.log_line(LogStream::Login, &entry).await;
                     }
 
-                    // For password auth, the shard principal is acct:<name> (via the auth blob).
-                    auth_blob = Some(make_shard_auth_blob(
-                        name.as_deref().unwrap_or(""),
-                        "password",
-                        None,
-                        None,
-                        None,
-                        None,
-                        None,
-                    ));
-
-                    line_bytes.zeroize();
-                    let uname = name.as_deref().unwrap_or("");
-                    if let Some(saved) = wait_for_saved_player(
-                        &cfg.players_path,
-                        uname,
-                        Duration::from_secs(2),
-                    )
-                    .await
-                    {
-                        let shard_auth = auth_blob.clone().expect("auth blob set");
-                        attach_authenticated_session(
-                            session,
-                            peer_ip,
-                            uname,
-                            saved.is_bot,
-                            &saved.race,
-                            &saved.class,
-                            &saved.sex,
-                            &saved.pronouns,
-                            shard_auth,
-                            auth_method.as_deref().unwrap_or("unknown"),
-                            &write_tx,
-                            &disconnect_tx,
-                            &sessions,
-                            &holds,
-                            &eventlog,
-                            &shard_tx,
-                        )
-                        .await?;
-                        state = ConnState::InWorld;
-                    } else {
-                        state = ConnState::NeedBotDisclosure;
-                        let _ = write_tx
-                            .send(Bytes::from_static(
-                                b"character creation (step 2/4)\r\nare you using automation?\r\ntype: human | bot\r\n> ",
-                            ))
-                            .await;
-                    }
+                    // Log in with the new password as usual, so 2fa still applies.
+                    state = ConnState::NeedPasswordLogin;
+                    let mut b = Vec::new();
+                    b.extend_from_slice(b"\r\nok: password changed; log in with it now\r\n");
+                    b.extend_from_slice(b"password: ");
+                    let _ = write_tx.send(Bytes::from(b)).await;
                     continue;
                 }
-                ConnState::NeedPasswordLogin => {
-                    let uname = name.as_deref().expect("name set");
+                ConnState::NeedTotp => {
+                    let uname = name.clone().expect("name set");
                     let now = std::time::Instant::now();
                     let wait = {
                         let mut t = login_throttle.lock().await;
-                        t.wait(peer_ip, uname, now)
+                        t.wait(peer_ip, &uname, now)
                     };
                     if !wait.is_zero() {
-                        let wait_s = wait_seconds(wait);
-                        let mut b = Vec::new();
-                        b.extend_from_slice(b"\r\nrate limit: retry in ");
-                        b.extend_from_slice(wait_s.to_string().as_bytes());
-                        b.extend_from_slice(b"s\r\n");
-                        if !password_echo_disabled {
-                            password_echo_disabled = true;
-                            b.extend_from_slice(telnet_will(TELNET_OPT_ECHO).as_slice());
-                        }
-                        b.extend_from_slice(b"password: ");
-                        let _ = write_tx.send(Bytes::from(b)).await;
+                        let msg =
+                            format!("rate limit: retry in {}s\r\n2fa code: ", wait_seconds(wait));
+                        let _ = write_tx.send(Bytes::from(msg)).await;
                         line_bytes.zeroize();
                         continue;
                     }
 
-                    let pw = trim_ascii_ws(&line_bytes);
-                    let rec = {
-                        let a = accounts.lock().await;
-                        a.by_name.get(uname).cloned()
+                    let mut code = String::from_utf8_lossy(&line_bytes).trim().to_string();
+                    line_bytes.zeroize();
+                    let now_unix = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs();
+                    let passed = {
+                        let mut a = accounts.lock().await;
+                        let passed = match a.by_name.get_mut(&uname) {
+                            Some(r) => r
+                                .check_second_factor(&code, now_unix)
+                                .map(|how| (how, r.totp_recovery.len())),
+                            None => None,
+                        };
+                        if passed.is_some() {
+                            a.save()?;
+                        }
+                        passed
                     };
-                    let (hash, caps) = match rec {
-                        Some(r) => (r.pw_hash, r.caps),
-                        None => (None, None),
+                    code.zeroize();
+
+                    let Some((how, recovery_left)) = passed else {
+                        let delay = {
+                            let mut t = login_throttle.lock().await;
+                            t.note_failure(peer_ip, &uname, now)
+                        };
+                        statsd.incr(statsd::LOGIN_FAILURES, peer_ip);
+                        let msg =
+                            format!("bad code; retry in {}s\r\n2fa code: ", wait_seconds(delay));
+                        let _ = write_tx.send(Bytes::from(msg)).await;
+                        continue;
                     };
 
-                    if hash.as_deref().is_none() {
-                        // Re-enable echo (best-effort).
-                        if password_echo_disabled {
-                            let _ = write_tx
-                                .send(Bytes::from(telnet_wont(TELNET_OPT_ECHO).to_vec()))
-                                .await;
-                            password_echo_disabled = false;
-                        }
-                        let _ = write_tx
-                            .send(Bytes::from_static(
-                                b"\r\naccount has no password set\r\nbye\r\n",
-                            ))
-                            .await;
-                        line_bytes.zeroize();
-                        break 'read;
+                    {
+                        let mut t = login_throttle.lock().await;
+                        t.note_success(peer_ip, &uname);
+                    }
+                    {
+                        let ts = Utc::now().to_rfc3339();
+                        let sid = session_hex(session);
+                        let entry = format!(
+                            "ts={} kind=second_factor session={} ip={} name={} via={}",
+                            logfmt_str(&ts),
+                            logfmt_str(&sid),
+                            logfmt_str(&peer_ip.to_string()),
+                            logfmt_str(&uname),
+                            logfmt_str(how),
+                        );
+                        eventlog.log_line(LogStream::Login, &entry).await;
+                    }
+                    if how == "recovery" {
+                        let msg = format!(
+                            "recovery code used; {recovery_left} left (account 2fa recovery <code> makes new ones)\r\n"
+                        );
+                        let _ = write_tx.send(Bytes::from(msg)).await;
                     }
 
-                    let ok = if let Some(hash) = hash {
-                        if let Ok(ph) = PasswordHash::new(&hash) {
-                            Argon2::default().verify_password(pw, &ph).is_ok()
+                    let (next, character) = enter_account(
+                        &shared,
+                        session,
+                        peer_ip,
+                        &uname,
+                        auth_blob.clone().expect("auth blob set"),
+                        auth_method.as_deref().unwrap_or("unknown"),
+                        &write_tx,
+                        &disconnect_tx,
+                    )
+                    .await?;
+                    account = Some(uname);
+                    name = character;
+                    state = next;
+                    continue;
+                }
+                ConnState::NeedCharacter => {
+                    let line = String::from_utf8_lossy(&line_bytes).trim().to_string();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    let Some(acct) = account.clone() else {
+                        continue;
+                    };
+                    let lc = line.to_ascii_lowercase();
+                    let (characters, limit) = {
+                        let a = accounts.lock().await;
+                        (
+                            a.characters(&acct),
+                            a.character_limit(&acct, cfg.max_characters),
+                        )
+                    };
+
+                    if let Some(c) = pending_delete.take() {
+                        let mut out = if lc == "yes" {
+                            let mut a = accounts.lock().await;
+                            if let Some(r) = a.by_name.get_mut(&acct) {
+                                r.characters_mut().retain(|x| !x.eq_ignore_ascii_case(&c));
+                            }
+                            a.save()?;
+                            a.forget_character(&c);
+                            drop(a);
+                            send_player_op(shard_tx, shard_signer, PlayerOp::Forget { name: &c })
+                                .await;
+                            format!("deleted {c}\r\n")
                         } else {
-                            false
+                            format!("kept {c}\r\n")
+                        };
+                        let characters = accounts.lock().await.characters(&acct);
+                        out.push_str(&character_menu_text(&cfg.players_path, &characters, limit));
+                        let _ = write_tx.send(Bytes::from(out)).await;
+                        continue;
+                    }
+
+                    let (verb, arg) = match lc.split_once(char::is_whitespace) {
+                        Some((v, _)) if v == "new" || v == "rename" || v == "delete" => {
+                            (v, line[v.len()..].trim())
                         }
-                    } else {
-                        false
+                        _ => ("", line.as_str()),
                     };
+                    let picked = characters
+                        .iter()
+                        .find(|c| c.eq_ignore_ascii_case(sanitize_name(arg).as_str()))
+                        .cloned();
+
+                    match verb {
+                        "new" => {
+                            let n = sanitize_name(arg);
+                            if n.is_empty() {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(
+                                        b"bad name (use letters/numbers/_/-, max 20)\r\n> ",
+                                    ))
+                                    .await;
+                                continue;
+                            }
+                            if accounts
+                                .lock()
+                                .await
+                                .at_character_limit(&acct, cfg.max_characters)
+                            {
+                                let msg = format!(
+                                    "character limit reached ({limit}); delete one first\r\n> "
+                                );
+                                let _ = write_tx.send(Bytes::from(msg)).await;
+                                continue;
+                            }
+                            if bans.lock().await.is_char_banned(&n).is_some() {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(b"that name is not allowed\r\n> "))
+                                    .await;
+                                continue;
+                            }
+                            {
+                                let mut a = accounts.lock().await;
+                                if a.name_taken(&n) {
+                                    drop(a);
+                                    let _ = write_tx
+                                        .send(Bytes::from_static(b"name already taken\r\n> "))
+                                        .await;
+                                    continue;
+                                }
+                                if let Some(r) = a.by_name.get_mut(&acct) {
+                                    r.characters_mut().push(n.clone());
+                                }
+                                a.save()?;
+                            }
+                            name = Some(n);
+                            fresh_character = true;
+                            state = ConnState::NeedBotDisclosure;
+                            let _ = write_tx
+                                .send(Bytes::from_static(CHARACTER_CREATION_PROMPT))
+                                .await;
+                        }
+                        "delete" => {
+                            let Some(c) = picked else {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(b"no such character\r\n> "))
+                                    .await;
+                                continue;
+                            };
+                            // An empty list would read as a pre-multi-character account.
+                            if characters.len() == 1 {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(
+                                        b"an account keeps at least one character\r\n> ",
+                                    ))
+                                    .await;
+                                continue;
+                            }
+                            let online = {
+                                let m = sessions.lock().await;
+                                m.values().any(|si| si.name.eq_ignore_ascii_case(&c))
+                            };
+                            if online {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(
+                                        b"that character is in the world right now\r\n> ",
+                                    ))
+                                    .await;
+                                continue;
+                            }
+                            if holds.lock().await.is_held(&c).is_some() {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(
+                                        b"that character can't be deleted right now\r\n> ",
+                                    ))
+                                    .await;
+                                continue;
+                            }
+                            let msg = format!(
+                                "delete {c} for good? their progress can't be recovered\r\ntype: yes | no\r\n> "
+                            );
+                            pending_delete = Some(c);
+                            let _ = write_tx.send(Bytes::from(msg)).await;
+                        }
+                        "rename" => {
+                            let mut parts = arg.split_whitespace();
+                            let (Some(old), Some(new), None) =
+                                (parts.next(), parts.next(), parts.next())
+                            else {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(
+                                        b"usage: rename <name> <new name>\r\n> ",
+                                    ))
+                                    .await;
+                                continue;
+                            };
+                            let Some(old) = characters
+                                .iter()
+                                .find(|c| c.eq_ignore_ascii_case(sanitize_name(old).as_str()))
+                                .cloned()
+                            else {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(b"no such character\r\n> "))
+                                    .await;
+                                continue;
+                            };
+                            let new = sanitize_name(new);
+                            if new.is_empty() {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(
+                                        b"bad name (use letters/numbers/_/-, max 20)\r\n> ",
+                                    ))
+                                    .await;
+                                continue;
+                            }
+                            if new == old {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(b"that's already its name\r\n> "))
+                                    .await;
+                                continue;
+                            }
+                            if bans.lock().await.is_char_banned(&new).is_some() {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(b"that name is not allowed\r\n> "))
+                                    .await;
+                                continue;
+                            }
+                            // Held records keep their name; don't say why.
+                            if holds.lock().await.is_held(&old).is_some() {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(
+                                        b"that character can't be renamed right now\r\n> ",
+                                    ))
+                                    .await;
+                                continue;
+                            }
+                            let online = {
+                                let m = sessions.lock().await;
+                                m.values().any(|si| si.name.eq_ignore_ascii_case(&old))
+                            };
+                            if online {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(
+                                        b"that character is in the world right now\r\n> ",
+                                    ))
+                                    .await;
+                                continue;
+                            }
 
-                    // Re-enable echo (best-effort).
-                    if password_echo_disabled {
-                        let _ = write_tx
-                            .send(Bytes::from(telnet_wont(TELNET_OPT_ECHO).to_vec()))
+                            let now_unix = std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .unwrap_or_default()
+                                .as_secs();
+                            let prev_renamed = {
+                                let mut a = accounts.lock().await;
+                                if !new.eq_ignore_ascii_case(&old) && a.name_taken(&new) {
+                                    drop(a);
+                                    let _ = write_tx
+                                        .send(Bytes::from_static(b"name already taken\r\n> "))
+                                        .await;
+                                    continue;
+                                }
+                                let Some(r) = a.by_name.get_mut(&acct) else {
+                                    continue;
+                                };
+                                let next = r
+                                    .renamed_unix
+                                    .map(|t| t.saturating_add(cfg.rename_cooldown_s));
+                                if let Some(next) = next.filter(|&t| now_unix < t) {
+                                    drop(a);
+                                    let msg = format!(
+                                        "this account can rename again after {}\r\n> ",
+                                        fmt_unix(next)
+                                    );
+                                    let _ = write_tx.send(Bytes::from(msg)).await;
+                                    continue;
+                                }
+                                let prev = r.renamed_unix.replace(now_unix);
+                                for c in r.characters_mut().iter_mut() {
+                                    if c.eq_ignore_ascii_case(&old) {
+                                        *c = new.clone();
+                                    }
+                                }
+                                a.save()?;
+                                prev
+                            };
+                            if let Err(e) = bans.lock().await.rename_char_ban(&old, &new, now_unix)
+                            {
+                                warn!(old = %old, new = %new, err = %e, "ban rename failed");
+                            }
+                            let had_snapshot = load_saved_player(&cfg.players_path, &old).is_some();
+                            send_player_op(
+                                shard_tx,
+                                shard_signer,
+                                PlayerOp::Rename {
+                                    name: &old,
+                                    new_name: &new,
+                                },
+                            )
                             .await;
-                        password_echo_disabled = false;
-                    }
-
-                    if !ok {
-                        let delay = {
-                            let mut t = login_throttle.lock().await;
-                            t.note_failure(peer_ip, uname, now)
-                        };
-                        let delay_s = wait_seconds(delay);
-
-                        password_echo_disabled = true;
-                        let mut b = Vec::new();
-                        b.extend_from_slice(b"\r\nbad password; retry in ");
-                        b.extend_from_slice(delay_s.to_string().as_bytes());
-                        b.extend_from_slice(b"s\r\n");
-                        b.extend_from_slice(telnet_will(TELNET_OPT_ECHO).as_slice());
-                        b.extend_from_slice(b"password: ");
-                        let _ = write_tx.send(Bytes::from(b)).await;
-                        line_bytes.zeroize();
-                        continue;
-                    }
-
-                    let _ = write_tx.send(Bytes::from_static(b"\r\n")).await;
-
-                    {
-                        let mut t = login_throttle.lock().await;
-                        t.note_success(peer_ip, uname);
-                    }
-
-                    // For password auth, the shard principal is acct:<name> (via the auth blob).
-                    auth_blob = Some(make_shard_auth_blob(
-                        name.as_deref().unwrap_or(""),
-                        "password",
-                        None,
-                        None,
-                        None,
-                        None,
-                        caps.as_deref(),
-                    ));
+                            let renamed = !had_snapshot
+                                || wait_for_saved_player(
+                                    &cfg.players_path,
+                                    &new,
+                                    Duration::from_secs(3),
+                                )
+                                .await
+                                .is_some();
+                            if !renamed {
+                                // No shard applied it (down, or too old for player ops). Put the
+                                // account back; the reverse op covers a shard that was just slow.
+                                {
+                                    let mut a = accounts.lock().await;
+                                    if let Some(r) = a.by_name.get_mut(&acct) {
+                                        r.renamed_unix = prev_renamed;
+                                        for c in r.characters_mut().iter_mut() {
+                                            if c.eq_ignore_ascii_case(&new) {
+                                                *c = old.clone();
+                                            }
+                                        }
+                                    }
+                                    a.save()?;
+                                }
+                                if let Err(e) =
+                                    bans.lock().await.rename_char_ban(&new, &old, now_unix)
+                                {
+                                    warn!(old = %old, new = %new, err = %e, "ban rename revert failed");
+                                }
+                                send_player_op(
+                                    shard_tx,
+                                    shard_signer,
+                                    PlayerOp::Rename {
+                                        name: &new,
+                                        new_name: &old,
+                                    },
+                                )
+                                .await;
+                                warn!(old = %old, new = %new, "character rename not applied by shard; reverted");
+                                let _ = write_tx
+                                    .send(Bytes::from_static(
+                                        b"rename failed; try again later\r\n> ",
+                                    ))
+                                    .await;
+                                continue;
+                            }
 
-                    line_bytes.zeroize();
-                    if let Some(saved) = wait_for_saved_player(
-                        &cfg.players_path,
-                        uname,
-                        Duration::from_secs(2),
-                    )
-                    .await
-                    {
-                        let shard_auth = auth_blob.clone().expect("auth blob set");
-                        attach_authenticated_session(
-                            session,
-                            peer_ip,
-                            uname,
-                            saved.is_bot,
-                            &saved.race,
-                            &saved.class,
-                            &saved.sex,
-                            &saved.pronouns,
-                            shard_auth,
-                            auth_method.as_deref().unwrap_or("unknown"),
-                            &write_tx,
-                            &disconnect_tx,
-                            &sessions,
-                            &holds,
-                            &eventlog,
-                            &shard_tx,
-                        )
-                        .await?;
-                        state = ConnState::InWorld;
-                    } else {
-                        state = ConnState::NeedBotDisclosure;
-                        let _ = write_tx
-                            .send(Bytes::from_static(
-                                b"character creation (step 2/4)\r\nare you using automation?\r\ntype: human | bot\r\n> ",
-                            ))
-                            .await;
+                            {
+                                let ts = Utc::now().to_rfc3339();
+                                let entry = format!(
+                                    "ts={} kind=character_renamed session={} ip={} account={} old={} new={}",
+                                    logfmt_str(&ts),
+                                    logfmt_str(&session_hex(session)),
+                                    logfmt_str(&peer_ip.to_string()),
+                                    logfmt_str(&acct),
+                                    logfmt_str(&old),
+                                    logfmt_str(&new),
+                                );
+                                eventlog.log_line(LogStream::All, &entry).await;
+                                eventlog.log_line(LogStream::Character(&old), &entry).await;
+                                eventlog.log_line(LogStream::Character(&new), &entry).await;
+                            }
+                            let characters = accounts.lock().await.characters(&acct);
+                            let mut out = format!("ok: {old} is now {new}\r\n");
+                            out.push_str(&character_menu_text(
+                                &cfg.players_path,
+                                &characters,
+                                limit,
+                            ));
+                            let _ = write_tx.send(Bytes::from(out)).await;
+                        }
+                        _ => {
+                            let Some(c) = picked else {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(
+                                        b"type: <name> | new <name> | rename <name> <new name> | delete <name>\r\n> ",
+                                    ))
+                                    .await;
+                                continue;
+                            };
+                            if bans.lock().await.is_char_banned(&c).is_some() {
+                                let _ = write_tx
+                                    .send(Bytes::from_static(b"that character is banned\r\n> "))
+                                    .await;
+                                continue;
+                            }
+                            let saved = load_saved_player(&cfg.players_path, &c);
+                            state = start_character(
+                                session,
+                                peer_ip,
+                                &c,
+                                saved,
+                                auth_blob.clone().expect("auth blob set"),
+                                auth_method.as_deref().unwrap_or("unknown"),
+                                &write_tx,
+                                &disconnect_tx,
+                                &shared,
+                            )
+                            .await?;
+                            name = Some(c);
+                        }
                     }
                     continue;
                 }
@@ -4796,7 +8458,7 @@ async fn handle_conn(
                 let pro_s = pronouns.clone().unwrap_or_else(|| "they".to_string());
                 let shard_auth = auth_blob.clone().unwrap_or_else(|| {
                     make_shard_auth_blob(
-                        &n,
+                        account.as_deref().unwrap_or(&n),
                         auth_method.as_deref().unwrap_or("unknown"),
                         google_sub.as_deref(),
                         google_email.as_deref(),
@@ -4849,6 +8511,8 @@ async fn handle_conn(
                 }
 
                 let body = attach_body(
+                    session,
+                    shard_signer,
                     bot,
                     Some(shard_auth.as_ref()),
                     &race_s,
@@ -4856,10 +8520,14 @@ async fn handle_conn(
                     &sex_s,
                     &pro_s,
                     n.as_bytes(),
-                );
+                    None,
+                    fresh_character,
+                )
+                .map_err(|e| anyhow::anyhow!("encode shard attach: {e}"))?;
+                fresh_character = false;
                 let _ = shard_tx
                     .send(ShardMsg {
-                        t: REQ_ATTACH,
+                        t: REQ_ATTACH_V2,
                         session,
                         body,
                     })
@@ -4868,7 +8536,21 @@ async fn handle_conn(
             }
 
             // In-world command handling at the broker level.
-            let line = String::from_utf8_lossy(&line_bytes).trim().to_string();
+            let mut line = String::from_utf8_lossy(&line_bytes).trim().to_string();
+            if !from_alias {
+                match aliases::expand(&aliases, &line) {
+                    Some(Ok(cmds)) => {
+                        alias_queue.extend(cmds.into_iter().map(String::into_bytes));
+                        continue;
+                    }
+                    Some(Err(e)) => {
+                        let msg = format!("alias: {e}\r\n");
+                        let _ = write_tx.send(Bytes::from(msg)).await;
+                        continue;
+                    }
+                    None => line = aliases::unescape(&line).to_string(),
+                }
+            }
             if line.is_empty() {
                 continue;
             }
@@ -4932,10 +8614,57 @@ async fn handle_conn(
                 // `uptime` (no args) also forwards to shard so the user can see shard wall time + world time.
             }
 
+            if lc == "characters" {
+                let Some(acct) = account.clone() else {
+                    continue;
+                };
+                // Back to the character menu without dropping the connection.
+                leave_world(session, sessions, eventlog, shard_tx, chat_tx.as_ref()).await;
+                name = None;
+                is_bot = None;
+                race = None;
+                class = None;
+                sex = None;
+                pronouns = None;
+                color_pref_loaded = false;
+                let (characters, limit) = {
+                    let a = accounts.lock().await;
+                    (
+                        a.characters(&acct),
+                        a.character_limit(&acct, cfg.max_characters),
+                    )
+                };
+                let menu = character_menu_text(&cfg.players_path, &characters, limit);
+                let _ = write_tx.send(Bytes::from(menu)).await;
+                state = ConnState::NeedCharacter;
+                continue;
+            }
+
             if lc == "report" || lc.starts_with("report ") {
                 let nm = name.as_deref().unwrap_or("");
-                let out = handle_report_command(
-                    &sessions, &holds, &nearline, &eventlog, session, peer_ip, nm, &line,
+                let out = handle_report_command(&shared, session, peer_ip, nm, &line).await;
+                let _ = write_tx.send(Bytes::from(out)).await;
+                continue;
+            }
+
+            if lc == "silence" || lc.starts_with("silence ") {
+                let out = handle_silence_command(
+                    &shared,
+                    account.as_deref().unwrap_or(""),
+                    name.as_deref().unwrap_or(""),
+                    &line,
+                )
+                .await;
+                let _ = write_tx.send(Bytes::from(out)).await;
+                continue;
+            }
+
+            if lc == "reports" || lc.starts_with("reports ") {
+                let out = handle_reports_command(
+                    &shared,
+                    account.as_deref().unwrap_or(""),
+                    name.as_deref().unwrap_or(""),
+                    &line,
                 )
                 .await;
                 let _ = write_tx.send(Bytes::from(out)).await;
@@ -4945,10 +8674,10 @@ async fn handle_conn(
             if lc == "accounthold" || lc.starts_with("accounthold ") {
                 let nm = name.as_deref().unwrap_or("");
                 let out = handle_accounthold_command(
-                    &sessions,
-                    &holds,
+                    sessions,
+                    holds,
                     &cfg.sbc_admin_sock,
-                    &eventlog,
+                    eventlog,
                     peer_ip,
                     session,
                     nm,
@@ -4960,20 +8689,135 @@ async fn handle_conn(
             }
 
             if lc == "account" || lc.starts_with("account ") {
-                let nm = name.as_deref().unwrap_or("");
-                let out = handle_account_command(&accounts, nm, &line).await;
+                let nm = account.as_deref().unwrap_or("");
+                let out = handle_account_command(cfg, accounts, mailer, nearline, nm, &line).await;
+                let _ = write_tx.send(Bytes::from(out)).await;
+                continue;
+            }
+
+            if lc == "color"
+                || lc.starts_with("color ")
+                || lc == "colour"
+                || lc.starts_with("colour ")
+            {
+                let nm = account.as_deref().unwrap_or("");
+                let out =
+                    handle_color_command(accounts, nm, &line, &term_caps, &mut color_pref).await;
+                color_tx.send_replace(color_pref.effective(term_caps.detected_color()));
+                let _ = write_tx.send(Out::Markup(Bytes::from(out))).await;
+                continue;
+            }
+
+            if lc == "chat" || lc.starts_with("chat ") || is_chat_channel_line(&lc) {
+                let Some(chat_tx) = chat_tx.as_ref() else {
+                    let _ = write_tx
+                        .send(Bytes::from_static(b"chat is not available here\r\n"))
+                        .await;
+                    continue;
+                };
+                // `chat <cmd>` strips the prefix; `ooc hi` is already a chatd command line.
+                let cmdline = if lc == "chat" || lc.starts_with("chat ") {
+                    line.trim()[4..].trim()
+                } else {
+                    line.trim()
+                };
+                let now_unix = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                let refusal = {
+                    let b = bans.lock().await;
+                    chat_mute_refusal(&b, name.as_deref().unwrap_or(""), cmdline, now_unix)
+                };
+                if let Some(msg) = refusal {
+                    let _ = write_tx.send(Bytes::from(msg)).await;
+                    continue;
+                }
+                let _ = chat_tx
+                    .send(ShardMsg {
+                        t: mudproto::chat::REQ_SAY,
+                        session,
+                        body: Bytes::copy_from_slice(cmdline.as_bytes()),
+                    })
+                    .await;
+                continue;
+            }
+
+            if lc == "alias"
+                || lc.starts_with("alias ")
+                || lc == "unalias"
+                || lc.starts_with("unalias ")
+            {
+                let nm = account.as_deref().unwrap_or("");
+                let out = handle_alias_command(accounts, nm, &line, &mut aliases).await;
+                let _ = write_tx.send(Bytes::from(out)).await;
+                continue;
+            }
+
+            if lc == "term" || lc.starts_with("term ") {
+                let out = handle_term_command(
+                    &line,
+                    &term_caps,
+                    &mut width_override,
+                    &mut height_override,
+                );
+                let (width, height) =
+                    term_size(&term_caps, width_override, height_override, is_bot);
+                let _ = pager_tx.send(pager::Ctl::Resize { width, height }).await;
                 let _ = write_tx.send(Bytes::from(out)).await;
                 continue;
             }
 
+            let Ok(body) = encode_input_v2(line.as_bytes(), Some(input_seq + 1)) else {
+                let _ = write_tx
+                    .send(Bytes::from_static(b"huh? (that line is too long)\r\n"))
+                    .await;
+                continue;
+            };
+            input_seq += 1;
             let _ = shard_tx
                 .send(ShardMsg {
-                    t: REQ_INPUT,
+                    t: REQ_INPUT_V2,
                     session,
-                    body: Bytes::from(line.into_bytes()),
+                    body,
                 })
                 .await;
         }
+
+        // Once in world, switch to the account's saved color setting.
+        if state == ConnState::InWorld && !color_pref_loaded {
+            color_pref_loaded = true;
+            aliases = load_aliases(accounts, account.as_deref()).await;
+            let saved = match account.as_deref() {
+                Some(nm) => accounts
+                    .lock()
+                    .await
+                    .by_name
+                    .get(nm)
+                    .and_then(|r| r.color.clone()),
+                None => None,
+            };
+            color_pref = saved
+                .as_deref()
+                .and_then(term::ColorPref::parse)
+                .unwrap_or_default();
+            color_tx.send_replace(color_pref.effective(term_caps.detected_color()));
+            // Bots are known by now; they never get paged.
+            let (width, height) = term_size(&term_caps, width_override, height_override, is_bot);
+            let _ = pager_tx.send(pager::Ctl::Resize { width, height }).await;
+            if let (Some(chat_tx), Some(nm)) = (chat_tx.as_ref(), name.as_deref()) {
+                let _ = chat_tx
+                    .send(ShardMsg {
+                        t: mudproto::chat::REQ_JOIN,
+                        session,
+                        body: Bytes::copy_from_slice(nm.as_bytes()),
+                    })
+                    .await;
+            }
+            if let Some(nm) = name.as_deref() {
+                deliver_report_notices(reports, &write_tx, nm).await;
+            }
+        }
     }
 
     // Best-effort: if we disconnected mid-password, restore echo.
@@ -4983,34 +8827,67 @@ async fn handle_conn(
             .await;
     }
 
-    // Disconnect cleanup.
-    let removed = { sessions.lock().await.remove(&session) };
-    if let Some(si) = removed {
-        {
-            let ts = Utc::now().to_rfc3339();
-            let sid = session_hex(session);
-            let entry = format!(
-                "ts={} kind=logout session={} ip={} name={}",
-                logfmt_str(&ts),
-                logfmt_str(&sid),
-                logfmt_str(&si.peer_ip.to_string()),
-                logfmt_str(&si.name),
-            );
-            eventlog.log_line(LogStream::All, &entry).await;
-            eventlog
-                .log_line(LogStream::Character(&si.name), &entry)
-                .await;
-            eventlog.log_line(LogStream::Login, &entry).await;
-        }
+    // Hotboot: the session goes to the next process as is, so no detach or logout.
+    if parking {
+        let si = sessions.lock().await.remove(&session);
+        let scrollback = match si.as_ref() {
+            Some(si) => si
+                .scrollback
+                .lock()
+                .await
+                .last_n(SCROLLBACK_MAX_LINES)
+                .into_iter()
+                .map(|l| hotboot::ScrollbackLine {
+                    id: l.id.0,
+                    ts_unix_ms: l.ts_unix_ms,
+                    text: l.text,
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        let mut conn = hotboot::Conn {
+            fd: -1,
+            session: session.0,
+            peer,
+            peer_ip,
+            peer_port,
+            in_world: state == ConnState::InWorld && si.is_some(),
+            account,
+            name: si.as_ref().map(|si| si.name.clone()).or(name),
+            auth_method,
+            auth_blob: auth_blob.map(|b| String::from_utf8_lossy(&b).into_owned()),
+            google_sub,
+            google_email,
+            oidc_sub,
+            oidc_email,
+            is_bot: si.as_ref().map(|si| si.is_bot).or(is_bot),
+            race: si.as_ref().map(|si| si.race.clone()).or(race),
+            class: si.as_ref().map(|si| si.class.clone()).or(class),
+            sex: si.as_ref().map(|si| si.sex.clone()).or(sex),
+            pronouns: si.as_ref().map(|si| si.pronouns.clone()).or(pronouns),
+            held: si.as_ref().is_some_and(|si| si.held),
+            term: term_caps,
+            width_override,
+            height_override,
+            color_pref: color_pref.as_str().to_string(),
+            pending_input: linebuf,
+            input_seq,
+            scrollback,
+            shard: None,
+            handoff_snapshot: None,
+        };
+        drop(si);
+        drop(write_tx);
+        let _ = writer_stop_tx.send(());
+        let wr = writer.await?;
+        let stream = rd.reunite(wr)?.into_std()?;
+        conn.fd = hotboot::park(stream)?;
+        let _ = hotboot_parked.send(conn).await;
+        return Ok(());
+    }
 
-        let _ = shard_tx
-            .send(ShardMsg {
-                t: REQ_DETACH,
-                session,
-                body: Bytes::new(),
-            })
-            .await;
-    } else {
+    // Disconnect cleanup.
+    if !leave_world(session, sessions, eventlog, shard_tx, chat_tx.as_ref()).await {
         info!(peer=%peer, "disconnected before entering world");
     }
 
@@ -5322,7 +9199,8 @@ fn try_pop_line(buf: &mut Vec<u8>) -> Option<Vec<u8>> {
 #[cfg(test)]
 mod tests {
     use super::{
-        LineId, Scrollback, extract_scrollback_lines, normalize_email, redact_pii, trim_ascii_ws,
+        AccountRec, Accounts, LineId, Scrollback, ban, chat_mute_refusal, extract_scrollback_lines,
+        normalize_email, parse_duration_s, redact_input_for_logs, redact_pii, trim_ascii_ws,
     };
 
     #[test]
@@ -5352,6 +9230,27 @@ mod tests {
         assert!(normalize_email("a b@example.com").is_none());
     }
 
+    #[test]
+    fn redact_input_hides_emails_and_2fa_codes() {
+        assert_eq!(
+            redact_input_for_logs("account email set a@example.com"),
+            "account email set <redacted>"
+        );
+        assert_eq!(
+            redact_input_for_logs("account email verify 12345678"),
+            "account email verify <redacted>"
+        );
+        assert_eq!(
+            redact_input_for_logs("account 2fa verify 123456"),
+            "account 2fa verify <redacted>"
+        );
+        assert_eq!(
+            redact_input_for_logs("account 2fa enable"),
+            "account 2fa enable"
+        );
+        assert_eq!(redact_input_for_logs("say 123456"), "say 123456");
+    }
+
     #[test]
     fn scrollback_splits_and_skips_prompt() {
         let lines = extract_scrollback_lines(b"hello\r\n> \r\nworld\n\n");
@@ -5398,4 +9297,92 @@ mod tests {
         );
         assert_eq!(redact_pii("no pii here"), "no pii here".to_string());
     }
+
+    #[test]
+    fn silence_durations() {
+        assert_eq!(parse_duration_s("90s"), Some(90));
+        assert_eq!(parse_duration_s("30"), Some(1800));
+        assert_eq!(parse_duration_s("2H"), Some(7200));
+        assert_eq!(parse_duration_s("7d"), Some(604_800));
+        assert_eq!(parse_duration_s("0m"), None);
+        assert_eq!(parse_duration_s("soon"), None);
+    }
+
+    #[test]
+    fn muted_players_cant_post_to_chat_channels() {
+        let path =
+            std::env::temp_dir().join(format!("slopmud-chat-mute-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut bans = ban::BanState::load(path.clone());
+        let (now, until) = (1_000, 1_290);
+        bans.upsert_char_mute("Mallory", until, now, "Mod".into(), "spam".into())
+            .unwrap();
+        let refusal = |name, line, at| chat_mute_refusal(&bans, name, line, at);
+
+        for post in ["ooc hi", "newbie hi", "TRADE wts", "party omw", "guild gg"] {
+            let msg = refusal("mallory", post, now).unwrap_or_default();
+            assert_eq!(msg, "you are muted (5m left)\r\n", "{post}");
+        }
+        for other in ["list", "join trade", "history ooc", "ooc"] {
+            assert_eq!(refusal("Mallory", other, now), None, "{other}");
+        }
+        assert_eq!(refusal("Alice", "ooc hello", now), None);
+        assert_eq!(refusal("Mallory", "ooc hello", until), None);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    fn accounts(recs: serde_json::Value) -> Accounts {
+        let path =
+            std::env::temp_dir().join(format!("slopmud-accounts-{}.json", std::process::id()));
+        let recs: Vec<AccountRec> = serde_json::from_value(recs).unwrap();
+        Accounts {
+            path: path.to_string_lossy().into_owned(),
+            by_name: recs.into_iter().map(|r| (r.name.clone(), r)).collect(),
+            forgets: super::forget::PendingForgets::load(path.with_extension("forget")),
+        }
+    }
+
+    #[test]
+    fn legacy_account_has_its_name_as_character() {
+        let mut a = accounts(serde_json::json!([
+            {"name": "old", "created_unix": 0},
+            {"name": "new", "characters": ["alpha", "beta"], "created_unix": 0},
+        ]));
+        assert_eq!(a.characters("old"), vec!["old"]);
+        assert_eq!(a.characters("new"), vec!["alpha", "beta"]);
+        assert!(a.characters("nobody").is_empty());
+        // Reading doesn't write the migration back.
+        assert!(a.by_name["old"].characters.is_empty());
+        assert!(!std::path::Path::new(&a.path).exists());
+
+        let r = a.by_name.get_mut("old").unwrap();
+        r.characters_mut().push("second".to_string());
+        assert_eq!(a.characters("old"), vec!["old", "second"]);
+    }
+
+    #[test]
+    fn character_names_are_unique_across_accounts() {
+        let a = accounts(serde_json::json!([
+            {"name": "old", "created_unix": 0},
+            {"name": "new", "characters": ["Alpha"], "created_unix": 0},
+        ]));
+        assert!(a.name_taken("OLD"));
+        assert!(a.name_taken("alpha"));
+        assert!(a.name_taken("new"));
+        assert!(!a.name_taken("gamma"));
+        assert_eq!(a.owner_of("ALPHA").map(|r| r.name.as_str()), Some("new"));
+        assert_eq!(a.owner_of("old").map(|r| r.name.as_str()), Some("old"));
+    }
+
+    #[test]
+    fn character_limit_per_account() {
+        let a = accounts(serde_json::json!([
+            {"name": "one", "characters": ["a", "b"], "created_unix": 0},
+            {"name": "two", "characters": ["c", "d"], "max_characters": 3, "created_unix": 0},
+        ]));
+        assert_eq!(a.character_limit("one", 2), 2);
+        assert!(a.at_character_limit("one", 2));
+        assert_eq!(a.character_limit("two", 2), 3);
+        assert!(!a.at_character_limit("two", 2));
+    }
 }
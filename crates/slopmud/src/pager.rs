@@ -0,0 +1,295 @@
+//! Per-session word wrapping and `--more--` paging of client output.
+//!
+//! Runs in the connection's writer task, before color markup is rendered: markup codes and
+//! telnet commands are zero-width here, and visible width is counted in chars (not bytes).
+//!
+//! A single output chunk (one shard response, one broker reply) taller than the screen is held
+//! back and shown a page at a time. Anything written while paging queues behind it so ordering is
+//! preserved; quitting drops only the rest of the paged chunk. A queue past `MAX_PENDING_LINES`
+//! (nobody answering `--more--`) is flushed whole.
+
+use std::collections::VecDeque;
+
+pub const MORE_PROMPT: &[u8] = b"{W}--more--{x} (enter/more, q to stop) ";
+
+const MIN_WIDTH: usize = 20;
+const MIN_HEIGHT: usize = 5;
+const TELNET_IAC: u8 = 255;
+const MAX_PENDING_LINES: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ctl {
+    /// New effective terminal size; `None` disables wrapping/paging.
+    Resize {
+        width: Option<u16>,
+        height: Option<u16>,
+    },
+    /// The client sent a line (its local echo moved the cursor to column 0).
+    LineEntered,
+    More,
+    Quit,
+}
+
+#[derive(Debug, Default)]
+pub struct Pager {
+    width: Option<usize>,
+    height: Option<usize>,
+    col: usize,
+    pending: VecDeque<Vec<u8>>,
+    /// How many of the `pending` lines (from the front) are left of the chunk being paged.
+    paged: usize,
+}
+
+/// Length of a markup code at `b[0] == b'{'` (0 if it isn't one). `{{` is not a code.
+fn markup_len(b: &[u8]) -> usize {
+    if b.len() >= 3 && b[2] == b'}' && b[1].is_ascii_alphabetic() {
+        return 3;
+    }
+    if b.len() >= 9 && b[1] == b'#' && b[8] == b'}' && b[2..8].iter().all(u8::is_ascii_hexdigit) {
+        return 9;
+    }
+    0
+}
+
+/// Word-wrap `src` at `width` visible columns, starting at column `*col` (updated on return).
+pub fn wrap(src: &[u8], width: usize, col: &mut usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(src.len() + src.len() / width.max(1) * 2);
+    // Output index of the last space on the current line, and the column just after it.
+    let mut last_space: Option<(usize, usize)> = None;
+    let mut i = 0;
+    while i < src.len() {
+        let b = src[i];
+        match b {
+            b'\r' | b'\n' => {
+                out.push(b);
+                *col = 0;
+                last_space = None;
+                i += 1;
+                continue;
+            }
+            b'{' => {
+                let n = markup_len(&src[i..]);
+                if n > 0 {
+                    out.extend_from_slice(&src[i..i + n]);
+                    i += n;
+                    continue;
+                }
+            }
+            // Telnet commands (e.g. echo toggles around password prompts) are zero-width.
+            TELNET_IAC => {
+                let n = match src.get(i + 1) {
+                    Some(251..=254) => 3,
+                    _ => 2,
+                };
+                let end = (i + n).min(src.len());
+                out.extend_from_slice(&src[i..end]);
+                i = end;
+                continue;
+            }
+            _ => {}
+        }
+
+        // One visible char: `{{`, or a UTF-8 sequence.
+        let n = if b == b'{' && src.get(i + 1) == Some(&b'{') {
+            2
+        } else {
+            1 + src[i + 1..]
+                .iter()
+                .take_while(|c| (**c & 0xC0) == 0x80)
+                .count()
+        };
+
+        if *col >= width {
+            if b == b' ' {
+                // Break here and swallow the space.
+                out.extend_from_slice(b"\r\n");
+                *col = 0;
+                last_space = None;
+                i += n;
+                continue;
+            }
+            match last_space.take() {
+                Some((idx, col_after)) => {
+                    out[idx] = b'\r';
+                    out.insert(idx + 1, b'\n');
+                    *col -= col_after;
+                }
+                None => {
+                    out.extend_from_slice(b"\r\n");
+                    *col = 0;
+                }
+            }
+        }
+
+        if b == b' ' {
+            last_space = Some((out.len(), *col + 1));
+        }
+        out.extend_from_slice(&src[i..i + n]);
+        *col += 1;
+        i += n;
+    }
+    out
+}
+
+/// Split into lines, keeping terminators (the last element may be a partial line).
+fn split_lines(b: &[u8]) -> Vec<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    for (i, c) in b.iter().enumerate() {
+        if *c == b'\n' {
+            out.push(b[start..=i].to_vec());
+            start = i + 1;
+        }
+    }
+    if start < b.len() {
+        out.push(b[start..].to_vec());
+    }
+    out
+}
+
+impl Pager {
+    pub fn is_paging(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn resize(&mut self, width: Option<u16>, height: Option<u16>) {
+        self.width = width
+            .filter(|w| *w > 0)
+            .map(|w| usize::from(w).max(MIN_WIDTH));
+        self.height = height
+            .filter(|h| *h > 0)
+            .map(|h| usize::from(h).max(MIN_HEIGHT));
+    }
+
+    pub fn line_entered(&mut self) {
+        self.col = 0;
+    }
+
+    /// Feed a chunk of output; returns what to write to the client now.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<u8> {
+        let wrapped = match self.width {
+            Some(w) => wrap(chunk, w, &mut self.col),
+            None => chunk.to_vec(),
+        };
+        let Some(h) = self.height else {
+            return wrapped;
+        };
+        if self.pending.is_empty() {
+            let lines = split_lines(&wrapped);
+            let full = lines.iter().filter(|l| l.ends_with(b"\n")).count();
+            if full < h {
+                return wrapped;
+            }
+            self.paged = lines.len();
+            self.pending.extend(lines);
+            return self.next_page();
+        }
+        self.pending.extend(split_lines(&wrapped));
+        if self.pending.len() > MAX_PENDING_LINES {
+            return self.drain_all();
+        }
+        Vec::new()
+    }
+
+    /// Show the next page (if paging).
+    pub fn next_page(&mut self) -> Vec<u8> {
+        let Some(h) = self.height else {
+            return self.drain_all();
+        };
+        let mut out = Vec::new();
+        let mut shown = 0;
+        while shown + 1 < h {
+            let Some(l) = self.pending.pop_front() else {
+                break;
+            };
+            out.extend_from_slice(&l);
+            self.paged = self.paged.saturating_sub(1);
+            shown += 1;
+        }
+        if self.pending.is_empty() {
+            return out;
+        }
+        // Don't stop for a trailing partial line (usually a prompt).
+        if self.pending.len() == 1 && !self.pending[0].ends_with(b"\n") {
+            out.extend(self.pending.pop_front().unwrap_or_default());
+            self.paged = 0;
+            return out;
+        }
+        out.extend_from_slice(MORE_PROMPT);
+        self.col = 0;
+        out
+    }
+
+    /// Stop paging: drop the rest of the paged chunk and send what queued up behind it.
+    pub fn quit(&mut self) -> Vec<u8> {
+        if self.pending.is_empty() {
+            return Vec::new();
+        }
+        self.pending.drain(..self.paged.min(self.pending.len()));
+        if self.pending.is_empty() {
+            return b"> ".to_vec();
+        }
+        self.drain_all()
+    }
+
+    fn drain_all(&mut self) -> Vec<u8> {
+        self.paged = 0;
+        let mut out = Vec::new();
+        for l in self.pending.drain(..) {
+            out.extend_from_slice(&l);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_on_words_and_ignores_markup() {
+        let mut col = 0;
+        let out = wrap(b"{R}aaa bbb{x} ccc dddd", 8, &mut col);
+        assert_eq!(out, b"{R}aaa bbb{x}\r\nccc dddd");
+        assert_eq!(col, 8);
+
+        let mut col = 0;
+        assert_eq!(wrap(b"abcdefghij", 4, &mut col), b"abcd\r\nefgh\r\nij");
+    }
+
+    #[test]
+    fn pages_tall_chunks() {
+        let mut p = Pager::default();
+        p.resize(None, Some(5));
+        let text = (1..=10).map(|i| format!("{i}\r\n")).collect::<String>() + "> ";
+        let first = p.push(text.as_bytes());
+        assert!(first.starts_with(b"1\r\n2\r\n3\r\n4\r\n"));
+        assert!(first.ends_with(MORE_PROMPT));
+        assert!(p.is_paging());
+        assert!(p.push(b"late\r\n").is_empty());
+        let _ = p.next_page();
+        let last = p.next_page();
+        assert_eq!(last, b"9\r\n10\r\n> late\r\n");
+        assert!(!p.is_paging());
+    }
+
+    #[test]
+    fn quit_keeps_output_that_arrived_while_paging() {
+        let mut p = Pager::default();
+        p.resize(None, Some(5));
+        let text = (1..=10).map(|i| format!("{i}\r\n")).collect::<String>() + "> ";
+        let _ = p.push(text.as_bytes());
+        assert!(p.push(b"Bob tells you: hi\r\n").is_empty());
+        assert_eq!(p.quit(), b"Bob tells you: hi\r\n");
+        assert!(!p.is_paging());
+
+        let _ = p.push(text.as_bytes());
+        assert_eq!(p.quit(), b"> ");
+
+        let _ = p.push(text.as_bytes());
+        for _ in 0..MAX_PENDING_LINES {
+            let _ = p.push(b"spam\r\n");
+        }
+        assert!(!p.is_paging());
+    }
+}
@@ -0,0 +1,465 @@
+//! Abuse report queue: what `report submit` files, for moderators to work through in game
+//! (`reports`) or over the admin socket.
+//!
+//! Kept as one JSON file next to the ban list. The event log's reports stream stays the record of
+//! truth; callers log every change there. Resolved reports are pruned past `KEEP_RESOLVED`, and
+//! `admit` bounds the open ones: each reporter may file `PER_REPORTER_PER_HOUR` an hour, one line
+//! collects at most `MAX_OPEN_PER_LINE` open reports, and the queue holds at most `MAX_OPEN`.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+const KEEP_RESOLVED: usize = 1000;
+const MAX_OPEN: usize = 5000;
+const MAX_OPEN_PER_LINE: usize = 5;
+const PER_REPORTER_PER_HOUR: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Dismiss,
+    Warn,
+    Mute,
+    Ban,
+}
+
+impl Action {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "dismiss" | "none" => Some(Action::Dismiss),
+            "warn" => Some(Action::Warn),
+            "mute" => Some(Action::Mute),
+            "ban" => Some(Action::Ban),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Action::Dismiss => "dismiss",
+            Action::Warn => "warn",
+            Action::Mute => "mute",
+            Action::Ban => "ban",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ContextLine {
+    pub id: String,
+    pub ts_unix_ms: u64,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Resolution {
+    pub by: String,
+    pub unix: u64,
+    pub action: Action,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default)]
+    pub note: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Report {
+    /// Short number moderators type; `id` is what the event log uses.
+    #[serde(default)]
+    pub no: u64,
+    pub id: String,
+    pub created_unix: u64,
+    pub reporter: String,
+    pub line_id: String,
+    pub line_ts_unix_ms: u64,
+    /// Already redacted if the reporter is on legal hold.
+    pub text: String,
+    /// The reporter was on legal hold; only the stored (redacted) lines may be shown.
+    #[serde(default)]
+    pub redacted: bool,
+    pub reason: String,
+    #[serde(default)]
+    pub note: String,
+    /// Scrollback around the line when it was reported.
+    #[serde(default)]
+    pub context: Vec<ContextLine>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<Resolution>,
+    /// Resolved while the reporter was offline; tell them when they're back.
+    #[serde(default)]
+    pub notify_pending: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ReportsFile {
+    #[serde(default)]
+    version: u32,
+    #[serde(default)]
+    next_no: u64,
+    #[serde(default)]
+    reports: Vec<Report>,
+}
+
+/// Why `admit` turned a report away.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Refused {
+    RateLimited,
+    LineFull,
+    AlreadyReported,
+    QueueFull,
+}
+
+impl std::fmt::Display for Refused {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Refused::RateLimited => write!(
+                f,
+                "you've filed {PER_REPORTER_PER_HOUR} reports in the last hour; try again later"
+            ),
+            Refused::LineFull => write!(f, "that line has already been reported"),
+            Refused::AlreadyReported => write!(f, "you've already reported that line"),
+            Refused::QueueFull => write!(f, "the report queue is full; try again later"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ReportQueue {
+    path: PathBuf,
+    file: ReportsFile,
+    /// Set by `write_in_background`; without it saves write the file in place.
+    writer: Option<tokio::sync::watch::Sender<Vec<u8>>>,
+    /// Reports a moderator is acting on right now (see `claim`); not saved.
+    claimed: HashSet<u64>,
+}
+
+impl ReportQueue {
+    /// A file that can't be read or parsed is moved aside and the queue starts empty; the event
+    /// log still has every report.
+    pub fn load(path: PathBuf) -> Self {
+        let file = match std::fs::read_to_string(&path) {
+            Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+                let bad = path.with_extension(format!("json.bad-{}", now_unix()));
+                warn!(
+                    path = %path.display(),
+                    moved_to = %bad.display(),
+                    err = %e,
+                    "bad reports file; starting with an empty queue"
+                );
+                if let Err(e) = std::fs::rename(&path, &bad) {
+                    warn!(path = %path.display(), err = %e, "failed to move bad reports file");
+                }
+                ReportsFile::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => ReportsFile::default(),
+            Err(e) => {
+                warn!(path = %path.display(), err = %e, "failed to read reports file");
+                ReportsFile::default()
+            }
+        };
+        Self {
+            path,
+            file,
+            writer: None,
+            claimed: HashSet::new(),
+        }
+    }
+
+    /// Write the file from a background task from now on, so a save never blocks the caller
+    /// (who holds the queue lock) on disk. Saves that land while a write is running collapse into
+    /// one. Needs a tokio runtime.
+    pub fn write_in_background(&mut self) {
+        let (tx, mut rx) = tokio::sync::watch::channel(Vec::new());
+        let path = self.path.clone();
+        tokio::spawn(async move {
+            while rx.changed().await.is_ok() {
+                let buf = rx.borrow_and_update().clone();
+                let path = path.clone();
+                let res = tokio::task::spawn_blocking(move || write_file(&path, &buf)).await;
+                if let Err(e) = res.map_err(anyhow::Error::from).and_then(|r| r) {
+                    warn!(err = %e, "failed to save reports file");
+                }
+            }
+        });
+        self.writer = Some(tx);
+    }
+
+    /// Whether `reporter` may file a report about `line_id` at `now` (unix seconds).
+    pub fn admit(&self, reporter: &str, line_id: &str, now: u64) -> Result<(), Refused> {
+        let hour_ago = now.saturating_sub(60 * 60);
+        let by_reporter = |r: &&Report| r.reporter.eq_ignore_ascii_case(reporter);
+        let open = self.list(false);
+        if open.iter().any(|r| r.line_id == line_id && by_reporter(r)) {
+            return Err(Refused::AlreadyReported);
+        }
+        let recent = self
+            .file
+            .reports
+            .iter()
+            .filter(by_reporter)
+            .filter(|r| r.created_unix >= hour_ago)
+            .count();
+        if recent >= PER_REPORTER_PER_HOUR {
+            return Err(Refused::RateLimited);
+        }
+        if open.iter().filter(|r| r.line_id == line_id).count() >= MAX_OPEN_PER_LINE {
+            return Err(Refused::LineFull);
+        }
+        if open.len() >= MAX_OPEN {
+            return Err(Refused::QueueFull);
+        }
+        Ok(())
+    }
+
+    /// File a report; returns its number.
+    pub fn add(&mut self, mut r: Report) -> anyhow::Result<u64> {
+        self.file.next_no = self.file.next_no.max(1);
+        r.no = self.file.next_no;
+        self.file.next_no += 1;
+        self.file.reports.push(r);
+        self.save()?;
+        Ok(self.file.next_no - 1)
+    }
+
+    /// By number (`12` or `#12`) or full report id.
+    pub fn get(&self, key: &str) -> Option<&Report> {
+        let i = self.index(key)?;
+        self.file.reports.get(i)
+    }
+
+    /// Unresolved reports, oldest first; with `all`, resolved ones too.
+    pub fn list(&self, all: bool) -> Vec<&Report> {
+        self.file
+            .reports
+            .iter()
+            .filter(|r| all || r.resolution.is_none())
+            .collect()
+    }
+
+    pub fn assign(&mut self, key: &str, assignee: Option<String>) -> anyhow::Result<Report> {
+        let r = self.open_mut(key)?;
+        r.assignee = assignee;
+        let r = r.clone();
+        self.save()?;
+        Ok(r)
+    }
+
+    /// Take an open report to act on, so two moderators can't resolve it at once. Follow with
+    /// `resolve`, or `release` if the action couldn't be applied.
+    pub fn claim(&mut self, key: &str) -> anyhow::Result<u64> {
+        let no = self.open_mut(key)?.no;
+        if !self.claimed.insert(no) {
+            anyhow::bail!("report #{no} is already being resolved");
+        }
+        Ok(no)
+    }
+
+    pub fn release(&mut self, no: u64) {
+        self.claimed.remove(&no);
+    }
+
+    pub fn resolve(&mut self, key: &str, res: Resolution) -> anyhow::Result<Report> {
+        let r = self.open_mut(key)?;
+        r.resolution = Some(res);
+        r.notify_pending = true;
+        let r = r.clone();
+        self.claimed.remove(&r.no);
+        self.save()?;
+        Ok(r)
+    }
+
+    /// The reporter has been told how report `no` went.
+    pub fn mark_notified(&mut self, no: u64) -> anyhow::Result<()> {
+        if let Some(r) = self.file.reports.iter_mut().find(|r| r.no == no) {
+            r.notify_pending = false;
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Resolved reports `reporter` hasn't heard back about.
+    pub fn pending_notices(&self, reporter: &str) -> Vec<Report> {
+        self.file
+            .reports
+            .iter()
+            .filter(|r| r.notify_pending && r.reporter.eq_ignore_ascii_case(reporter))
+            .cloned()
+            .collect()
+    }
+
+    fn index(&self, key: &str) -> Option<usize> {
+        let key = key.trim();
+        let key = key.strip_prefix('#').unwrap_or(key);
+        match key.parse::<u64>() {
+            Ok(no) => self.file.reports.iter().position(|r| r.no == no),
+            Err(_) => self.file.reports.iter().position(|r| r.id == key),
+        }
+    }
+
+    fn open_mut(&mut self, key: &str) -> anyhow::Result<&mut Report> {
+        let Some(i) = self.index(key) else {
+            anyhow::bail!("no report {key}");
+        };
+        let r = &mut self.file.reports[i];
+        if r.resolution.is_some() {
+            anyhow::bail!("report #{} is already resolved", r.no);
+        }
+        Ok(r)
+    }
+
+    fn save(&mut self) -> anyhow::Result<()> {
+        let resolved = self
+            .file
+            .reports
+            .iter()
+            .filter(|r| r.resolution.is_some() && !r.notify_pending)
+            .count();
+        let mut drop = resolved.saturating_sub(KEEP_RESOLVED);
+        self.file.reports.retain(|r| {
+            if drop > 0 && r.resolution.is_some() && !r.notify_pending {
+                drop -= 1;
+                return false;
+            }
+            true
+        });
+
+        self.file.version = 1;
+        let buf = serde_json::to_vec_pretty(&self.file)?;
+        match &self.writer {
+            Some(tx) => {
+                tx.send_replace(buf);
+                Ok(())
+            }
+            None => write_file(&self.path, &buf),
+        }
+    }
+}
+
+fn write_file(path: &Path, buf: &[u8]) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| anyhow::anyhow!("failed to create reports dir {:?}: {e}", parent))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, buf)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn now_unix() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(id: &str, reporter: &str) -> Report {
+        Report {
+            no: 0,
+            id: id.to_string(),
+            created_unix: 1,
+            reporter: reporter.to_string(),
+            line_id: "0a8xtq8tmhg00".to_string(),
+            line_ts_unix_ms: 1000,
+            text: "Mallory: rude".to_string(),
+            redacted: false,
+            reason: "harassment".to_string(),
+            note: String::new(),
+            context: Vec::new(),
+            assignee: None,
+            resolution: None,
+            notify_pending: false,
+        }
+    }
+
+    #[test]
+    fn numbers_assigns_resolves_and_persists() {
+        let dir = std::env::temp_dir().join(format!("slopmud_reports_test_{}", std::process::id()));
+        let path = dir.join("reports.json");
+        let _ = std::fs::remove_file(&path);
+
+        let mut q = ReportQueue::load(path.clone());
+        assert_eq!(q.add(report("rep-a", "Alice")).unwrap(), 1);
+        assert_eq!(q.add(report("rep-b", "Bob")).unwrap(), 2);
+        assert_eq!(q.get("#2").map(|r| r.id.as_str()), Some("rep-b"));
+        assert_eq!(q.get("rep-a").map(|r| r.no), Some(1));
+
+        q.assign("1", Some("Mod".to_string())).unwrap();
+        assert_eq!(q.claim("#1").unwrap(), 1);
+        assert!(q.claim("1").is_err());
+        q.release(1);
+        q.claim("1").unwrap();
+        let res = Resolution {
+            by: "Mod".to_string(),
+            unix: 2,
+            action: Action::Warn,
+            target: Some("Mallory".to_string()),
+            note: String::new(),
+        };
+        q.resolve("1", res.clone()).unwrap();
+        assert!(q.resolve("1", res).is_err());
+        assert!(q.claim("1").is_err());
+        assert_eq!(q.list(false).len(), 1);
+        assert_eq!(q.pending_notices("alice").len(), 1);
+        q.mark_notified(1).unwrap();
+        assert!(q.pending_notices("alice").is_empty());
+
+        let q = ReportQueue::load(path.clone());
+        assert_eq!(q.list(true).len(), 2);
+        assert_eq!(q.get("1").and_then(|r| r.assignee.as_deref()), Some("Mod"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn bad_file_is_moved_aside() {
+        let dir = std::env::temp_dir().join(format!("slopmud_reports_bad_{}", std::process::id()));
+        let path = dir.join("reports.json");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, "{not json").unwrap();
+
+        let q = ReportQueue::load(path.clone());
+        assert!(q.list(true).is_empty());
+        assert!(!path.exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn admits_within_limits() {
+        let dir =
+            std::env::temp_dir().join(format!("slopmud_reports_admit_{}", std::process::id()));
+        let path = dir.join("reports.json");
+        let _ = std::fs::remove_file(&path);
+        let mut q = ReportQueue::load(path);
+
+        let on_line = |q: &mut ReportQueue, reporter: &str, line: &str| {
+            let mut r = report(&format!("rep-{reporter}-{line}"), reporter);
+            r.line_id = line.to_string();
+            q.add(r).unwrap();
+        };
+        on_line(&mut q, "Alice", "l0");
+        assert_eq!(q.admit("alice", "l0", 1), Err(Refused::AlreadyReported));
+        for i in 1..PER_REPORTER_PER_HOUR {
+            on_line(&mut q, "Alice", &format!("l{i}"));
+        }
+        assert_eq!(q.admit("Alice", "new", 1), Err(Refused::RateLimited));
+        assert_eq!(q.admit("Alice", "new", 60 * 60 + 2), Ok(()));
+
+        for i in 1..MAX_OPEN_PER_LINE {
+            on_line(&mut q, &format!("r{i}"), "l0");
+        }
+        assert_eq!(q.admit("Bob", "l0", 1), Err(Refused::LineFull));
+        assert_eq!(q.admit("Bob", "l1", 1), Ok(()));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,57 @@
+//! Signs the auth blob the broker hands the shard on attach.
+//!
+//! Assertions are short-lived and bound to the session, so they are signed each time an attach
+//! goes out (login, shard reconnect, handoff) rather than once at login.
+
+use bytes::Bytes;
+use mudproto::assertion::{self, AssertionKey};
+use mudproto::session::SessionId;
+
+#[derive(Debug)]
+pub struct Signer {
+    key: Option<AssertionKey>,
+    ttl_s: u64,
+}
+
+impl Signer {
+    /// Without a key, blobs go out unsigned and shards treat every session as a guest.
+    pub fn new(key: Option<AssertionKey>, ttl_s: u64) -> Self {
+        Self {
+            key,
+            ttl_s: ttl_s.max(1),
+        }
+    }
+
+    pub fn seal(&self, session: SessionId, blob: &[u8]) -> Bytes {
+        let Some(key) = &self.key else {
+            return Bytes::copy_from_slice(blob);
+        };
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Bytes::from(assertion::sign(
+            key,
+            session,
+            now.saturating_add(self.ttl_s),
+            blob,
+        ))
+    }
+
+    /// Seal an attach's auth blob. A handoff snapshot travels beside the assertion rather than in
+    /// it, so its digest goes into the blob; shards drop any snapshot the broker didn't vouch for.
+    pub fn seal_attach(&self, session: SessionId, blob: &[u8], snapshot: Option<&[u8]>) -> Bytes {
+        let Some(snapshot) = snapshot else {
+            return self.seal(session, blob);
+        };
+        let Ok(mut fields) = serde_json::from_slice::<serde_json::Map<_, _>>(blob) else {
+            return self.seal(session, blob);
+        };
+        fields.insert(
+            "snapshot_sha256".to_string(),
+            assertion::digest(snapshot).into(),
+        );
+        let blob = serde_json::to_vec(&fields).expect("serialize shard auth blob");
+        self.seal(session, &blob)
+    }
+}
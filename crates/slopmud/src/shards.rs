@@ -0,0 +1,184 @@
+//! Shard directory and per-session shard routing.
+//!
+//! The world is split by area: `SHARD_DIRECTORY` maps area ids to shard endpoints, and anything
+//! not listed lives on the default shard (`SHARD_ADDR`). Sessions start on the default shard and
+//! move when a shard hands their character off (`RESP_HANDOFF`) to an area it doesn't own.
+//!
+//! Each shard connection keeps an `InputQueue`: numbered input the shard hasn't acked, and input
+//! typed while it was down. It is resent after the sessions re-attach.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+
+use bytes::Bytes;
+use mudproto::session::SessionId;
+
+#[derive(Debug, Clone)]
+pub struct ShardDirectory {
+    default: SocketAddr,
+    areas: HashMap<String, SocketAddr>,
+}
+
+impl ShardDirectory {
+    /// Parse `area=host:port,area=host:port`. An empty spec means a single shard.
+    pub fn parse(default: SocketAddr, spec: &str) -> anyhow::Result<Self> {
+        let mut areas = HashMap::new();
+        for ent in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let Some((area, addr)) = ent.split_once('=') else {
+                anyhow::bail!("bad shard directory entry (want area=host:port): {ent}");
+            };
+            let area = area.trim();
+            if area.is_empty() {
+                anyhow::bail!("bad shard directory entry (empty area): {ent}");
+            }
+            let addr = addr
+                .trim()
+                .parse::<SocketAddr>()
+                .map_err(|e| anyhow::anyhow!("bad shard address in {ent}: {e}"))?;
+            areas.insert(area.to_string(), addr);
+        }
+        Ok(Self { default, areas })
+    }
+
+    pub fn default_shard(&self) -> SocketAddr {
+        self.default
+    }
+
+    pub fn lookup(&self, area: &str) -> SocketAddr {
+        self.areas.get(area).copied().unwrap_or(self.default)
+    }
+
+    /// Every distinct endpoint, default first.
+    pub fn endpoints(&self) -> Vec<SocketAddr> {
+        let mut out = vec![self.default];
+        let mut rest = self.areas.values().copied().collect::<Vec<_>>();
+        rest.sort();
+        rest.dedup();
+        out.extend(rest.into_iter().filter(|a| *a != self.default));
+        out
+    }
+}
+
+/// Where a session currently lives.
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub shard: SocketAddr,
+    /// Last handoff snapshot; replayed on re-attach in case the target shard missed it
+    /// (shards keep whichever copy is newer).
+    pub snapshot: Option<Bytes>,
+}
+
+/// A shard asked us to move a session to the owner of `area`.
+#[derive(Debug, Clone)]
+pub struct ShardHandoff {
+    pub from: SocketAddr,
+    pub session: SessionId,
+    pub area: String,
+    pub snapshot: Bytes,
+}
+
+/// Unacked input lines (`REQ_INPUT_V2` bodies with their sequence numbers), oldest first, at
+/// most `max` per session.
+#[derive(Debug)]
+pub struct InputQueue {
+    max: usize,
+    pending: HashMap<SessionId, VecDeque<(u64, Bytes)>>,
+}
+
+impl InputQueue {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Queue a line; false (and nothing queued) when the session already has `max`.
+    pub fn push(&mut self, session: SessionId, seq: u64, body: Bytes) -> bool {
+        let q = self.pending.entry(session).or_default();
+        if q.len() >= self.max {
+            return false;
+        }
+        q.push_back((seq, body));
+        true
+    }
+
+    /// The shard took everything up to `seq`.
+    pub fn ack(&mut self, session: SessionId, seq: u64) {
+        if let Some(q) = self.pending.get_mut(&session) {
+            while q.front().is_some_and(|(s, _)| *s <= seq) {
+                q.pop_front();
+            }
+            if q.is_empty() {
+                self.pending.remove(&session);
+            }
+        }
+    }
+
+    pub fn len(&self, session: SessionId) -> usize {
+        self.pending.get(&session).map_or(0, VecDeque::len)
+    }
+
+    pub fn forget(&mut self, session: SessionId) {
+        self.pending.remove(&session);
+    }
+
+    /// Take every session's queue, e.g. to resend after a reconnect.
+    pub fn drain(&mut self) -> Vec<(SessionId, VecDeque<(u64, Bytes)>)> {
+        self.pending.drain().collect()
+    }
+
+    /// Put a drained queue back (still unacked after resending).
+    pub fn restore(&mut self, session: SessionId, q: VecDeque<(u64, Bytes)>) {
+        if !q.is_empty() {
+            self.pending.insert(session, q);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_looks_up_areas() {
+        let def: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let d = ShardDirectory::parse(
+            def,
+            " midgaard=127.0.0.1:5001, sewers=127.0.0.1:5001,school=127.0.0.1:5000",
+        )
+        .unwrap();
+        assert_eq!(d.lookup("midgaard"), "127.0.0.1:5001".parse().unwrap());
+        assert_eq!(d.lookup("elsewhere"), def);
+        assert_eq!(d.endpoints(), vec![def, "127.0.0.1:5001".parse().unwrap()]);
+
+        assert!(ShardDirectory::parse(def, "midgaard").is_err());
+        assert!(ShardDirectory::parse(def, "").unwrap().endpoints() == vec![def]);
+    }
+
+    #[test]
+    fn input_queue_is_bounded_and_trimmed_by_acks() {
+        let (a, b) = (SessionId(1), SessionId(2));
+        let mut q = InputQueue::new(2);
+        assert!(q.push(a, 1, Bytes::from_static(b"n")));
+        assert!(q.push(a, 2, Bytes::from_static(b"e")));
+        assert!(!q.push(a, 3, Bytes::from_static(b"s")));
+        assert!(q.push(b, 7, Bytes::from_static(b"look")));
+
+        q.ack(a, 1);
+        assert_eq!(q.len(a), 1);
+        q.ack(b, 9);
+        assert_eq!(q.len(b), 0);
+
+        let mut drained = q.drain();
+        assert_eq!(q.len(a), 0);
+        let (sid, pending) = drained.pop().unwrap();
+        assert_eq!((sid, pending.front().map(|(s, _)| *s)), (a, Some(2)));
+        q.restore(sid, pending);
+        assert_eq!(q.len(a), 1);
+    }
+}
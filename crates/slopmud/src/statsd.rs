@@ -0,0 +1,73 @@
+//! Fire-and-forget StatsD counters tagged with the client address, for sbc_metricsd.
+//!
+//! Lines look like `sbc.login_failures:1|c|#src=198.51.100.7`. Sends never block and errors are
+//! dropped: metrics must not slow down or break a login.
+
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+
+pub const CONNS: &str = "sbc.conns";
+pub const LOGIN_FAILURES: &str = "sbc.login_failures";
+pub const ACCOUNTS_CREATED: &str = "sbc.accounts_created";
+pub const REPORTS: &str = "sbc.reports";
+
+#[derive(Debug)]
+pub struct Statsd {
+    target: Option<(UdpSocket, SocketAddr)>,
+}
+
+impl Statsd {
+    /// `None` (or a socket that fails to bind) yields a client that drops everything.
+    pub fn new(addr: Option<SocketAddr>) -> Self {
+        let target = addr.and_then(|addr| {
+            let bind: SocketAddr = if addr.is_ipv4() {
+                "0.0.0.0:0".parse().ok()?
+            } else {
+                "[::]:0".parse().ok()?
+            };
+            let sock = UdpSocket::bind(bind).ok()?;
+            sock.set_nonblocking(true).ok()?;
+            Some((sock, addr))
+        });
+        Self { target }
+    }
+
+    pub fn incr(&self, name: &str, src: IpAddr) {
+        if let Some((sock, addr)) = &self.target {
+            let _ = sock.send_to(format_line(name, 1, src).as_bytes(), addr);
+        }
+    }
+}
+
+fn format_line(name: &str, value: u64, src: IpAddr) -> String {
+    // IPv4-mapped IPv6 peers (dual-stack listeners) are reported as plain IPv4 so they
+    // aggregate into the right /18.
+    let src = match src {
+        IpAddr::V6(v6) => v6
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(v6)),
+        v4 => v4,
+    };
+    format!("{name}:{value}|c|#src={src}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_tagged_counters() {
+        assert_eq!(
+            format_line(LOGIN_FAILURES, 1, "198.51.100.7".parse().unwrap()),
+            "sbc.login_failures:1|c|#src=198.51.100.7"
+        );
+        assert_eq!(
+            format_line(CONNS, 1, "::ffff:198.51.100.7".parse().unwrap()),
+            "sbc.conns:1|c|#src=198.51.100.7"
+        );
+        assert_eq!(
+            format_line(REPORTS, 1, "2001:db8::1".parse().unwrap()),
+            "sbc.reports:1|c|#src=2001:db8::1"
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Per-connection terminal capabilities negotiated over telnet.
+//!
+//! NAWS (RFC 1073) reports the window size; it's re-sent by the client on every resize.
+//!
+//! TTYPE (RFC 1091) is cycled per the MTTS convention: the first reply is the client name, the
+//! second the terminal type, the third `MTTS <bitvector>`. Clients that don't cycle repeat the
+//! same value, which ends the negotiation.
+
+use mudproto::color::ColorMode;
+use slopio::telnet::TelnetEvent;
+
+pub const TELNET_OPT_TTYPE: u8 = 24;
+pub const TELNET_OPT_NAWS: u8 = 31;
+
+const TELNET_IAC: u8 = 255;
+const TELNET_DO: u8 = 253;
+const TELNET_SB: u8 = 250;
+const TELNET_SE: u8 = 240;
+const TTYPE_IS: u8 = 0;
+const TTYPE_SEND: u8 = 1;
+const TTYPE_MAX_ROUNDS: u8 = 3;
+
+/// Serializable so a hotboot can carry it over (see `crate::hotboot`).
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct TermCaps {
+    pub client: Option<String>,
+    pub ttype: Option<String>,
+    pub mtts: Option<u32>,
+    pub width: Option<u16>,
+    pub height: Option<u16>,
+    rounds: u8,
+    done: bool,
+}
+
+fn ttype_send() -> Vec<u8> {
+    vec![
+        TELNET_IAC,
+        TELNET_SB,
+        TELNET_OPT_TTYPE,
+        TTYPE_SEND,
+        TELNET_IAC,
+        TELNET_SE,
+    ]
+}
+
+impl TermCaps {
+    /// Bytes to send right after connect to start negotiation.
+    pub fn negotiate_start() -> [u8; 6] {
+        [
+            TELNET_IAC,
+            TELNET_DO,
+            TELNET_OPT_TTYPE,
+            TELNET_IAC,
+            TELNET_DO,
+            TELNET_OPT_NAWS,
+        ]
+    }
+
+    /// Feed one telnet event; returns bytes to write back (may be empty).
+    pub fn on_event(&mut self, ev: &TelnetEvent) -> Vec<u8> {
+        match ev {
+            TelnetEvent::Will(TELNET_OPT_TTYPE) if self.rounds == 0 && !self.done => ttype_send(),
+            TelnetEvent::Wont(TELNET_OPT_TTYPE) => {
+                self.done = true;
+                Vec::new()
+            }
+            TelnetEvent::Subneg {
+                opt: TELNET_OPT_TTYPE,
+                data,
+            } => {
+                if self.done || data.first() != Some(&TTYPE_IS) {
+                    return Vec::new();
+                }
+                let v = String::from_utf8_lossy(&data[1..]).trim().to_string();
+                self.rounds += 1;
+
+                if let Some(n) = v.strip_prefix("MTTS ") {
+                    self.mtts = n.trim().parse().ok();
+                    self.done = true;
+                    return Vec::new();
+                }
+                let repeated = self.client.as_deref() == Some(v.as_str())
+                    || self.ttype.as_deref() == Some(v.as_str());
+                match self.rounds {
+                    1 => self.client = Some(v),
+                    _ if !repeated => self.ttype = Some(v),
+                    _ => {}
+                }
+                if repeated || self.rounds >= TTYPE_MAX_ROUNDS {
+                    self.done = true;
+                    return Vec::new();
+                }
+                ttype_send()
+            }
+            TelnetEvent::Subneg {
+                opt: TELNET_OPT_NAWS,
+                data,
+            } => {
+                if let [w1, w0, h1, h0] = data[..] {
+                    let w = u16::from_be_bytes([w1, w0]);
+                    let h = u16::from_be_bytes([h1, h0]);
+                    // 0 means "unknown" for either dimension.
+                    self.width = (w > 0).then_some(w);
+                    self.height = (h > 0).then_some(h);
+                }
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Best color mode the client advertised (Off if it said nothing).
+    pub fn detected_color(&self) -> ColorMode {
+        if let Some(bits) = self.mtts {
+            return ColorMode::from_mtts(bits);
+        }
+        let by_ttype = self.ttype.as_deref().map(ColorMode::from_ttype);
+        let by_client = self.client.as_deref().map(ColorMode::from_ttype);
+        by_ttype.max(by_client).unwrap_or(ColorMode::Off)
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(c) = self.client.as_deref() {
+            parts.push(format!("client={c}"));
+        }
+        if let Some(t) = self.ttype.as_deref() {
+            parts.push(format!("ttype={t}"));
+        }
+        if let Some(m) = self.mtts {
+            parts.push(format!("mtts={m}"));
+        }
+        if let (Some(w), Some(h)) = (self.width, self.height) {
+            parts.push(format!("naws={w}x{h}"));
+        }
+        if parts.is_empty() {
+            "(not reported)".to_string()
+        } else {
+            parts.join(" ")
+        }
+    }
+}
+
+/// Player color setting (`color on|off|256`); stored on the account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorPref {
+    /// Follow whatever the client advertised.
+    #[default]
+    Auto,
+    On,
+    Off,
+    Ansi256,
+}
+
+impl ColorPref {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Some(ColorPref::Auto),
+            "on" | "16" => Some(ColorPref::On),
+            "off" => Some(ColorPref::Off),
+            "256" => Some(ColorPref::Ansi256),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ColorPref::Auto => "auto",
+            ColorPref::On => "on",
+            ColorPref::Off => "off",
+            ColorPref::Ansi256 => "256",
+        }
+    }
+
+    pub fn effective(self, detected: ColorMode) -> ColorMode {
+        match self {
+            ColorPref::Auto => detected,
+            ColorPref::On => detected.max(ColorMode::Ansi16),
+            ColorPref::Off => ColorMode::Off,
+            ColorPref::Ansi256 => ColorMode::Ansi256,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is(v: &str) -> TelnetEvent {
+        let mut data = vec![TTYPE_IS];
+        data.extend_from_slice(v.as_bytes());
+        TelnetEvent::Subneg {
+            opt: TELNET_OPT_TTYPE,
+            data,
+        }
+    }
+
+    #[test]
+    fn mtts_cycle_and_plain_ttype() {
+        let mut t = TermCaps::default();
+        assert_eq!(
+            t.on_event(&TelnetEvent::Will(TELNET_OPT_TTYPE)),
+            ttype_send()
+        );
+        assert_eq!(t.on_event(&is("MUDLET")), ttype_send());
+        assert_eq!(t.on_event(&is("XTERM-256COLOR")), ttype_send());
+        assert!(t.on_event(&is("MTTS 2825")).is_empty());
+        assert_eq!(t.detected_color(), ColorMode::TrueColor);
+
+        let mut t = TermCaps::default();
+        t.on_event(&TelnetEvent::Will(TELNET_OPT_TTYPE));
+        assert_eq!(t.on_event(&is("ANSI")), ttype_send());
+        assert!(t.on_event(&is("ANSI")).is_empty());
+        assert_eq!(t.detected_color(), ColorMode::Ansi16);
+        assert_eq!(ColorPref::Off.effective(t.detected_color()), ColorMode::Off);
+    }
+}
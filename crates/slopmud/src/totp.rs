@@ -0,0 +1,155 @@
+//! TOTP second factor (RFC 6238: HMAC-SHA1, 30 s steps, 6 digits) and one-time recovery codes.
+//!
+//! Secrets are stored base32 in the accounts file, since the broker has to compute codes from
+//! them. Recovery codes are only stored as SHA-256 hashes; they are random enough that a salt
+//! buys nothing.
+
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+const STEP_S: u64 = 30;
+const DIGITS: u32 = 6;
+/// Steps either side of now that still count, for clock drift and slow typists.
+const SKEW_STEPS: u64 = 1;
+const SECRET_LEN: usize = 20;
+pub const RECOVERY_CODES: usize = 10;
+
+pub fn new_secret() -> String {
+    let mut b = [0u8; SECRET_LEN];
+    getrandom::getrandom(&mut b).expect("getrandom");
+    data_encoding::BASE32_NOPAD.encode(&b)
+}
+
+fn decode_secret(secret: &str) -> Option<Vec<u8>> {
+    let s = secret.trim().trim_end_matches('=').to_ascii_uppercase();
+    data_encoding::BASE32_NOPAD.decode(s.as_bytes()).ok()
+}
+
+/// `otpauth://` URI for authenticator apps (most render it as a QR code, all accept the secret).
+pub fn provisioning_uri(issuer: &str, account: &str, secret: &str) -> String {
+    let (issuer, account) = (pct_encode(issuer), pct_encode(account));
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_S}"
+    )
+}
+
+/// Percent-encode everything but RFC 3986 unreserved characters, so a `:`, `&` or `?` in a name
+/// can't split the label or add query parameters.
+fn pct_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{b:02X}"));
+        }
+    }
+    out
+}
+
+fn code_at_step(key: &[u8], step: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("hmac takes any key");
+    mac.update(&step.to_be_bytes());
+    let h = mac.finalize().into_bytes();
+    let off = (h[h.len() - 1] & 0x0f) as usize;
+    let bin = u32::from_be_bytes([h[off] & 0x7f, h[off + 1], h[off + 2], h[off + 3]]);
+    bin % 10u32.pow(DIGITS)
+}
+
+/// Check `code` against the steps around `now_unix`. Steps at or before `last_step` were already
+/// used and are refused. Returns the matching step, which the caller stores as the new
+/// `last_step`.
+pub fn verify(secret: &str, code: &str, now_unix: u64, last_step: Option<u64>) -> Option<u64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let want: u32 = code.parse().ok()?;
+    let key = decode_secret(secret)?;
+    let now = now_unix / STEP_S;
+    (now.saturating_sub(SKEW_STEPS)..=now + SKEW_STEPS)
+        .filter(|s| last_step.is_none_or(|l| *s > l))
+        .find(|s| code_at_step(&key, *s) == want)
+}
+
+fn normalize_recovery(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn hash_recovery(code: &str) -> String {
+    let d = Sha256::digest(normalize_recovery(code).as_bytes());
+    d.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Fresh recovery codes: (shown to the player once, stored hashes).
+pub fn new_recovery_codes() -> (Vec<String>, Vec<String>) {
+    let mut shown = Vec::with_capacity(RECOVERY_CODES);
+    for _ in 0..RECOVERY_CODES {
+        let mut b = [0u8; 7];
+        getrandom::getrandom(&mut b).expect("getrandom");
+        let s = data_encoding::BASE32_NOPAD.encode(&b).to_ascii_lowercase();
+        shown.push(format!("{}-{}", &s[..5], &s[5..10]));
+    }
+    let hashes = shown.iter().map(|c| hash_recovery(c)).collect();
+    (shown, hashes)
+}
+
+/// Use up a recovery code; true if it was one of `hashes`.
+pub fn take_recovery_code(hashes: &mut Vec<String>, code: &str) -> bool {
+    if normalize_recovery(code).len() != 10 {
+        return false;
+    }
+    let h = hash_recovery(code);
+    let before = hashes.len();
+    hashes.retain(|x| *x != h);
+    hashes.len() < before
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_rfc6238_and_refuses_replays() {
+        // RFC 6238 appendix B, SHA-1 seed, truncated to 6 digits.
+        let secret = data_encoding::BASE32_NOPAD.encode(b"12345678901234567890");
+        assert_eq!(verify(&secret, "287082", 59, None), Some(1));
+        assert_eq!(
+            verify(&secret, "081804", 1_111_111_109, None),
+            Some(37_037_036)
+        );
+        // One step of drift either way is fine; two is not.
+        assert_eq!(
+            verify(&secret, "081804", 1_111_111_109 + 30, None),
+            Some(37_037_036)
+        );
+        assert_eq!(verify(&secret, "081804", 1_111_111_109 + 60, None), None);
+        // A used step can't be replayed.
+        assert_eq!(
+            verify(&secret, "081804", 1_111_111_109, Some(37_037_036)),
+            None
+        );
+        assert_eq!(verify(&secret, "81804", 1_111_111_109, None), None);
+
+        let (shown, mut hashes) = new_recovery_codes();
+        assert_eq!(hashes.len(), RECOVERY_CODES);
+        assert!(take_recovery_code(
+            &mut hashes,
+            &shown[3].to_ascii_uppercase()
+        ));
+        assert!(!take_recovery_code(&mut hashes, &shown[3]));
+        assert_eq!(hashes.len(), RECOVERY_CODES - 1);
+    }
+
+    #[test]
+    fn provisioning_uri_encodes_the_label_and_issuer() {
+        assert_eq!(
+            provisioning_uri("slop mud", "al:ice&x?y", "ABC"),
+            "otpauth://totp/slop%20mud:al%3Aice%26x%3Fy?secret=ABC&issuer=slop%20mud&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
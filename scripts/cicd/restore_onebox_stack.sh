@@ -363,6 +363,7 @@ EOF
   append_unit_env "$unit_path" "SHARD_BIND"
   append_unit_env "$unit_path" "SHARD_RAFT_LOG"
   append_unit_env "$unit_path" "SHARD_PLAYERS_PATH"
+  append_unit_env "$unit_path" "SHARD_AUTH_KEYS"
   append_unit_env "$unit_path" "OPENAI_API_BASE"
   append_unit_env "$unit_path" "OPENAI_PING_MODEL"
   append_unit_env "$unit_path" "OPENAI_API_KEY_SSM"
@@ -426,6 +427,7 @@ EOF
     append_unit_env "$unit_path" "SHARD_ADDR"
     unset SHARD_ADDR
   fi
+  append_unit_env "$unit_path" "SLOPMUD_SHARD_AUTH_KEY"
   append_unit_env "$unit_path" "SLOPMUD_OIDC_TOKEN_URL"
   append_unit_env "$unit_path" "SLOPMUD_OIDC_CLIENT_ID"
   append_unit_env "$unit_path" "SLOPMUD_OIDC_CLIENT_SECRET"
@@ -436,6 +438,7 @@ EOF
   append_unit_env "$unit_path" "SLOPMUD_LOCALE"
   append_unit_env "$unit_path" "SLOPMUD_ADMIN_BIND"
   append_unit_env "$unit_path" "SLOPMUD_BANS_PATH"
+  append_unit_env "$unit_path" "SLOPMUD_REPORTS_PATH"
   append_unit_env "$unit_path" "SLOPMUD_EVENTLOG_ENABLED"
   append_unit_env "$unit_path" "SLOPMUD_EVENTLOG_SPOOL_DIR"
   append_unit_env "$unit_path" "SLOPMUD_EVENTLOG_FLUSH_INTERVAL_S"
@@ -92,9 +92,11 @@ SBC_ENABLE_DNS_NAME="${SBC_ENABLE_DNS_NAME:-sbc-anti-lockout-prd.slopmud.com}"
 SBC_ENABLE_DNS_IP="${SBC_ENABLE_DNS_IP:-192.0.2.1}"
 SBC_ENABLE_DNS_INTERVAL_S="${SBC_ENABLE_DNS_INTERVAL_S:-60}"
 SBC_APPLY_SNAPSHOT="${SBC_APPLY_SNAPSHOT:-0}"
+SBC_BACKEND="${SBC_BACKEND:-noop}"
 SBC_STATUS_HTTP="${SBC_STATUS_HTTP:-127.0.0.1:9911}"
 SBC_STATSD_BIND="${SBC_STATSD_BIND:-0.0.0.0:8125}"
 SBC_METRICS_HTTP="${SBC_METRICS_HTTP:-127.0.0.1:9912}"
+SBC_FLEET_HTTP="${SBC_FLEET_HTTP:-127.0.0.1:9913}"
 
 cp "${unit_dir_local}/sbc-raftd.service" "$tmp_raft"
 cp "${unit_dir_local}/sbc-metricsd.service" "$tmp_met"
@@ -128,10 +130,15 @@ Environment=SBC_APPLY_SNAPSHOT=${SBC_APPLY_SNAPSHOT}
 # Exempt prefix list (block-level enforcement disabled).
 Environment=SBC_EXEMPT_PREFIXES_PATH=${REMOTE_ROOT}/sbc_exempt_prefixes.txt
 
+# noop | nftables. nftables needs CAP_NET_ADMIN to load the ruleset.
+Environment=SBC_BACKEND=${SBC_BACKEND}
+AmbientCapabilities=CAP_NET_ADMIN
+CapabilityBoundingSet=CAP_NET_ADMIN
+
 ExecStart=${remote_bin_dir}/sbc_enforcerd
 
-# Always detach enforcement on stop (noop today; required once XDP is implemented).
-ExecStopPost=${remote_bin_dir}/sbc_enforcerd --detach-xdp --iface eth0
+# Always detach enforcement on stop so a dead enforcer never leaves bans in the kernel.
+ExecStopPost=${remote_bin_dir}/sbc_enforcerd --detach
 
 Restart=always
 RestartSec=2
@@ -158,6 +165,12 @@ sed -i \
   -e "s#^Environment=SBC_METRICS_HTTP=.*#Environment=SBC_METRICS_HTTP=${SBC_METRICS_HTTP}#" \
   "$tmp_met"
 
+# Serve the enforcer fleet view from raftd.
+sed -i \
+  -e "/^Environment=SBC_FLEET_HTTP=/d" \
+  -e "/^\[Service\]/a Environment=SBC_FLEET_HTTP=${SBC_FLEET_HTTP}" \
+  "$tmp_raft"
+
 echo "Installing SBC systemd units"
 scp "${ssh_opts[@]}" "${scp_port_opt[@]}" "$tmp_raft" "${SSH_USER}@${HOST}:/tmp/sbc-raftd.service"
 scp "${ssh_opts[@]}" "${scp_port_opt[@]}" "$tmp_met" "${SSH_USER}@${HOST}:/tmp/sbc-metricsd.service"
@@ -181,3 +194,6 @@ ssh "${ssh_opts[@]}" "${ssh_port_opt[@]}" "${SSH_USER}@${HOST}" "\
 
 echo "SBC enforcer status"
 ssh "${ssh_opts[@]}" "${ssh_port_opt[@]}" "${SSH_USER}@${HOST}" "curl -fsSL http://${SBC_STATUS_HTTP}/status | sed -n '1,160p'"
+
+echo "SBC fleet"
+ssh "${ssh_opts[@]}" "${ssh_port_opt[@]}" "${SSH_USER}@${HOST}" "curl -sS http://${SBC_FLEET_HTTP}/fleet/alerts || true"
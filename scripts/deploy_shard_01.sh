@@ -84,6 +84,10 @@ fi
 if [[ -n "${SHARD_PLAYERS_PATH:-}" ]]; then
   echo "Environment=SHARD_PLAYERS_PATH=${SHARD_PLAYERS_PATH}" >>"$tmp_unit"
 fi
+# Broker signing keys (kid:secret,...); without them every session is a guest.
+if [[ -n "${SHARD_AUTH_KEYS:-}" ]]; then
+  echo "Environment=SHARD_AUTH_KEYS=${SHARD_AUTH_KEYS}" >>"$tmp_unit"
+fi
 
 # Optional: OpenAI config for admin `aiping` command.
 if [[ -n "${OPENAI_API_BASE:-}" ]]; then
@@ -87,6 +87,10 @@ if [[ -n "${SHARD_ADDR:-}" ]]; then
 elif [[ -n "${SHARD_BIND:-}" ]]; then
   echo "Environment=SHARD_ADDR=${SHARD_BIND}" >>"$tmp_unit"
 fi
+# Signs attach assertions; must be one of the shard's SHARD_AUTH_KEYS.
+if [[ -n "${SLOPMUD_SHARD_AUTH_KEY:-}" ]]; then
+  echo "Environment=SLOPMUD_SHARD_AUTH_KEY=${SLOPMUD_SHARD_AUTH_KEY}" >>"$tmp_unit"
+fi
 
 # Optional: internal OIDC token minting. Secrets should contain no spaces.
 if [[ -n "${SLOPMUD_OIDC_TOKEN_URL:-}" ]]; then
@@ -134,6 +138,9 @@ fi
 if [[ -n "${SLOPMUD_BANS_PATH:-}" ]]; then
   echo "Environment=SLOPMUD_BANS_PATH=${SLOPMUD_BANS_PATH}" >>"$tmp_unit"
 fi
+if [[ -n "${SLOPMUD_REPORTS_PATH:-}" ]]; then
+  echo "Environment=SLOPMUD_REPORTS_PATH=${SLOPMUD_REPORTS_PATH}" >>"$tmp_unit"
+fi
 
 # Optional: eventlog archival (spool-to-disk + S3 upload).
 if [[ -n "${SLOPMUD_EVENTLOG_ENABLED:-}" ]]; then
@@ -182,6 +189,7 @@ fi
 
 cat >>"$tmp_unit" <<EOF
 ExecStart=${exec_start}
+ExecReload=/bin/kill -USR2 \$MAINPID
 Restart=always
 RestartSec=2
 NoNewPrivileges=true
@@ -194,6 +202,14 @@ EOF
 
 unit_name="${SLOPMUD_APP_NAME}.service"
 
+# SLOPMUD_DEPLOY_HOTBOOT=1 swaps the binary in place (systemctl reload) instead of restarting, so
+# players stay connected. The running process keeps its old environment; use a restart when the
+# unit's Environment= lines changed.
+restart_cmd="restart"
+if [[ "${SLOPMUD_DEPLOY_HOTBOOT:-0}" == "1" ]]; then
+  restart_cmd="reload-or-restart"
+fi
+
 echo "Installing systemd unit (${unit_name})"
 scp "${ssh_opts[@]}" "${scp_port_opt[@]}" "$tmp_unit" "${SSH_USER}@${HOST}:/tmp/${unit_name}"
 ssh "${ssh_opts[@]}" "${ssh_port_opt[@]}" "${SSH_USER}@${HOST}" "\
@@ -201,7 +217,7 @@ ssh "${ssh_opts[@]}" "${ssh_port_opt[@]}" "${SSH_USER}@${HOST}" "\
   sudo mv \"/tmp/${unit_name}\" \"/etc/systemd/system/${unit_name}\"; \
   sudo systemctl daemon-reload; \
   sudo systemctl enable --now \"${unit_name}\"; \
-  sudo systemctl restart \"${unit_name}\"; \
+  sudo systemctl ${restart_cmd} \"${unit_name}\"; \
   sudo systemctl --no-pager --full status \"${unit_name}\" || true \
 "
 
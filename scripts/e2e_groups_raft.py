@@ -180,6 +180,8 @@ def main():
     env["SHARD_BIND"] = shard_bind
     env["SLOPMUD_BIND"] = broker_bind
     env["SHARD_ADDR"] = shard_bind
+    # The broker signs attach assertions; without a shared key the shard sees only guests.
+    env["SLOPMUD_SHARD_AUTH_KEY"] = env["SHARD_AUTH_KEYS"] = "e2e:e2e-local-shard-auth"
     env["WORLD_TICK_MS"] = "200"
     env["BARTENDER_EMOTE_MS"] = "1000"
     env["RUST_BACKTRACE"] = env.get("RUST_BACKTRACE", "1")
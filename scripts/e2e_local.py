@@ -1,6 +1,12 @@
 #!/usr/bin/env python3
 import argparse
+import base64
+import hashlib
+import hmac
+import json
 import os
+import re
+import struct
 import signal
 import socket
 import subprocess
@@ -108,6 +114,12 @@ def connect_and_create(name, is_bot=False, host="127.0.0.1", port=54010):
         )
     if b"set password" in out or b"password (never logged/echoed)" in out:
         send_line(s, pw)
+    finish_creation(c, is_bot)
+    return c
+
+
+def finish_creation(c, is_bot=False):
+    s = c.sock
     c.read_until("type: human | bot", timeout_s=12.0)
     send_line(s, "bot" if is_bot else "human")
     c.read_until("type: agree")
@@ -126,7 +138,28 @@ def connect_and_create(name, is_bot=False, host="127.0.0.1", port=54010):
     c.read_until(">", timeout_s=15.0)
     send_line(s, "look")
     c.read_until("Orientation Wing", timeout_s=15.0)
-    return c
+
+
+def totp_code(secret_b32: str, now: float | None = None) -> str:
+    key = base64.b32decode(secret_b32 + "=" * (-len(secret_b32) % 8))
+    step = int((time.time() if now is None else now) // 30)
+    h = hmac.new(key, struct.pack(">Q", step), hashlib.sha1).digest()
+    off = h[-1] & 0x0F
+    return "%06d" % ((struct.unpack(">I", h[off : off + 4])[0] & 0x7FFFFFFF) % 1_000_000)
+
+
+def outbox_code(outbox: Path, to: str, subject: str, timeout_s=5.0) -> str:
+    # SLOPMUD_EMAIL_MODE=file writes one JSON file per message; take the newest match.
+    deadline = time.time() + timeout_s
+    while time.time() < deadline:
+        for path in sorted(outbox.glob("*.json"), reverse=True):
+            msg = json.loads(path.read_text(encoding="utf-8"))
+            if msg["to"] == to and subject in msg["subject"]:
+                m = re.search(r"\b(\d{8})\b", msg["body"])
+                if m:
+                    return m.group(1)
+        time.sleep(0.1)
+    raise TimeoutError(f"no {subject!r} email to {to} in {outbox}")
 
 
 def wait_for_room_occupant(sock, who, tries=40):
@@ -277,9 +310,10 @@ def main():
         return 0
 
     # Allocate a port block so this can run alongside other local stacks.
-    base = args.base_port or _alloc_port_block(args.port_range, args.stride, "0,1")
+    base = args.base_port or _alloc_port_block(args.port_range, args.stride, "0,1,2")
     shard_bind = f"127.0.0.1:{base + 1}"
     broker_bind = f"127.0.0.1:{base + 0}"
+    admin_bind = f"127.0.0.1:{base + 2}"
 
     run_id = str(time.time_ns())
 
@@ -287,11 +321,20 @@ def main():
     env["SHARD_BIND"] = shard_bind
     env["SLOPMUD_BIND"] = broker_bind
     env["SHARD_ADDR"] = shard_bind
+    # The broker signs attach assertions; without a shared key the shard sees only guests.
+    env["SLOPMUD_SHARD_AUTH_KEY"] = env["SHARD_AUTH_KEYS"] = "e2e:e2e-local-shard-auth"
     env["WORLD_TICK_MS"] = "200"
     env["BARTENDER_EMOTE_MS"] = "1000"
     env["RUST_BACKTRACE"] = env.get("RUST_BACKTRACE", "1")
     # Keep accounts isolated per run so we always exercise the "set password" path.
     env["SLOPMUD_ACCOUNTS_PATH"] = f"/tmp/slopmud_accounts_e2e_local_{run_id}.json"
+    outbox = Path(f"/tmp/slopmud_e2e_local_outbox_{run_id}")
+    env["SLOPMUD_EMAIL_MODE"] = "file"
+    env["SLOPMUD_EMAIL_FILE_DIR"] = str(outbox)
+    env["SLOPMUD_HOTBOOT_PATH"] = f"/tmp/slopmud_e2e_local_hotboot_{run_id}.json"
+    env["SLOPMUD_ADMIN_BIND"] = admin_bind
+    env["SLOPMUD_BANS_PATH"] = f"/tmp/slopmud_e2e_local_bans_{run_id}.json"
+    env["SLOPMUD_REPORTS_PATH"] = f"/tmp/slopmud_e2e_local_reports_{run_id}.json"
     shard_log = Path(f"/tmp/slopmud_e2e_local_shard_{run_id}.log")
     broker_log = Path(f"/tmp/slopmud_e2e_local_broker_{run_id}.log")
     shard_f = open(shard_log, "wb")
@@ -436,12 +479,190 @@ def main():
         a.read_until("party: disbanded", timeout_s=3.0)
         b.read_until("party: disbanded by", timeout_s=3.0)
 
+        # Second character on Bob's account, then back to the first from the menu.
+        send_line(b.sock, "characters")
+        b.read_until("character select (1/", timeout_s=5.0)
+        send_line(b.sock, "new Bobby")
+        finish_creation(b, is_bot=True)
+        send_line(b.sock, "characters")
+        out = b.read_until("type: <name>", timeout_s=5.0)
+        if b"character select (2/" not in out or b"Bobby (human fighter)" not in out:
+            raise RuntimeError(f"unexpected character menu: {out!r}")
+        send_line(b.sock, "Bob")
+        b.read_until("hi Bob", timeout_s=10.0)
+
+        # 2fa: enroll Alice, then log back in with a recovery code.
+        send_line(a.sock, "account 2fa enable")
+        out = a.read_until("account 2fa verify <code>", timeout_s=5.0)
+        m = re.search(rb"enter the key by hand: ([A-Z2-7]+)", out)
+        if not m:
+            raise RuntimeError(f"no totp secret in: {out!r}")
+        send_line(a.sock, "account 2fa verify " + totp_code(m.group(1).decode()))
+        out = a.read_until("> ", timeout_s=5.0)
+        out += a.read_until("> ", timeout_s=5.0)
+        codes = re.findall(rb" - ([a-z2-7]{5}-[a-z2-7]{5})", out)
+        if b"ok: 2fa on" not in out or len(codes) != 10:
+            raise RuntimeError(f"2fa enable failed: {out!r}")
+
+        # Email: Bob verifies an address, which later lets him reset his password.
+        send_line(b.sock, "account email set bob@example.com")
+        b.read_until("account email verify <code>", timeout_s=5.0)
+        code = outbox_code(outbox, "bob@example.com", "verify your email")
+        send_line(b.sock, "account email verify " + code)
+        b.read_until("ok: bob@example.com verified", timeout_s=5.0)
+
+        # Hotboot: the broker re-execs itself; both connections stay open and in the world.
+        broker.send_signal(signal.SIGUSR2)
+        time.sleep(1.0)
+        send_line(a.sock, "say still here")
+        b.read_until("Alice: still here", timeout_s=15.0)
+        # Scrollback from before the hotboot came along.
+        send_line(b.sock, "report last 50")
+        b.read_until("* stenchworm dies.", timeout_s=5.0)
+
+        # Shard restart: input typed while it's down is queued and runs once it's back.
+        os.killpg(shard.pid, signal.SIGTERM)
+        shard.wait(timeout=5)
+        a.read_until("# shard disconnected", timeout_s=5.0)
+        send_line(a.sock, "say typed while down")
+        a.read_until("# shard offline; queued (1/", timeout_s=5.0)
+        shard = subprocess.Popen(
+            ["target/debug/shard_01"],
+            env=env,
+            stdout=shard_f,
+            stderr=shard_f,
+            start_new_session=True,
+        )
+        a.read_until("# shard back; sending 1 queued command(s)", timeout_s=10.0)
+        b.read_until("Alice: typed while down", timeout_s=10.0)
+
+        # Aliases expand at the broker into one or more commands.
+        send_line(a.sock, "alias gr say greetings $1; say farewell $*")
+        a.read_until("ok: alias gr", timeout_s=5.0)
+        send_line(a.sock, "gr bob now")
+        b.read_until("Alice: greetings bob", timeout_s=5.0)
+        b.read_until("Alice: farewell bob now", timeout_s=5.0)
+        send_line(a.sock, "alias lp lp")
+        a.read_until("ok: alias lp", timeout_s=5.0)
+        send_line(a.sock, "lp")
+        a.read_until("nests too deep", timeout_s=5.0)
+
+        # Reports: Bob reports a line, Alice (made a moderator over the admin socket) mutes the
+        # speaker, and Bob hears back.
+        send_line(a.sock, "reports")
+        a.read_until("nope: mod.reports", timeout_s=5.0)
+        admin_host, admin_port = admin_bind.rsplit(":", 1)
+        with socket.create_connection((admin_host, int(admin_port)), timeout=3.0) as adm:
+            req = {
+                "type": "grant_account_caps",
+                "name": "Alice",
+                "caps": ["mod.reports", "mod.silence"],
+            }
+            adm.sendall((json.dumps(req) + "\n").encode("utf-8"))
+            resp = json.loads(adm.makefile("rb").readline())
+        if resp.get("type") != "ok_account":
+            raise RuntimeError(f"grant failed: {resp!r}")
+        send_line(b.sock, "report search farewell")
+        b.read_until('report search "farewell"', timeout_s=5.0)
+        out = b.read_until("\r\n\r\n> ", timeout_s=5.0)
+        m = re.search(rb"\[([0-9a-z]+) [0-9:]+\] Alice: farewell", out)
+        if m is None:
+            raise RuntimeError(f"reported line not found: {out!r}")
+        send_line(b.sock, f"report submit {m.group(1).decode()} spam")
+        b.read_until("report: #1", timeout_s=5.0)
+        send_line(a.sock, "reports")
+        a.read_until("#1 [", timeout_s=5.0)
+        a.read_until("spam from Bob", timeout_s=5.0)
+        send_line(a.sock, "reports show 1")
+        a.read_until("context:", timeout_s=5.0)
+        send_line(a.sock, "reports resolve 1 mute Alice e2e")
+        a.read_until("report #1 resolved: mute Alice", timeout_s=5.0)
+        b.read_until("your report #1 was reviewed and action was taken", timeout_s=5.0)
+        send_line(a.sock, "say am I muted")
+        a.read_until("you are muted", timeout_s=5.0)
+
+        # Silence: timed, refused by the shard, lifted early by a moderator.
+        send_line(a.sock, "silence Alice off")
+        a.read_until("ok: Alice can talk again", timeout_s=5.0)
+        send_line(a.sock, "silence Bob 10m e2e check")
+        a.read_until("ok: Bob silenced for 10m", timeout_s=5.0)
+        b.read_until("silenced you for 10m: e2e check", timeout_s=5.0)
+        send_line(b.sock, "tell Alice let me talk")
+        b.read_until("you are muted (10m left)", timeout_s=5.0)
+        send_line(a.sock, "silence Bob off")
+        b.read_until("you can talk again", timeout_s=5.0)
+
+        # Ignore: Bob stops seeing Alice's talk, then hears her again.
+        send_line(b.sock, "ignore Alice")
+        b.read_until("ignore: you no longer hear Alice", timeout_s=5.0)
+        send_line(a.sock, "say can you hear me")
+        send_line(a.sock, "tell Bob psst")
+        a.read_until("tell: Bob isn't taking tells from you", timeout_s=5.0)
+        send_line(b.sock, "unignore Alice")
+        b.read_until("ignore: hearing Alice again", timeout_s=5.0)
+        send_line(a.sock, "say how about now")
+        out = b.read_until("Alice: how about now", timeout_s=5.0)
+        if b"can you hear me" in out:
+            raise RuntimeError(f"ignored talk got through: {out!r}")
+
         # Clean shutdown.
         send_line(a.sock, "exit")
         send_line(b.sock, "exit")
         a.sock.close()
         b.sock.close()
 
+        s = socket.create_connection(("127.0.0.1", broker_port), timeout=3.0)
+        a = Client(s)
+        a.read_until("name:")
+        send_line(s, "Alice")
+        a.read_until("type: password", timeout_s=12.0)
+        send_line(s, "password")
+        a.read_until("password (never logged/echoed)", timeout_s=12.0)
+        send_line(s, "pw-Alice-1234")
+        a.read_until("2fa code", timeout_s=12.0)
+        send_line(s, "000000")
+        a.read_until("bad code", timeout_s=5.0)
+        time.sleep(1.2)
+        send_line(s, codes[0].decode())
+        a.read_until("9 left", timeout_s=5.0)
+        a.read_until("hi Alice", timeout_s=10.0)
+        send_line(s, "exit")
+        s.close()
+
+        s = socket.create_connection(("127.0.0.1", broker_port), timeout=3.0)
+        b = Client(s)
+        b.read_until("name:")
+        send_line(s, "Bob")
+        b.read_until("type: password", timeout_s=12.0)
+        send_line(s, "password")
+        b.read_until("password (never logged/echoed)", timeout_s=12.0)
+        send_line(s, "forgot")
+        b.read_until("sent a reset code to b***@example.com", timeout_s=10.0)
+        b.read_until("reset code", timeout_s=5.0)
+        send_line(s, outbox_code(outbox, "bob@example.com", "password reset"))
+        b.read_until("new password", timeout_s=5.0)
+        send_line(s, "pw-Bob-5678")
+        b.read_until("password changed", timeout_s=5.0)
+        b.read_until("password: ", timeout_s=5.0)
+        send_line(s, "pw-Bob-5678")
+        b.read_until("character select (2/", timeout_s=10.0)
+        b.read_until("type: <name>", timeout_s=5.0)
+        # Rename the second character from the menu; the shard carries its snapshot over.
+        send_line(s, "rename Bobby Roberta")
+        out = b.read_until("type: <name>", timeout_s=10.0)
+        if b"ok: Bobby is now Roberta" not in out or b"Roberta (human fighter)" not in out:
+            raise RuntimeError(f"rename failed: {out!r}")
+        send_line(s, "rename Bob Robert")
+        b.read_until("can rename again after", timeout_s=5.0)
+        send_line(s, "Bob")
+        b.read_until("hi Bob", timeout_s=10.0)
+        send_line(s, "account export")
+        out = b.read_until("-----END SLOPMUD EXPORT-----", timeout_s=10.0)
+        if b"slopmud-account-export/1" not in out or b'"Roberta"' not in out:
+            raise RuntimeError(f"unexpected export: {out[-2000:]!r}")
+        send_line(s, "exit")
+        s.close()
+
         ok = True
         print("e2e ok")
         return 0
@@ -480,6 +701,12 @@ def main():
         except Exception:
             pass
         if ok:
+            for path in outbox.glob("*.json"):
+                path.unlink()
+            try:
+                outbox.rmdir()
+            except Exception:
+                pass
             try:
                 broker_log.unlink()
             except Exception:
@@ -176,6 +176,8 @@ def main():
     env["SLOPMUD_BIND"] = broker_bind
     env["SHARD_BIND"] = shard_bind
     env["SHARD_ADDR"] = shard_bind
+    # The broker signs attach assertions; without a shared key the shard sees only guests.
+    env["SLOPMUD_SHARD_AUTH_KEY"] = env["SHARD_AUTH_KEYS"] = "e2e:e2e-local-shard-auth"
     env["WORLD_TICK_MS"] = "200"
     env["SESSION_TCP_ADDR"] = broker_bind
 
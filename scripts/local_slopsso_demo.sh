@@ -30,6 +30,8 @@ export WORLD_TICK_MS=200
 export SLOPMUD_BIND="${broker_bind}"
 export SHARD_BIND="${shard_bind}"
 export SHARD_ADDR="${shard_bind}"
+export SLOPMUD_SHARD_AUTH_KEY="demo:slopmud-local-shard-auth"
+export SHARD_AUTH_KEYS="${SLOPMUD_SHARD_AUTH_KEY}"
 export SESSION_TCP_ADDR="${broker_bind}"
 
 export OIDC_BIND="${oidc_bind}"
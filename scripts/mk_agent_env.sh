@@ -43,6 +43,7 @@ ws_port=$((base + 4))
 admin_port=$((base + 11))
 
 mkdir -p "$(dirname "${out}")"
+shard_auth_key="agent:$(head -c 16 /dev/urandom | od -An -tx1 | tr -d ' \n')"
 
 cat >"${out}" <<EOF
 # autogenerated: $(date -u +%Y-%m-%dT%H:%M:%SZ)
@@ -57,6 +58,7 @@ CARGO_TARGET_DIR=/tmp/slopmud_target_${name}
 SLOPMUD_BIND=127.0.0.1:${broker_port}
 SESSION_TCP_ADDR=127.0.0.1:${broker_port}
 SHARD_ADDR=127.0.0.1:${shard_port}
+SLOPMUD_SHARD_AUTH_KEY=${shard_auth_key}
 SLOPMUD_ADMIN_BIND=127.0.0.1:${admin_port}
 SLOPMUD_ACCOUNTS_PATH=/tmp/slopmud_accounts_${name}.json
 SLOPMUD_GOOGLE_AUTH_BASE_URL=http://127.0.0.1:${oauth_port}
@@ -67,6 +69,7 @@ SBC_EVENTS_SOCK=/tmp/slopmud_sbc_events_${name}.sock
 
 # --- shard ---
 SHARD_BIND=127.0.0.1:${shard_port}
+SHARD_AUTH_KEYS=${shard_auth_key}
 
 # --- web ---
 STATIC_WEB_BIND=127.0.0.1:${static_port}
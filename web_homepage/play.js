@@ -174,6 +174,7 @@
     if (
       tail.includes("Orientation Wing") ||
       tail.includes("character creation (step 2/4)") ||
+      tail.includes("character select (") ||
       tail.includes("auth method:")
     ) {
       finishOauthCallbackHandoff();